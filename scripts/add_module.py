@@ -5,16 +5,16 @@ priority = input('With priority: ')
 is_enabled = input('enabled (Y/n): ').lower() != 'n'
 enabled = 1 if is_enabled else 0
 typem = input('With type: ')
-condition = input('With condition: ')
+condition = input('With condition (regex or intent json): ')
 path = input('With path: ')
 
 # TODO metadatas
 
 conn = sqlite3.connect('rori.db')
 c = conn.cursor()
-arguments = '("' + name + '", ' + str(priority) + ', ' + str(enabled) + ', "'
-arguments += typem + '", "' + condition + '", "' + path + '")'
-print('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES' + arguments)
-c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES' + arguments)
+# condition can contain quotes (intent conditions are json)
+arguments = (name, priority, enabled, typem, condition, path)
+print('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES' + str(arguments))
+c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES (?, ?, ?, ?, ?, ?)', arguments)
 print(c.lastrowid)
 conn.commit()
@@ -1,3 +1,4 @@
+import json
 import sqlite3
 
 conn = sqlite3.connect('rori.db')
@@ -7,11 +8,6 @@ c = conn.cursor()
 c.execute('CREATE TABLE IF NOT EXISTS modules (id INTEGER PRIMARY KEY, name TEXT, priority INTEGER, enabled BOOLEAN, type TEXT, condition TEXT, path TEXT)')
 c.execute('DELETE FROM modules WHERE 1=1')
 
-# talk/history
-print('add history module')
-arguments = '("history", 0, 1, "plain/text", ".*", "history")'
-c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES' + arguments)
-
 # talk/hello_world
 print('add hello_world module')
 arguments = '("hello_world", 1, 1, "plain/text", "^(salut|bonjour|bonsoir|hei|hi|hello|yo|o/)( rori| ?!?)$", "talk/hello_world")'
@@ -54,8 +50,14 @@ c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path)
 
 # talk/humor
 print('add humor module')
-arguments = '("humor", 2, 1, "plain/text", "(c|ç)a.va.{0,10}$|tu.{0,30}va(s| ?).{0,30}(bien|mal|comment|bof)|are you ok|comment.{0,30}va(s| ?)|how are you", "talk/humor")'
-c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES' + arguments)
+condition = json.dumps({'type': 'intent',
+                        'examples': ['how are you', 'how r u', 'are you ok', 'how do you feel',
+                                     'ça va', 'comment vas tu', 'tu vas bien', 'comment ça va'],
+                        'keywords': [],
+                        'threshold': 0.6,
+                        'max_distance': 1})
+c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES (?, ?, ?, ?, ?, ?)',
+          ('humor', 2, 1, 'plain/text', condition, 'talk/humor'))
 
 # talk/sing
 print('add sing module')
@@ -64,7 +66,7 @@ c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path)
 
 # talk/alarm
 print('add alarm module')
-arguments = '("alarm", 2, 1, "plain/text", "(wake|veille).{0,100}(in|at|dans|à|a).([0-9]+)(:|h|.*)([0-9]*)", "talk/alarm")'
+arguments = '("alarm", 2, 1, "plain/text", "(wake|veille).{0,100}(in|at|dans|à|a).(?P<hours>[0-9]+)(:|h|.*)(?P<minutes>[0-9]*)", "talk/alarm")'
 c.execute('INSERT INTO modules (name, priority, enabled, type, condition, path) VALUES' + arguments)
 
 # talk/uptime
@@ -26,12 +26,16 @@
  **/
 
 extern crate clokwerk;
+#[macro_use]
 extern crate cpython;
 extern crate dbus;
 extern crate env_logger;
 extern crate iron;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
+extern crate openssl;
 extern crate regex;
 extern crate router;
 extern crate rusqlite;
@@ -39,7 +43,7 @@ extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
-extern crate string_error;
 extern crate time;
+extern crate unicode_normalization;
 
 pub mod rori;
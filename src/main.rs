@@ -26,12 +26,16 @@
  **/
 
 extern crate clokwerk;
+#[macro_use]
 extern crate cpython;
 extern crate dbus;
 extern crate env_logger;
 extern crate iron;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
+extern crate openssl;
 extern crate regex;
 extern crate router;
 extern crate rusqlite;
@@ -39,17 +43,23 @@ extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
-extern crate string_error;
 extern crate time;
+extern crate unicode_normalization;
 
 pub mod rori;
 
+use rori::backup::Backup;
+use rori::database::Database;
+use rori::link::LinkPolicy;
 use rori::manager::Manager;
+use rori::modulemanager::Fallback;
+use rori::normalizer::Normalizer;
 use rori::api::API;
+use rori::vault::Vault;
 use serde_json::{Value, from_str};
 use std::io::prelude::*;
 use std::io::{stdin,stdout,Write};
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -127,13 +137,22 @@ fn create_config_file() {
         stdin().read_line(&mut s).expect("Did not enter a correct string");
         s = clean_string(s);
         let password = s.clone();
-        Manager::add_account(&*main_info, &*password, from_archive);
+        if let Err(e) = Manager::add_account(&*main_info, &*password, from_archive) {
+            println!("Can't create the account: {}", e);
+            return;
+        }
         // Let some time for the daemon
         let three_secs = Duration::from_millis(3000);
         thread::sleep(three_secs);
     }
 
-    let accounts = Manager::get_account_list();
+    let accounts = match Manager::get_account_list() {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            println!("Can't get accounts: {}", e);
+            return;
+        }
+    };
     let mut idx = 0;
     println!("Choose an account:");
     for account in &accounts {
@@ -163,10 +182,252 @@ fn create_config_file() {
 
 }
 
+/**
+ * Read config.json without creating it
+ * @return the configuration or Null if not found
+ */
+fn read_config() -> Value {
+    let mut config = String::new();
+    if let Ok(mut file) = File::open("config.json") {
+        let _ = file.read_to_string(&mut config);
+    }
+    from_str(&*config).unwrap_or(Value::Null)
+}
+
+/**
+ * @param args command line
+ * @param name of the option
+ * @return the value following an option (--password-file value)
+ */
+fn option_value<'a>(args: &'a Vec<String>, name: &str) -> Option<&'a str> {
+    let idx = args.iter().position(|arg| arg == name)?;
+    args.get(idx + 1).map(|value| &**value)
+}
+
+/**
+ * rori backup <file> [--with-account] [--password-file <file>]
+ * Save the database and the config (and the account) in an archive
+ * @param args command line
+ */
+fn backup(args: &Vec<String>) {
+    let path = match args.get(2) {
+        Some(path) => path,
+        None => {
+            println!("Usage: rori backup <file> [--with-account] [--password-file <file>]");
+            std::process::exit(1);
+        }
+    };
+    let config = read_config();
+    Database::set_path(config["database"].as_str().unwrap_or("rori.db"));
+    let password = account_password(args);
+    let with_account = if args.iter().any(|arg| arg == "--with-account") { Some(&*password) } else { None };
+    let result = Backup::create(&config, with_account).and_then(|backup| backup.save(&*path));
+    match result {
+        Ok(_) => println!("Backup of {} saved to {}", Database::path(), path),
+        Err(e) => {
+            println!("Backup failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/**
+ * rori restore <file> [--force] [--password-file <file>]
+ * Restore the database, config.json and the account from an archive
+ * @param args command line
+ */
+fn restore(args: &Vec<String>) {
+    let path = match args.get(2) {
+        Some(path) => path,
+        None => {
+            println!("Usage: rori restore <file> [--force] [--password-file <file>]");
+            std::process::exit(1);
+        }
+    };
+    let backup = match Backup::load(&*path) {
+        Ok(backup) => backup,
+        Err(e) => {
+            println!("Invalid backup {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let force = args.iter().any(|arg| arg == "--force");
+    if Path::new("config.json").exists() && !force {
+        println!("config.json already exists, use --force to replace it");
+        std::process::exit(1);
+    }
+    let db_path = String::from(backup.config["database"].as_str().unwrap_or("rori.db"));
+    if force && db_path != ":memory:" {
+        for file in &[db_path.clone(), format!("{}-wal", db_path), format!("{}-shm", db_path)] {
+            let _ = fs::remove_file(file);
+        }
+    }
+    Database::set_path(&*db_path);
+    match backup.restore_database() {
+        Ok(version) => println!("Database {} restored (version {})", db_path, version),
+        Err(e) => {
+            println!("Restoration of {} failed: {}", db_path, e);
+            std::process::exit(1);
+        }
+    }
+    let mut config = backup.config.clone();
+    match backup.restore_account(&*account_password(args)) {
+        Ok(Some(account)) => {
+            println!("Account {} imported", account);
+            config["ring_id"] = Value::from(account);
+        },
+        Ok(None) => {},
+        Err(e) => {
+            println!("Can't import the account: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let config = serde_json::to_string_pretty(&config).unwrap_or(String::new());
+    if let Err(e) = File::create("config.json").and_then(|mut file| file.write_all(config.as_bytes())) {
+        println!("Can't write config.json: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/**
+ * Password of the account archive, read from --password-file, else from $RORI_ACCOUNT_PASSWORD,
+ * so it doesn't appear in the list of processes
+ * @param args command line
+ * @return the password (empty if none)
+ */
+fn account_password(args: &Vec<String>) -> String {
+    read_passphrase(option_value(args, "--password-file"), "RORI_ACCOUNT_PASSWORD").unwrap_or(String::new())
+}
+
+/**
+ * Read a passphrase from a key file (first line) or from an environment variable
+ * @param key_file path of the key file if any
+ * @param env name of the environment variable used if there is no key file
+ * @return the passphrase if found
+ */
+fn read_passphrase(key_file: Option<&str>, env: &str) -> Option<String> {
+    match key_file {
+        Some(path) => {
+            let mut passphrase = String::new();
+            if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut passphrase)) {
+                println!("Can't read key file {}: {}", path, e);
+                std::process::exit(1);
+            }
+            passphrase.lines().next().map(String::from)
+        },
+        None => std::env::var(env).ok(),
+    }.filter(|passphrase| !passphrase.is_empty())
+}
+
+/**
+ * Unlock the vault with --vault-key-file, else "vault_key_file" in config.json, else $RORI_VAULT_PASSPHRASE
+ * Quit if the passphrase is wrong
+ * @param args command line
+ * @param config content of config.json
+ * @return if the vault is unlocked
+ */
+fn unlock_vault(args: &Vec<String>, config: &Value) -> bool {
+    let key_file = option_value(args, "--vault-key-file").or(config["vault_key_file"].as_str());
+    let passphrase = match read_passphrase(key_file, "RORI_VAULT_PASSPHRASE") {
+        Some(passphrase) => passphrase,
+        None => return false,
+    };
+    if let Err(e) = Vault::unlock(&*passphrase) {
+        println!("Can't unlock the vault: {}", e);
+        std::process::exit(1);
+    }
+    true
+}
+
+/**
+ * rori secret set|delete <module> <name> [--user <username>]
+ * rori secret list [module]
+ * rori secret rotate [--new-key-file <file>]
+ * Manage secrets of modules. Values are read from stdin and never printed
+ * @param args command line
+ */
+fn secret(args: &Vec<String>) {
+    let usage = "Usage: rori secret set|delete <module> <name> [--user <username>]\n       \
+                 rori secret list [module]\n       \
+                 rori secret rotate [--new-key-file <file>]";
+    let config = read_config();
+    Database::set_path(config["database"].as_str().unwrap_or("rori.db"));
+    if let Err(e) = Database::migrate() {
+        println!("Migration of {} failed: {}", Database::path(), e);
+        std::process::exit(1);
+    }
+    let action = args.get(2).map(|arg| &**arg).unwrap_or("");
+    let module = args.get(3).cloned().unwrap_or(String::new());
+    let name = args.get(4).cloned().unwrap_or(String::new());
+    let username = String::from(option_value(args, "--user").unwrap_or(""));
+    if action != "list" && action != "delete" && !unlock_vault(args, &config) {
+        println!("No passphrase, use --vault-key-file or RORI_VAULT_PASSPHRASE");
+        std::process::exit(1);
+    }
+    let result = match action {
+        "set" if !module.is_empty() && !name.is_empty() => {
+            println!("Value of {} for {}:", name, module);
+            let mut value = String::new();
+            stdin().read_line(&mut value).expect("Did not enter a correct string");
+            Vault::set(&module, &username, &name, &clean_string(value)).map(|_| format!("Secret {} set", name))
+        },
+        "delete" if !module.is_empty() && !name.is_empty() => {
+            Vault::delete(&module, &username, &name).map(|removed| format!("{} secret(s) removed", removed))
+        },
+        "list" => Vault::list(&module).map(|secrets| secrets.iter().map(|secret| {
+            format!("{} {} {}", secret.module, secret.name,
+                    if secret.username.is_empty() { "(all users)" } else { &*secret.username })
+        }).collect::<Vec<String>>().join("\n")),
+        "rotate" => {
+            let passphrase = match read_passphrase(option_value(args, "--new-key-file"), "RORI_VAULT_NEW_PASSPHRASE") {
+                Some(passphrase) => passphrase,
+                None => {
+                    println!("No new passphrase, use --new-key-file or RORI_VAULT_NEW_PASSPHRASE");
+                    std::process::exit(1);
+                }
+            };
+            Vault::rotate(&*passphrase).map(|rotated| format!("{} secret(s) encrypted with the new passphrase", rotated))
+        },
+        _ => {
+            println!("{}", usage);
+            std::process::exit(1);
+        }
+    };
+    match result {
+        Ok(message) => println!("{}", message),
+        Err(e) => {
+            println!("Can't {} secret: {}", action, e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     // Init logging
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|arg| &**arg) {
+        Some("backup") => return backup(&args),
+        Some("restore") => return restore(&args),
+        Some("secret") => return secret(&args),
+        _ => {}
+    }
+
+    // rori --migrate-only upgrades the database and quits
+    if args.iter().any(|arg| arg == "--migrate-only") {
+        let config = read_config();
+        Database::set_path(config["database"].as_str().unwrap_or("rori.db"));
+        match Database::migrate() {
+            Ok(version) => println!("Database {} migrated to version {}", Database::path(), version),
+            Err(e) => {
+                println!("Migration of {} failed: {}", Database::path(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // if not config, create it
     if !Path::new("config.json").exists() {
         create_config_file();
@@ -186,10 +447,24 @@ fn main() {
     let config: Value = from_str(&*config).ok()
                         .expect("Incorrect config file. Please check config.json");
 
-    let shared_manager : Arc<Mutex<Manager>> = Arc::new(Mutex::new(
-        Manager::init(config["ring_id"].as_str().unwrap_or(""))
-        .ok().expect("Can't initialize ConfigurationManager"))
-    );
+    Database::set_path(config["database"].as_str().unwrap_or("rori.db"));
+    let mut manager = match Manager::init(config["ring_id"].as_str().unwrap_or("")) {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("Can't initialize RORI: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !unlock_vault(&args, &config) {
+        warn!("The vault is locked, modules will not get their secrets");
+    }
+    if let Err(e) = manager.server.set_admin(&String::from(config["admin_device"].as_str().unwrap_or(""))) {
+        error!("Can't set the admin: {}", e);
+    }
+    manager.server.fallback = Fallback::from_config(&config["fallback"]);
+    manager.server.normalizer = Normalizer::from_config(&config["normalization"]);
+    manager.server.link_policy = LinkPolicy::from_config(&config["link"]);
+    let shared_manager : Arc<Mutex<Manager>> = Arc::new(Mutex::new(manager));
     let shared_manager_cloned = shared_manager.clone();
     let stop = Arc::new(AtomicBool::new(false));
     let stop_cloned = stop.clone();
@@ -199,6 +474,7 @@ fn main() {
     let mut api = API::new(shared_manager,
                            String::from(config["api_listener"].as_str().unwrap_or(""))
                         );
+    api.set_admin_token(&String::from(config["api_token"].as_str().unwrap_or("")));
     api.start();
     stop.store(false, Ordering::SeqCst);
     let _ = test.join();
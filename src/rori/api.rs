@@ -28,12 +28,20 @@
 
 use iron::prelude::*;
 use iron::Handler;
+use iron::headers::{Authorization, Bearer};
 use iron::mime::Mime;
 use iron::status;
 use router::Router;
+use rori::capabilities::{self, Capabilities};
 use rori::manager::Manager;
 use rori::scheduler::Scheduler;
 use rori::database::Database;
+use rori::emotions::Emotions;
+use rori::error::RoriError;
+use rori::group::{self, Group};
+use rori::history::{self, HistoryEntry};
+use rori::presence::{self, Presence};
+use rori::reply::SubAuthorMapping;
 use serde_json;
 use std::collections::HashMap;
 use std::io::Read;
@@ -49,6 +57,7 @@ pub struct API {
     address: String,
     scheduler: Arc<Mutex<Scheduler>>,
     manager: Arc<Mutex<Manager>>,
+    admin_token: AdminToken,
 }
 
 impl API {
@@ -64,10 +73,20 @@ impl API {
         API {
             address: address,
             scheduler: Arc::new(Mutex::new(Scheduler::new())),
-            manager: manager
+            manager: manager,
+            admin_token: AdminToken { token: String::new() },
         }
     }
 
+    /**
+     * Set the token needed by admin routes. Admin routes are refused if empty
+     * @param self
+     * @param token from "api_token" in config.json
+     */
+    pub fn set_admin_token(&mut self, token: &String) {
+        self.admin_token = AdminToken { token: token.clone() };
+    }
+
     /**
      * Launch an API instance
      * @param self
@@ -90,6 +109,48 @@ impl API {
         };
         let task_search_handler = TaskSearchHandler { };
         let module_handler = ModuleHandler { };
+        let unmatched_handler = UnmatchedHandler { };
+        let emotions_handler = EmotionsHandler { };
+        let history_handler = HistoryHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let user_export_handler = UserExportHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let user_presence_handler = UserPresenceHandler { };
+        let user_capabilities_handler = UserCapabilitiesHandler { };
+        let user_purge_handler = UserPurgeHandler {
+            manager: self.manager.clone(),
+            admin_token: self.admin_token.clone()
+        };
+        let sub_authors_handler = SubAuthorsHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let sub_author_handler = SubAuthorHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let sub_author_rm_handler = SubAuthorRmHandler {
+            manager: self.manager.clone(),
+            admin_token: self.admin_token.clone()
+        };
+        let groups_handler = GroupsHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let group_handler = GroupHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let group_add_handler = GroupAddHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let group_rm_handler = GroupRmHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let group_member_add_handler = GroupMemberAddHandler {
+            admin_token: self.admin_token.clone()
+        };
+        let group_member_rm_handler = GroupMemberRmHandler {
+            admin_token: self.admin_token.clone()
+        };
 
         router.get("/name/:name", name_handler, "name");
         router.get("/addr/:addr", addr_handler, "addr");
@@ -103,12 +164,108 @@ impl API {
         router.post("/task/search/:name", task_search_handler, "task_search");
         // GET module/name
         router.get("/module/:name", module_handler, "module");
+        // GET unmatched
+        router.get("/unmatched", unmatched_handler, "unmatched");
+        // GET emotions/username
+        router.get("/emotions/:username", emotions_handler, "emotions");
+        // GET history/username?q=words&page=1
+        router.get("/history/:username", history_handler, "history");
+        // GET user/username/export
+        router.get("/user/:username/export", user_export_handler, "user_export");
+        // GET user/username/presence
+        router.get("/user/:username/presence", user_presence_handler, "user_presence");
+        // GET user/username/capabilities?datatype=music/play
+        router.get("/user/:username/capabilities", user_capabilities_handler, "user_capabilities");
+        // DELETE user/username
+        router.delete("/user/:username", user_purge_handler, "user_purge");
+        // GET bridge/hash/sub_authors
+        router.get("/bridge/:hash/sub_authors", sub_authors_handler, "sub_authors");
+        // GET bridge/hash/sub_authors/sa
+        router.get("/bridge/:hash/sub_authors/:sa", sub_author_handler, "sub_author");
+        // DELETE bridge/hash/sub_authors/sa
+        router.delete("/bridge/:hash/sub_authors/:sa", sub_author_rm_handler, "sub_author_rm");
+        // GET groups
+        router.get("/groups", groups_handler, "groups");
+        // GET group/name
+        router.get("/group/:name", group_handler, "group");
+        // POST group/name
+        router.post("/group/:name", group_add_handler, "group_add");
+        // DELETE group/name
+        router.delete("/group/:name", group_rm_handler, "group_rm");
+        // PUT group/name/members/username
+        router.put("/group/:name/members/:username", group_member_add_handler, "group_member_add");
+        // DELETE group/name/members/username
+        router.delete("/group/:name/members/:username", group_member_rm_handler, "group_member_rm");
         info!("start API endpoint at {}", self.address);
         // Start router
-        Iron::new(router).http(&*self.address).unwrap();
+        if let Err(e) = Iron::new(router).http(&*self.address) {
+            error!("Can't start API endpoint at {}: {}", self.address, e);
+        }
     }
 }
 
+/**
+ * Body of any failed request
+ */
+#[derive(Serialize, Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/**
+ * Build the answer for an error
+ * @param error
+ * @return a JSON response with the status matching the error
+ */
+fn error_response(error: &RoriError) -> Response {
+    let content_type = "application/json".parse::<Mime>().unwrap();
+    let answer = ErrorResponse { error: error.to_string() };
+    let response = serde_json::to_string(&answer).unwrap_or(String::new());
+    Response::with((content_type, status::Status::from_u16(error.http_status()), response))
+}
+
+/**
+ * Credential of admin routes, sent as "Authorization: Bearer <token>"
+ */
+#[derive(Clone)]
+struct AdminToken {
+    token: String,
+}
+
+impl AdminToken {
+    /**
+     * Check the token of a request
+     * @param self
+     * @param request
+     * @return a 403 response if the token is missing or wrong, or if no token is configured
+     */
+    fn check(&self, request: &Request) -> Result<(), Response> {
+        let given = match request.headers.get::<Authorization<Bearer>>() {
+            Some(authorization) => authorization.token.clone(),
+            None => String::new()
+        };
+        // Compare every byte, to not leak the length of the common prefix
+        let same = given.len() == self.token.len()
+                   && given.bytes().zip(self.token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+        if self.token.is_empty() || !same {
+            warn!("refused admin request to {}", request.url);
+            return Err(error_response(&RoriError::Denied(String::from("admin route"))));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Read the body of a request
+ * @param request
+ * @return the body or a parse error
+ */
+fn read_body(request: &mut Request) -> Result<String, RoriError> {
+    let mut body = String::new();
+    request.body.read_to_string(&mut body).map_err(|e| RoriError::Parse(e.to_string()))?;
+    Ok(body)
+}
+
 /**
  * Following classes are used for the Jami compatible name server.
  * See documentation here:
@@ -156,11 +313,19 @@ impl Handler for NameHandler {
             let response = serde_json::to_string(&answer).unwrap_or(String::new());
             return Ok(Response::with((content_type, status::Ok, response)))
         }
-        let all_devices = Database::get_devices();
+        let all_devices = match Database::get_devices() {
+            Ok(devices) => devices,
+            Err(e) => return Ok(error_response(&e))
+        };
+        // A group is answered with the devices of all its members
+        let usernames = match group::resolve(&String::from(name)) {
+            Ok(usernames) => usernames,
+            Err(e) => return Ok(error_response(&e))
+        };
         let mut devices = Vec::new();
-        for (id, hash, username, devicename, is_bridge) in all_devices {
-            if username == name || name == &*format!("{}_{}", username, devicename) {
-                devices.push((id, hash, username, devicename, is_bridge))
+        for device in all_devices {
+            if usernames.contains(&device.username) || name == &*format!("{}_{}", device.username, device.devicename) {
+                devices.push(device)
             }
         }
         // Build the response
@@ -169,16 +334,16 @@ impl Handler for NameHandler {
             let mut addr = String::new();
             let mut full_devices = String::new();
             let mut bridges_devices = String::new();
-            for (_, h, _, _, ib) in devices {
+            for device in devices {
                 if is_first {
                     is_first = false;
-                    addr = format!("0x{}", h.replace("ring:", ""));
+                    addr = format!("0x{}", device.hash.replace("ring:", ""));
                 }
-                if ib {
-                    bridges_devices += &*h;
+                if device.is_bridge {
+                    bridges_devices += &*device.hash;
                     bridges_devices += ";";
                 } else {
-                    full_devices += &*h;
+                    full_devices += &*device.hash;
                     full_devices += ";";
                 }
             }
@@ -226,7 +391,10 @@ impl Handler for AddrHandler {
         info!("GET /addr/{}", ring_id);
 
         // get usernames
-        let devices = Database::get_devices_for_hash(&String::from(ring_id));
+        let devices = match Database::get_devices_for_hash(&String::from(ring_id)) {
+            Ok(devices) => devices,
+            Err(e) => return Ok(error_response(&e))
+        };
         let mut username = String::new();
         let mut is_bridge = false;
         let mut users_list = String::new();
@@ -234,14 +402,14 @@ impl Handler for AddrHandler {
         // Build the response
         if devices.len() > 0 {
             let mut is_first = true;
-            for (_, _, u, _, ib) in devices {
-                if is_first && u.len() > 0 {
+            for device in devices {
+                if is_first && device.username.len() > 0 {
                     is_first = false;
-                    username = u.clone();
-                    is_bridge = ib;
+                    username = device.username.clone();
+                    is_bridge = device.is_bridge;
                 }
-                if u.len() > 0 {
-                    users_list += &*u;
+                if device.username.len() > 0 {
+                    users_list += &*device.username;
                     users_list += ";";
                 }
             }
@@ -277,33 +445,23 @@ struct TaskAddResponse {
     id: i32,
 }
 
-/**
- * Used if an error occurs
- */
-#[derive(Serialize, Deserialize)]
-struct TaskAddError {
-    error: String,
-}
-
 impl Handler for TaskAddHandler {
     fn handle(&self, request: &mut Request) -> IronResult<Response> {
         let content_type = "application/json".parse::<Mime>().unwrap();
-        let mut body = String::new();
-        request.body.read_to_string(&mut body).unwrap();
+        let body = match read_body(request) {
+            Ok(body) => body,
+            Err(e) => return Ok(error_response(&e))
+        };
         info!("POST /task/add {}", body);
         let result = self.scheduler.lock().unwrap().add_task(&body);
         match result {
-            Some(result) => {
+            Ok(result) => {
                 let answer = TaskAddResponse { id: result };
                 let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::Ok, response)));
+                Ok(Response::with((content_type, status::Ok, response)))
             },
-            _ => {
-                let answer = TaskAddError { error: String::from("Could not add task" )};
-                let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::NotFound, response)));
-            }
-        };
+            Err(e) => Ok(error_response(&e))
+        }
     }
 }
 
@@ -322,33 +480,23 @@ struct TaskUpdateResponse {
     id: i32,
 }
 
-/**
- * Used if an error occurs
- */
-#[derive(Serialize, Deserialize)]
-struct TaskUpdateError {
-    error: String,
-}
-
 impl Handler for TaskUpdateHandler {
     fn handle(&self, request: &mut Request) -> IronResult<Response> {
         let content_type = "application/json".parse::<Mime>().unwrap();
-        let mut body = String::new();
-        request.body.read_to_string(&mut body).unwrap();
+        let body = match read_body(request) {
+            Ok(body) => body,
+            Err(e) => return Ok(error_response(&e))
+        };
         info!("POST /task/update {}", body);
         let result = self.scheduler.lock().unwrap().update_task(&body);
         match result {
-            Some(result) => {
+            Ok(result) => {
                 let answer = TaskUpdateResponse { id: result };
                 let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::Ok, response)));
+                Ok(Response::with((content_type, status::Ok, response)))
             },
-            _ => {
-                let answer = TaskUpdateError { error: String::from("Could not update task" )};
-                let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::NotFound, response)));
-            }
-        };
+            Err(e) => Ok(error_response(&e))
+        }
     }
 }
 
@@ -367,14 +515,6 @@ struct TaskRmResponse {
     id: i32,
 }
 
-/**
- * Used if an error occurs
- */
-#[derive(Serialize, Deserialize)]
-struct TaskRmError {
-    error: String,
-}
-
 impl Handler for TaskRmHandler {
     fn handle(&self, request: &mut Request) -> IronResult<Response> {
         let content_type = "application/json".parse::<Mime>().unwrap();
@@ -382,17 +522,13 @@ impl Handler for TaskRmHandler {
         info!("DELETE /task/{}", id);
         let result = self.scheduler.lock().unwrap().rm_task(&id);
         match result {
-            Some(result) => {
+            Ok(result) => {
                 let answer = TaskRmResponse { id: result };
                 let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::Ok, response)));
+                Ok(Response::with((content_type, status::Ok, response)))
             },
-            _ => {
-                let answer = TaskRmError { error: String::from("Could not remove task" )};
-                let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::NotFound, response)));
-            }
-        };
+            Err(e) => Ok(error_response(&e))
+        }
     }
 }
 
@@ -421,24 +557,27 @@ impl Handler for TaskSearchHandler {
     fn handle(&self, request: &mut Request) -> IronResult<Response> {
         let content_type = "application/json".parse::<Mime>().unwrap();
         let name = request.extensions.get::<Router>().unwrap().find("name").unwrap_or("");
-        let mut body = String::new();
-        request.body.read_to_string(&mut body).unwrap();
+        let name = String::from(name);
+        let body = match read_body(request) {
+            Ok(body) => body,
+            Err(e) => return Ok(error_response(&e))
+        };
         info!("POST /task/search/{} {}", name, body);
 
-        let content: HashMap<String, String> = serde_json::from_str(&*body).unwrap_or(HashMap::new());
-        let result = Database::search_task(&String::from(name), content);
+        let content: HashMap<String, String> = match serde_json::from_str(&*body) {
+            Ok(content) => content,
+            Err(e) => return Ok(error_response(&RoriError::from(e)))
+        };
+        let result = Database::search_task(&name, content);
         match result {
-            Some(result) => {
+            Ok(Some(result)) => {
                 let answer = ModuleResponse { id: result.id };
                 let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::Ok, response)));
+                Ok(Response::with((content_type, status::Ok, response)))
             },
-            _ => {
-                let answer = ModuleError { error: String::from("Could not get module" )};
-                let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::NotFound, response)));
-            }
-        };
+            Ok(None) => Ok(error_response(&RoriError::NotFound(format!("task for {}", name)))),
+            Err(e) => Ok(error_response(&e))
+        }
     }
 }
 
@@ -455,14 +594,6 @@ struct ModuleResponse {
     id: i32,
 }
 
-/**
- * Used if an error occurs
- */
-#[derive(Serialize, Deserialize)]
-struct ModuleError {
-    error: String,
-}
-
 impl Handler for ModuleHandler {
     fn handle(&self, request: &mut Request) -> IronResult<Response> {
         let content_type = "application/json".parse::<Mime>().unwrap();
@@ -471,16 +602,599 @@ impl Handler for ModuleHandler {
 
         let result = Database::get_module_id_by_name(&String::from(name));
         match result {
-            0 => {
-                let answer = ModuleError { error: String::from("Could not get module" )};
+            Ok(0) => Ok(error_response(&RoriError::NotFound(format!("module {}", name)))),
+            Ok(result) => {
+                let answer = ModuleResponse { id: result };
                 let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::NotFound, response)));
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to get phrases which didn't trigger any module
+ */
+struct UnmatchedHandler { }
+
+/**
+ * A phrase not understood by RORI
+ */
+#[derive(Serialize, Deserialize)]
+struct UnmatchedResponse {
+    body: String,
+    occurrences: i64,
+}
+
+impl Handler for UnmatchedHandler {
+    fn handle(&self, _: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        info!("GET /unmatched");
+
+        let unmatched = match Database::get_unmatched() {
+            Ok(unmatched) => unmatched,
+            Err(e) => return Ok(error_response(&e))
+        };
+        let mut answer = Vec::new();
+        for (body, occurrences) in unmatched {
+            answer.push(UnmatchedResponse { body, occurrences });
+        }
+        let response = serde_json::to_string(&answer).unwrap_or(String::new());
+        Ok(Response::with((content_type, status::Ok, response)))
+    }
+}
+
+/**
+ * Used to get emotions of RORI toward an user
+ */
+struct EmotionsHandler { }
+
+impl Handler for EmotionsHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let username = request.extensions.get::<Router>().unwrap().find("username").unwrap_or("");
+        info!("GET /emotions/{}", username);
+
+        let username = String::from(username);
+        match Database::search_username(&username) {
+            Ok(true) => {},
+            Ok(false) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        match Emotions::for_user(&username) {
+            Ok(emotions) => {
+                let response = serde_json::to_string(&emotions).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to search messages exchanged with an user
+ */
+struct HistoryHandler {
+    admin_token: AdminToken
+}
+
+/**
+ * A page of history
+ */
+#[derive(Serialize, Deserialize)]
+struct HistoryResponse {
+    page: u32,
+    entries: Vec<HistoryEntry>,
+}
+
+impl Handler for HistoryHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        let mut text = String::new();
+        let mut page = 1;
+        for (key, value) in request.url.as_ref().query_pairs() {
+            if key == "q" {
+                text = value.into_owned();
+            } else if key == "page" {
+                page = match value.parse::<u32>() {
+                    Ok(page) if page > 0 => page,
+                    _ => return Ok(error_response(&RoriError::Parse(format!("invalid page {}", value))))
+                };
             }
-            result => {
-                let answer = ModuleResponse { id: result };
+        }
+        info!("GET /history/{}?q={}&page={}", username, text, page);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        match Database::search_username(&username) {
+            Ok(true) => {},
+            Ok(false) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        match history::search(&username, &*text, page) {
+            Ok(entries) => {
+                let answer = HistoryResponse { page, entries };
+                let response = serde_json::to_string(&answer).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to export all data of an user
+ */
+struct UserExportHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for UserExportHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        info!("GET /user/{}/export", username);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        match Database::get_user(&username) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        match Database::export_user(&username) {
+            Ok(data) => {
+                let response = serde_json::to_string(&data).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to get the presence of each device of an user
+ */
+struct UserPresenceHandler { }
+
+/**
+ * Presence of one device
+ */
+#[derive(Serialize)]
+struct DevicePresenceResponse {
+    ring_id: String,
+    name: String,
+    is_bridge: bool,
+    presence: Presence,
+    last_seen: i64,
+}
+
+impl Handler for UserPresenceHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        info!("GET /user/{}/presence", username);
+
+        match Database::get_user(&username) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        match Database::get_devices_for_username(&*username) {
+            Ok(devices) => {
+                let answer: Vec<DevicePresenceResponse> = devices.into_iter().map(|device| {
+                    let state = presence::get(&device.hash);
+                    DevicePresenceResponse {
+                        ring_id: device.hash,
+                        name: device.devicename,
+                        is_bridge: device.is_bridge,
+                        presence: state.presence,
+                        last_seen: state.last_seen,
+                    }
+                }).collect();
                 let response = serde_json::to_string(&answer).unwrap_or(String::new());
-                return Ok(Response::with((content_type, status::Ok, response)));
+                Ok(Response::with((content_type, status::Ok, response)))
             },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to get the capabilities announced by the devices of an user
+ */
+struct UserCapabilitiesHandler { }
+
+/**
+ * Capabilities of one device
+ */
+#[derive(Serialize)]
+struct DeviceCapabilitiesResponse {
+    ring_id: String,
+    name: String,
+    is_bridge: bool,
+    #[serde(flatten)]
+    capabilities: Capabilities,
+}
+
+impl Handler for UserCapabilitiesHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        let mut datatype = String::new();
+        for (key, value) in request.url.as_ref().query_pairs() {
+            if key == "datatype" {
+                datatype = value.into_owned();
+            }
+        }
+        info!("GET /user/{}/capabilities?datatype={}", username, datatype);
+
+        match Database::get_user(&username) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        let devices = match Database::get_devices_for_username(&*username) {
+            Ok(devices) => devices,
+            Err(e) => return Ok(error_response(&e))
         };
+        // Only devices handling the datatype, if any
+        let mut answer = Vec::new();
+        for device in devices {
+            if !datatype.is_empty() {
+                match capabilities::handles(&device.id, &datatype) {
+                    Ok(true) => {},
+                    Ok(false) => continue,
+                    Err(e) => return Ok(error_response(&e))
+                }
+            }
+            match Database::get_capabilities(&device.id) {
+                Ok(capabilities) => answer.push(DeviceCapabilitiesResponse {
+                    ring_id: device.hash,
+                    name: device.devicename,
+                    is_bridge: device.is_bridge,
+                    capabilities: capabilities,
+                }),
+                Err(e) => return Ok(error_response(&e))
+            }
+        }
+        let response = serde_json::to_string(&answer).unwrap_or(String::new());
+        Ok(Response::with((content_type, status::Ok, response)))
+    }
+}
+
+/**
+ * Used to remove all data of an user
+ */
+struct UserPurgeHandler {
+    manager: Arc<Mutex<Manager>>,
+    admin_token: AdminToken
+}
+
+/**
+ * Used if the user is removed
+ */
+#[derive(Serialize, Deserialize)]
+struct UserPurgeResponse {
+    removed: usize,
+}
+
+impl Handler for UserPurgeHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        info!("DELETE /user/{}", username);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        match Database::get_user(&username) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        match self.manager.lock().unwrap().server.purge_user(&username) {
+            Ok(removed) => {
+                let response = serde_json::to_string(&UserPurgeResponse { removed }).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Check that a device is a bridge
+ * @param hash of the device
+ * @return an error response if not
+ */
+fn check_bridge(hash: &String) -> Result<(), Response> {
+    match Database::is_bridge(hash) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(error_response(&RoriError::NotFound(format!("bridge {}", hash)))),
+        Err(e) => Err(error_response(&e))
+    }
+}
+
+/**
+ * Used to list the sub authors of a bridge
+ */
+struct SubAuthorsHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for SubAuthorsHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let hash = String::from(request.extensions.get::<Router>().unwrap().find("hash").unwrap_or(""));
+        info!("GET /bridge/{}/sub_authors", hash);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        if let Err(response) = check_bridge(&hash) {
+            return Ok(response);
+        }
+        match Database::get_devices_for_hash(&*hash) {
+            Ok(devices) => {
+                let answer: Vec<SubAuthorMapping> = devices.into_iter()
+                    .filter(|device| device.is_bridge && !device.username.is_empty())
+                    .map(|device| SubAuthorMapping { sa: device.sub_author, username: device.username })
+                    .collect();
+                let response = serde_json::to_string(&answer).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to get the user linked to a sub author
+ */
+struct SubAuthorHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for SubAuthorHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let hash = String::from(request.extensions.get::<Router>().unwrap().find("hash").unwrap_or(""));
+        let sa = String::from(request.extensions.get::<Router>().unwrap().find("sa").unwrap_or(""));
+        info!("GET /bridge/{}/sub_authors/{}", hash, sa);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        if let Err(response) = check_bridge(&hash) {
+            return Ok(response);
+        }
+        match Database::sub_author(&hash, &sa) {
+            Ok(ref username) if username.is_empty() => Ok(error_response(&RoriError::NotFound(format!("sub author {}", sa)))),
+            Ok(username) => {
+                let response = serde_json::to_string(&SubAuthorMapping { sa, username }).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to unlink a sub author from its user
+ */
+struct SubAuthorRmHandler {
+    manager: Arc<Mutex<Manager>>,
+    admin_token: AdminToken
+}
+
+impl Handler for SubAuthorRmHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let hash = String::from(request.extensions.get::<Router>().unwrap().find("hash").unwrap_or(""));
+        let sa = String::from(request.extensions.get::<Router>().unwrap().find("sa").unwrap_or(""));
+        info!("DELETE /bridge/{}/sub_authors/{}", hash, sa);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        if let Err(response) = check_bridge(&hash) {
+            return Ok(response);
+        }
+        match self.manager.lock().unwrap().server.remove_sub_author(&hash, &sa) {
+            Ok(Some(username)) => {
+                let response = serde_json::to_string(&SubAuthorMapping { sa, username }).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Ok(None) => Ok(error_response(&RoriError::NotFound(format!("sub author {}", sa)))),
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Get a group
+ * @param name of the group
+ * @return the group or an error response if not found
+ */
+fn find_group(name: &String) -> Result<Group, Response> {
+    match Database::get_group(name) {
+        Ok(Some(group)) => Ok(group),
+        Ok(None) => Err(error_response(&RoriError::NotFound(format!("group {}", name)))),
+        Err(e) => Err(error_response(&e))
+    }
+}
+
+/**
+ * Build the answer for a group
+ * @param name of the group
+ * @return the group as JSON, or an error response
+ */
+fn group_response(name: &String) -> Response {
+    let content_type = "application/json".parse::<Mime>().unwrap();
+    match find_group(name) {
+        Ok(group) => {
+            let response = serde_json::to_string(&group).unwrap_or(String::new());
+            Response::with((content_type, status::Ok, response))
+        },
+        Err(response) => response
+    }
+}
+
+/**
+ * Used to list groups
+ */
+struct GroupsHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for GroupsHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        info!("GET /groups");
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        match Database::get_groups() {
+            Ok(groups) => {
+                let response = serde_json::to_string(&groups).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to get the members of a group
+ */
+struct GroupHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for GroupHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let name = String::from(request.extensions.get::<Router>().unwrap().find("name").unwrap_or(""));
+        info!("GET /group/{}", name);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        Ok(group_response(&name))
+    }
+}
+
+/**
+ * Used to create a group
+ */
+struct GroupAddHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for GroupAddHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let name = String::from(request.extensions.get::<Router>().unwrap().find("name").unwrap_or(""));
+        info!("POST /group/{}", name);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        match Database::add_group(&name, &String::new()) {
+            Ok(_) => Ok(group_response(&name)),
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to remove a group
+ */
+struct GroupRmHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for GroupRmHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        let name = String::from(request.extensions.get::<Router>().unwrap().find("name").unwrap_or(""));
+        info!("DELETE /group/{}", name);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        let group = match find_group(&name) {
+            Ok(group) => group,
+            Err(response) => return Ok(response)
+        };
+        match Database::rm_group(&name) {
+            Ok(_) => {
+                let response = serde_json::to_string(&group).unwrap_or(String::new());
+                Ok(Response::with((content_type, status::Ok, response)))
+            },
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to add an user to a group
+ */
+struct GroupMemberAddHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for GroupMemberAddHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let name = String::from(request.extensions.get::<Router>().unwrap().find("name").unwrap_or(""));
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        info!("PUT /group/{}/members/{}", name, username);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        if let Err(response) = find_group(&name) {
+            return Ok(response);
+        }
+        match Database::get_user(&username) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Ok(error_response(&RoriError::NotFound(format!("user {}", username)))),
+            Err(e) => return Ok(error_response(&e))
+        }
+        match Database::add_group_member(&name, &username) {
+            Ok(_) => Ok(group_response(&name)),
+            Err(e) => Ok(error_response(&e))
+        }
+    }
+}
+
+/**
+ * Used to remove an user from a group
+ */
+struct GroupMemberRmHandler {
+    admin_token: AdminToken
+}
+
+impl Handler for GroupMemberRmHandler {
+    fn handle(&self, request: &mut Request) -> IronResult<Response> {
+        let name = String::from(request.extensions.get::<Router>().unwrap().find("name").unwrap_or(""));
+        let username = String::from(request.extensions.get::<Router>().unwrap().find("username").unwrap_or(""));
+        info!("DELETE /group/{}/members/{}", name, username);
+
+        if let Err(response) = self.admin_token.check(request) {
+            return Ok(response);
+        }
+        if let Err(response) = find_group(&name) {
+            return Ok(response);
+        }
+        match Database::rm_group_member(&name, &username) {
+            Ok(0) => Ok(error_response(&RoriError::NotFound(format!("member {}", username)))),
+            Ok(_) => Ok(group_response(&name)),
+            Err(e) => Ok(error_response(&e))
+        }
     }
 }
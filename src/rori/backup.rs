@@ -0,0 +1,241 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use openssl::rand::rand_bytes;
+use rori::database::{Database, MIGRATIONS};
+use rori::error::{RoriError, RoriResult};
+use rori::manager::Manager;
+use serde_json::{self, Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions, Permissions};
+use std::io::prelude::*;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+use time;
+
+// Version of the archive format, increased when the format changes
+pub const BACKUP_VERSION: i32 = 1;
+// Tables saved in an archive. NOTE: history and unmatched messages are not saved
+pub const TABLES: [&'static str; 15] = ["users", "devices", "modules", "scheduler", "emotions", "languages", "module_store",
+                                           "vault", "secrets", "preferences", "device_kinds", "capabilities",
+                                           "groups", "group_members", "bridges"];
+
+/**
+ * State of RORI, saved as JSON to move an instance to another host
+ */
+#[derive(Deserialize, Serialize)]
+pub struct Backup {
+    // Version of the format (see BACKUP_VERSION)
+    pub version: i32,
+    // Version of the database schema of the tables
+    pub schema: i32,
+    // Timestamp of the backup
+    pub created: i64,
+    // Content of config.json
+    pub config: Value,
+    // Rows (column -> value) of each table
+    pub tables: BTreeMap<String, Vec<Map<String, Value>>>,
+    // Archive of the Jami account exported by the daemon, hex encoded
+    pub account: Option<String>,
+}
+
+impl Backup {
+    /**
+     * Save the current database (and the account if wanted). The database is not modified
+     * @param config content of config.json
+     * @param account_password if set, the account of the config is exported with this password
+     * @return the backup, or a parse error if the schema is not the current one
+     */
+    pub fn create(config: &Value, account_password: Option<&str>) -> RoriResult<Backup> {
+        let schema = Database::version()?;
+        if schema != MIGRATIONS.len() as i32 {
+            return Err(RoriError::Parse(format!("database schema {} is not {}, run rori --migrate-only first",
+                                                schema, MIGRATIONS.len())));
+        }
+        let mut tables = BTreeMap::new();
+        for table in TABLES.iter() {
+            tables.insert(table.to_string(), Database::dump_table(table)?);
+        }
+        let account = match account_password {
+            Some(password) => {
+                let ring_id = config["ring_id"].as_str().unwrap_or("");
+                Some(to_hex(&Backup::export_account(ring_id, password)?))
+            },
+            None => None
+        };
+        Ok(Backup {
+            version: BACKUP_VERSION,
+            schema: schema,
+            created: time::get_time().sec,
+            config: config.clone(),
+            tables: tables,
+            account: account,
+        })
+    }
+
+    /**
+     * Read and validate an archive
+     * @param path of the archive
+     * @return the backup or a parse error if invalid
+     */
+    pub fn load(path: &str) -> RoriResult<Backup> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let backup: Backup = serde_json::from_str(&*content)?;
+        backup.validate()?;
+        Ok(backup)
+    }
+
+    /**
+     * Write the archive, only readable by its owner (0600)
+     * @param self
+     * @param path of the archive
+     * @return an error if the file can't be written
+     */
+    pub fn save(&self, path: &str) -> RoriResult<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+        // mode is only used if the file is created
+        file.set_permissions(Permissions::from_mode(0o600))?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /**
+     * Fill an empty database with the archive, then migrate it to the current schema
+     * @param self
+     * @return the version of the database or an error (and nothing is inserted)
+     */
+    pub fn restore_database(&self) -> RoriResult<i32> {
+        self.validate()?;
+        if Database::version()? != 0 {
+            return Err(RoriError::AlreadyExists(format!("database {}", Database::path())));
+        }
+        // Rows are inserted in the schema they were saved with
+        Database::migrate_to(self.schema)?;
+        Database::import_tables(&self.tables)?;
+        Database::migrate()
+    }
+
+    /**
+     * Import the account of the archive in the daemon
+     * @param self
+     * @param password of the account archive
+     * @return the id of the new account, or None if there is no account in the archive
+     */
+    pub fn restore_account(&self, password: &str) -> RoriResult<Option<String>> {
+        let account = match self.account {
+            Some(ref account) => from_hex(account)?,
+            None => return Ok(None)
+        };
+        let dir = Backup::private_temp_dir()?;
+        let path = dir.join("account.gz").to_string_lossy().to_string();
+        let result = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&*path)
+                     .and_then(|mut file| file.write_all(&account)).map_err(RoriError::from)
+                     .and_then(|_| Manager::add_account(&*path, password, true));
+        let _ = fs::remove_dir_all(&dir);
+        Ok(Some(result?))
+    }
+
+    /**
+     * Check that this RORI can restore the archive
+     * @param self
+     * @return a parse error if the archive is invalid
+     */
+    fn validate(&self) -> RoriResult<()> {
+        if self.version < 1 || self.version > BACKUP_VERSION {
+            return Err(RoriError::Parse(format!("unsupported backup version {}", self.version)));
+        }
+        if self.schema < 1 || self.schema as usize > MIGRATIONS.len() {
+            return Err(RoriError::Parse(format!("unsupported schema version {}", self.schema)));
+        }
+        if !self.config.is_object() {
+            return Err(RoriError::Parse(String::from("invalid config")));
+        }
+        for table in self.tables.keys() {
+            if !TABLES.contains(&&**table) {
+                return Err(RoriError::Parse(format!("unexpected table {}", table)));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Ask the daemon to export an account
+     * @param ring_id of the account
+     * @param password to protect the archive
+     * @return the content of the archive
+     */
+    fn export_account(ring_id: &str, password: &str) -> RoriResult<Vec<u8>> {
+        let dir = Backup::private_temp_dir()?;
+        let path = dir.join("account.gz").to_string_lossy().to_string();
+        let result = Manager::export_account(ring_id, &*path, password).and_then(|_| {
+            let mut content = Vec::new();
+            File::open(&*path)?.read_to_end(&mut content)?;
+            Ok(content)
+        });
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    /**
+     * Create a directory with a random name in the temporary directory, only accessible by its
+     * owner (0700), to exchange account archives with the daemon
+     * @return the path of the directory, to remove after use
+     */
+    fn private_temp_dir() -> RoriResult<PathBuf> {
+        let mut random = [0; 16];
+        rand_bytes(&mut random)?;
+        let path = env::temp_dir().join(format!("rori-{}", to_hex(&random)));
+        // Fails if the path already exists
+        DirBuilder::new().mode(0o700).create(&path)?;
+        Ok(path)
+    }
+}
+
+/**
+ * @param bytes
+ * @return lowercase hexadecimal representation of bytes
+ */
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/**
+ * @param hex string built by to_hex
+ * @return the bytes or a parse error
+ */
+pub fn from_hex(hex: &str) -> RoriResult<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(RoriError::Parse(String::from("invalid hexadecimal string")));
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map_err(|_| RoriError::Parse(String::from("invalid hexadecimal string"))))
+        .collect()
+}
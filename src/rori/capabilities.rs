@@ -0,0 +1,151 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::database::Database;
+use rori::error::{RoriError, RoriResult};
+use rori::reply::PROTOCOL_VERSION;
+use rori::routing::TEXT;
+use serde_json::{self, Value};
+
+// Datatype of the announcements sent by the devices
+pub const DATATYPE: &'static str = "rori/capabilities";
+
+/**
+ * What a device is
+ */
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Speaker,
+    Screen,
+    Shell,
+}
+
+impl Kind {
+    /**
+     * @return the name of the kind, as stored in the database
+     */
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Kind::Speaker => "speaker",
+            Kind::Screen => "screen",
+            Kind::Shell => "shell",
+        }
+    }
+
+    /**
+     * @param kind name of the kind
+     * @return the kind, None if unknown
+     */
+    pub fn parse(kind: &str) -> Option<Kind> {
+        match kind {
+            "speaker" => Some(Kind::Speaker),
+            "screen" => Some(Kind::Screen),
+            "shell" => Some(Kind::Shell),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * An action supported by a device
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Action {
+    // Datatype of the messages executing this action (e.g. music/play)
+    pub datatype: String,
+    // JSON schema of the parameters of the action (null if none)
+    #[serde(default)]
+    pub parameters: Value,
+}
+
+/**
+ * Capabilities announced by a device
+ */
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Capabilities {
+    #[serde(default)]
+    pub kinds: Vec<Kind>,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+}
+
+impl Capabilities {
+    /**
+     * @param datatype
+     * @return the action executed by this datatype, if announced
+     */
+    pub fn action(&self, datatype: &str) -> Option<&Action> {
+        self.actions.iter().find(|action| action.datatype == datatype)
+    }
+}
+
+#[derive(Deserialize)]
+struct Announcement {
+    version: u32,
+    #[serde(flatten)]
+    capabilities: Capabilities,
+}
+
+/**
+ * Parse the rori/capabilities message of a device:
+ * {"version":1,"kinds":["speaker"],"actions":[{"datatype":"music/play","parameters":{"type":"object"}}]}
+ * @param body of the message
+ * @return the capabilities, an error if invalid or from a newer protocol
+ */
+pub fn parse(body: &str) -> RoriResult<Capabilities> {
+    let announcement: Announcement = serde_json::from_str(body)?;
+    if announcement.version > PROTOCOL_VERSION {
+        return Err(RoriError::Parse(format!("unsupported protocol version {}", announcement.version)));
+    }
+    for action in &announcement.capabilities.actions {
+        if action.datatype.is_empty() || action.datatype.contains(char::is_whitespace) {
+            return Err(RoriError::Parse(format!("invalid datatype \"{}\"", action.datatype)));
+        }
+        if !action.parameters.is_null() && !action.parameters.is_object() {
+            return Err(RoriError::Parse(format!("parameters of {} must be a JSON schema", action.datatype)));
+        }
+    }
+    Ok(announcement.capabilities)
+}
+
+/**
+ * Check if a device can receive a datatype, because it announced this action or
+ * because the datatype was added with /add_types
+ * @param id of the device
+ * @param datatype (text/plain is handled by every device)
+ * @return if the device handles the datatype
+ */
+pub fn handles(id: &i32, datatype: &String) -> RoriResult<bool> {
+    if datatype == TEXT {
+        return Ok(true);
+    }
+    if Database::get_capabilities(id)?.action(datatype).is_some() {
+        return Ok(true);
+    }
+    Ok(Database::get_datatypes(id)?.contains(datatype))
+}
@@ -0,0 +1,461 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::repository::Role;
+
+/**
+ * Who can send a command
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    // Only devices without user
+    Anonymous,
+    // Only registered users
+    Registered,
+    // Everybody
+    Any,
+}
+
+/**
+ * A parsed rori/command
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Order {
+    Register { username: String },
+    AddDevice { name: String, ring_id: Option<String> },
+    RmDevice { ring_id: Option<String> },
+    Unregister,
+    Rename { username: String },
+    RenameDevice { name: String, new_name: String },
+    Lang { lang: String },
+    Prefer { device: String, datatype: String },
+    RmPrefer { datatype: String },
+    Groups,
+    AddGroup { name: String },
+    RmGroup { name: String },
+    AddMember { group: String, username: String },
+    RmMember { group: String, username: String },
+    Export,
+    ForgetMe,
+    History { page: u32, words: String },
+    SetRole { username: String, role: Role },
+    EnableModule { name: String, enabled: bool },
+    RmTask { id: i32 },
+    AddTypes { device: Option<String>, types: Vec<String> },
+    RmTypes { device: Option<String>, types: Vec<String> },
+    SetTypes { device: Option<String>, types: Vec<String> },
+    Bridgify { ring_id: Option<String> },
+    SubAuthors { bridge: String },
+    SubAuthor { bridge: String, name: String },
+    RmSubAuthor { bridge: String, sub_author: String },
+    Link { argument: String, code: String },
+    Help,
+}
+
+/**
+ * A command of the registry
+ */
+pub struct Command {
+    // Name with the leading /
+    pub name: &'static str,
+    // Arguments, <required> and [optional]
+    pub usage: &'static str,
+    pub description: &'static str,
+    // Minimal role of the author (User for everybody)
+    pub role: Role,
+    pub scope: Scope,
+    // Build the order from the arguments, None if malformed
+    pub parser: fn(&[&str]) -> Option<Order>,
+}
+
+impl Command {
+    /**
+     * @param self
+     * @param role of the author (None if anonymous)
+     * @return if the author can send this command
+     */
+    pub fn is_available(&self, role: Option<Role>) -> bool {
+        match role {
+            Some(role) => self.scope != Scope::Anonymous && role.allows(self.role),
+            None => self.scope != Scope::Registered && self.role == Role::User,
+        }
+    }
+
+    /**
+     * @param self
+     * @return the full usage, e.g. "/lang <lang>"
+     */
+    pub fn full_usage(&self) -> String {
+        if self.usage.is_empty() {
+            return String::from(self.name);
+        }
+        format!("{} {}", self.name, self.usage)
+    }
+}
+
+/**
+ * Result of the parsing of a command
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parsed {
+    // Not in the registry (can be handled by a module)
+    Unknown(String),
+    // Arguments don't match the usage of the command
+    Malformed(&'static Command),
+    Valid(&'static Command, Order),
+}
+
+/**
+ * All commands handled by the server
+ */
+pub static COMMANDS: &'static [Command] = &[
+    Command { name: "/help", usage: "", description: "list available commands",
+              role: Role::User, scope: Scope::Any, parser: parse_help },
+    Command { name: "/register", usage: "<username>", description: "register this device with a new username",
+              role: Role::User, scope: Scope::Anonymous, parser: parse_register },
+    Command { name: "/link", usage: "<username> [code] | <ring_id>", description: "link a new device to an user (send from both devices)",
+              role: Role::User, scope: Scope::Any, parser: parse_link },
+    Command { name: "/add_device", usage: "<name> [ring_id]", description: "name a device",
+              role: Role::User, scope: Scope::Registered, parser: parse_add_device },
+    Command { name: "/rm_device", usage: "[ring_id]", description: "revoke a device",
+              role: Role::User, scope: Scope::Registered, parser: parse_rm_device },
+    Command { name: "/rename", usage: "<username>", description: "change your username",
+              role: Role::User, scope: Scope::Registered, parser: parse_rename },
+    Command { name: "/rename_device", usage: "<name> <new_name>", description: "change the name of a device",
+              role: Role::User, scope: Scope::Registered, parser: parse_rename_device },
+    Command { name: "/unregister", usage: "", description: "unregister this device",
+              role: Role::User, scope: Scope::Registered, parser: parse_unregister },
+    Command { name: "/lang", usage: "<lang>", description: "change the language",
+              role: Role::User, scope: Scope::Registered, parser: parse_lang },
+    Command { name: "/prefer", usage: "<device> [datatype]", description: "choose the device receiving messages (of a datatype)",
+              role: Role::User, scope: Scope::Registered, parser: parse_prefer },
+    Command { name: "/rm_prefer", usage: "[datatype]", description: "forget the preferred device (of a datatype)",
+              role: Role::User, scope: Scope::Registered, parser: parse_rm_prefer },
+    Command { name: "/groups", usage: "", description: "list your groups",
+              role: Role::User, scope: Scope::Registered, parser: parse_groups },
+    Command { name: "/add_group", usage: "<name>", description: "create a group, you are its first member",
+              role: Role::User, scope: Scope::Registered, parser: parse_add_group },
+    Command { name: "/rm_group", usage: "<name>", description: "remove one of your groups",
+              role: Role::User, scope: Scope::Registered, parser: parse_rm_group },
+    Command { name: "/add_member", usage: "<group> <username>", description: "add an user to one of your groups",
+              role: Role::User, scope: Scope::Registered, parser: parse_add_member },
+    Command { name: "/rm_member", usage: "<group> <username>", description: "remove an user from one of your groups",
+              role: Role::User, scope: Scope::Registered, parser: parse_rm_member },
+    Command { name: "/export", usage: "", description: "get all your data",
+              role: Role::User, scope: Scope::Registered, parser: parse_export },
+    Command { name: "/forget_me", usage: "", description: "remove all your data",
+              role: Role::User, scope: Scope::Registered, parser: parse_forget_me },
+    Command { name: "/history", usage: "[page] [words]", description: "search old messages",
+              role: Role::User, scope: Scope::Registered, parser: parse_history },
+    Command { name: "/add_types", usage: "[--device ring_id] <types>", description: "add datatypes handled by a device",
+              role: Role::User, scope: Scope::Any, parser: parse_add_types },
+    Command { name: "/rm_types", usage: "[--device ring_id] <types>", description: "remove datatypes handled by a device",
+              role: Role::User, scope: Scope::Any, parser: parse_rm_types },
+    Command { name: "/set_types", usage: "[--device ring_id] [types]", description: "set datatypes handled by a device",
+              role: Role::User, scope: Scope::Any, parser: parse_set_types },
+    Command { name: "/bridgify", usage: "<ring_id>", description: "turn an anonymous device into a bridge",
+              role: Role::BridgeOperator, scope: Scope::Registered, parser: parse_bridgify },
+    Command { name: "/sub_authors", usage: "<bridge>", description: "list the sub authors of a bridge and their users",
+              role: Role::BridgeOperator, scope: Scope::Registered, parser: parse_sub_authors },
+    Command { name: "/sub_author", usage: "<bridge> <sub_author|username>", description: "show the user of a sub author (or the sub author of an user)",
+              role: Role::BridgeOperator, scope: Scope::Registered, parser: parse_sub_author },
+    Command { name: "/rm_sub_author", usage: "<bridge> <sub_author>", description: "unlink a sub author from its user",
+              role: Role::BridgeOperator, scope: Scope::Registered, parser: parse_rm_sub_author },
+    Command { name: "/role", usage: "<username> <user|bridge-operator|admin>", description: "change the role of an user",
+              role: Role::Admin, scope: Scope::Registered, parser: parse_role },
+    Command { name: "/module", usage: "<enable|disable> <name>", description: "enable or disable a module",
+              role: Role::Admin, scope: Scope::Registered, parser: parse_module },
+    Command { name: "/task", usage: "rm <id>", description: "remove a scheduled task",
+              role: Role::Admin, scope: Scope::Registered, parser: parse_task },
+];
+
+/**
+ * Parse a rori/command
+ * @param body of the interaction, e.g. "/lang fr"
+ * @return the command and its order if valid
+ */
+pub fn parse(body: &str) -> Parsed {
+    let mut words: Vec<&str> = body.split_whitespace().collect();
+    let name = if words.is_empty() { "" } else { words.remove(0) };
+    match find(name) {
+        Some(command) => match (command.parser)(&words) {
+            Some(order) => Parsed::Valid(command, order),
+            None => Parsed::Malformed(command),
+        },
+        None => Parsed::Unknown(String::from(name)),
+    }
+}
+
+/**
+ * @param name of the command with the leading /
+ * @return the command if in the registry
+ */
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/**
+ * Build the answer of /help
+ * @param role of the author (None if anonymous)
+ * @return one line per available command
+ */
+pub fn help(role: Option<Role>) -> String {
+    COMMANDS.iter()
+        .filter(|command| command.is_available(role))
+        .map(|command| format!("{}: {}", command.full_usage(), command.description))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+impl PartialEq for Command {
+    fn eq(&self, other: &Command) -> bool {
+        self.name == other.name
+    }
+}
+
+impl ::std::fmt::Debug for Command {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Parsers
+
+fn parse_help(args: &[&str]) -> Option<Order> {
+    if args.is_empty() { Some(Order::Help) } else { None }
+}
+
+fn parse_register(args: &[&str]) -> Option<Order> {
+    match args {
+        [username] => Some(Order::Register { username: username.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_link(args: &[&str]) -> Option<Order> {
+    match args {
+        [argument] => Some(Order::Link { argument: argument.to_string(), code: String::new() }),
+        [argument, code] => Some(Order::Link { argument: argument.to_string(), code: code.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_add_device(args: &[&str]) -> Option<Order> {
+    match args {
+        [name] => Some(Order::AddDevice { name: name.to_string(), ring_id: None }),
+        [name, ring_id] => Some(Order::AddDevice { name: name.to_string(), ring_id: Some(ring_id.to_string()) }),
+        _ => None,
+    }
+}
+
+fn parse_rm_device(args: &[&str]) -> Option<Order> {
+    match args {
+        [] => Some(Order::RmDevice { ring_id: None }),
+        [ring_id] => Some(Order::RmDevice { ring_id: Some(ring_id.to_string()) }),
+        _ => None,
+    }
+}
+
+fn parse_rename(args: &[&str]) -> Option<Order> {
+    match args {
+        [username] => Some(Order::Rename { username: username.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_rename_device(args: &[&str]) -> Option<Order> {
+    match args {
+        [name, new_name] => Some(Order::RenameDevice { name: name.to_string(), new_name: new_name.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_unregister(args: &[&str]) -> Option<Order> {
+    if args.is_empty() { Some(Order::Unregister) } else { None }
+}
+
+fn parse_lang(args: &[&str]) -> Option<Order> {
+    match args {
+        [lang] => Some(Order::Lang { lang: lang.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_prefer(args: &[&str]) -> Option<Order> {
+    match args {
+        [device] => Some(Order::Prefer { device: device.to_string(), datatype: String::new() }),
+        [device, datatype] => Some(Order::Prefer { device: device.to_string(), datatype: datatype.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_rm_prefer(args: &[&str]) -> Option<Order> {
+    match args {
+        [] => Some(Order::RmPrefer { datatype: String::new() }),
+        [datatype] => Some(Order::RmPrefer { datatype: datatype.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_groups(args: &[&str]) -> Option<Order> {
+    if args.is_empty() { Some(Order::Groups) } else { None }
+}
+
+fn parse_add_group(args: &[&str]) -> Option<Order> {
+    match args {
+        [name] => Some(Order::AddGroup { name: name.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_rm_group(args: &[&str]) -> Option<Order> {
+    match args {
+        [name] => Some(Order::RmGroup { name: name.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_add_member(args: &[&str]) -> Option<Order> {
+    match args {
+        [group, username] => Some(Order::AddMember { group: group.to_string(), username: username.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_rm_member(args: &[&str]) -> Option<Order> {
+    match args {
+        [group, username] => Some(Order::RmMember { group: group.to_string(), username: username.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_export(args: &[&str]) -> Option<Order> {
+    if args.is_empty() { Some(Order::Export) } else { None }
+}
+
+fn parse_forget_me(args: &[&str]) -> Option<Order> {
+    if args.is_empty() { Some(Order::ForgetMe) } else { None }
+}
+
+fn parse_history(args: &[&str]) -> Option<Order> {
+    let mut words = args.to_vec();
+    let mut page = 1;
+    if let Some(first) = words.first().and_then(|word| word.parse::<u32>().ok()) {
+        page = first;
+        words.remove(0);
+    }
+    if page == 0 {
+        return None;
+    }
+    Some(Order::History { page: page, words: words.join(" ") })
+}
+
+/**
+ * Parse [--device ring_id] types
+ * @param args
+ * @return (device, types), None if --device has no value
+ */
+fn parse_types(args: &[&str]) -> Option<(Option<String>, Vec<String>)> {
+    let mut args = args.to_vec();
+    let mut device = None;
+    if args.first() == Some(&"--device") {
+        if args.len() < 2 {
+            return None;
+        }
+        device = Some(args[1].to_string());
+        args.drain(..2);
+    }
+    Some((device, args.iter().map(|t| t.to_string()).collect()))
+}
+
+fn parse_add_types(args: &[&str]) -> Option<Order> {
+    match parse_types(args) {
+        Some((device, types)) if !types.is_empty() => Some(Order::AddTypes { device: device, types: types }),
+        _ => None,
+    }
+}
+
+fn parse_rm_types(args: &[&str]) -> Option<Order> {
+    match parse_types(args) {
+        Some((device, types)) if !types.is_empty() => Some(Order::RmTypes { device: device, types: types }),
+        _ => None,
+    }
+}
+
+fn parse_set_types(args: &[&str]) -> Option<Order> {
+    parse_types(args).map(|(device, types)| Order::SetTypes { device: device, types: types })
+}
+
+fn parse_bridgify(args: &[&str]) -> Option<Order> {
+    match args {
+        [ring_id] => Some(Order::Bridgify { ring_id: Some(ring_id.to_string()) }),
+        _ => None,
+    }
+}
+
+fn parse_sub_authors(args: &[&str]) -> Option<Order> {
+    match args {
+        [bridge] => Some(Order::SubAuthors { bridge: bridge.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_sub_author(args: &[&str]) -> Option<Order> {
+    match args {
+        [bridge, name] => Some(Order::SubAuthor { bridge: bridge.to_string(), name: name.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_rm_sub_author(args: &[&str]) -> Option<Order> {
+    match args {
+        [bridge, sub_author] => Some(Order::RmSubAuthor { bridge: bridge.to_string(), sub_author: sub_author.to_string() }),
+        _ => None,
+    }
+}
+
+fn parse_role(args: &[&str]) -> Option<Order> {
+    match args {
+        [username, role] => Role::parse(role).map(|role| Order::SetRole { username: username.to_string(), role: role }),
+        _ => None,
+    }
+}
+
+fn parse_module(args: &[&str]) -> Option<Order> {
+    match args {
+        ["enable", name] => Some(Order::EnableModule { name: name.to_string(), enabled: true }),
+        ["disable", name] => Some(Order::EnableModule { name: name.to_string(), enabled: false }),
+        _ => None,
+    }
+}
+
+fn parse_task(args: &[&str]) -> Option<Order> {
+    match args {
+        ["rm", id] => id.parse::<i32>().ok().map(|id| Order::RmTask { id: id }),
+        _ => None,
+    }
+}
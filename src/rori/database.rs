@@ -25,12 +25,265 @@
  * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  **/
 
+use rori::capabilities::{Action, Capabilities, Kind};
+use rori::emotions::Emotions;
+use rori::error::{RoriError, RoriResult};
+use rori::group::Group;
+use rori::history::{self, HistoryEntry};
+use rori::interaction::Interaction;
+use rori::link::LinkRequest;
 use rori::module::*;
+use rori::repository::{DeviceRecord, Repository, Role, UserRecord};
 use rori::scheduler::ScheduledTask;
+use rori::vault::{Secret, Vault};
 use rusqlite;
+use rusqlite::types::Value as SqlValue;
+use serde_json::{self, Map, Number, Value};
+use std::collections::BTreeMap;
 use std::collections::HashMap;
-use std::error::Error;
-use string_error::static_err;
+use std::fs;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use time;
+
+// Tables with a username column, exported and purged for an user
+const USER_TABLES: [&'static str; 7] = ["users", "emotions", "languages", "interactions", "unmatched", "preferences",
+                                        "group_members"];
+// Max number of idle connections kept by the pool
+const MAX_IDLE_CONNECTIONS: usize = 8;
+// How long (in ms) a connection waits for a locked database
+const BUSY_TIMEOUT: u64 = 5000;
+
+/**
+ * Migrations of the schema. MIGRATIONS[i] upgrades the database from version i to i + 1.
+ * NOTE: never change a released migration, add a new one.
+ */
+pub const MIGRATIONS: [&'static str; 15] = [
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS devices (
+        id               INTEGER PRIMARY KEY,
+        hash             TEXT,
+        username         TEXT,
+        sub_author       TEXT,
+        devicename       TEXT,
+        additional_types TEXT,
+        is_bridge        INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS modules (
+        id          INTEGER PRIMARY KEY,
+        name        TEXT,
+        priority    INTEGER,
+        enabled     BOOLEAN,
+        type        TEXT,
+        condition   TEXT,
+        path        TEXT
+    );
+    CREATE TABLE IF NOT EXISTS emotions (
+        username    TEXT PRIMARY KEY,
+        love        INTEGER,
+        joy         INTEGER,
+        surprise    INTEGER,
+        anger       INTEGER,
+        sadness     INTEGER,
+        fear        INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS scheduler (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        module      INTEGER,
+        parameter   TEXT,
+        at          TEXT,
+        seconds     INTEGER,
+        minutes     INTEGER,
+        hours       INTEGER,
+        days        STRING,
+        repeat      INTEGER,
+        FOREIGN KEY (module) REFERENCES modules(id)
+    );",
+    // 2: interactions not understood
+    "CREATE TABLE IF NOT EXISTS unmatched (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        username    TEXT,
+        device      INTEGER,
+        datatype    TEXT,
+        body        TEXT,
+        time        TEXT
+    );",
+    // 3: values stored by modules
+    "CREATE TABLE IF NOT EXISTS module_store (
+        module      TEXT,
+        key         TEXT,
+        value       TEXT,
+        PRIMARY KEY (module, key)
+    );",
+    // 4: last update of emotions
+    "ALTER TABLE emotions ADD COLUMN updated INTEGER DEFAULT 0;",
+    // 5: languages of users
+    "CREATE TABLE IF NOT EXISTS languages (
+        username    TEXT PRIMARY KEY,
+        lang        TEXT
+    );",
+    // 6: users table, devices are linked to users via user_id
+    "CREATE TABLE IF NOT EXISTS users (
+        id           INTEGER PRIMARY KEY,
+        username     TEXT NOT NULL UNIQUE,
+        display_name TEXT NOT NULL DEFAULT '',
+        created      INTEGER NOT NULL DEFAULT 0
+    );
+    INSERT INTO users (username, created)
+        SELECT DISTINCT username, CAST(strftime('%s', 'now') AS INTEGER) FROM devices
+        WHERE username IS NOT NULL AND username!='';
+    CREATE TABLE devices_v6 (
+        id               INTEGER PRIMARY KEY,
+        hash             TEXT,
+        user_id          INTEGER REFERENCES users(id),
+        sub_author       TEXT,
+        devicename       TEXT,
+        additional_types TEXT,
+        is_bridge        INTEGER
+    );
+    INSERT INTO devices_v6 (id, hash, user_id, sub_author, devicename, additional_types, is_bridge)
+        SELECT id, hash, (SELECT users.id FROM users WHERE users.username=devices.username),
+               sub_author, devicename, additional_types, is_bridge FROM devices;
+    DROP TABLE devices;
+    ALTER TABLE devices_v6 RENAME TO devices;
+    CREATE VIEW IF NOT EXISTS user_devices AS
+        SELECT devices.id, devices.hash, COALESCE(users.username, '') AS username, devices.sub_author,
+               devices.devicename, devices.additional_types, devices.is_bridge
+        FROM devices LEFT JOIN users ON users.id=devices.user_id;",
+    // 7: history of interactions, with a full-text index on bodies
+    "CREATE TABLE IF NOT EXISTS interactions (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        username    TEXT,
+        device      TEXT,
+        outgoing    INTEGER,
+        datatype    TEXT,
+        body        TEXT,
+        time        INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS interactions_username ON interactions (username, time);
+    CREATE VIRTUAL TABLE IF NOT EXISTS interactions_fts USING fts5(body, content='interactions', content_rowid='id');
+    CREATE TRIGGER IF NOT EXISTS interactions_insert AFTER INSERT ON interactions BEGIN
+        INSERT INTO interactions_fts (rowid, body) VALUES (new.id, new.body);
+    END;
+    CREATE TRIGGER IF NOT EXISTS interactions_delete AFTER DELETE ON interactions BEGIN
+        INSERT INTO interactions_fts (interactions_fts, rowid, body) VALUES ('delete', old.id, old.body);
+    END;",
+    // 8: encrypted secrets of modules (username is empty for secrets shared by all users)
+    "CREATE TABLE IF NOT EXISTS vault (
+        id          INTEGER PRIMARY KEY CHECK (id=1),
+        salt        TEXT,
+        verifier    TEXT
+    );
+    CREATE TABLE IF NOT EXISTS secrets (
+        module      TEXT NOT NULL,
+        username    TEXT NOT NULL DEFAULT '',
+        name        TEXT NOT NULL,
+        value       TEXT NOT NULL,
+        updated     INTEGER,
+        PRIMARY KEY (module, username, name)
+    );",
+    // 9: roles of users (user, bridge-operator or admin)
+    "ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user';",
+    // 10: pending /link between a device and an user
+    "CREATE TABLE IF NOT EXISTS link_requests (
+        id          INTEGER PRIMARY KEY,
+        hash        TEXT NOT NULL,
+        username    TEXT NOT NULL,
+        from_user   INTEGER NOT NULL,
+        sub_author  TEXT NOT NULL DEFAULT '',
+        code        TEXT NOT NULL DEFAULT '',
+        expires     INTEGER NOT NULL,
+        UNIQUE (hash, username)
+    );",
+    // 11: devices preferred by users for a datatype (empty datatype for every datatype)
+    "CREATE TABLE IF NOT EXISTS preferences (
+        username    TEXT NOT NULL,
+        datatype    TEXT NOT NULL DEFAULT '',
+        device      INTEGER NOT NULL,
+        PRIMARY KEY (username, datatype)
+    );",
+    // 12: capabilities announced by devices (kinds and actions with the JSON schema of their parameters)
+    "CREATE TABLE IF NOT EXISTS device_kinds (
+        device      INTEGER NOT NULL,
+        kind        TEXT NOT NULL,
+        PRIMARY KEY (device, kind)
+    );
+    CREATE TABLE IF NOT EXISTS capabilities (
+        device      INTEGER NOT NULL,
+        datatype    TEXT NOT NULL,
+        parameters  TEXT NOT NULL DEFAULT 'null',
+        PRIMARY KEY (device, datatype)
+    );",
+    // 13: groups of users (names of groups and users are unique together)
+    "CREATE TABLE IF NOT EXISTS groups (
+        id          INTEGER PRIMARY KEY,
+        name        TEXT NOT NULL UNIQUE,
+        created     INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS group_members (
+        group_id    INTEGER NOT NULL,
+        username    TEXT NOT NULL,
+        PRIMARY KEY (group_id, username)
+    );",
+    // 14: bridge operators owning bridges (NULL for bridges created before or if the operator is removed)
+    "CREATE TABLE IF NOT EXISTS bridges (
+        hash        TEXT PRIMARY KEY,
+        owner       INTEGER REFERENCES users(id) ON DELETE SET NULL
+    );",
+    // 15: users owning groups (NULL for groups created before, from the API or if the user is removed)
+    "ALTER TABLE groups ADD COLUMN owner INTEGER REFERENCES users(id) ON DELETE SET NULL;",
+];
+
+/**
+ * Connections shared by module threads, the scheduler and the API
+ */
+struct Pool {
+    path: String,
+    // Increased when the database changes, to close connections on the previous database
+    generation: u64,
+    connections: Vec<rusqlite::Connection>,
+}
+
+lazy_static! {
+    static ref POOL: Mutex<Pool> = Mutex::new(Pool {
+        path: String::from("rori.db"),
+        generation: 0,
+        connections: Vec::new(),
+    });
+}
+
+/**
+ * A connection taken from the pool, returned to the pool when dropped
+ */
+pub struct PooledConnection {
+    conn: Option<rusqlite::Connection>,
+    generation: u64,
+}
+
+impl Deref for PooledConnection {
+    type Target = rusqlite::Connection;
+
+    fn deref(&self) -> &rusqlite::Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut rusqlite::Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let mut pool = POOL.lock().unwrap();
+        if pool.generation == self.generation && pool.connections.len() < MAX_IDLE_CONNECTIONS {
+            pool.connections.push(self.conn.take().unwrap());
+        }
+    }
+}
 
 /**
  * This class furnish helpers to manipulate the rori.db sqlite database
@@ -38,91 +291,170 @@ use string_error::static_err;
 pub struct Database;
 
 impl Database {
+    /**
+     * Change the location of the database. Must be called before init_db()
+     * @param path of the sqlite file, or ":memory:" for an in-memory database (for tests)
+     */
+    pub fn set_path(path: &str) {
+        let mut pool = POOL.lock().unwrap();
+        pool.path = String::from(path);
+        pool.generation += 1;
+        pool.connections.clear();
+    }
+
+    /**
+     * @return the location of the database
+     */
+    pub fn path() -> String {
+        POOL.lock().unwrap().path.clone()
+    }
+
+    /**
+     * Get a connection to the database from the pool (or open a new one)
+     * @return the connection
+     */
+    pub fn connection() -> RoriResult<PooledConnection> {
+        let (path, generation, conn) = {
+            let mut pool = POOL.lock().unwrap();
+            let conn = pool.connections.pop();
+            (pool.path.clone(), pool.generation, conn)
+        };
+        let conn = match conn {
+            Some(conn) => conn,
+            None => {
+                // Each generation has its own in-memory database, shared by all connections
+                let location = if path == ":memory:" {
+                    format!("file:rori{}?mode=memory&cache=shared", generation)
+                } else {
+                    path
+                };
+                let conn = rusqlite::Connection::open(location)?;
+                let _ = conn.busy_timeout(Duration::from_millis(BUSY_TIMEOUT));
+                let _ = conn.pragma_update(None, "journal_mode", &"WAL");
+                // SQLite doesn't check foreign keys by default
+                conn.pragma_update(None, "foreign_keys", &true)?;
+                conn
+            }
+        };
+        Ok(PooledConnection {
+            conn: Some(conn),
+            generation: generation,
+        })
+    }
+
     /**
      * Set is_bridge to true
      * @param id of the device to modify
      * @return if success
      */
-    pub fn bridgify(id: &i32) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("UPDATE devices SET is_bridge=1 WHERE id=:id").unwrap();
-        stmt.execute_named(&[(":id", id)])
-    }
-
-    /**
-     * Create tables in rori.db
-     * NOTE: maybe has to change in case of migrations
-     */
-    pub fn init_db() {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let version: i32 = conn
-            .pragma_query_value(None, "user_version", |row| row.get(0))
-            .unwrap_or(0);
-        let do_migration = version != 1;
-        if do_migration {
-            info!("migrate database to version 1");
-            conn.execute("CREATE TABLE IF NOT EXISTS devices (
-                id               INTEGER PRIMARY KEY,
-                hash             TEXT,
-                username         TEXT,
-                sub_author       TEXT,
-                devicename       TEXT,
-                additional_types TEXT,
-                is_bridge        INTEGER
-                )", rusqlite::NO_PARAMS).unwrap();
-            conn.execute("CREATE TABLE IF NOT EXISTS modules (
-                id          INTEGER PRIMARY KEY,
-                name        TEXT,
-                priority    INTEGER,
-                enabled     BOOLEAN,
-                type        TEXT,
-                condition   TEXT,
-                path        TEXT
-                )", rusqlite::NO_PARAMS).unwrap();
-            conn.execute("CREATE TABLE IF NOT EXISTS emotions (
-                username    TEXT PRIMARY KEY,
-                love        INTEGER,
-                joy         INTEGER,
-                surprise    INTEGER,
-                anger       INTEGER,
-                sadness     INTEGER,
-                fear        INTEGER
-                )", rusqlite::NO_PARAMS).unwrap();
-            conn.execute("CREATE TABLE IF NOT EXISTS scheduler (
-                id          INTEGER PRIMARY KEY AUTOINCREMENT,
-                module      INTEGER,
-                parameter   TEXT,
-                at          TEXT,
-                seconds     INTEGER,
-                minutes     INTEGER,
-                hours       INTEGER,
-                days        STRING,
-                repeat      INTEGER,
-                FOREIGN KEY (module) REFERENCES modules(id)
-                )", rusqlite::NO_PARAMS).unwrap();
-            conn.pragma_update(None, "user_version", &1).unwrap();
-        }
-        info!("database ready");
-    }
-
-    pub fn is_bridge(hash: &String) -> bool {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT additional_types FROM devices WHERE hash=:hash AND is_bridge=1").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", hash)]).unwrap();
-        if let Ok(Some(_)) = rows.next() {
-            return true;
-        }
-        false
-    }
-
-    pub fn is_bridge_with_username(hash: &String, username: &String) -> bool {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT additional_types FROM devices WHERE hash=:hash AND username=:username AND is_bridge=1").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username)]).unwrap();
-        if let Ok(Some(_)) = rows.next() {
-            return true;
-        }
-        false
+    pub fn bridgify(id: &i32) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE devices SET is_bridge=1 WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", id)])?)
+    }
+
+    /**
+     * Create or upgrade tables in rori.db
+     * @return the version of the database or the error of the failing migration
+     */
+    pub fn init_db() -> RoriResult<i32> {
+        // The file may have been replaced, do not reuse old connections
+        let path = Database::path();
+        Database::set_path(&*path);
+        if path != ":memory:" && !Path::new(&*path).exists() {
+            // A WAL without its database comes from a removed database and must not be replayed
+            let _ = fs::remove_file(format!("{}-wal", path));
+            let _ = fs::remove_file(format!("{}-shm", path));
+        }
+        let version = Database::migrate()?;
+        info!("database ready (version {})", version);
+        Ok(version)
+    }
+
+    /**
+     * Apply missing migrations, each one in its own transaction
+     * @return the new version of the database or the error of the failing migration
+     */
+    pub fn migrate() -> RoriResult<i32> {
+        Database::migrate_to(MIGRATIONS.len() as i32)
+    }
+
+    /**
+     * Apply missing migrations until a version
+     * @param target version of the schema (must be <= MIGRATIONS.len())
+     * @return the new version of the database or the error of the failing migration
+     */
+    pub fn migrate_to(target: i32) -> RoriResult<i32> {
+        if target < 0 || target as usize > MIGRATIONS.len() {
+            return Err(RoriError::Parse(format!("unknown schema version {}", target)));
+        }
+        let mut conn = Database::connection()?;
+        let mut version = Database::version()?;
+        while version < target {
+            info!("migrate database to version {}", version + 1);
+            let tx = conn.transaction()?;
+            tx.execute_batch(MIGRATIONS[version as usize])?;
+            tx.pragma_update(None, "user_version", &(version + 1))?;
+            tx.commit()?;
+            version += 1;
+        }
+        Ok(version)
+    }
+
+    /**
+     * @return the version of the database schema (0 if empty)
+     */
+    pub fn version() -> RoriResult<i32> {
+        let conn = Database::connection()?;
+        Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    pub fn is_bridge(hash: &String) -> RoriResult<bool> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT additional_types FROM devices WHERE hash=:hash AND is_bridge=1")?;
+        let mut rows = stmt.query_named(&[(":hash", hash)])?;
+        if let Some(_) = rows.next()? {
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn is_bridge_with_username(hash: &String, username: &String) -> RoriResult<bool> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT additional_types FROM user_devices WHERE hash=:hash AND username=:username AND is_bridge=1")?;
+        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username)])?;
+        if let Some(_) = rows.next()? {
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /**
+     * Record the bridge operator who created a bridge
+     * @param hash of the bridge
+     * @param username of the operator
+     * @return the number of modified bridges
+     */
+    pub fn set_bridge_owner(hash: &String, username: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO bridges (hash, owner) \
+                                     VALUES (:hash, (SELECT id FROM users WHERE username=:username))")?;
+        Ok(stmt.execute_named(&[(":hash", hash), (":username", username)])?)
+    }
+
+    /**
+     * @param hash of the bridge
+     * @return the username of the operator who created the bridge, None if unknown
+     */
+    pub fn get_bridge_owner(hash: &String) -> RoriResult<Option<String>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT users.username FROM bridges JOIN users ON users.id=bridges.owner \
+                                     WHERE bridges.hash=:hash")?;
+        let mut rows = stmt.query_named(&[(":hash", hash)])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None)
+        }
     }
 
     /**
@@ -131,12 +463,12 @@ impl Database {
      * @param id of the device
      * @return Vec<String> where each string is a supported datatype
      */
-    pub fn get_datatypes(id: &i32) -> Vec<String> {
+    pub fn get_datatypes(id: &i32) -> RoriResult<Vec<String>> {
         let mut datatypes = Vec::new();
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT additional_types FROM devices WHERE id=:id").unwrap();
-        let mut rows = stmt.query_named(&[(":id", id)]).unwrap();
-        if let Ok(Some(row)) = rows.next() {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT additional_types FROM devices WHERE id=:id")?;
+        let mut rows = stmt.query_named(&[(":id", id)])?;
+        if let Some(row) = rows.next()? {
             let row: String = row.get(0).unwrap_or(String::new());
             let dts: Vec<&str> = row.split(' ').collect();
             for dt in dts.into_iter() {
@@ -145,25 +477,26 @@ impl Database {
                 }
             }
         }
-        datatypes
+        Ok(datatypes)
     }
 
     /**
      * Get datatypes supported for the reception to execute modules conditions
      * @return Vec<String> where each string is a supported datatype
      */
-    pub fn get_modules_datatypes() -> Vec<String> {
+    pub fn get_modules_datatypes() -> RoriResult<Vec<String>> {
         let mut datatypes = Vec::new();
         datatypes.push(String::from("text/plain")); // Basic datatype handled by the core
         datatypes.push(String::from("rori/command")); // Basic datatype handled by the core
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT DISTINCT type FROM modules;").unwrap();
-        let mut rows = stmt.query(rusqlite::NO_PARAMS).unwrap();
-        if let Ok(Some(row)) = rows.next() {
+        datatypes.push(String::from("rori/capabilities")); // Basic datatype handled by the core
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT DISTINCT type FROM modules;")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        if let Some(row) = rows.next()? {
             let row: String = row.get(0).unwrap_or(String::new());
             datatypes.push(String::from(row));
         }
-        datatypes
+        Ok(datatypes)
     }
 
     /**
@@ -173,35 +506,30 @@ impl Database {
      * @param devicename device's name related
      * @return the line's id inserted if success, else an error
      */
-    pub fn insert_new_device(hash: &String, username: &String, devicename: &String, is_bridge: bool) -> Result<usize, Box<dyn Error>> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
+    pub fn insert_new_device(hash: &String, username: &String, devicename: &String, is_bridge: bool) -> RoriResult<usize> {
+        let conn = Database::connection()?;
 
         // If already exists
         if is_bridge {
-            let mut stmt = conn.prepare("SELECT id FROM devices WHERE hash=:hash AND username=:username AND devicename=:devicename").unwrap();
-            let mut rows = stmt.query_named(&[(":hash", hash), (":username", username), (":devicename", devicename)]).unwrap();
-            while let Ok(Some(_)) = rows.next() {
-                return Err(static_err("Device already inserted"));
+            let mut stmt = conn.prepare("SELECT id FROM user_devices WHERE hash=:hash AND username=:username AND devicename=:devicename")?;
+            let mut rows = stmt.query_named(&[(":hash", hash), (":username", username), (":devicename", devicename)])?;
+            while let Some(_) = rows.next()? {
+                return Err(RoriError::AlreadyExists(String::from("Device already inserted")));
             }
         } else {
-            let mut stmt = conn.prepare("SELECT id FROM devices WHERE hash=:hash").unwrap();
-            let mut rows = stmt.query_named(&[(":hash", hash)]).unwrap();
-            while let Ok(Some(_)) = rows.next() {
-                return Err(static_err("Device already inserted"));
+            let mut stmt = conn.prepare("SELECT id FROM devices WHERE hash=:hash")?;
+            let mut rows = stmt.query_named(&[(":hash", hash)])?;
+            while let Some(_) = rows.next()? {
+                return Err(RoriError::AlreadyExists(String::from("Device already inserted")));
             }
         }
 
         // Else insert!
-        let mut conn = conn.prepare("INSERT INTO devices (hash, username, sub_author, devicename, additional_types, is_bridge)
-                                     VALUES (:hash, :username, \"\", :devicename, \"\", :is_bridge)").unwrap();
-        match conn.execute_named(&[(":hash", hash), (":username", username), (":devicename", devicename), (":is_bridge", &is_bridge)]) {
-            Ok(_) => {
-                return Ok(Database::get_device(hash, username).0 as usize);
-            }
-            Err(e) => {
-                return Err(Box::new(e));
-            }
-        }
+        let user_id = Database::user_id(&conn, username)?;
+        let mut stmt = conn.prepare("INSERT INTO devices (hash, user_id, sub_author, devicename, additional_types, is_bridge)
+                                     VALUES (:hash, :user_id, \"\", :devicename, \"\", :is_bridge)")?;
+        stmt.execute_named(&[(":hash", hash), (":user_id", &user_id), (":devicename", devicename), (":is_bridge", &is_bridge)])?;
+        Ok(conn.last_insert_rowid() as usize)
     }
 
     /**
@@ -209,25 +537,26 @@ impl Database {
      * @param priority
      * @return a vector of modules
      */
-    pub fn get_enabled_modules(priority: u64) -> Vec<Module> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
+    pub fn get_enabled_modules(priority: u64) -> RoriResult<Vec<Module>> {
+        let conn = Database::connection()?;
         let mut stmt = conn.prepare("SELECT name, condition, path \
                                      FROM modules WHERE priority=:priority AND enabled=1"
-                                   ).unwrap();
-        let mut rows = stmt.query_named(&[(":priority", &priority.to_string())]).unwrap();
+                                   )?;
+        let mut rows = stmt.query_named(&[(":priority", &priority.to_string())])?;
         let mut modules = Vec::new();
-        while let Ok(Some(row)) = rows.next() {
+        while let Some(row) = rows.next()? {
+            let name = row.get(0).unwrap_or(String::new());
             modules.push(
                 Module {
-                    condition: Box::new(TextCondition::new(row.get(1).unwrap_or(String::new()))),
-                    name: row.get(0).unwrap_or(String::new()),
+                    condition: module_condition(&name, &row.get(1).unwrap_or(String::new())),
+                    name: name,
                     path: row.get(2).unwrap_or(String::new()),
                     priority: priority,
                     enabled: true,
                 }
             );
         }
-        modules
+        Ok(modules)
     }
 
     /**
@@ -235,24 +564,25 @@ impl Database {
      * @param id    Id of the module
      * @return The module or None if not found
      */
-    pub fn get_module(id: &i32) -> Option<Module> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
+    pub fn get_module(id: &i32) -> RoriResult<Option<Module>> {
+        let conn = Database::connection()?;
         let mut stmt = conn.prepare("SELECT name, condition, path, priority, enabled \
                                      FROM modules WHERE id=:id"
-                                   ).unwrap();
-        let mut rows = stmt.query_named(&[(":id", &id)]).unwrap();
-        if let Ok(Some(row)) = rows.next() {
-            return Some(
+                                   )?;
+        let mut rows = stmt.query_named(&[(":id", &id)])?;
+        if let Some(row) = rows.next()? {
+            let name = row.get(0).unwrap_or(String::new());
+            return Ok(Some(
                 Module {
-                    condition: Box::new(TextCondition::new(row.get(1).unwrap_or(String::new()))),
-                    name: row.get(0).unwrap_or(String::new()),
+                    condition: module_condition(&name, &row.get(1).unwrap_or(String::new())),
+                    name: name,
                     path: row.get(2).unwrap_or(String::new()),
                     priority: row.get(3).unwrap_or(0 as i32) as u64,
-                    enabled: row.get(4).unwrap(),
+                    enabled: row.get(4)?,
                 }
-            );
+            ));
         }
-        None
+        Ok(None)
     }
 
     /**
@@ -260,96 +590,223 @@ impl Database {
      * @param name    Name of the module
      * @return The id or 0 if not found
      */
-    pub fn get_module_id_by_name(name: &String) -> i32 {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
+    pub fn get_module_id_by_name(name: &String) -> RoriResult<i32> {
+        let conn = Database::connection()?;
         let mut stmt = conn.prepare("SELECT id \
                                      FROM modules WHERE name=:name"
-                                   ).unwrap();
-        let mut rows = stmt.query_named(&[(":name", &name)]).unwrap();
-        if let Ok(Some(row)) = rows.next() {
-            return row.get(0).unwrap();
+                                   )?;
+        let mut rows = stmt.query_named(&[(":name", &name)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(row.get(0)?);
+        }
+        Ok(0)
+    }
+
+    /**
+     * Enable or disable a module
+     * @param name of the module
+     * @param enabled
+     * @return the number of modified modules
+     */
+    pub fn set_module_enabled(name: &String, enabled: bool) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE modules SET enabled=:enabled WHERE name=:name")?;
+        Ok(stmt.execute_named(&[(":name", name), (":enabled", &enabled)])?)
+    }
+
+    /**
+     * Get conditions of enabled modules which can answer (priority > 0)
+     * @return a vector of (name, condition)
+     */
+    pub fn get_modules_conditions() -> RoriResult<Vec<(String, String)>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT name, condition FROM modules WHERE priority>0 AND enabled=1")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        let mut conditions = Vec::new();
+        while let Some(row) = rows.next()? {
+            conditions.push((row.get(0).unwrap_or(String::new()), row.get(1).unwrap_or(String::new())));
         }
-        0
+        Ok(conditions)
     }
 
     /**
      * Return one device
-     * @hash the ring id of the device to search
-     * @return (id, hash, username, devicename, is_bridge) or empty strings with id = -1 if hash not found
+     * @param hash the ring id of the device to search
+     * @param username owner of the device (empty if anonymous)
+     * @return the device if found
      */
-    pub fn get_device(hash: &String, username: &String) -> (i32, String, String, String, i32) {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, is_bridge FROM devices \
-            WHERE hash=:hash AND username=:username").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username)]).unwrap();
-        while let Ok(Some(row)) = rows.next() {
-            return (row.get(0).unwrap_or(0), row.get(1).unwrap_or(String::new()), row.get(2).unwrap_or(String::new()), row.get(3).unwrap_or(String::new()), row.get(4).unwrap_or(0));
+    pub fn get_device(hash: &String, username: &String) -> RoriResult<Option<DeviceRecord>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, sub_author, is_bridge FROM user_devices \
+            WHERE hash=:hash AND username=:username")?;
+        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(Database::device_from_row(&row)));
         }
-        (-1, String::new(), String::new(), String::new(), 0)
+        Ok(None)
+    }
+
+    /**
+     * Return one device
+     * @param id of the device
+     * @return the device if found
+     */
+    pub fn get_device_by_id(id: &i32) -> RoriResult<Option<DeviceRecord>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, sub_author, is_bridge FROM user_devices \
+            WHERE id=:id")?;
+        let mut rows = stmt.query_named(&[(":id", id)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(Database::device_from_row(&row)));
+        }
+        Ok(None)
     }
 
     /**
      * Return all devices
-     * @return a Vector of devices (id, hash, username, devicename, is_bridge)
+     * @return a Vector of devices
      */
-    pub fn get_devices() -> Vec<(i32, String, String, String, bool)> {
-        let mut devices: Vec<(i32, String, String, String, bool)> = Vec::new();
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, is_bridge FROM devices").unwrap();
-        let mut rows = stmt.query(rusqlite::NO_PARAMS).unwrap();
-        while let Ok(Some(row)) = rows.next() {
-            devices.push((row.get(0).unwrap_or(0), row.get(1).unwrap_or(String::new()), row.get(2).unwrap_or(String::new()), row.get(3).unwrap_or(String::new()), row.get(4).unwrap_or(false)));
+    pub fn get_devices() -> RoriResult<Vec<DeviceRecord>> {
+        let mut devices = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, sub_author, is_bridge FROM user_devices")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            devices.push(Database::device_from_row(&row));
         }
-        devices
+        Ok(devices)
     }
 
     /**
      * @note till Rust doesn't supports optional parameters
      * Return all devices for a hash
-     * @return a Vector of devices (id, hash, username, devicename, is_bridge)
+     * @return a Vector of devices
      */
-    pub fn get_devices_for_hash(hash: &str) -> Vec<(i32, String, String, String, bool)> {
-        let mut devices: Vec<(i32, String, String, String, bool)> = Vec::new();
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, is_bridge FROM devices \
-            WHERE hash=:hash").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", &hash.to_string())]).unwrap();
-        while let Ok(Some(row)) = rows.next() {
-            devices.push((row.get(0).unwrap_or(0), row.get(1).unwrap_or(String::new()), row.get(2).unwrap_or(String::new()), row.get(3).unwrap_or(String::new()), row.get(4).unwrap_or(false)));
+    pub fn get_devices_for_hash(hash: &str) -> RoriResult<Vec<DeviceRecord>> {
+        let mut devices = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, sub_author, is_bridge FROM user_devices \
+            WHERE hash=:hash")?;
+        let mut rows = stmt.query_named(&[(":hash", &hash.to_string())])?;
+        while let Some(row) = rows.next()? {
+            devices.push(Database::device_from_row(&row));
         }
-        devices
+        Ok(devices)
     }
 
     /**
      * @note till Rust doesn't supports optional parameters
      * Return all devices for an username
-     * @return a Vector of devices (id, hash, username, devicename, is_bridge)
+     * @return a Vector of devices
+     */
+    pub fn get_devices_for_username(username: &str) -> RoriResult<Vec<DeviceRecord>> {
+        let mut devices = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, sub_author, is_bridge FROM user_devices \
+            WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", &username.to_string())])?;
+        while let Some(row) = rows.next()? {
+            devices.push(Database::device_from_row(&row));
+        }
+        Ok(devices)
+    }
+
+    /**
+     * Return all registered users
+     * @return a Vector of users sorted by username
+     */
+    pub fn get_users() -> RoriResult<Vec<UserRecord>> {
+        let mut users = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, username, display_name, created, role FROM users ORDER BY username")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            users.push(Database::user_from_row(&row));
+        }
+        Ok(users)
+    }
+
+    /**
+     * Return one user
+     * @param username to search
+     * @return the user if registered
+     */
+    pub fn get_user(username: &String) -> RoriResult<Option<UserRecord>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, username, display_name, created, role FROM users WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(Database::user_from_row(&row)));
+        }
+        Ok(None)
+    }
+
+    /**
+     * Set the name displayed for an user
+     * @param username of the user
+     * @param display_name new name
+     * @return the number of modified users
+     */
+    pub fn set_display_name(username: &String, display_name: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE users SET display_name=:display_name WHERE username=:username")?;
+        Ok(stmt.execute_named(&[(":username", username), (":display_name", display_name)])?)
+    }
+
+    /**
+     * Change the role of an user
+     * @param username of the user
+     * @param role new role
+     * @return the number of modified users
+     */
+    pub fn set_role(username: &String, role: Role) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE users SET role=:role WHERE username=:username")?;
+        Ok(stmt.execute_named(&[(":username", username), (":role", &role.as_str())])?)
+    }
+
+    /**
+     * Give the admin role to the user of a device (not a bridge) if there is no admin with a device yet
+     * @param hash of the device of the first admin (from config.json)
+     * @return the number of modified users
+     */
+    pub fn bootstrap_admin(hash: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE users SET role='admin' \
+                                     WHERE id=(SELECT user_id FROM devices WHERE hash=:hash AND is_bridge=0) \
+                                     AND NOT EXISTS (SELECT 1 FROM users WHERE role='admin' \
+                                                     AND id IN (SELECT user_id FROM devices))")?;
+        Ok(stmt.execute_named(&[(":hash", hash)])?)
+    }
+
+    /**
+     * Return all registered usernames
+     * @return a Vector of usernames
      */
-    pub fn get_devices_for_username(username: &str) -> Vec<(i32, String, String, String, bool)> {
-        let mut devices: Vec<(i32, String, String, String, bool)> = Vec::new();
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT id, hash, username, devicename, is_bridge FROM devices \
-            WHERE username=:username").unwrap();
-        let mut rows = stmt.query_named(&[(":username", &username.to_string())]).unwrap();
-        while let Ok(Some(row)) = rows.next() {
-            devices.push((row.get(0).unwrap_or(0), row.get(1).unwrap_or(String::new()), row.get(2).unwrap_or(String::new()), row.get(3).unwrap_or(String::new()), row.get(4).unwrap_or(false)));
+    pub fn get_usernames() -> RoriResult<Vec<String>> {
+        let mut usernames: Vec<String> = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT username FROM users ORDER BY username")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            usernames.push(row.get(0).unwrap_or(String::new()));
         }
-        devices
+        Ok(usernames)
     }
 
     /**
      * Return the last priority to treat
      * @return i64
      */
-    pub fn get_descending_priorities() -> Vec<i64> {
+    pub fn get_descending_priorities() -> RoriResult<Vec<i64>> {
         let mut result = Vec::new();
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT DISTINCT priority FROM modules ORDER BY priority ASC").unwrap();
-        let mut rows = stmt.query(rusqlite::NO_PARAMS).unwrap();
-        while let Ok(Some(row)) = rows.next() {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT DISTINCT priority FROM modules ORDER BY priority ASC")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
             result.push(row.get(0).unwrap_or(0));
         }
-        result
+        Ok(result)
     }
 
     /**
@@ -357,10 +814,12 @@ impl Database {
      * @param hash to remove
      * @return the id of the removed row or an error
      */
-    pub fn remove_device(id: &i32) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut conn = conn.prepare("DELETE FROM devices WHERE id=:id").unwrap();
-        conn.execute_named(&[(":id", id)])
+    pub fn remove_device(id: &i32) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("DELETE FROM devices WHERE id=:id")?;
+        let result = stmt.execute_named(&[(":id", id)])?;
+        Database::remove_orphan_capabilities(&conn)?;
+        Ok(result)
     }
 
     /**
@@ -370,14 +829,14 @@ impl Database {
      * @param devicename to search
      * @return if found
      */
-    pub fn search_devicename(username: &String, devicename: &String) -> bool {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM devices WHERE username=:username AND devicename=:devicename").unwrap();
-        let mut rows = stmt.query_named(&[(":username", username), (":devicename", devicename)]).unwrap();
-        while let Ok(Some(_)) = rows.next() {
-            return true;
+    pub fn search_devicename(username: &String, devicename: &String) -> RoriResult<bool> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id FROM user_devices WHERE username=:username AND devicename=:devicename")?;
+        let mut rows = stmt.query_named(&[(":username", username), (":devicename", devicename)])?;
+        while let Some(_) = rows.next()? {
+            return Ok(true);
         }
-        false
+        Ok(false)
     }
 
     /**
@@ -385,14 +844,14 @@ impl Database {
      * @param hash to search
      * @return if found
      */
-    pub fn search_hash(hash: &String) -> bool {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM devices WHERE hash=:hash").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", hash)]).unwrap();
-        while let Ok(Some(_)) = rows.next() {
-            return true;
+    pub fn search_hash(hash: &String) -> RoriResult<bool> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT * FROM devices WHERE hash=:hash")?;
+        let mut rows = stmt.query_named(&[(":hash", hash)])?;
+        while let Some(_) = rows.next()? {
+            return Ok(true);
         }
-        false
+        Ok(false)
     }
 
     /**
@@ -400,18 +859,18 @@ impl Database {
      * @param username to search
      * @return if found
      */
-    pub fn search_username(username: &String) -> bool {
+    pub fn search_username(username: &String) -> RoriResult<bool> {
         if username.to_lowercase() == "rori" {
             // RESERVED
-            return true;
+            return Ok(true);
         }
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM devices WHERE username=:username").unwrap();
-        let mut rows = stmt.query_named(&[(":username", username)]).unwrap();
-        while let Ok(Some(_)) = rows.next() {
-            return true;
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id FROM users WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        while let Some(_) = rows.next()? {
+            return Ok(true);
         }
-        false
+        Ok(false)
     }
 
     /**
@@ -420,11 +879,63 @@ impl Database {
      * @param datatypes to set
      * @return if success
      */
-    pub fn set_datatypes(id: &i32, datatypes: Vec<String>) -> Result<usize, rusqlite::Error> {
+    pub fn set_datatypes(id: &i32, datatypes: Vec<String>) -> RoriResult<usize> {
         let datatypes = datatypes.join(" ");
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("UPDATE devices SET additional_types=:additional_types WHERE id=:id").unwrap();
-        stmt.execute_named(&[(":id", id), (":additional_types", &String::from(datatypes))])
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE devices SET additional_types=:additional_types WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", id), (":additional_types", &String::from(datatypes))])?)
+    }
+
+    /**
+     * Get the capabilities announced by a device
+     * @param id of the device
+     * @return the capabilities, empty if nothing was announced
+     */
+    pub fn get_capabilities(id: &i32) -> RoriResult<Capabilities> {
+        let mut capabilities = Capabilities::default();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT kind FROM device_kinds WHERE device=:id ORDER BY kind")?;
+        let mut rows = stmt.query_named(&[(":id", id)])?;
+        while let Some(row) = rows.next()? {
+            if let Some(kind) = Kind::parse(&*row.get::<_, String>(0)?) {
+                capabilities.kinds.push(kind);
+            }
+        }
+        let mut stmt = conn.prepare("SELECT datatype, parameters FROM capabilities WHERE device=:id ORDER BY datatype")?;
+        let mut rows = stmt.query_named(&[(":id", id)])?;
+        while let Some(row) = rows.next()? {
+            capabilities.actions.push(Action {
+                datatype: row.get(0)?,
+                parameters: serde_json::from_str(&*row.get::<_, String>(1)?).unwrap_or(Value::Null),
+            });
+        }
+        Ok(capabilities)
+    }
+
+    /**
+     * Replace the capabilities announced by a device
+     * @param id of the device
+     * @param capabilities announced
+     * @return the number of stored kinds and actions
+     */
+    pub fn set_capabilities(id: &i32, capabilities: &Capabilities) -> RoriResult<usize> {
+        let mut conn = Database::connection()?;
+        let tx = conn.transaction()?;
+        tx.execute_named("DELETE FROM device_kinds WHERE device=:id", &[(":id", id)])?;
+        tx.execute_named("DELETE FROM capabilities WHERE device=:id", &[(":id", id)])?;
+        let mut result = 0;
+        for kind in &capabilities.kinds {
+            result += tx.execute_named("INSERT OR IGNORE INTO device_kinds (device, kind) VALUES (:id, :kind)",
+                                       &[(":id", id), (":kind", &kind.as_str())])?;
+        }
+        for action in &capabilities.actions {
+            result += tx.execute_named("INSERT OR REPLACE INTO capabilities (device, datatype, parameters) \
+                                        VALUES (:id, :datatype, :parameters)",
+                                       &[(":id", id), (":datatype", &action.datatype),
+                                         (":parameters", &serde_json::to_string(&action.parameters)?)])?;
+        }
+        tx.commit()?;
+        Ok(result)
     }
 
     /**
@@ -433,15 +944,15 @@ impl Database {
      * @param sub_author of the device
      * @return String linked
      */
-    pub fn sub_author(hash: &String, sub_author: &String) -> String {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT username FROM devices WHERE hash=:hash AND sub_author=:sub_author").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", hash), (":sub_author", sub_author)]).unwrap();
-        if let Ok(Some(row)) = rows.next() {
+    pub fn sub_author(hash: &String, sub_author: &String) -> RoriResult<String> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT username FROM user_devices WHERE hash=:hash AND sub_author=:sub_author")?;
+        let mut rows = stmt.query_named(&[(":hash", hash), (":sub_author", sub_author)])?;
+        if let Some(row) = rows.next()? {
             let username : String = row.get(0).unwrap_or(String::new());
-            return username;
+            return Ok(username);
         }
-        String::new()
+        Ok(String::new())
     }
 
     /**
@@ -450,15 +961,15 @@ impl Database {
      * @param sub_author of the device
      * @return String linked
      */
-    pub fn sub_author_id(hash: &String, username: &String) -> String {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT sub_author FROM devices WHERE hash=:hash AND username=:username").unwrap();
-        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username)]).unwrap();
-        if let Ok(Some(row)) = rows.next() {
+    pub fn sub_author_id(hash: &String, username: &String) -> RoriResult<String> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT sub_author FROM user_devices WHERE hash=:hash AND username=:username")?;
+        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username)])?;
+        if let Some(row) = rows.next()? {
             let sub_author : String = row.get(0).unwrap_or(String::new());
-            return sub_author;
+            return Ok(sub_author);
         }
-        String::new()
+        Ok(String::new())
     }
 
     /**
@@ -467,10 +978,10 @@ impl Database {
      * @param devicename new devicename
      * @return the id of the modified row if success else an error
      */
-    pub fn update_devicename(id: &i32, devicename: &String) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("UPDATE devices SET devicename=:devicename WHERE id=:id").unwrap();
-        stmt.execute_named(&[(":id", id), (":devicename", devicename)])
+    pub fn update_devicename(id: &i32, devicename: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE devices SET devicename=:devicename WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", id), (":devicename", devicename)])?)
     }
 
     /**
@@ -479,10 +990,10 @@ impl Database {
      * @param sub_author to set
      * @return if success
      */
-    pub fn update_sub_author(id: &i32, sub_author: &String) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("UPDATE devices SET sub_author=:sub_author WHERE id=:id").unwrap();
-        stmt.execute_named(&[(":id", id), (":sub_author", sub_author)])
+    pub fn update_sub_author(id: &i32, sub_author: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("UPDATE devices SET sub_author=:sub_author WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", id), (":sub_author", sub_author)])?)
     }
 
     /**
@@ -491,35 +1002,699 @@ impl Database {
      * @param username new username
      * @return the id of the modified row if success else an error
      */
-    pub fn update_username(id: &i32, username: &String) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("UPDATE devices SET username=:username WHERE id=:id").unwrap();
-        stmt.execute_named(&[(":id", id), (":username", username)])
+    pub fn update_username(id: &i32, username: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let user_id = Database::user_id(&conn, username)?;
+        let mut stmt = conn.prepare("UPDATE devices SET user_id=:user_id WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", id), (":user_id", &user_id)])?)
+    }
+
+    /**
+     * Remember an interaction which didn't trigger any module
+     * @param username author of the interaction (empty if anonymous)
+     * @param interaction not handled
+     * @return the id of the inserted row or an error
+     */
+    pub fn add_unmatched(username: &String, interaction: &Interaction) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT INTO unmatched (username, device, datatype, body, time)
+                                     VALUES (:username, :device, :datatype, :body, :time)")?;
+        Ok(stmt.execute_named(&[(":username", username),
+                             (":device", &interaction.device_author.id),
+                             (":datatype", &interaction.datatype),
+                             (":body", &interaction.body),
+                             (":time", &interaction.time.rfc3339().to_string())])?)
+    }
+
+    /**
+     * Get phrases which didn't trigger any module, most frequent first
+     * @return a vector of (body, occurrences)
+     */
+    pub fn get_unmatched() -> RoriResult<Vec<(String, i64)>> {
+        let mut unmatched = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT body, COUNT(*) AS occurrences FROM unmatched \
+                                     GROUP BY body ORDER BY occurrences DESC")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            unmatched.push((row.get(0).unwrap_or(String::new()), row.get(1).unwrap_or(0)));
+        }
+        Ok(unmatched)
+    }
+
+    /**
+     * Export all rows of a table
+     * @param table to export
+     * @return one JSON object (column -> value) per row
+     */
+    pub fn dump_table(table: &str) -> RoriResult<Vec<Map<String, Value>>> {
+        let conn = Database::connection()?;
+        Database::columns(&conn, table)?;
+        Database::query_json(&conn, &*format!("SELECT * FROM \"{}\"", table), &[])
+    }
+
+    /**
+     * Export every row linked to an user
+     * NOTE: values of modules are linked to an user if their key is the username or starts with "username:"
+     * @param username
+     * @return rows (column -> value) by table
+     */
+    pub fn export_user(username: &String) -> RoriResult<BTreeMap<String, Vec<Map<String, Value>>>> {
+        let conn = Database::connection()?;
+        let mut result = BTreeMap::new();
+        for table in USER_TABLES.iter() {
+            let sql = format!("SELECT * FROM \"{}\" WHERE username=:username", table);
+            result.insert(table.to_string(), Database::query_json(&conn, &*sql, &[(":username", username)])?);
+        }
+        let devices = Database::query_json(&conn, "SELECT devices.*, users.username FROM devices \
+            JOIN users ON users.id=devices.user_id WHERE users.username=:username", &[(":username", username)])?;
+        result.insert(String::from("devices"), devices);
+        let user_devices = "SELECT devices.id FROM devices JOIN users ON users.id=devices.user_id WHERE users.username=:username";
+        for table in ["device_kinds", "capabilities"].iter() {
+            let sql = format!("SELECT * FROM \"{}\" WHERE device IN ({})", table, user_devices);
+            result.insert(table.to_string(), Database::query_json(&conn, &*sql, &[(":username", username)])?);
+        }
+        let prefix = format!("{}:", username);
+        let values = Database::query_json(&conn, "SELECT * FROM module_store WHERE key=:username \
+            OR substr(key, 1, length(:prefix))=:prefix", &[(":username", username), (":prefix", &prefix)])?;
+        result.insert(String::from("module_store"), values);
+        let tasks = Database::query_json(&conn, "SELECT * FROM scheduler", &[])?.into_iter()
+                    .filter(|task| Database::task_owner(task["parameter"].as_str().unwrap_or("")) == *username)
+                    .collect();
+        result.insert(String::from("scheduler"), tasks);
+        Ok(result)
+    }
+
+    /**
+     * Remove every row linked to an user (see export_user). Devices are kept as
+     * anonymous devices, except bridges
+     * @param username
+     * @return the number of removed or anonymized rows
+     */
+    pub fn purge_user(username: &String) -> RoriResult<usize> {
+        let mut conn = Database::connection()?;
+        let tx = conn.transaction()?;
+        let mut result = 0;
+        let user = "(SELECT id FROM users WHERE username=:username)";
+        result += tx.execute_named(&*format!("DELETE FROM devices WHERE is_bridge=1 AND user_id={}", user),
+                                   &[(":username", username)])?;
+        result += tx.execute_named(&*format!("UPDATE devices SET user_id=NULL, devicename='', sub_author='' \
+                                              WHERE user_id={}", user), &[(":username", username)])?;
+        result += tx.execute_named("DELETE FROM users WHERE username=:username", &[(":username", username)])?;
+        result += Database::remove_orphan_capabilities(&tx)?;
+        result += Database::remove_user_rows(&tx, username)?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /**
+     * Rename an user and move all its data in a single transaction
+     * @param username current username
+     * @param new_username (must not be registered)
+     * @param secrets of the user, re-encrypted for new_username (see Vault::rename_user)
+     * @return the number of modified rows
+     */
+    pub fn rename_user(username: &String, new_username: &String, secrets: &Vec<Secret>) -> RoriResult<usize> {
+        let mut conn = Database::connection()?;
+        let tasks = Database::get_tasks()?;
+        let tx = conn.transaction()?;
+        let names: [(&str, &dyn rusqlite::ToSql); 2] = [(":username", username), (":new_username", new_username)];
+        let mut result = tx.execute_named("UPDATE users SET username=:new_username WHERE username=:username", &names)?;
+        if result == 0 {
+            return Err(RoriError::NotFound(format!("user {}", username)));
+        }
+        // Rows left by an old user with the same name are removed, not merged with the data of the user
+        Database::remove_user_rows(&tx, new_username)?;
+        for table in USER_TABLES.iter().filter(|t| **t != "users") {
+            result += tx.execute_named(&*format!("UPDATE \"{}\" SET username=:new_username WHERE username=:username", table),
+                                       &names)?;
+        }
+        result += tx.execute_named("UPDATE link_requests SET username=:new_username WHERE username=:username", &names)?;
+        let prefix = format!("{}:", username);
+        result += tx.execute_named("UPDATE module_store SET key=:new_username || substr(key, length(:username) + 1) \
+                                    WHERE key=:username OR substr(key, 1, length(:prefix))=:prefix",
+                                   &[(":username", username), (":new_username", new_username), (":prefix", &prefix)])?;
+        tx.execute_named("DELETE FROM secrets WHERE username=:username", &[(":username", username)])?;
+        for secret in secrets {
+            result += tx.execute_named("INSERT OR REPLACE INTO secrets (module, username, name, value, updated) \
+                                        VALUES (:module, :username, :name, :value, :updated)",
+                                       &[(":module", &secret.module), (":username", &secret.username),
+                                         (":name", &secret.name), (":value", &secret.value), (":updated", &secret.updated)])?;
+        }
+        for task in tasks.iter().filter(|task| Database::task_owner(&*task.parameter) == *username) {
+            let mut metadatas: HashMap<String, String> = serde_json::from_str(&*task.parameter)?;
+            metadatas.insert(String::from("username"), new_username.clone());
+            result += tx.execute_named("UPDATE scheduler SET parameter=:parameter WHERE id=:id",
+                                       &[(":id", &task.id), (":parameter", &serde_json::to_string(&metadatas)?)])?;
+        }
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /**
+     * Insert exported rows in a single transaction. Nothing is inserted if a row is invalid
+     * @param tables rows to insert by table (see dump_table)
+     * @return the number of inserted rows
+     */
+    pub fn import_tables(tables: &BTreeMap<String, Vec<Map<String, Value>>>) -> RoriResult<usize> {
+        let mut conn = Database::connection()?;
+        let tx = conn.transaction()?;
+        // Tables are not imported in the order of their references
+        tx.pragma_update(None, "defer_foreign_keys", &true)?;
+        let mut inserted = 0;
+        for (table, rows) in tables {
+            let columns = Database::columns(&tx, table)?;
+            for row in rows {
+                let mut names = Vec::new();
+                let mut values = Vec::new();
+                for (column, value) in row {
+                    if !columns.contains(column) {
+                        return Err(RoriError::Parse(format!("unknown column {}.{}", table, column)));
+                    }
+                    let value = match value {
+                        Value::Null => SqlValue::Null,
+                        Value::Bool(b) => SqlValue::Integer(*b as i64),
+                        Value::Number(n) => match n.as_i64() {
+                            Some(i) => SqlValue::Integer(i),
+                            None => SqlValue::Real(n.as_f64().unwrap_or(0.0)),
+                        },
+                        Value::String(s) => SqlValue::Text(s.clone()),
+                        _ => return Err(RoriError::Parse(format!("invalid value for {}.{}", table, column))),
+                    };
+                    names.push(format!("\"{}\"", column));
+                    values.push(value);
+                }
+                let placeholders = vec!["?"; values.len()].join(", ");
+                tx.execute(&*format!("INSERT INTO \"{}\" ({}) VALUES ({})", table, names.join(", "), placeholders), &values)?;
+                inserted += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /**
+     * Store a message in the history
+     * @param entry to store (id is ignored)
+     * @return the id of the new entry
+     */
+    pub fn add_history(entry: &HistoryEntry) -> RoriResult<i32> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT INTO interactions (username, device, outgoing, datatype, body, time)
+                                     VALUES (:username, :device, :outgoing, :datatype, :body, :time)")?;
+        stmt.execute_named(&[(":username", &entry.username), (":device", &entry.device), (":outgoing", &entry.outgoing),
+                             (":datatype", &entry.datatype), (":body", &entry.body), (":time", &entry.time)])?;
+        Ok(conn.last_insert_rowid() as i32)
+    }
+
+    /**
+     * Search text messages exchanged with an user, most recent first
+     * @param username
+     * @param text words to search, quoted before the FTS5 MATCH (every message if empty)
+     * @param offset number of messages to skip
+     * @param limit max number of messages
+     * @return messages found
+     */
+    pub fn search_history(username: &String, text: &str, offset: u32, limit: u32) -> RoriResult<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+        let query = history::fts_query(text);
+        let conn = Database::connection()?;
+        let mut sql = String::from("SELECT interactions.id, username, device, outgoing, datatype, interactions.body, time \
+                                    FROM interactions ");
+        if !query.is_empty() {
+            sql += "JOIN interactions_fts ON interactions_fts.rowid=interactions.id AND interactions_fts MATCH :query ";
+        }
+        sql += "WHERE username=:username AND datatype='text/plain' ORDER BY time DESC, interactions.id DESC \
+                LIMIT :limit OFFSET :offset";
+        let mut stmt = conn.prepare(&*sql)?;
+        let (offset, limit) = (offset as i64, limit as i64);
+        let mut params: Vec<(&str, &dyn rusqlite::ToSql)> = vec![(":username", username), (":limit", &limit), (":offset", &offset)];
+        if !query.is_empty() {
+            params.push((":query", &query));
+        }
+        let mut rows = stmt.query_named(&*params)?;
+        while let Some(row) = rows.next()? {
+            entries.push(HistoryEntry {
+                id: row.get(0).unwrap_or(0),
+                username: row.get(1).unwrap_or(String::new()),
+                device: row.get(2).unwrap_or(String::new()),
+                outgoing: row.get(3).unwrap_or(false),
+                datatype: row.get(4).unwrap_or(String::new()),
+                body: row.get(5).unwrap_or(String::new()),
+                time: row.get(6).unwrap_or(0),
+            });
+        }
+        Ok(entries)
+    }
+
+    /**
+     * Get the emotions of RORI toward an user
+     * @param username
+     * @return (emotions, timestamp of the last update) or None if never set
+     */
+    pub fn get_emotions(username: &String) -> RoriResult<Option<(Emotions, i64)>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT love, joy, surprise, anger, sadness, fear, updated \
+                                     FROM emotions WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some((Emotions {
+                love: row.get(0).unwrap_or(0),
+                joy: row.get(1).unwrap_or(0),
+                surprise: row.get(2).unwrap_or(0),
+                anger: row.get(3).unwrap_or(0),
+                sadness: row.get(4).unwrap_or(0),
+                fear: row.get(5).unwrap_or(0),
+            }, row.get(6).unwrap_or(0))));
+        }
+        Ok(None)
+    }
+
+    /**
+     * Save the emotions of RORI toward an user
+     * @param username
+     * @param emotions
+     * @param updated timestamp of the update
+     * @return if success
+     */
+    pub fn set_emotions(username: &String, emotions: &Emotions, updated: i64) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO emotions (username, love, joy, surprise, anger, sadness, fear, updated)
+                                     VALUES (:username, :love, :joy, :surprise, :anger, :sadness, :fear, :updated)")?;
+        Ok(stmt.execute_named(&[(":username", username),
+                             (":love", &emotions.love),
+                             (":joy", &emotions.joy),
+                             (":surprise", &emotions.surprise),
+                             (":anger", &emotions.anger),
+                             (":sadness", &emotions.sadness),
+                             (":fear", &emotions.fear),
+                             (":updated", &updated)])?)
+    }
+
+    /**
+     * Get the language chosen by an user
+     * @param username
+     * @return the language or None if not set
+     */
+    pub fn get_lang(username: &String) -> RoriResult<Option<String>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT lang FROM languages WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(row.get(0).ok());
+        }
+        Ok(None)
+    }
+
+    /**
+     * Set the language of an user
+     * @param username
+     * @param lang
+     * @return if success
+     */
+    pub fn set_lang(username: &String, lang: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO languages (username, lang) VALUES (:username, :lang)")?;
+        Ok(stmt.execute_named(&[(":username", username), (":lang", lang)])?)
+    }
+
+    /**
+     * Get the devices preferred by an user
+     * @param username
+     * @return datatype -> id of the device (empty datatype for every datatype)
+     */
+    pub fn get_preferences(username: &String) -> RoriResult<HashMap<String, i32>> {
+        let mut preferences = HashMap::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT datatype, device FROM preferences WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        while let Some(row) = rows.next()? {
+            preferences.insert(row.get(0)?, row.get(1)?);
+        }
+        Ok(preferences)
+    }
+
+    /**
+     * Set the device preferred by an user for a datatype
+     * @param username
+     * @param datatype (empty for every datatype)
+     * @param device id of the device
+     * @return if success
+     */
+    pub fn set_preference(username: &String, datatype: &String, device: &i32) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO preferences (username, datatype, device) \
+                                     VALUES (:username, :datatype, :device)")?;
+        Ok(stmt.execute_named(&[(":username", username), (":datatype", datatype), (":device", device)])?)
+    }
+
+    /**
+     * Remove the device preferred by an user for a datatype
+     * @param username
+     * @param datatype (empty for every datatype)
+     * @return the number of removed preferences
+     */
+    pub fn rm_preference(username: &String, datatype: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("DELETE FROM preferences WHERE username=:username AND datatype=:datatype")?;
+        Ok(stmt.execute_named(&[(":username", username), (":datatype", datatype)])?)
+    }
+
+    /**
+     * Get when the devices of an user sent their last message
+     * @param username
+     * @return hash of the device -> timestamp of its last message
+     */
+    pub fn get_last_activity(username: &String) -> RoriResult<HashMap<String, i64>> {
+        let mut activity = HashMap::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT device, MAX(time) FROM interactions \
+                                     WHERE username=:username AND outgoing=0 GROUP BY device")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        while let Some(row) = rows.next()? {
+            activity.insert(row.get(0)?, row.get(1)?);
+        }
+        Ok(activity)
+    }
+
+    /**
+     * Create an empty group
+     * @param name of the group (must not be used by an user)
+     * @param owner username of the owner (empty for none)
+     * @return the id of the group, or AlreadyExists
+     */
+    pub fn add_group(name: &String, owner: &String) -> RoriResult<i32> {
+        if Database::search_username(name)? || Database::get_group(name)?.is_some() {
+            return Err(RoriError::AlreadyExists(format!("name {}", name)));
+        }
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT INTO groups (name, created, owner) \
+                                     VALUES (:name, :created, (SELECT id FROM users WHERE username=:owner))")?;
+        stmt.execute_named(&[(":name", name), (":created", &time::get_time().sec), (":owner", owner)])?;
+        Ok(conn.last_insert_rowid() as i32)
+    }
+
+    /**
+     * Remove a group and its members
+     * @param name of the group
+     * @return the number of removed groups
+     */
+    pub fn rm_group(name: &String) -> RoriResult<usize> {
+        let mut conn = Database::connection()?;
+        let tx = conn.transaction()?;
+        tx.execute_named("DELETE FROM group_members WHERE group_id IN (SELECT id FROM groups WHERE name=:name)",
+                         &[(":name", name)])?;
+        let result = tx.execute_named("DELETE FROM groups WHERE name=:name", &[(":name", name)])?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /**
+     * @param name of the group
+     * @return the group, its members and owner, None if not found
+     */
+    pub fn get_group(name: &String) -> RoriResult<Option<Group>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT groups.id, COALESCE(users.username, '') FROM groups \
+                                     LEFT JOIN users ON users.id=groups.owner WHERE name=:name")?;
+        let mut rows = stmt.query_named(&[(":name", name)])?;
+        let (id, owner): (i32, String) = match rows.next()? {
+            Some(row) => (row.get(0)?, row.get(1)?),
+            None => return Ok(None)
+        };
+        let mut group = Group { name: name.clone(), members: Vec::new(), owner: owner };
+        let mut stmt = conn.prepare("SELECT username FROM group_members WHERE group_id=:id ORDER BY username")?;
+        let mut rows = stmt.query_named(&[(":id", &id)])?;
+        while let Some(row) = rows.next()? {
+            group.members.push(row.get(0)?);
+        }
+        Ok(Some(group))
+    }
+
+    /**
+     * @return all groups with their members
+     */
+    pub fn get_groups() -> RoriResult<Vec<Group>> {
+        let mut names: Vec<String> = Vec::new();
+        {
+            let conn = Database::connection()?;
+            let mut stmt = conn.prepare("SELECT name FROM groups ORDER BY name")?;
+            let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+            while let Some(row) = rows.next()? {
+                names.push(row.get(0)?);
+            }
+        }
+        let mut groups = Vec::new();
+        for name in names {
+            if let Some(group) = Database::get_group(&name)? {
+                groups.push(group);
+            }
+        }
+        Ok(groups)
+    }
+
+    /**
+     * Add an user to a group
+     * @param name of the group
+     * @param username to add
+     * @return the number of added members, NotFound if the group doesn't exist
+     */
+    pub fn add_group_member(name: &String, username: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR IGNORE INTO group_members (group_id, username) \
+                                     SELECT id, :username FROM groups WHERE name=:name")?;
+        let result = stmt.execute_named(&[(":name", name), (":username", username)])?;
+        if result == 0 && Database::get_group(name)?.is_none() {
+            return Err(RoriError::NotFound(format!("group {}", name)));
+        }
+        Ok(result)
+    }
+
+    /**
+     * Remove an user from a group
+     * @param name of the group
+     * @param username to remove
+     * @return the number of removed members
+     */
+    pub fn rm_group_member(name: &String, username: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("DELETE FROM group_members WHERE username=:username \
+                                     AND group_id IN (SELECT id FROM groups WHERE name=:name)")?;
+        Ok(stmt.execute_named(&[(":name", name), (":username", username)])?)
+    }
+
+    /**
+     * Get a value stored by a module
+     * @param module    name of the module
+     * @param key
+     * @return the value if any
+     */
+    pub fn get_module_value(module: &String, key: &String) -> RoriResult<Option<String>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT value FROM module_store WHERE module=:module AND key=:key")?;
+        let mut rows = stmt.query_named(&[(":module", module), (":key", key)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(row.get(0).ok());
+        }
+        Ok(None)
+    }
+
+    /**
+     * Store a value for a module (replace the previous one)
+     * @param module    name of the module
+     * @param key
+     * @param value
+     * @return if success
+     */
+    pub fn set_module_value(module: &String, key: &String, value: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO module_store (module, key, value)
+                                     VALUES (:module, :key, :value)")?;
+        Ok(stmt.execute_named(&[(":module", module), (":key", key), (":value", value)])?)
+    }
+
+    /**
+     * Store a link request, replacing the previous request for the same device and user
+     * @param request to store (id is ignored)
+     * @return the id of the request
+     */
+    pub fn add_link_request(request: &LinkRequest) -> RoriResult<i32> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO link_requests (hash, username, from_user, sub_author, code, expires)
+                                     VALUES (:hash, :username, :from_user, :sub_author, :code, :expires)")?;
+        stmt.execute_named(&[(":hash", &request.hash), (":username", &request.username), (":from_user", &request.from_user),
+                             (":sub_author", &request.sub_author), (":code", &request.code), (":expires", &request.expires)])?;
+        Ok(conn.last_insert_rowid() as i32)
+    }
+
+    /**
+     * Get a pending link request
+     * @param hash of the device to link
+     * @param username user to link to
+     * @param now current timestamp, expired requests are ignored
+     * @return the request if any
+     */
+    pub fn get_link_request(hash: &String, username: &String, now: i64) -> RoriResult<Option<LinkRequest>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT id, hash, username, from_user, sub_author, code, expires FROM link_requests \
+                                     WHERE hash=:hash AND username=:username AND expires>:now")?;
+        let mut rows = stmt.query_named(&[(":hash", hash), (":username", username), (":now", &now)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(LinkRequest {
+                id: row.get(0).unwrap_or(0),
+                hash: row.get(1).unwrap_or(String::new()),
+                username: row.get(2).unwrap_or(String::new()),
+                from_user: row.get(3).unwrap_or(false),
+                sub_author: row.get(4).unwrap_or(String::new()),
+                code: row.get(5).unwrap_or(String::new()),
+                expires: row.get(6).unwrap_or(0),
+            }));
+        }
+        Ok(None)
+    }
+
+    /**
+     * Remove a link request
+     * @param id of the request
+     * @return the number of removed requests
+     */
+    pub fn rm_link_request(id: &i32) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("DELETE FROM link_requests WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", id)])?)
+    }
+
+    /**
+     * Remove expired link requests
+     * @param now current timestamp
+     * @return the number of removed requests
+     */
+    pub fn rm_expired_link_requests(now: i64) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("DELETE FROM link_requests WHERE expires<=:now")?;
+        Ok(stmt.execute_named(&[(":now", &now)])?)
+    }
+
+    /**
+     * Get the parameters of the vault
+     * @return (salt, verifier) as hex strings, None if the vault was never unlocked
+     */
+    pub fn get_vault() -> RoriResult<Option<(String, String)>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT salt, verifier FROM vault WHERE id=1")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some((row.get(0).unwrap_or(String::new()), row.get(1).unwrap_or(String::new()))));
+        }
+        Ok(None)
+    }
+
+    /**
+     * Set the parameters of the vault
+     * @param salt used to derive the key (hex)
+     * @param verifier encrypted value used to check the passphrase (hex)
+     * @return if success
+     */
+    pub fn set_vault(salt: &String, verifier: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO vault (id, salt, verifier) VALUES (1, :salt, :verifier)")?;
+        Ok(stmt.execute_named(&[(":salt", salt), (":verifier", verifier)])?)
+    }
+
+    /**
+     * Get the encrypted secrets of a module
+     * @param module name of the module (every module if empty)
+     * @return the secrets, still encrypted
+     */
+    pub fn get_secrets(module: &String) -> RoriResult<Vec<Secret>> {
+        let mut secrets = Vec::new();
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT module, username, name, value, updated FROM secrets \
+                                     WHERE module=:module OR :module='' ORDER BY module, username, name")?;
+        let mut rows = stmt.query_named(&[(":module", module)])?;
+        while let Some(row) = rows.next()? {
+            secrets.push(Secret {
+                module: row.get(0).unwrap_or(String::new()),
+                username: row.get(1).unwrap_or(String::new()),
+                name: row.get(2).unwrap_or(String::new()),
+                value: row.get(3).unwrap_or(String::new()),
+                updated: row.get(4).unwrap_or(0),
+            });
+        }
+        Ok(secrets)
+    }
+
+    /**
+     * Store an encrypted secret (replace the previous one)
+     * @param secret
+     * @return if success
+     */
+    pub fn set_secret(secret: &Secret) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("INSERT OR REPLACE INTO secrets (module, username, name, value, updated)
+                                     VALUES (:module, :username, :name, :value, :updated)")?;
+        Ok(stmt.execute_named(&[(":module", &secret.module), (":username", &secret.username), (":name", &secret.name),
+                                (":value", &secret.value), (":updated", &secret.updated)])?)
+    }
+
+    /**
+     * Remove a secret
+     * @param module name of the module
+     * @param username owner of the secret (empty for every user)
+     * @param name of the secret
+     * @return the number of removed secrets
+     */
+    pub fn rm_secret(module: &String, username: &String, name: &String) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("DELETE FROM secrets WHERE module=:module AND username=:username AND name=:name")?;
+        Ok(stmt.execute_named(&[(":module", module), (":username", username), (":name", name)])?)
+    }
+
+    /**
+     * Replace the parameters of the vault and every secret in a single transaction
+     * @param salt new salt (hex)
+     * @param verifier new verifier (hex)
+     * @param secrets every secret encrypted with the new key
+     * @return the number of updated secrets
+     */
+    pub fn rekey_secrets(salt: &String, verifier: &String, secrets: &Vec<Secret>) -> RoriResult<usize> {
+        let mut conn = Database::connection()?;
+        let tx = conn.transaction()?;
+        tx.execute_named("INSERT OR REPLACE INTO vault (id, salt, verifier) VALUES (1, :salt, :verifier)",
+                         &[(":salt", salt), (":verifier", verifier)])?;
+        let mut result = 0;
+        for secret in secrets {
+            result += tx.execute_named("UPDATE secrets SET value=:value WHERE module=:module AND username=:username \
+                                        AND name=:name", &[(":value", &secret.value), (":module", &secret.module),
+                                        (":username", &secret.username), (":name", &secret.name)])?;
+        }
+        tx.commit()?;
+        Ok(result)
     }
 
     /**
      * Return tasks for the scheduler
      * @return a list of tasks
      */
-    pub fn get_tasks() -> Vec<ScheduledTask> {
+    pub fn get_tasks() -> RoriResult<Vec<ScheduledTask>> {
         let mut tasks: Vec<ScheduledTask> = Vec::new();
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM scheduler").unwrap();
-        let mut rows = stmt.query(rusqlite::NO_PARAMS).unwrap();
-        while let Ok(Some(row)) = rows.next() {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT * FROM scheduler")?;
+        let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
             tasks.push(ScheduledTask {
-                id: row.get(0).unwrap(),
-                module: row.get(1).unwrap(),
-                parameter: row.get(2).unwrap(),
-                at: row.get(3).unwrap(),
-                seconds: row.get(4).unwrap(),
-                minutes: row.get(5).unwrap(),
-                hours: row.get(6).unwrap(),
-                days: row.get(7).unwrap(),
-                repeat: row.get(8).unwrap(),
+                id: row.get(0)?,
+                module: row.get(1)?,
+                parameter: row.get(2)?,
+                at: row.get(3)?,
+                seconds: row.get(4)?,
+                minutes: row.get(5)?,
+                hours: row.get(6)?,
+                days: row.get(7)?,
+                repeat: row.get(8)?,
             });
         }
-        tasks
+        Ok(tasks)
     }
 
     /**
@@ -527,24 +1702,24 @@ impl Database {
      * @param id    Id of the task
      * @return the task or None
      */
-    pub fn get_task(id: &i32) -> Option<ScheduledTask> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM scheduler WHERE id=:id").unwrap();
-        let mut rows = stmt.query_named(&[(":id", id)]).unwrap();
-        if let Ok(Some(row)) = rows.next() {
-            return Some(ScheduledTask {
-                id: row.get(0).unwrap(),
-                module: row.get(1).unwrap(),
-                parameter: row.get(2).unwrap(),
-                at: row.get(3).unwrap(),
-                seconds: row.get(4).unwrap(),
-                minutes: row.get(5).unwrap(),
-                hours: row.get(6).unwrap(),
-                days: row.get(7).unwrap(),
-                repeat: row.get(8).unwrap(),
-            });
+    pub fn get_task(id: &i32) -> RoriResult<Option<ScheduledTask>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT * FROM scheduler WHERE id=:id")?;
+        let mut rows = stmt.query_named(&[(":id", id)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(ScheduledTask {
+                id: row.get(0)?,
+                module: row.get(1)?,
+                parameter: row.get(2)?,
+                at: row.get(3)?,
+                seconds: row.get(4)?,
+                minutes: row.get(5)?,
+                hours: row.get(6)?,
+                days: row.get(7)?,
+                repeat: row.get(8)?,
+            }));
         }
-        None
+        Ok(None)
     }
 
     /**
@@ -553,11 +1728,11 @@ impl Database {
      * @param parameters    The subset of parameters to search
      * @return The task if found otherwise returns None
      */
-    pub fn search_task(module: &String, parameters: HashMap<String, String>) -> Option<ScheduledTask> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM scheduler WHERE module=:module").unwrap();
-        let mut rows = stmt.query_named(&[(":module", module)]).unwrap();
-        while let Ok(Some(row)) = rows.next() {
+    pub fn search_task(module: &String, parameters: HashMap<String, String>) -> RoriResult<Option<ScheduledTask>> {
+        let conn = Database::connection()?;
+        let mut stmt = conn.prepare("SELECT * FROM scheduler WHERE module=:module")?;
+        let mut rows = stmt.query_named(&[(":module", module)])?;
+        while let Some(row) = rows.next()? {
             // Test subset
             let row_parameters: HashMap<String, String> = serde_json::from_str(&*row.get(2).unwrap_or(String::new())).unwrap_or(HashMap::new());
             let mut is_subset = true;
@@ -569,47 +1744,41 @@ impl Database {
             }
             // Return task if successful
             if is_subset {
-                return Some(ScheduledTask {
-                    id: row.get(0).unwrap(),
-                    module: row.get(1).unwrap(),
-                    parameter: row.get(2).unwrap(),
-                    at: row.get(3).unwrap(),
-                    seconds: row.get(4).unwrap(),
-                    minutes: row.get(5).unwrap(),
-                    hours: row.get(6).unwrap(),
-                    days: row.get(7).unwrap(),
-                    repeat: row.get(8).unwrap(),
-                });
+                return Ok(Some(ScheduledTask {
+                    id: row.get(0)?,
+                    module: row.get(1)?,
+                    parameter: row.get(2)?,
+                    at: row.get(3)?,
+                    seconds: row.get(4)?,
+                    minutes: row.get(5)?,
+                    hours: row.get(6)?,
+                    days: row.get(7)?,
+                    repeat: row.get(8)?,
+                }));
             }
         }
-        None
+        Ok(None)
     }
 
     /**
      * Add a scheduler task
      * @param task      The task to add (id is ignored as generated by the sql query)
-     * @return The result of the operation
+     * @return The id of the new task
      */
-    pub fn add_task(task: &ScheduledTask) -> Option<i32> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
+    pub fn add_task(task: &ScheduledTask) -> RoriResult<i32> {
+        let conn = Database::connection()?;
         // Else insert!
-        let mut conn = conn.prepare("INSERT INTO scheduler (module, parameter, at, seconds, minutes, hours, days, repeat)
-                                     VALUES (:module, :parameter, :at, :seconds, :minutes, :hours, :days, :repeat)").unwrap();
-        match conn.execute_named(&[(":module", &task.module),
-                                   (":parameter", &task.parameter),
-                                   (":at", &task.at),
-                                   (":seconds", &task.seconds),
-                                   (":minutes", &task.minutes),
-                                   (":hours", &task.hours),
-                                   (":days", &task.days),
-                                   (":repeat", &task.repeat)]) {
-            Ok(id) => {
-                return Some(id as i32);
-            }
-            Err(_) => {
-                return None;
-            }
-        }
+        let mut stmt = conn.prepare("INSERT INTO scheduler (module, parameter, at, seconds, minutes, hours, days, repeat)
+                                     VALUES (:module, :parameter, :at, :seconds, :minutes, :hours, :days, :repeat)")?;
+        stmt.execute_named(&[(":module", &task.module),
+                             (":parameter", &task.parameter),
+                             (":at", &task.at),
+                             (":seconds", &task.seconds),
+                             (":minutes", &task.minutes),
+                             (":hours", &task.hours),
+                             (":days", &task.days),
+                             (":repeat", &task.repeat)])?;
+        Ok(conn.last_insert_rowid() as i32)
     }
 
     /**
@@ -617,10 +1786,10 @@ impl Database {
      * @param id    Id of the task
      * @return the result of the operation
      */
-    pub fn rm_task(id: &i32) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut conn = conn.prepare("DELETE FROM scheduler WHERE id=:id").unwrap();
-        conn.execute_named(&[(":id", id)])
+    pub fn rm_task(id: &i32) -> RoriResult<usize> {
+        let conn = Database::connection()?;
+        let mut conn = conn.prepare("DELETE FROM scheduler WHERE id=:id")?;
+        Ok(conn.execute_named(&[(":id", id)])?)
     }
 
     /**
@@ -628,8 +1797,8 @@ impl Database {
      * @param task      The task to update (identified by its id)
      * @return The result of the operation
      */
-    pub fn update_task(task: &ScheduledTask) -> Result<usize, rusqlite::Error> {
-        let conn = rusqlite::Connection::open("rori.db").unwrap();
+    pub fn update_task(task: &ScheduledTask) -> RoriResult<usize> {
+        let conn = Database::connection()?;
         let mut stmt = conn.prepare("UPDATE scheduler SET module=:module, \
                                                         parameter=:parameter, \
                                                         at=:at, \
@@ -638,8 +1807,8 @@ impl Database {
                                                         hours=:hours, \
                                                         days=:days, \
                                                         repeat=:repeat \
-                                                        WHERE id=:id").unwrap();
-        stmt.execute_named(&[(":id", &task.id),
+                                                        WHERE id=:id")?;
+        Ok(stmt.execute_named(&[(":id", &task.id),
                              (":module", &task.module),
                              (":parameter", &task.parameter),
                              (":at", &task.at),
@@ -647,6 +1816,326 @@ impl Database {
                              (":minutes", &task.minutes),
                              (":hours", &task.hours),
                              (":days", &task.days),
-                             (":repeat", &task.repeat)])
+                             (":repeat", &task.repeat)])?)
+    }
+
+    /**
+     * Get the id of an user, the user is created if needed
+     * @param conn to use
+     * @param username of the user
+     * @return the id of the user or None for anonymous devices (empty username)
+     */
+    fn user_id(conn: &rusqlite::Connection, username: &String) -> RoriResult<Option<i64>> {
+        if username.is_empty() {
+            return Ok(None);
+        }
+        let mut stmt = conn.prepare("INSERT OR IGNORE INTO users (username, created) VALUES (:username, :created)")?;
+        stmt.execute_named(&[(":username", username), (":created", &time::get_time().sec)])?;
+        let mut stmt = conn.prepare("SELECT id FROM users WHERE username=:username")?;
+        let mut rows = stmt.query_named(&[(":username", username)])?;
+        if let Some(row) = rows.next()? {
+            return Ok(Some(row.get(0)?));
+        }
+        Ok(None)
+    }
+
+    /**
+     * @param conn to use
+     * @param sql query to execute
+     * @param params of the query
+     * @return one JSON object (column -> value) per row
+     */
+    fn query_json(conn: &rusqlite::Connection, sql: &str, params: &[(&str, &dyn rusqlite::ToSql)]) -> RoriResult<Vec<Map<String, Value>>> {
+        let mut stmt = conn.prepare(sql)?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt.query_named(params)?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut object = Map::new();
+            for (idx, column) in columns.iter().enumerate() {
+                let value = match row.get::<_, SqlValue>(idx)? {
+                    SqlValue::Null => Value::Null,
+                    SqlValue::Integer(i) => Value::from(i),
+                    SqlValue::Real(f) => Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null),
+                    SqlValue::Text(t) => Value::String(t),
+                    SqlValue::Blob(_) => return Err(RoriError::Parse(format!("can't export binary column {}", column))),
+                };
+                object.insert(column.clone(), value);
+            }
+            result.push(object);
+        }
+        Ok(result)
+    }
+
+    /**
+     * @param parameter of a scheduled task
+     * @return the username of the task (empty if none)
+     */
+    fn task_owner(parameter: &str) -> String {
+        let metadatas: HashMap<String, String> = serde_json::from_str(parameter).unwrap_or(HashMap::new());
+        metadatas.get("username").cloned().unwrap_or(String::new())
+    }
+
+    /**
+     * @param conn to use
+     * @param table
+     * @return the columns of a table, or an error if the table doesn't exist
+     */
+    fn columns(conn: &rusqlite::Connection, table: &str) -> RoriResult<Vec<String>> {
+        let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(:table)")?;
+        let mut rows = stmt.query_named(&[(":table", &table)])?;
+        let mut columns = Vec::new();
+        while let Some(row) = rows.next()? {
+            columns.push(row.get(0)?);
+        }
+        if columns.is_empty() {
+            return Err(RoriError::Parse(format!("unknown table {}", table)));
+        }
+        Ok(columns)
+    }
+
+    /**
+     * Remove the rows linked to an username, except the user and its devices (see export_user)
+     * @param conn to use
+     * @param username
+     * @return the number of removed rows
+     */
+    fn remove_user_rows(conn: &rusqlite::Connection, username: &String) -> RoriResult<usize> {
+        let mut result = 0;
+        for table in USER_TABLES.iter().filter(|t| **t != "users") {
+            result += conn.execute_named(&*format!("DELETE FROM \"{}\" WHERE username=:username", table),
+                                         &[(":username", username)])?;
+        }
+        let prefix = format!("{}:", username);
+        result += conn.execute_named("DELETE FROM module_store WHERE key=:username OR substr(key, 1, length(:prefix))=:prefix",
+                                     &[(":username", username), (":prefix", &prefix)])?;
+        result += conn.execute_named("DELETE FROM secrets WHERE username=:username", &[(":username", username)])?;
+        result += conn.execute_named("DELETE FROM link_requests WHERE username=:username", &[(":username", username)])?;
+        let mut tasks: Vec<i32> = Vec::new();
+        {
+            let mut stmt = conn.prepare("SELECT id, parameter FROM scheduler")?;
+            let mut rows = stmt.query(rusqlite::NO_PARAMS)?;
+            while let Some(row) = rows.next()? {
+                let parameter: String = row.get(1)?;
+                if Database::task_owner(&*parameter) == *username {
+                    tasks.push(row.get(0)?);
+                }
+            }
+        }
+        for id in tasks {
+            result += conn.execute_named("DELETE FROM scheduler WHERE id=:id", &[(":id", &id)])?;
+        }
+        Ok(result)
+    }
+
+    /**
+     * Remove capabilities of removed devices
+     * @param conn to use
+     * @return the number of removed kinds and actions
+     */
+    fn remove_orphan_capabilities(conn: &rusqlite::Connection) -> RoriResult<usize> {
+        let mut result = conn.execute("DELETE FROM device_kinds WHERE device NOT IN (SELECT id FROM devices)", rusqlite::NO_PARAMS)?;
+        result += conn.execute("DELETE FROM capabilities WHERE device NOT IN (SELECT id FROM devices)", rusqlite::NO_PARAMS)?;
+        Ok(result)
+    }
+
+    /**
+     * @param row (id, hash, username, devicename, sub_author, is_bridge)
+     * @return the device
+     */
+    fn device_from_row(row: &rusqlite::Row) -> DeviceRecord {
+        DeviceRecord {
+            id: row.get(0).unwrap_or(0),
+            hash: row.get(1).unwrap_or(String::new()),
+            username: row.get(2).unwrap_or(String::new()),
+            devicename: row.get(3).unwrap_or(String::new()),
+            sub_author: row.get(4).unwrap_or(String::new()),
+            is_bridge: row.get(5).unwrap_or(false),
+        }
+    }
+
+    /**
+     * @param row (id, username, display_name, created, role)
+     * @return the user
+     */
+    fn user_from_row(row: &rusqlite::Row) -> UserRecord {
+        UserRecord {
+            id: row.get(0).unwrap_or(0),
+            username: row.get(1).unwrap_or(String::new()),
+            display_name: row.get(2).unwrap_or(String::new()),
+            created: row.get(3).unwrap_or(0),
+            role: Role::parse(&*row.get::<_, String>(4).unwrap_or(String::new())).unwrap_or_default(),
+        }
+    }
+}
+
+impl Repository for Database {
+    fn get_users(&self) -> RoriResult<Vec<UserRecord>> {
+        Database::get_users()
+    }
+
+    fn get_user(&self, username: &String) -> RoriResult<Option<UserRecord>> {
+        Database::get_user(username)
+    }
+
+    fn set_display_name(&self, username: &String, display_name: &String) -> RoriResult<usize> {
+        Database::set_display_name(username, display_name)
+    }
+
+    fn set_role(&self, username: &String, role: Role) -> RoriResult<usize> {
+        Database::set_role(username, role)
+    }
+
+    fn bootstrap_admin(&self, hash: &String) -> RoriResult<usize> {
+        Database::bootstrap_admin(hash)
+    }
+
+    fn rename_user(&self, username: &String, new_username: &String) -> RoriResult<usize> {
+        let secrets = Vault::rename_user(username, new_username)?;
+        Database::rename_user(username, new_username, &secrets)
+    }
+
+    fn get_devices(&self) -> RoriResult<Vec<DeviceRecord>> {
+        Database::get_devices()
+    }
+
+    fn get_devices_for_hash(&self, hash: &str) -> RoriResult<Vec<DeviceRecord>> {
+        Database::get_devices_for_hash(hash)
+    }
+
+    fn get_devices_for_username(&self, username: &str) -> RoriResult<Vec<DeviceRecord>> {
+        Database::get_devices_for_username(username)
+    }
+
+    fn get_device(&self, hash: &String, username: &String) -> RoriResult<Option<DeviceRecord>> {
+        Database::get_device(hash, username)
+    }
+
+    fn insert_new_device(&self, hash: &String, username: &String, devicename: &String, is_bridge: bool) -> RoriResult<usize> {
+        Database::insert_new_device(hash, username, devicename, is_bridge)
+    }
+
+    fn remove_device(&self, id: &i32) -> RoriResult<usize> {
+        Database::remove_device(id)
+    }
+
+    fn update_username(&self, id: &i32, username: &String) -> RoriResult<usize> {
+        Database::update_username(id, username)
+    }
+
+    fn update_devicename(&self, id: &i32, devicename: &String) -> RoriResult<usize> {
+        Database::update_devicename(id, devicename)
+    }
+
+    fn update_sub_author(&self, id: &i32, sub_author: &String) -> RoriResult<usize> {
+        Database::update_sub_author(id, sub_author)
+    }
+
+    fn sub_author(&self, hash: &String, sub_author: &String) -> RoriResult<String> {
+        Database::sub_author(hash, sub_author)
+    }
+
+    fn sub_author_id(&self, hash: &String, username: &String) -> RoriResult<String> {
+        Database::sub_author_id(hash, username)
+    }
+
+    fn is_bridge(&self, hash: &String) -> RoriResult<bool> {
+        Database::is_bridge(hash)
+    }
+
+    fn bridgify(&self, id: &i32) -> RoriResult<usize> {
+        Database::bridgify(id)
+    }
+
+    fn get_datatypes(&self, id: &i32) -> RoriResult<Vec<String>> {
+        Database::get_datatypes(id)
+    }
+
+    fn set_datatypes(&self, id: &i32, datatypes: Vec<String>) -> RoriResult<usize> {
+        Database::set_datatypes(id, datatypes)
+    }
+
+    fn set_capabilities(&self, id: &i32, capabilities: &Capabilities) -> RoriResult<usize> {
+        Database::set_capabilities(id, capabilities)
+    }
+
+    fn set_bridge_owner(&self, hash: &String, username: &String) -> RoriResult<usize> {
+        Database::set_bridge_owner(hash, username)
+    }
+
+    fn get_bridge_owner(&self, hash: &String) -> RoriResult<Option<String>> {
+        Database::get_bridge_owner(hash)
+    }
+
+    fn add_unmatched(&self, username: &String, interaction: &Interaction) -> RoriResult<usize> {
+        Database::add_unmatched(username, interaction)
+    }
+
+    fn set_lang(&self, username: &String, lang: &String) -> RoriResult<usize> {
+        Database::set_lang(username, lang)
+    }
+
+    fn set_preference(&self, username: &String, datatype: &String, device: &i32) -> RoriResult<usize> {
+        Database::set_preference(username, datatype, device)
+    }
+
+    fn rm_preference(&self, username: &String, datatype: &String) -> RoriResult<usize> {
+        Database::rm_preference(username, datatype)
+    }
+
+    fn export_user(&self, username: &String) -> RoriResult<BTreeMap<String, Vec<Map<String, Value>>>> {
+        Database::export_user(username)
+    }
+
+    fn purge_user(&self, username: &String) -> RoriResult<usize> {
+        Database::purge_user(username)
+    }
+
+    fn add_link_request(&self, request: &LinkRequest) -> RoriResult<i32> {
+        Database::add_link_request(request)
+    }
+
+    fn get_link_request(&self, hash: &String, username: &String, now: i64) -> RoriResult<Option<LinkRequest>> {
+        Database::get_link_request(hash, username, now)
+    }
+
+    fn rm_link_request(&self, id: &i32) -> RoriResult<usize> {
+        Database::rm_link_request(id)
+    }
+
+    fn rm_expired_link_requests(&self, now: i64) -> RoriResult<usize> {
+        Database::rm_expired_link_requests(now)
+    }
+
+    fn add_group(&self, name: &String, owner: &String) -> RoriResult<i32> {
+        Database::add_group(name, owner)
+    }
+
+    fn rm_group(&self, name: &String) -> RoriResult<usize> {
+        Database::rm_group(name)
+    }
+
+    fn get_group(&self, name: &String) -> RoriResult<Option<Group>> {
+        Database::get_group(name)
+    }
+
+    fn get_groups(&self) -> RoriResult<Vec<Group>> {
+        Database::get_groups()
+    }
+
+    fn add_group_member(&self, name: &String, username: &String) -> RoriResult<usize> {
+        Database::add_group_member(name, username)
+    }
+
+    fn rm_group_member(&self, name: &String, username: &String) -> RoriResult<usize> {
+        Database::rm_group_member(name, username)
+    }
+
+    fn set_module_enabled(&self, name: &String, enabled: bool) -> RoriResult<usize> {
+        Database::set_module_enabled(name, enabled)
+    }
+
+    fn rm_task(&self, id: &i32) -> RoriResult<usize> {
+        Database::rm_task(id)
     }
 }
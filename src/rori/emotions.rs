@@ -0,0 +1,152 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::database::Database;
+use rori::error::RoriResult;
+use rori::interaction::Interaction;
+use rori::text::words;
+use time;
+
+// Emotions are between 0 (neutral) and MAX_EMOTION
+const MAX_EMOTION: i64 = 100;
+// Emotions are divided by 2 each HALF_LIFE seconds (one day)
+const HALF_LIFE: f64 = 86400.0;
+// Interactions closer than this (in seconds) are considered as spam
+const SPAM_DELAY: i64 = 5;
+// RORI is happy to see users back after this delay (one week)
+const ABSENCE_DELAY: i64 = 7 * 86400;
+
+const POLITE_WORDS: [&'static str; 14] = ["merci", "thanks", "thank", "thx", "please", "plz", "stp", "svp",
+                                          "hello", "hi", "bonjour", "salut", "bravo", "congrats"];
+const INSULTS: [&'static str; 14] = ["idiot", "stupid", "stupide", "dumb", "moron", "nul", "nulle", "con",
+                                     "conne", "connard", "debile", "shut", "tais", "crap"];
+
+/**
+ * Emotional state of RORI toward an user, stored in the emotions table
+ */
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Emotions {
+    pub love: i64,
+    pub joy: i64,
+    pub surprise: i64,
+    pub anger: i64,
+    pub sadness: i64,
+    pub fear: i64,
+}
+
+impl Emotions {
+    /**
+     * Get the current state toward an user
+     * @param username
+     * @return emotions (decayed since the last update)
+     */
+    pub fn for_user(username: &String) -> RoriResult<Emotions> {
+        Ok(match Database::get_emotions(username)? {
+            Some((emotions, updated)) => emotions.decay(time::get_time().sec - updated),
+            None => Emotions::default(),
+        })
+    }
+
+    /**
+     * Update the state toward the author of an interaction and save it
+     * Politeness increases love and joy, insults increase anger, sadness and fear,
+     * spamming surprises and angers RORI, and RORI is happy when someone comes back.
+     * @param username author of the interaction
+     * @param interaction
+     * @return the new emotions
+     */
+    pub fn update(username: &String, interaction: &Interaction) -> RoriResult<Emotions> {
+        let now = time::get_time().sec;
+        let (mut emotions, elapsed) = match Database::get_emotions(username)? {
+            Some((emotions, updated)) => (emotions.decay(now - updated), Some(now - updated)),
+            None => (Emotions::default(), None),
+        };
+        let text = if interaction.normalized_body.is_empty() { &interaction.body } else { &interaction.normalized_body };
+        let text = words(text);
+        let polite = text.iter().filter(|w| POLITE_WORDS.contains(&w.as_str())).count() as i64;
+        let insults = text.iter().filter(|w| INSULTS.contains(&w.as_str())).count() as i64;
+
+        emotions.love += 5 * polite - 5 * insults;
+        emotions.joy += 5 * polite;
+        emotions.anger += 15 * insults - 5 * polite;
+        emotions.sadness += 10 * insults;
+        emotions.fear += 5 * insults;
+        match elapsed {
+            Some(elapsed) if elapsed < SPAM_DELAY => {
+                emotions.surprise += 5;
+                emotions.anger += 2;
+            },
+            Some(elapsed) if elapsed > ABSENCE_DELAY => {
+                emotions.surprise += 10;
+                emotions.joy += 10;
+            },
+            _ => {}
+        }
+
+        let emotions = emotions.clamp();
+        Database::set_emotions(username, &emotions, now)?;
+        Ok(emotions)
+    }
+
+    /**
+     * Decay emotions toward neutral
+     * @param self
+     * @param elapsed seconds since the last update
+     * @return decayed emotions
+     */
+    pub fn decay(&self, elapsed: i64) -> Emotions {
+        let factor = 0.5f64.powf(elapsed.max(0) as f64 / HALF_LIFE);
+        let decay = |value: i64| (value as f64 * factor).round() as i64;
+        Emotions {
+            love: decay(self.love),
+            joy: decay(self.joy),
+            surprise: decay(self.surprise),
+            anger: decay(self.anger),
+            sadness: decay(self.sadness),
+            fear: decay(self.fear),
+        }
+    }
+
+// Private stuff
+
+    /**
+     * Keep emotions between 0 and MAX_EMOTION
+     * @param self
+     * @return emotions in bounds
+     */
+    fn clamp(&self) -> Emotions {
+        let clamp = |value: i64| value.max(0).min(MAX_EMOTION);
+        Emotions {
+            love: clamp(self.love),
+            joy: clamp(self.joy),
+            surprise: clamp(self.surprise),
+            anger: clamp(self.anger),
+            sadness: clamp(self.sadness),
+            fear: clamp(self.fear),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use dbus;
+use openssl::error::ErrorStack;
+use rusqlite;
+use serde_json;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/**
+ * Errors returned by the database, the server and the API
+ */
+#[derive(Debug)]
+pub enum RoriError {
+    // The database can't be used (locked, invalid query...)
+    Database(rusqlite::Error),
+    // A file can't be read or written
+    Io(io::Error),
+    // The daemon can't be reached or answered an error
+    Transport(String),
+    // Invalid content (json, command, parameters...)
+    Parse(String),
+    // The requested object doesn't exist
+    NotFound(String),
+    // The object to create already exists
+    AlreadyExists(String),
+    // The vault is locked or can't decrypt a secret
+    Vault(String),
+    // The author is not allowed to do this
+    Denied(String),
+}
+
+pub type RoriResult<T> = Result<T, RoriError>;
+
+impl RoriError {
+    /**
+     * @return the HTTP status code to answer for this error
+     */
+    pub fn http_status(&self) -> u16 {
+        match self {
+            RoriError::Parse(_) => 400,
+            RoriError::Denied(_) => 403,
+            RoriError::NotFound(_) => 404,
+            RoriError::AlreadyExists(_) => 409,
+            RoriError::Database(_) | RoriError::Io(_) | RoriError::Vault(_) => 500,
+            RoriError::Transport(_) => 502,
+        }
+    }
+}
+
+// Used for println!
+impl fmt::Display for RoriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoriError::Database(e) => write!(f, "database error: {}", e),
+            RoriError::Io(e) => write!(f, "io error: {}", e),
+            RoriError::Transport(e) => write!(f, "transport error: {}", e),
+            RoriError::Parse(e) => write!(f, "parse error: {}", e),
+            RoriError::NotFound(e) => write!(f, "{} not found", e),
+            RoriError::AlreadyExists(e) => write!(f, "{} already exists", e),
+            RoriError::Vault(e) => write!(f, "vault error: {}", e),
+            RoriError::Denied(e) => write!(f, "{} denied", e),
+        }
+    }
+}
+
+impl Error for RoriError {}
+
+impl From<rusqlite::Error> for RoriError {
+    fn from(e: rusqlite::Error) -> RoriError {
+        RoriError::Database(e)
+    }
+}
+
+impl From<io::Error> for RoriError {
+    fn from(e: io::Error) -> RoriError {
+        RoriError::Io(e)
+    }
+}
+
+impl From<dbus::Error> for RoriError {
+    fn from(e: dbus::Error) -> RoriError {
+        RoriError::Transport(String::from(e.message().unwrap_or("unknown dbus error")))
+    }
+}
+
+impl From<ErrorStack> for RoriError {
+    fn from(e: ErrorStack) -> RoriError {
+        RoriError::Vault(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for RoriError {
+    fn from(e: serde_json::Error) -> RoriError {
+        RoriError::Parse(e.to_string())
+    }
+}
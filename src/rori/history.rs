@@ -0,0 +1,137 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::database::Database;
+use rori::error::RoriResult;
+use time;
+
+// Number of messages in a page of history
+pub const PAGE_SIZE: u32 = 10;
+
+/**
+ * A message received or sent by RORI, stored in the interactions table
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub id: i32,
+    // Owner of the device (empty if anonymous)
+    pub username: String,
+    // Hash of the device which sent or received the message
+    pub device: String,
+    // true if sent by RORI
+    pub outgoing: bool,
+    pub datatype: String,
+    pub body: String,
+    // Timestamp of the message
+    pub time: i64,
+}
+
+/**
+ * Store a message. Failures are only logged, history must not block interactions
+ * @param username owner of the device
+ * @param device hash of the device
+ * @param outgoing if sent by RORI
+ * @param datatype of the message
+ * @param body of the message
+ * @param time timestamp of the message
+ */
+pub fn record(username: &String, device: &String, outgoing: bool, datatype: &str, body: &str, time: i64) {
+    let entry = HistoryEntry {
+        id: 0,
+        username: username.clone(),
+        device: device.clone(),
+        outgoing: outgoing,
+        datatype: String::from(datatype),
+        body: String::from(body),
+        time: time,
+    };
+    if let Err(e) = Database::add_history(&entry) {
+        warn!("can't store interaction with {} in history: {}", device, e);
+    }
+}
+
+/**
+ * Store a message sent by RORI to a device
+ * @param device hash of the destination
+ * @param datatype of the message
+ * @param body of the message
+ */
+pub fn record_outgoing(device: &String, datatype: &str, body: &str) {
+    // NOTE: a bridge can be shared by several users, keep the first one
+    let username = match Database::get_devices_for_hash(&*device) {
+        Ok(devices) => devices.into_iter().map(|d| d.username).find(|u| !u.is_empty()).unwrap_or(String::new()),
+        Err(e) => {
+            warn!("can't find the user of {}: {}", device, e);
+            String::new()
+        }
+    };
+    record(&username, device, true, datatype, body, time::get_time().sec);
+}
+
+/**
+ * Search text messages exchanged with an user, most recent first
+ * @param username
+ * @param text words to search (every message if empty)
+ * @param page to retrieve, starting at 1
+ * @return at most PAGE_SIZE messages
+ */
+pub fn search(username: &String, text: &str, page: u32) -> RoriResult<Vec<HistoryEntry>> {
+    let offset = (page.max(1) - 1) * PAGE_SIZE;
+    Database::search_history(username, text, offset, PAGE_SIZE)
+}
+
+/**
+ * Build a FTS5 query matching all words of a text. Words are quoted, so the
+ * syntax of FTS5 (AND, NEAR, *, ...) can't be used by users
+ * @param text
+ * @return the query (empty if no words)
+ */
+pub fn fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace("\"", "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/**
+ * Build a readable answer for the /history command
+ * @param entries to show
+ * @return one line per message
+ */
+pub fn format(entries: &Vec<HistoryEntry>) -> String {
+    if entries.is_empty() {
+        return String::from("Nothing found");
+    }
+    let mut result = Vec::new();
+    for entry in entries {
+        let date = time::at(time::Timespec::new(entry.time, 0));
+        let author = if entry.outgoing { "rori" } else { "you" };
+        result.push(format!("[{}] {}: {}", date.strftime("%Y-%m-%d %H:%M").map(|d| d.to_string()).unwrap_or(String::new()),
+                            author, entry.body));
+    }
+    result.join("\n")
+}
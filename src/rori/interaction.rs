@@ -25,12 +25,13 @@
  * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  **/
 
+use rori::emotions::Emotions;
 use rori::user::Device;
 use std::collections::HashMap;
 use serde::ser::{Serialize, SerializeStruct};
 use serde::Serializer;
 use std::fmt;
-use time::Tm;
+use time::{self, Tm};
 
 /**
  * Represent a RING interaction, just here to store informations.
@@ -41,11 +42,37 @@ pub struct Interaction
 {
     pub device_author: Device,
     pub body: String,
+    // body after the Normalizer (lowercase, without accents...)
+    pub normalized_body: String,
     pub datatype: String,
+    // Language of the author ("en", "fr"...)
+    pub lang: String,
     pub metadatas: HashMap<String, String>,
+    pub slots: HashMap<String, String>,
+    // Emotions of RORI toward the author
+    pub emotions: Emotions,
     pub time: Tm
 }
 
+impl Default for Interaction {
+    /**
+     * An empty text interaction received now from an unknown device, in English
+     */
+    fn default() -> Interaction {
+        Interaction {
+            device_author: Device::new(&0, &String::new()),
+            body: String::new(),
+            normalized_body: String::new(),
+            datatype: String::from("text/plain"),
+            lang: String::from("en"),
+            metadatas: HashMap::new(),
+            slots: HashMap::new(),
+            emotions: Emotions::default(),
+            time: time::now()
+        }
+    }
+}
+
 // Used for println!
 impl fmt::Display for Interaction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -62,11 +89,15 @@ impl Serialize for Interaction {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where S: Serializer
     {
-        // 5 is the number of fields in the struct.
-        let mut state = serializer.serialize_struct("Interaction", 5)?;
+        // 9 is the number of fields in the struct.
+        let mut state = serializer.serialize_struct("Interaction", 9)?;
         state.serialize_field("device_author", &self.device_author).unwrap();
         state.serialize_field("body", &self.body).unwrap();
+        state.serialize_field("normalized_body", &self.normalized_body).unwrap();
+        state.serialize_field("lang", &self.lang).unwrap();
         state.serialize_field("metadatas", &self.metadatas).unwrap();
+        state.serialize_field("slots", &self.slots).unwrap();
+        state.serialize_field("emotions", &self.emotions).unwrap();
         state.serialize_field("body", &self.body).unwrap();
         state.serialize_field("time", &self.time.rfc3339().to_string()).unwrap();
         state.end()
@@ -0,0 +1,95 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::database::Database;
+use rori::interaction::Interaction;
+use rori::text::words;
+
+/**
+ * Languages understood by RORI
+ */
+pub const SUPPORTED_LANGS: [&'static str; 2] = ["en", "fr"];
+
+// Frequent words used to guess the language of a text
+const EN_WORDS: [&'static str; 30] = ["the", "with", "an", "is", "are", "you", "i", "me", "my", "your",
+                                      "what", "how", "who", "when", "where", "can", "do", "does", "it", "to",
+                                      "of", "and", "in", "at", "hello", "hi", "please", "thanks", "wake", "up"];
+const FR_WORDS: [&'static str; 30] = ["le", "la", "les", "un", "une", "des", "est", "es", "tu", "je",
+                                      "moi", "mon", "ton", "quel", "quelle", "comment", "qui", "quand", "où", "peux",
+                                      "de", "et", "dans", "à", "au", "bonjour", "salut", "merci", "ça", "vas"];
+
+/**
+ * Tells if a language is supported
+ * @param lang code of the language (like "en")
+ * @return if supported
+ */
+pub fn is_supported(lang: &str) -> bool {
+    SUPPORTED_LANGS.contains(&lang)
+}
+
+/**
+ * Guess the language of a text by counting frequent words of each language
+ * @param text to analyze
+ * @return the language or None if unknown
+ */
+pub fn detect(text: &String) -> Option<String> {
+    let text = words(text);
+    let en = text.iter().filter(|w| EN_WORDS.contains(&w.as_str())).count();
+    let fr = text.iter().filter(|w| FR_WORDS.contains(&w.as_str())).count();
+    if en > fr {
+        return Some(String::from("en"));
+    } else if fr > en {
+        return Some(String::from("fr"));
+    }
+    None
+}
+
+/**
+ * Get the language to use for an interaction. In order:
+ * 1. the preference of the user (set with /lang)
+ * 2. the "lang" metadata sent by the client
+ * 3. the language detected in the body
+ * 4. "en"
+ * @param username author of the interaction (empty if anonymous)
+ * @param interaction
+ * @return the language
+ */
+pub fn resolve(username: &String, interaction: &Interaction) -> String {
+    if username.len() > 0 {
+        match Database::get_lang(username) {
+            Ok(Some(lang)) => return lang,
+            Ok(None) => {},
+            Err(e) => warn!("Can't get the language of {}: {}", username, e),
+        }
+    }
+    if let Some(lang) = interaction.metadatas.get("lang") {
+        if is_supported(lang) {
+            return lang.clone();
+        }
+    }
+    detect(&interaction.body).unwrap_or(String::from("en"))
+}
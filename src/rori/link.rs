@@ -0,0 +1,107 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use openssl::rand::rand_bytes;
+use rori::error::RoriResult;
+use serde_json::Value;
+
+// Default lifetime of a link request, in seconds
+pub const DEFAULT_TTL: i64 = 600;
+// Number of digits of a one-time code
+const CODE_LEN: usize = 6;
+
+/**
+ * A pending /link between a device and an user, waiting for the other side
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkRequest {
+    pub id: i32,
+    // Device to link
+    pub hash: String,
+    pub username: String,
+    // If the request comes from a device of the user (else from the new device)
+    pub from_user: bool,
+    // Sub author of the new device (if bridge)
+    pub sub_author: String,
+    // One-time code to echo from the new device (empty if codes are disabled)
+    pub code: String,
+    // Timestamp after which the request is ignored
+    pub expires: i64,
+}
+
+/**
+ * How devices are linked, from the "link" object of config.json
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkPolicy {
+    // Lifetime of a request in seconds
+    pub ttl: i64,
+    // If the new device must echo a code sent to a registered device
+    pub code: bool,
+}
+
+impl LinkPolicy {
+    /**
+     * Default policy: requests expire after 10 minutes, no code
+     */
+    pub fn new() -> LinkPolicy {
+        LinkPolicy {
+            ttl: DEFAULT_TTL,
+            code: false,
+        }
+    }
+
+    /**
+     * Build the policy from the "link" object of config.json
+     * e.g. {"ttl": 300, "code": true}
+     * @param config
+     * @return the LinkPolicy, missing values are the default ones
+     */
+    pub fn from_config(config: &Value) -> LinkPolicy {
+        LinkPolicy {
+            ttl: config["ttl"].as_i64().filter(|ttl| *ttl > 0).unwrap_or(DEFAULT_TTL),
+            code: config["code"].as_bool().unwrap_or(false),
+        }
+    }
+}
+
+/**
+ * Generate a one-time code
+ * @return CODE_LEN random digits
+ */
+pub fn generate_code() -> RoriResult<String> {
+    let mut code = String::new();
+    while code.len() < CODE_LEN {
+        let mut byte = [0; 1];
+        rand_bytes(&mut byte)?;
+        // Ignore 250..255 to keep digits uniform
+        if byte[0] < 250 {
+            code.push(char::from(b'0' + byte[0] % 10));
+        }
+    }
+    Ok(code)
+}
@@ -29,13 +29,15 @@ use dbus::{Connection, ConnectionItem, BusType, Message};
 use dbus::arg::{Array, Dict};
 use rori::account::Account;
 use rori::database::Database;
+use rori::error::{RoriError, RoriResult};
 use rori::interaction::Interaction;
+use rori::presence;
+use rori::repository::DeviceRecord;
 use rori::server::Server;
 use rori::user::Device;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use time;
 
 /**
  * This class is used to load RORI accounts and handle signals from Ring.
@@ -55,8 +57,8 @@ impl Manager {
      * @param ring_id to retrieve
      * @return a Manager if success, else an error
      */
-    pub fn init(ring_id: &str) -> Result<Manager, &'static str> {
-        Database::init_db();
+    pub fn init(ring_id: &str) -> RoriResult<Manager> {
+        Database::init_db()?;
         let mut manager = Manager {
             server: Server::new(Account::null()),
 
@@ -64,16 +66,17 @@ impl Manager {
             configuration_path: "/cx/ring/Ring/ConfigurationManager",
             configuration_iface: "cx.ring.Ring.ConfigurationManager",
         };
-        manager.server.account = Manager::build_account(ring_id);
+        let account = Manager::build_account(ring_id)?;
+        manager.server.set_account(account);
         if !manager.server.account.enabled {
             info!("{} was not enabled. Enable it", ring_id);
-            manager.enable_account();
+            manager.enable_account()?;
         }
         debug!("Get: {}", manager.server.account.ring_id);
         if manager.server.account.ring_id == "" {
-            return Err("Cannot build RORI account, please check configuration");
+            return Err(RoriError::NotFound(format!("account {}", ring_id)));
         }
-        manager.load_contacts();
+        manager.load_contacts()?;
         info!("{}: Account loaded", manager.server.account.id);
         Ok(manager)
     }
@@ -84,11 +87,13 @@ impl Manager {
      */
     pub fn handle_signals(manager: Arc<Mutex<Manager>>, stop: Arc<AtomicBool>) {
         // Use another dbus connection to listen signals.
-        let dbus_listener = Connection::get_private(BusType::Session).unwrap();
-        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=incomingAccountMessage").unwrap();
-        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=incomingTrustRequest").unwrap();
-        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=accountsChanged").unwrap(); // TODO
-        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=registrationStateChanged").unwrap(); // TODO
+        let dbus_listener = match Manager::signal_listener() {
+            Ok(dbus_listener) => dbus_listener,
+            Err(e) => {
+                error!("Can't listen signals from the daemon: {}", e);
+                return;
+            }
+        };
         // For each signals, call handlers.
         for i in dbus_listener.iter(100) {
             let mut m = manager.lock().unwrap();
@@ -99,10 +104,18 @@ impl Manager {
                     m.server.handle_interaction(interaction);
                 }
             };
+            if let Some((account_id, hash, online)) = m.handle_presence(&i) {
+                if account_id == m.server.account.id {
+                    debug!("{} is now {}", hash, if online { "online" } else { "offline" });
+                    presence::update(&hash, online);
+                }
+            };
             if let Some((account_id, from)) = m.handle_requests(&i) {
                 if account_id == m.server.account.id {
                     info!("New request from {}", from);
-                    m.accept_request(&*account_id, &*from, true);
+                    if let Err(e) = m.accept_request(&*account_id, &*from, true) {
+                        error!("Can't accept request from {}: {}", from, e);
+                    }
                     // At first, the new account is considered as anonymous
                     // The device should send a new message to be registered to its RORI
                     m.server.add_new_anonymous_device(&from);
@@ -121,8 +134,9 @@ impl Manager {
      * @param main_info path or alias
      * @param password
      * @param from_archive if main_info is a path
+     * @return the id of the new account
      */
-    pub fn add_account(main_info: &str, password: &str, from_archive: bool) {
+    pub fn add_account(main_info: &str, password: &str, from_archive: bool) -> RoriResult<String> {
         let mut details: HashMap<&str, &str> = HashMap::new();
         if from_archive {
             details.insert("Account.archivePath", main_info);
@@ -135,32 +149,58 @@ impl Manager {
         let dbus_msg = Message::new_method_call("cx.ring.Ring", "/cx/ring/Ring/ConfigurationManager",
                                                 "cx.ring.Ring.ConfigurationManager",
                                                 "addAccount")
-                                                .ok().expect("addAccount fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("dbus connection not ok");
-        let response = dbus.send_with_reply_and_block(dbus_msg.append1(details), 2000).unwrap();
+                                                .map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        let response = dbus.send_with_reply_and_block(dbus_msg.append1(details), 2000)?;
         // addAccount returns one argument, which is a string.
         let account_added: &str  = response.get1().unwrap_or("");
         info!("New account: {:?}", account_added);
+        Ok(String::from(account_added))
+    }
+
+    /**
+     * Export a RING account to an archive
+     * @param account_id account to export
+     * @param path of the archive to create
+     * @param password to protect the archive
+     * @return an error if the daemon can't export the account
+     */
+    pub fn export_account(account_id: &str, path: &str, password: &str) -> RoriResult<()> {
+        let dbus_msg = Message::new_method_call("cx.ring.Ring", "/cx/ring/Ring/ConfigurationManager",
+                                                "cx.ring.Ring.ConfigurationManager",
+                                                "exportToFile")
+                                                .map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        let response = dbus.send_with_reply_and_block(dbus_msg.append3(account_id, path, password), 10000)?;
+        // exportToFile returns one argument, which is a boolean.
+        if !response.get1().unwrap_or(false) {
+            return Err(RoriError::Transport(format!("can't export account {}", account_id)));
+        }
+        info!("Account {} exported to {}", account_id, path);
+        Ok(())
     }
 
     /**
      * Get current ring accounts
      * @return current accounts
      */
-    pub fn get_account_list() -> Vec<Account> {
+    pub fn get_account_list() -> RoriResult<Vec<Account>> {
         let mut account_list: Vec<Account> = Vec::new();
         let dbus_msg = Message::new_method_call("cx.ring.Ring", "/cx/ring/Ring/ConfigurationManager",
                                                 "cx.ring.Ring.ConfigurationManager",
                                                 "getAccountList")
-                                                .ok().expect("getAccountList fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("dbus connection not ok");
-        let response = dbus.send_with_reply_and_block(dbus_msg, 2000).unwrap();
+                                                .map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        let response = dbus.send_with_reply_and_block(dbus_msg, 2000)?;
         // getAccountList returns one argument, which is an array of strings.
-        let accounts: Array<&str, _>  = response.get1().unwrap();
+        let accounts: Array<&str, _> = match response.get1() {
+            Some(accounts) => accounts,
+            None => return Err(RoriError::Parse(String::from("getAccountList returned no account list")))
+        };
         for account in accounts {
-            account_list.push(Manager::build_account(account));
+            account_list.push(Manager::build_account(account)?);
         }
-        account_list
+        Ok(account_list)
     }
 
 // Private stuff
@@ -173,17 +213,17 @@ impl Manager {
      * @param accept true if accept the request
      * @return if the contact was accepted
      */
-    fn accept_request(&self, account_id: &str, from: &str, accept: bool) -> bool {
+    fn accept_request(&self, account_id: &str, from: &str, accept: bool) -> RoriResult<bool> {
         let method = if accept {"acceptTrustRequest"} else {"discardTrustRequest"};
         let dbus_msg = Message::new_method_call(self.ring_dbus, self.configuration_path,
                                                 self.configuration_iface,
-                                                method).ok().expect("method call fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("connection not ok.");
+                                                method).map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
         let response = dbus.send_with_reply_and_block(
-            dbus_msg.append3(account_id, from, accept), 2000).unwrap();
+            dbus_msg.append3(account_id, from, accept), 2000)?;
         let result = response.get1().unwrap_or(false);
         info!("{} handles request from {} with success: {}", account_id, from, result);
-        return result;
+        Ok(result)
     }
 
     /**
@@ -191,15 +231,16 @@ impl Manager {
      * @param id the account id to build
      * @return the account retrieven
      */
-    fn build_account(id: &str) -> Account {
+    fn build_account(id: &str) -> RoriResult<Account> {
         let dbus_msg = Message::new_method_call("cx.ring.Ring", "/cx/ring/Ring/ConfigurationManager",
                                                 "cx.ring.Ring.ConfigurationManager",
-                                                "getAccountDetails").ok().expect("method call fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("connection not ok.");
-        let response = dbus.send_with_reply_and_block(
-                                           dbus_msg.append1(id), 2000
-                                       ).ok().expect("Is the ring-daemon launched?");
-        let details: Dict<&str, &str, _> = response.get1().unwrap();
+                                                "getAccountDetails").map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        let response = dbus.send_with_reply_and_block(dbus_msg.append1(id), 2000)?;
+        let details: Dict<&str, &str, _> = match response.get1() {
+            Some(details) => details,
+            None => return Err(RoriError::Parse(format!("getAccountDetails returned no details for {}", id)))
+        };
 
         let mut account = Account::null();
         account.id = id.to_owned();
@@ -218,20 +259,22 @@ impl Manager {
                 }
             }
         }
-        account
+        Ok(account)
     }
 
     /**
      * Enable a Ring account
      * @param self
+     * @return an error if the daemon can't be reached
      */
-    pub fn enable_account(&self) {
+    pub fn enable_account(&self) -> RoriResult<()> {
         let dbus_msg = Message::new_method_call(self.ring_dbus, self.configuration_path,
                                                 self.configuration_iface,
-                                                "sendRegister").ok().expect("method call fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("connection not ok.");
-        let _ = dbus.send_with_reply_and_block(
-            dbus_msg.append2(self.server.account.id.clone(), true), 2000);
+                                                "sendRegister").map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        dbus.send_with_reply_and_block(
+            dbus_msg.append2(self.server.account.id.clone(), true), 2000)?;
+        Ok(())
     }
 
     /**
@@ -240,14 +283,17 @@ impl Manager {
      * @param account_id related
      * @return a Vec of devices ring_id
      */
-    fn get_devices(&self, account_id: &str) -> Vec<String> {
+    fn get_devices(&self, account_id: &str) -> RoriResult<Vec<String>> {
         let mut devices: Vec<String> = Vec::new();
         let dbus_msg = Message::new_method_call(self.ring_dbus, self.configuration_path,
                                                 self.configuration_iface,
-                                                "getContacts").ok().expect("method call fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("connection not ok.");
-        let response = dbus.send_with_reply_and_block(dbus_msg.append1(account_id), 2000).unwrap();
-        let devices_vec: Array<Dict<&str, &str, _>, _> = response.get1().unwrap();
+                                                "getContacts").map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        let response = dbus.send_with_reply_and_block(dbus_msg.append1(account_id), 2000)?;
+        let devices_vec: Array<Dict<&str, &str, _>, _> = match response.get1() {
+            Some(devices_vec) => devices_vec,
+            None => return Err(RoriError::Parse(String::from("getContacts returned no contacts")))
+        };
         for details in devices_vec {
             for detail in details {
                 match detail {
@@ -259,7 +305,7 @@ impl Manager {
                 }
             }
         }
-        devices
+        Ok(devices)
     }
 
     /**
@@ -279,7 +325,13 @@ impl Manager {
         let mut body = String::new();
         let mut datatype = String::new();
         let mut metadatas: HashMap<String, String> = HashMap::new();
-        let supported_types = Database::get_modules_datatypes();
+        let supported_types = match Database::get_modules_datatypes() {
+            Ok(supported_types) => supported_types,
+            Err(e) => {
+                error!("Can't get supported datatypes: {}", e);
+                return None;
+            }
+        };
         for detail in payloads.unwrap() {
             match detail {
                 (key, value) => {
@@ -301,7 +353,7 @@ impl Manager {
             body: body,
             metadatas: metadatas,
             datatype: datatype,
-            time: time::now()
+            ..Interaction::default()
         };
         Some((account_id.unwrap().to_string(), interaction))
     }
@@ -322,43 +374,86 @@ impl Manager {
         Some((account_id.unwrap().to_string(), from.unwrap().to_string()))
     }
 
+    /**
+     * Handle presence signals of subscribed devices
+     * @param self
+     * @param ci
+     * @return (accountId, buddyUri, online)
+     */
+    fn handle_presence(&self, ci: &ConnectionItem) -> Option<(String, String, bool)> {
+        // Check signal
+        let msg = if let &ConnectionItem::Signal(ref signal) = ci { signal } else { return None };
+        if &*msg.interface().unwrap() != "cx.ring.Ring.PresenceManager" { return None };
+        if &*msg.member().unwrap() != "newBuddyNotification" { return None };
+        // newBuddyNotification return four arguments
+        let (account_id, uri, online, _) = msg.get4::<&str, &str, bool, &str>();
+        Some((account_id?.to_string(), uri?.to_string(), online.unwrap_or(false)))
+    }
+
     /**
      * Synchronizes contacts between database and daemon and init account.
      * @param self
+     * @return an error if the database or the daemon fails
      */
-    fn load_contacts(&mut self) {
-        let mut db_devices = Database::get_devices();
-        let ring_devices = self.get_devices(&*self.server.account.id);
+    fn load_contacts(&mut self) -> RoriResult<()> {
+        let mut db_devices = self.server.repository.get_devices()?;
+        let ring_devices = self.get_devices(&*self.server.account.id)?;
 
         // Remove non existing devices
         let mut idx: usize = 0;
         for device in db_devices.clone() {
-            match ring_devices.iter().position(|c| c == &*device.1) {
+            match ring_devices.iter().position(|c| c == &*device.hash) {
                 Some(_) => {
                     idx += 1;
                 },
                 None => {
-                    info!("{} found in db but not from daemon, update db.", device.0);
+                    info!("{} found in db but not from daemon, update db.", device.id);
                     db_devices.remove(idx);
-                    let error_msg = format!("Failed to remove {} from database", device.0);
-                    Database::remove_device(&device.0).ok().expect(&*error_msg);
+                    self.server.repository.remove_device(&device.id)?;
                 }
             }
         }
 
         // Add new devices
         for device in &ring_devices {
-            match db_devices.iter().position(|c| &*c.1 == &*device) {
+            match db_devices.iter().position(|c| &*c.hash == &*device) {
                 Some(_) => {},
                 None => {
                     info!("{} found from daemon but not in daemon, update db.", device);
-                    let error_msg = format!("Failed to insert {} from database", device);
-                    let result = Database::insert_new_device(&device, &String::new(), &String::new(), false).ok().expect(&*error_msg);
-                    db_devices.push((result as i32, device.clone(), String::new(), String::new(), false));
+                    let result = self.server.repository.insert_new_device(&device, &String::new(), &String::new(), false)?;
+                    db_devices.push(DeviceRecord {
+                        id: result as i32,
+                        hash: device.clone(),
+                        username: String::new(),
+                        devicename: String::new(),
+                        sub_author: String::new(),
+                        is_bridge: false,
+                    });
                 }
             }
         }
 
+        // Follow the presence of registered devices
+        for device in db_devices.iter().filter(|device| !device.username.is_empty()) {
+            if let Err(e) = self.server.subscribe_presence(&*device.hash) {
+                warn!("can't subscribe to the presence of {}: {}", device.hash, e);
+            }
+        }
         self.server.load_devices(db_devices);
+        Ok(())
+    }
+
+    /**
+     * Open a new dbus connection listening signals from the daemon
+     * @return the connection
+     */
+    fn signal_listener() -> RoriResult<Connection> {
+        let dbus_listener = Connection::get_private(BusType::Session)?;
+        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=incomingAccountMessage")?;
+        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=incomingTrustRequest")?;
+        dbus_listener.add_match("interface=cx.ring.Ring.PresenceManager,member=newBuddyNotification")?;
+        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=accountsChanged")?; // TODO
+        dbus_listener.add_match("interface=cx.ring.Ring.ConfigurationManager,member=registrationStateChanged")?; // TODO
+        Ok(dbus_listener)
     }
 }
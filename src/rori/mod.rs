@@ -27,11 +27,29 @@
 
 pub mod account;
 pub mod api;
+pub mod backup;
+pub mod capabilities;
+pub mod command;
 pub mod database;
+pub mod emotions;
+pub mod error;
+pub mod group;
+pub mod history;
 pub mod interaction;
+pub mod lang;
+pub mod link;
 pub mod manager;
 pub mod module;
 pub mod modulemanager;
+pub mod normalizer;
+pub mod presence;
+pub mod reply;
+pub mod repository;
+pub mod routing;
 pub mod scheduler;
+pub mod sdk;
+pub mod sender;
 pub mod server;
+pub mod text;
 pub mod user;
+pub mod vault;
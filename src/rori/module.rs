@@ -26,14 +26,93 @@
  **/
 use cpython::{PyDict, Python};
 use regex::Regex;
+use rori::error::{RoriError, RoriResult};
 use rori::interaction::Interaction;
+use rori::sdk;
+use rori::text::{similar_words, words};
 use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
 
 /**
  * Condition's trait to implement
  */
 pub trait Condition : Send + Sync {
     fn is_fulfilled_by(&self, interaction: &Interaction) -> bool;
+
+    /**
+     * Entities extracted from the interaction by the condition, passed to the module
+     * @param interaction
+     * @return slots (name, value). Empty by default
+     */
+    fn slots(&self, _interaction: &Interaction) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    /**
+     * Score of the interaction for conditions which compete with each others
+     * @param interaction
+     * @return None if the condition is not scored, else a score between 0 and 1
+     */
+    fn score(&self, _interaction: &Interaction) -> Option<f64> {
+        None
+    }
+
+    /**
+     * Words understood by the condition, used to suggest modules
+     * @return a list of lowercase words
+     */
+    fn vocabulary(&self) -> Vec<String>;
+}
+
+/**
+ * Build the condition of a module from the database and log the module if its condition is invalid
+ * @param module name of the module
+ * @param condition see parse_condition
+ * @return the condition, never fulfilled if invalid
+ */
+pub fn module_condition(module: &String, condition: &String) -> Box<dyn Condition> {
+    match parse_condition(condition) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Module {} will never be executed, invalid condition {}: {}", module, condition, e);
+            Box::new(NeverCondition)
+        }
+    }
+}
+
+/**
+ * Parse a condition. This is the only parser of the condition column
+ * @param condition a regex, or a json object like {"type":"intent", ...} (see IntentCondition and LangCondition)
+ * @return the condition or a parse error (invalid json or regex, unknown type)
+ */
+pub fn parse_condition(condition: &String) -> RoriResult<Box<dyn Condition>> {
+    if !condition.starts_with("{") {
+        return Ok(Box::new(TextCondition::new(condition.clone())?));
+    }
+    let json: Value = serde_json::from_str(&*condition)?;
+    if json["type"] == "intent" {
+        return Ok(Box::new(IntentCondition::parse(json)?));
+    }
+    if json["type"] == "lang" {
+        return Ok(Box::new(LangCondition::parse(&json["variants"])?));
+    }
+    Err(RoriError::Parse(format!("unknown condition type {}", json["type"])))
+}
+
+/**
+ * Condition of a module with an invalid condition, never fulfilled
+ */
+pub struct NeverCondition;
+
+impl Condition for NeverCondition {
+    fn is_fulfilled_by(&self, _interaction: &Interaction) -> bool {
+        false
+    }
+
+    fn vocabulary(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 /**
@@ -41,6 +120,7 @@ pub trait Condition : Send + Sync {
  */
 pub struct TextCondition {
     condition: String,
+    regex: Regex,
 }
 
 /**
@@ -48,22 +128,200 @@ pub struct TextCondition {
  */
 impl Condition for TextCondition {
     fn is_fulfilled_by(&self, interaction: &Interaction) -> bool {
-        let re = Regex::new(&*self.condition).unwrap();
-        re.is_match(&*interaction.body.to_lowercase())
+        let re = &self.regex;
+        re.is_match(&*interaction.body.to_lowercase()) || re.is_match(&*interaction.normalized_body)
+    }
+
+    /**
+     * Named groups of the regex, e.g. (?P<hours>[0-9]+) gives the slot "hours"
+     */
+    fn slots(&self, interaction: &Interaction) -> HashMap<String, String> {
+        let mut slots = HashMap::new();
+        let re = &self.regex;
+        let body = interaction.body.to_lowercase();
+        let captures = match re.captures(&*body) {
+            Some(captures) => Some(captures),
+            None => re.captures(&*interaction.normalized_body),
+        };
+        if let Some(captures) = captures {
+            for name in re.capture_names() {
+                if let Some(name) = name {
+                    if let Some(value) = captures.name(name) {
+                        slots.insert(String::from(name), String::from(value.as_str()));
+                    }
+                }
+            }
+        }
+        slots
+    }
+
+    fn vocabulary(&self) -> Vec<String> {
+        // Regex syntax is split, so only keep real words from the condition
+        words(&self.condition).into_iter().filter(|w| w.chars().count() > 2).collect()
     }
 }
 
 impl TextCondition {
     /**
      * Return a new TextCondition
-     * @param condition
-     * @return TextCondition
+     * @param condition regex
+     * @return TextCondition or a parse error if the regex is invalid
      */
-    pub fn new(condition: String) -> TextCondition {
-        TextCondition {
+    pub fn new(condition: String) -> RoriResult<TextCondition> {
+        Ok(TextCondition {
+            regex: Regex::new(&*condition).map_err(|e| RoriError::Parse(e.to_string()))?,
             condition: condition,
+        })
+    }
+}
+
+// Bonus added to the score for each keyword found
+const KEYWORD_BONUS: f64 = 0.2;
+
+fn default_threshold() -> f64 {
+    0.6
+}
+
+fn default_max_distance() -> usize {
+    1
+}
+
+/**
+ * Condition matching an intent without any regex. Stored as:
+ * {"type":"intent", "examples":["how are you", "how r u"], "keywords":["mood"], "threshold":0.6, "max_distance":1}
+ * The text is compared word by word to each example (words within max_distance typos are equal).
+ * The best example gives the score (2 * common words / total words), each keyword found adds a bonus.
+ * If several intents of the same priority are fulfilled, only the best one is executed.
+ */
+#[derive(Deserialize)]
+pub struct IntentCondition {
+    #[serde(default)]
+    examples: Vec<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default = "default_threshold")]
+    threshold: f64,
+    #[serde(default = "default_max_distance")]
+    max_distance: usize,
+}
+
+impl Condition for IntentCondition {
+    fn is_fulfilled_by(&self, interaction: &Interaction) -> bool {
+        self.score(interaction).unwrap_or(0.0) >= self.threshold
+    }
+
+    fn score(&self, interaction: &Interaction) -> Option<f64> {
+        Some(self.score_text(&interaction.body).max(self.score_text(&interaction.normalized_body)))
+    }
+
+    fn vocabulary(&self) -> Vec<String> {
+        let mut vocabulary = Vec::new();
+        for sentence in self.examples.iter().chain(self.keywords.iter()) {
+            vocabulary.append(&mut words(sentence));
+        }
+        vocabulary
+    }
+}
+
+impl IntentCondition {
+    /**
+     * Parse an IntentCondition
+     * @param condition json object of the condition
+     * @return IntentCondition or a parse error if the json is invalid
+     */
+    pub fn parse(condition: Value) -> RoriResult<IntentCondition> {
+        Ok(serde_json::from_value(condition)?)
+    }
+
+    /**
+     * Score a text against examples and keywords
+     * @param text
+     * @return a score between 0 and 1
+     */
+    pub fn score_text(&self, text: &String) -> f64 {
+        let text = words(text);
+        if text.is_empty() {
+            return 0.0;
+        }
+        let contains = |word: &String| text.iter().any(|w| similar_words(w, word, self.max_distance));
+        let mut score: f64 = 0.0;
+        for example in &self.examples {
+            let example = words(example);
+            let common = example.iter().filter(|w| contains(w)).count();
+            let example_score = (2 * common) as f64 / (example.len() + text.len()) as f64;
+            score = score.max(example_score);
+        }
+        for keyword in &self.keywords {
+            if contains(&keyword.to_lowercase()) {
+                score += KEYWORD_BONUS;
+            }
+        }
+        score.min(1.0)
+    }
+}
+
+/**
+ * Condition with one variant per language. Only the variant of the author's language is tested.
+ * {"type":"lang", "variants":{"en":"^hello", "fr":{"type":"intent", "examples":["bonjour"]}}}
+ */
+pub struct LangCondition {
+    variants: HashMap<String, Box<dyn Condition>>,
+}
+
+impl Condition for LangCondition {
+    fn is_fulfilled_by(&self, interaction: &Interaction) -> bool {
+        match self.variants.get(&interaction.lang) {
+            Some(condition) => condition.is_fulfilled_by(interaction),
+            None => false
+        }
+    }
+
+    fn slots(&self, interaction: &Interaction) -> HashMap<String, String> {
+        match self.variants.get(&interaction.lang) {
+            Some(condition) => condition.slots(interaction),
+            None => HashMap::new()
         }
     }
+
+    fn score(&self, interaction: &Interaction) -> Option<f64> {
+        match self.variants.get(&interaction.lang) {
+            Some(condition) => condition.score(interaction),
+            None => None
+        }
+    }
+
+    fn vocabulary(&self) -> Vec<String> {
+        let mut vocabulary = Vec::new();
+        for condition in self.variants.values() {
+            vocabulary.append(&mut condition.vocabulary());
+        }
+        vocabulary
+    }
+}
+
+impl LangCondition {
+    /**
+     * Parse a LangCondition
+     * @param variants json object (lang: condition). A condition is a regex or a json object
+     * @return LangCondition or a parse error if a variant is invalid
+     */
+    pub fn parse(variants: &Value) -> RoriResult<LangCondition> {
+        let variants = match variants.as_object() {
+            Some(variants) => variants,
+            None => return Err(RoriError::Parse(String::from("variants must be an object")))
+        };
+        let mut result = HashMap::new();
+        for (lang, condition) in variants {
+            let condition = match condition.as_str() {
+                Some(regex) => String::from(regex),
+                None => condition.to_string(),
+            };
+            result.insert(lang.clone(), parse_condition(&condition)?);
+        }
+        Ok(LangCondition {
+            variants: result,
+        })
+    }
 }
 
 /**
@@ -82,9 +340,10 @@ impl Module {
      * Execute the module and get if we should continue to process other modules
      * @param self
      * @param interaction which has trigerred this module
+     * @param username author of the interaction (empty if anonymous)
      * @return if we continue to process the interaction (true on error to avoid to stop other modules)
      */
-    pub fn exec(&self, interaction: &Interaction) -> bool {
+    pub fn exec(&self, interaction: &Interaction, username: &String) -> bool {
         // Init python module
         let py = Python::acquire_gil();
         let py = py.python();
@@ -94,6 +353,10 @@ impl Module {
         locals.set_item(py, "sys", py.import("sys").unwrap()).unwrap();
         py.eval("sys.path.append('.')", None, Some(&locals)).unwrap();
         py.eval("sys.path.append('./rori_modules/')", None, Some(&locals)).unwrap();
+        // Expose the rori module to the executed module
+        if !sdk::register(py, &self.name, username).is_ok() {
+            error!("Error while registering rori module for {}", self.name);
+        }
         // This will execute the linked module
         let load_module = py.import("rori_modules.load_module");
         if !load_module.is_ok() {
@@ -26,47 +26,144 @@
  **/
 use rori::database::Database;
 use rori::interaction::Interaction;
+use rori::module::module_condition;
+use rori::text::{similar_words, words};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+/**
+ * What to do when no module's condition is fulfilled by an interaction
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fallback {
+    // Do nothing (default)
+    Nothing,
+    // Execute the module with this name
+    Module(String),
+    // Answer a canned message. Keys are languages ("en", "fr"...)
+    Message(HashMap<String, String>),
+    // Answer the names of the nearest modules (at most N)
+    Suggestions(usize),
+}
+
+impl Fallback {
+    /**
+     * Build the fallback from the "fallback" object of config.json
+     * e.g. {"type": "message", "messages": {"en": "I didn't understand"}}
+     *      {"type": "module", "module": "wikipedia"}
+     *      {"type": "suggestions", "max": 3}
+     * @param config
+     * @return the Fallback, Nothing if invalid
+     */
+    pub fn from_config(config: &Value) -> Fallback {
+        match config["type"].as_str().unwrap_or("") {
+            "module" => {
+                let module = config["module"].as_str().unwrap_or("");
+                if module.is_empty() {
+                    warn!("fallback module without name, ignore it");
+                    return Fallback::Nothing;
+                }
+                Fallback::Module(String::from(module))
+            },
+            "message" => {
+                let mut messages = HashMap::new();
+                if let Some(values) = config["messages"].as_object() {
+                    for (lang, message) in values {
+                        if let Some(message) = message.as_str() {
+                            messages.insert(lang.clone(), String::from(message));
+                        }
+                    }
+                }
+                if messages.is_empty() {
+                    warn!("fallback message without messages, ignore it");
+                    return Fallback::Nothing;
+                }
+                Fallback::Message(messages)
+            },
+            "suggestions" => Fallback::Suggestions(config["max"].as_u64().unwrap_or(3) as usize),
+            _ => Fallback::Nothing,
+        }
+    }
+}
+
 /**
  * Class used to handle an interaction with the module activation loop
  */
 pub struct ModuleManager {
     pub interaction: Interaction,
+    // Author of the interaction (empty if anonymous)
+    pub username: String,
 }
 
 impl ModuleManager {
     /**
      * Generates a new ModuleManager
+     * @param interaction to process
+     * @param username author of the interaction (empty if anonymous)
      * @return ModuleManager
      */
-    pub fn new(interaction: Interaction) -> ModuleManager {
+    pub fn new(interaction: Interaction, username: &String) -> ModuleManager {
         ModuleManager {
             interaction: interaction,
+            username: username.clone(),
         }
     }
 
     /**
      * Execute module activation loop
+     * NOTE: modules with priority 0 (like history) see every interaction, so they are
+     * not considered as an answer.
      * @param self
+     * @return if a module (with a priority > 0) was triggered
      */
-    pub fn process(&self) {
+    pub fn process(&self) -> bool {
         let stop = Arc::new(Mutex::new(false));
+        let matched = Arc::new(Mutex::new(false));
         // get_descending_priorities will skip non exisiting priorities
         // will be something like [0, 1, 3, 4, 7...]
-        for priority in Database::get_descending_priorities() {
+        let priorities = match Database::get_descending_priorities() {
+            Ok(priorities) => priorities,
+            Err(e) => {
+                error!("Can't get priorities: {}", e);
+                return false;
+            }
+        };
+        for priority in priorities {
             // Get modules for this priority
-            let modules = Database::get_enabled_modules(priority as u64);
+            let modules = match Database::get_enabled_modules(priority as u64) {
+                Ok(modules) => modules,
+                Err(e) => {
+                    error!("Can't get modules with priority {}: {}", priority, e);
+                    continue;
+                }
+            };
+            // Only the best scored condition (intents) of this priority can be executed
+            let scores: Vec<Option<f64>> = modules.iter().map(|m| m.condition.score(&self.interaction)).collect();
+            let best_score = scores.iter().fold(None, |best: Option<f64>, score| match (best, *score) {
+                (Some(b), Some(s)) => Some(b.max(s)),
+                (None, s) => s,
+                (b, None) => b,
+            });
             // Test each modules
             let mut children = vec![];
-            for module in modules {
-                let interaction = self.interaction.clone();
+            for (module, score) in modules.into_iter().zip(scores.into_iter()) {
+                if score.is_some() && score != best_score {
+                    continue;
+                }
+                let mut interaction = self.interaction.clone();
+                let username = self.username.clone();
                 let stop_cloned = stop.clone();
+                let matched_cloned = matched.clone();
                 children.push(thread::spawn(move || {
                     if module.condition.is_fulfilled_by(&interaction) {
                         info!("{} module's condition fulfilled. Exec module", module.name);
-                        let result = module.exec(&interaction);
+                        if module.priority > 0 {
+                            *matched_cloned.lock().unwrap() = true;
+                        }
+                        interaction.slots = module.condition.slots(&interaction);
+                        let result = module.exec(&interaction, &username);
                         if !result {
                             info!("{} asks RORI to stop. Stopping at the next priority...", module.name);
                             *stop_cloned.lock().unwrap() = true;
@@ -84,6 +181,76 @@ impl ModuleManager {
                 break;
             }
         }
-        info!("Stopping processing")
+        info!("Stopping processing");
+        let matched = *matched.lock().unwrap();
+        matched
+    }
+
+    /**
+     * Execute the fallback when no module was triggered
+     * @param self
+     * @param fallback to execute
+     * @return the text to answer to the author if any
+     */
+    pub fn fallback(&self, fallback: &Fallback) -> Option<String> {
+        match fallback {
+            Fallback::Nothing => None,
+            Fallback::Module(name) => {
+                match Database::get_module_id_by_name(name).and_then(|id| Database::get_module(&id)) {
+                    Ok(Some(module)) => {
+                        info!("No module triggered, exec fallback module {}", module.name);
+                        module.exec(&self.interaction, &self.username);
+                    },
+                    Ok(None) => warn!("fallback module {} not found", name),
+                    Err(e) => error!("Can't get fallback module {}: {}", name, e)
+                }
+                None
+            },
+            Fallback::Message(messages) => {
+                match messages.get(&self.interaction.lang) {
+                    Some(message) => Some(message.clone()),
+                    None => messages.get("en").or(messages.values().next()).cloned()
+                }
+            },
+            Fallback::Suggestions(max) => {
+                let suggestions = ModuleManager::nearest_modules(&self.interaction.body, *max);
+                if suggestions.is_empty() {
+                    return None;
+                }
+                if self.interaction.lang == "fr" {
+                    return Some(format!("Je n'ai pas compris. Vouliez-vous dire : {} ?", suggestions.join(", ")));
+                }
+                Some(format!("I didn't understand. Did you mean: {}?", suggestions.join(", ")))
+            }
+        }
+    }
+
+    /**
+     * Search modules with a condition near of a text
+     * @param body the text to compare
+     * @param max number of modules to return
+     * @return names of the nearest modules, nearest first
+     */
+    pub fn nearest_modules(body: &String, max: usize) -> Vec<String> {
+        let body_words = words(body);
+        let mut scores: Vec<(usize, String)> = Vec::new();
+        let conditions = match Database::get_modules_conditions() {
+            Ok(conditions) => conditions,
+            Err(e) => {
+                error!("Can't get modules conditions: {}", e);
+                return Vec::new();
+            }
+        };
+        for (name, condition) in conditions {
+            let condition_words = module_condition(&name, &condition).vocabulary();
+            let score = body_words.iter()
+                        .filter(|w| condition_words.iter().any(|c| similar_words(w, c, 1)))
+                        .count();
+            if score > 0 {
+                scores.push((score, name));
+            }
+        }
+        scores.sort_by(|a, b| b.0.cmp(&a.0));
+        scores.into_iter().take(max).map(|(_, name)| name).collect()
     }
 }
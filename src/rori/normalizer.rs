@@ -0,0 +1,110 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use serde_json::Value;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/**
+ * Normalize the text of interactions before modules' conditions.
+ * Each step can be disabled in the "normalization" object of config.json
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Normalizer {
+    // Unicode NFKC normalization (e.g. "ｆｕｌｌ" -> "full", "ﬁ" -> "fi")
+    pub nfkc: bool,
+    // "é" -> "e", "ç" -> "c"...
+    pub fold_accents: bool,
+    // Replace punctuation by spaces
+    pub strip_punctuation: bool,
+    // Remove "rori" at the beginning of the text ("rori, what time is it" -> "what time is it")
+    pub strip_address: bool,
+    // Trim and replace multiple whitespaces by one space
+    pub collapse_whitespaces: bool,
+}
+
+impl Normalizer {
+    /**
+     * Generate a new Normalizer with all steps enabled
+     */
+    pub fn new() -> Normalizer {
+        Normalizer {
+            nfkc: true,
+            fold_accents: true,
+            strip_punctuation: true,
+            strip_address: true,
+            collapse_whitespaces: true,
+        }
+    }
+
+    /**
+     * Build the normalizer from the "normalization" object of config.json
+     * e.g. {"fold_accents": false, "strip_address": false}
+     * @param config
+     * @return the Normalizer, missing steps are enabled
+     */
+    pub fn from_config(config: &Value) -> Normalizer {
+        let enabled = |step: &str| config[step].as_bool().unwrap_or(true);
+        Normalizer {
+            nfkc: enabled("nfkc"),
+            fold_accents: enabled("fold_accents"),
+            strip_punctuation: enabled("strip_punctuation"),
+            strip_address: enabled("strip_address"),
+            collapse_whitespaces: enabled("collapse_whitespaces"),
+        }
+    }
+
+    /**
+     * Normalize a text
+     * @param self
+     * @param text to normalize
+     * @return the lowercase normalized text
+     */
+    pub fn normalize(&self, text: &String) -> String {
+        let mut result = text.to_lowercase();
+        if self.nfkc {
+            result = result.nfkc().collect();
+        }
+        if self.fold_accents {
+            result = result.nfd().filter(|c| !is_combining_mark(*c)).collect();
+        }
+        if self.strip_punctuation {
+            result = result.chars().map(|c| if c.is_alphanumeric() || c.is_whitespace() { c } else { ' ' }).collect();
+        }
+        if self.strip_address {
+            let trimmed = result.trim_start();
+            if trimmed.starts_with("rori")
+            && !trimmed["rori".len()..].chars().next().map_or(false, |c| c.is_alphanumeric()) {
+                result = String::from(&trimmed["rori".len()..]);
+            }
+        }
+        if self.collapse_whitespaces {
+            result = result.split_whitespace().collect::<Vec<&str>>().join(" ");
+        }
+        result
+    }
+}
@@ -0,0 +1,119 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use time;
+
+lazy_static! {
+    // State of each device (hash), updated from presence signals of the daemon
+    static ref PRESENCE: Mutex<HashMap<String, DevicePresence>> = Mutex::new(HashMap::new());
+}
+
+/**
+ * Presence of a device. Sorted from the best to the worst destination
+ */
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Presence {
+    Online,
+    // Not reported yet
+    Unknown,
+    Offline,
+}
+
+impl Presence {
+    /**
+     * @return the name of the presence, as in the API
+     */
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Presence::Online => "online",
+            Presence::Unknown => "unknown",
+            Presence::Offline => "offline",
+        }
+    }
+}
+
+/**
+ * What RORI knows about the presence of a device
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct DevicePresence {
+    pub presence: Presence,
+    // Timestamp of the last time the device was online (0 if never)
+    pub last_seen: i64,
+}
+
+impl Default for DevicePresence {
+    fn default() -> DevicePresence {
+        DevicePresence {
+            presence: Presence::Unknown,
+            last_seen: 0,
+        }
+    }
+}
+
+/**
+ * Handle a presence notification from the daemon
+ * @param hash of the device
+ * @param online new state of the device
+ */
+pub fn update(hash: &String, online: bool) {
+    let mut states = PRESENCE.lock().unwrap();
+    let state = states.entry(hash.clone()).or_insert(DevicePresence::default());
+    // A device going offline was online until now
+    if online || state.presence == Presence::Online {
+        state.last_seen = time::get_time().sec;
+    }
+    state.presence = if online { Presence::Online } else { Presence::Offline };
+}
+
+/**
+ * A device which sends a message is online
+ * @param hash of the device
+ */
+pub fn seen(hash: &String) {
+    update(hash, true);
+}
+
+/**
+ * Force the presence of a device
+ * @param hash of the device
+ * @param presence
+ */
+pub fn set(hash: &String, presence: Presence) {
+    PRESENCE.lock().unwrap().entry(hash.clone()).or_insert(DevicePresence::default()).presence = presence;
+}
+
+/**
+ * @param hash of the device
+ * @return the presence of the device (unknown if never reported)
+ */
+pub fn get(hash: &String) -> DevicePresence {
+    PRESENCE.lock().unwrap().get(hash).cloned().unwrap_or(DevicePresence::default())
+}
@@ -0,0 +1,156 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::capabilities::Kind;
+use rori::error::{RoriError, RoriResult};
+use rori::group::Group;
+use serde_json::{self, Value};
+
+// Version of the rori/message protocol, increased for each breaking change
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/**
+ * Why a command failed
+ */
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // Username or devicename already used
+    AlreadyRegistered,
+    // Device not owned by the user
+    BadDevice,
+    // Wrong or expired link code
+    InvalidCode,
+    // Unknown user or device
+    NotFound,
+    // Secrets can't be used while the vault is locked
+    VaultLocked,
+    // Bad arguments for a command
+    Usage,
+    // Command not handled by RORI nor by a module
+    UnknownCommand,
+    // Command not available for anonymous devices or for registered users
+    Scope,
+    // Role of the author too low
+    Denied,
+}
+
+/**
+ * An author behind a bridge and the user it is linked to
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SubAuthorMapping {
+    pub sa: String,
+    pub username: String,
+}
+
+/**
+ * A reply of RORI, sent as rori/message
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    // Device linked to (or removed from) an user
+    Registered { registered: bool, username: String, sa: String },
+    // All data of the user removed
+    Forgotten { username: String },
+    // Username changed, sent to every device of the user
+    Renamed { username: String, previous: String },
+    // Device named
+    DeviceRegistered { dregistered: bool, devicename: String },
+    // Device renamed, sent to every device of the user
+    DeviceRenamed { devicename: String, previous: String },
+    // One-time code to echo from the new device
+    LinkCode { link_code: String, device: String, ttl: i64 },
+    Lang { lang: String },
+    // Device preferred for a datatype (empty datatype for every datatype, empty devicename if removed)
+    Preferred { devicename: String, datatype: String },
+    // Capabilities stored for the device (datatypes of the announced actions)
+    Capabilities { kinds: Vec<Kind>, actions: Vec<String> },
+    Role { username: String, role: String },
+    // Group created or changed, with its members
+    Group { name: String, members: Vec<String> },
+    GroupRemoved { name: String },
+    // Groups of the user
+    Groups { groups: Vec<Group> },
+    // Sub authors of a bridge
+    SubAuthors { bridge: String, sub_authors: Vec<SubAuthorMapping> },
+    SubAuthor { bridge: String, sa: String, username: String },
+    SubAuthorRemoved { bridge: String, sa: String, username: String },
+    Module { module: String, enabled: bool },
+    TaskRemoved { task: i32 },
+    // All data of the user, by table
+    Export { export: Value },
+    Error { code: ErrorCode, err: String },
+}
+
+#[derive(Serialize)]
+struct Outgoing<'a> {
+    version: u32,
+    #[serde(flatten)]
+    reply: &'a Reply,
+}
+
+#[derive(Deserialize)]
+struct Incoming {
+    version: u32,
+    #[serde(flatten)]
+    reply: Reply,
+}
+
+impl Reply {
+    /**
+     * @param code of the error
+     * @param err human readable description
+     * @return an error reply
+     */
+    pub fn error(code: ErrorCode, err: &str) -> Reply {
+        Reply::Error { code: code, err: String::from(err) }
+    }
+}
+
+/**
+ * Serialize a reply with the version of the protocol
+ * @param reply to send
+ * @return the JSON body of the rori/message
+ */
+pub fn to_json(reply: &Reply) -> RoriResult<String> {
+    Ok(serde_json::to_string(&Outgoing { version: PROTOCOL_VERSION, reply: reply })?)
+}
+
+/**
+ * Parse a rori/message, for entry points and bridges
+ * @param body of the rori/message
+ * @return the reply, an error if invalid or from a newer protocol
+ */
+pub fn parse(body: &str) -> RoriResult<Reply> {
+    let incoming: Incoming = serde_json::from_str(body)?;
+    if incoming.version > PROTOCOL_VERSION {
+        return Err(RoriError::Parse(format!("unsupported protocol version {}", incoming.version)));
+    }
+    Ok(incoming.reply)
+}
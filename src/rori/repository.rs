@@ -0,0 +1,829 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::capabilities::Capabilities;
+use rori::error::{RoriError, RoriResult};
+use rori::group::Group;
+use rori::interaction::Interaction;
+use rori::link::LinkRequest;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use time;
+
+/**
+ * Permissions of an user for rori/command operations
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    User,
+    // Can turn anonymous devices into bridges
+    BridgeOperator,
+    // Can do everything (roles, bridges, devices of others, modules and tasks)
+    Admin,
+}
+
+impl Role {
+    /**
+     * @param role "user", "bridge-operator" or "admin"
+     * @return the role if valid
+     */
+    pub fn parse(role: &str) -> Option<Role> {
+        match role {
+            "user" => Some(Role::User),
+            "bridge-operator" => Some(Role::BridgeOperator),
+            "admin" => Some(Role::Admin),
+            _ => None
+        }
+    }
+
+    /**
+     * @return the name stored in the database
+     */
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Role::User => "user",
+            Role::BridgeOperator => "bridge-operator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /**
+     * @return if this role can create bridges
+     */
+    pub fn can_manage_bridges(&self) -> bool {
+        *self == Role::BridgeOperator || *self == Role::Admin
+    }
+
+    /**
+     * @param required role needed by an operation
+     * @return if this role has the permissions of the required role
+     */
+    pub fn allows(&self, required: Role) -> bool {
+        match required {
+            Role::User => true,
+            Role::BridgeOperator => self.can_manage_bridges(),
+            Role::Admin => *self == Role::Admin,
+        }
+    }
+}
+
+impl Default for Role {
+    fn default() -> Role {
+        Role::User
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/**
+ * A registered user, owner of devices
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserRecord {
+    pub id: i32,
+    pub username: String,
+    pub display_name: String,
+    // Timestamp of the registration
+    pub created: i64,
+    pub role: Role,
+}
+
+/**
+ * A device (ring id) known by RORI. username is empty for anonymous devices
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceRecord {
+    pub id: i32,
+    pub hash: String,
+    pub username: String,
+    pub devicename: String,
+    pub sub_author: String,
+    pub is_bridge: bool,
+}
+
+/**
+ * Storage used by the Server: users, devices and the data they own (groups, preferences, link requests...).
+ * Implemented by Database (sqlite) and by InMemoryRepository (for tests)
+ */
+pub trait Repository {
+    /**
+     * @return all registered users sorted by username
+     */
+    fn get_users(&self) -> RoriResult<Vec<UserRecord>>;
+    /**
+     * @param username
+     * @return the user if registered
+     */
+    fn get_user(&self, username: &String) -> RoriResult<Option<UserRecord>>;
+    /**
+     * @param username
+     * @param display_name to show instead of the username
+     * @return the number of modified users
+     */
+    fn set_display_name(&self, username: &String, display_name: &String) -> RoriResult<usize>;
+    /**
+     * @param username
+     * @param role new role of the user
+     * @return the number of modified users
+     */
+    fn set_role(&self, username: &String, role: Role) -> RoriResult<usize>;
+    /**
+     * Give the admin role to the user of a device (not a bridge) if there is no admin yet
+     * @param hash of the device
+     * @return the number of modified users
+     */
+    fn bootstrap_admin(&self, hash: &String) -> RoriResult<usize>;
+    /**
+     * Rename an user, with its devices and its data
+     * @param username current username
+     * @param new_username (must not be registered)
+     * @return the number of modified rows
+     */
+    fn rename_user(&self, username: &String, new_username: &String) -> RoriResult<usize>;
+    /**
+     * @return all devices
+     */
+    fn get_devices(&self) -> RoriResult<Vec<DeviceRecord>>;
+    /**
+     * @param hash ring id of the devices
+     * @return devices with this hash (a bridge can have one device per user)
+     */
+    fn get_devices_for_hash(&self, hash: &str) -> RoriResult<Vec<DeviceRecord>>;
+    /**
+     * @param username
+     * @return devices of this user
+     */
+    fn get_devices_for_username(&self, username: &str) -> RoriResult<Vec<DeviceRecord>>;
+    /**
+     * @param hash ring id of the device
+     * @param username owner of the device (empty if anonymous)
+     * @return the device if found
+     */
+    fn get_device(&self, hash: &String, username: &String) -> RoriResult<Option<DeviceRecord>>;
+    /**
+     * Insert a device. The user is created if needed
+     * @return the id of the new device, or AlreadyExists
+     */
+    fn insert_new_device(&self, hash: &String, username: &String, devicename: &String, is_bridge: bool) -> RoriResult<usize>;
+    /**
+     * Remove a device. The user and its data are kept, even without devices (see purge_user)
+     * @return the number of removed devices
+     */
+    fn remove_device(&self, id: &i32) -> RoriResult<usize>;
+    /**
+     * Link a device to another user (or to the anonymous user if empty)
+     * @return the number of modified devices
+     */
+    fn update_username(&self, id: &i32, username: &String) -> RoriResult<usize>;
+    /**
+     * @return the number of modified devices
+     */
+    fn update_devicename(&self, id: &i32, devicename: &String) -> RoriResult<usize>;
+    /**
+     * @return the number of modified devices
+     */
+    fn update_sub_author(&self, id: &i32, sub_author: &String) -> RoriResult<usize>;
+    /**
+     * @param hash of the bridge
+     * @param sub_author author behind the bridge
+     * @return the username linked or an empty string
+     */
+    fn sub_author(&self, hash: &String, sub_author: &String) -> RoriResult<String>;
+    /**
+     * @param hash of the bridge
+     * @param username linked to the bridge
+     * @return the sub author of the user behind the bridge or an empty string
+     */
+    fn sub_author_id(&self, hash: &String, username: &String) -> RoriResult<String>;
+    /**
+     * @return if a device with this hash is a bridge
+     */
+    fn is_bridge(&self, hash: &String) -> RoriResult<bool>;
+    /**
+     * Change a device to a bridge
+     * @return the number of modified devices
+     */
+    fn bridgify(&self, id: &i32) -> RoriResult<usize>;
+    /**
+     * @return additional datatypes supported by a device
+     */
+    fn get_datatypes(&self, id: &i32) -> RoriResult<Vec<String>>;
+    /**
+     * @return the number of modified devices
+     */
+    fn set_datatypes(&self, id: &i32, datatypes: Vec<String>) -> RoriResult<usize>;
+    /**
+     * Replace the capabilities announced by a device
+     * @return the number of stored kinds and actions
+     */
+    fn set_capabilities(&self, id: &i32, capabilities: &Capabilities) -> RoriResult<usize>;
+    /**
+     * Record the bridge operator who created a bridge
+     * @return the number of modified bridges
+     */
+    fn set_bridge_owner(&self, hash: &String, username: &String) -> RoriResult<usize>;
+    /**
+     * @return the username of the operator who created the bridge, None if unknown
+     */
+    fn get_bridge_owner(&self, hash: &String) -> RoriResult<Option<String>>;
+    /**
+     * Remember an interaction which didn't trigger any module
+     * @return the number of inserted rows
+     */
+    fn add_unmatched(&self, username: &String, interaction: &Interaction) -> RoriResult<usize>;
+    /**
+     * @return the number of modified languages
+     */
+    fn set_lang(&self, username: &String, lang: &String) -> RoriResult<usize>;
+    /**
+     * Set the device preferred by an user for a datatype (empty for every datatype)
+     * @return the number of modified preferences
+     */
+    fn set_preference(&self, username: &String, datatype: &String, device: &i32) -> RoriResult<usize>;
+    /**
+     * @return the number of removed preferences
+     */
+    fn rm_preference(&self, username: &String, datatype: &String) -> RoriResult<usize>;
+    /**
+     * @return rows (column -> value) linked to an user by table
+     */
+    fn export_user(&self, username: &String) -> RoriResult<BTreeMap<String, Vec<Map<String, Value>>>>;
+    /**
+     * Remove every row linked to an user. Devices are kept as anonymous devices, except bridges
+     * @return the number of removed or anonymized rows
+     */
+    fn purge_user(&self, username: &String) -> RoriResult<usize>;
+    /**
+     * Store a link request, replacing the previous request for the same device and user
+     * @return the id of the request
+     */
+    fn add_link_request(&self, request: &LinkRequest) -> RoriResult<i32>;
+    /**
+     * @param now current timestamp, expired requests are ignored
+     * @return the pending request to link hash to username if any
+     */
+    fn get_link_request(&self, hash: &String, username: &String, now: i64) -> RoriResult<Option<LinkRequest>>;
+    /**
+     * @return the number of removed requests
+     */
+    fn rm_link_request(&self, id: &i32) -> RoriResult<usize>;
+    /**
+     * @param now current timestamp
+     * @return the number of removed requests
+     */
+    fn rm_expired_link_requests(&self, now: i64) -> RoriResult<usize>;
+    /**
+     * Create an empty group
+     * @param owner username of the owner (empty for none)
+     * @return the id of the group, or AlreadyExists if the name is used by an user or a group
+     */
+    fn add_group(&self, name: &String, owner: &String) -> RoriResult<i32>;
+    /**
+     * Remove a group and its members
+     * @return the number of removed groups
+     */
+    fn rm_group(&self, name: &String) -> RoriResult<usize>;
+    /**
+     * @return the group and its members, None if not found
+     */
+    fn get_group(&self, name: &String) -> RoriResult<Option<Group>>;
+    /**
+     * @return all groups with their members
+     */
+    fn get_groups(&self) -> RoriResult<Vec<Group>>;
+    /**
+     * @return the number of added members, NotFound if the group doesn't exist
+     */
+    fn add_group_member(&self, name: &String, username: &String) -> RoriResult<usize>;
+    /**
+     * @return the number of removed members
+     */
+    fn rm_group_member(&self, name: &String, username: &String) -> RoriResult<usize>;
+    /**
+     * @return the number of modified modules
+     */
+    fn set_module_enabled(&self, name: &String, enabled: bool) -> RoriResult<usize>;
+    /**
+     * @return the number of removed tasks
+     */
+    fn rm_task(&self, id: &i32) -> RoriResult<usize>;
+}
+
+/**
+ * Content of an InMemoryRepository
+ */
+#[derive(Default)]
+struct Storage {
+    users: Vec<UserRecord>,
+    devices: Vec<(DeviceRecord, Vec<String>)>,
+    capabilities: HashMap<i32, Capabilities>,
+    bridge_owners: HashMap<String, String>,
+    // (username, body)
+    unmatched: Vec<(String, String)>,
+    languages: HashMap<String, String>,
+    // (username, datatype) -> device
+    preferences: HashMap<(String, String), i32>,
+    link_requests: Vec<LinkRequest>,
+    groups: Vec<Group>,
+    last_user_id: i32,
+    last_device_id: i32,
+    last_link_request_id: i32,
+}
+
+impl Storage {
+    /**
+     * Create the user if needed
+     * @param username
+     */
+    fn add_user(&mut self, username: &String) {
+        if username.is_empty() || self.users.iter().any(|u| &u.username == username) {
+            return;
+        }
+        self.last_user_id += 1;
+        self.users.push(UserRecord {
+            id: self.last_user_id,
+            username: username.clone(),
+            display_name: String::new(),
+            created: time::get_time().sec,
+            role: Role::User,
+        });
+    }
+
+    /**
+     * Move the data of an user to another username
+     * @param username
+     * @param new_username
+     * @return the number of modified rows
+     */
+    fn move_rows(&mut self, username: &String, new_username: &String) -> usize {
+        let mut result = 0;
+        for row in self.unmatched.iter_mut().filter(|(u, _)| u == username) {
+            row.0 = new_username.clone();
+            result += 1;
+        }
+        if let Some(lang) = self.languages.remove(username) {
+            self.languages.insert(new_username.clone(), lang);
+            result += 1;
+        }
+        let preferences: Vec<(String, String)> = self.preferences.keys().filter(|(u, _)| u == username).cloned().collect();
+        for key in preferences {
+            let device = self.preferences.remove(&key).unwrap_or(0);
+            self.preferences.insert((new_username.clone(), key.1), device);
+            result += 1;
+        }
+        for request in self.link_requests.iter_mut().filter(|r| &r.username == username) {
+            request.username = new_username.clone();
+            result += 1;
+        }
+        for group in self.groups.iter_mut() {
+            for member in group.members.iter_mut().filter(|m| *m == username) {
+                *member = new_username.clone();
+                result += 1;
+            }
+            if &group.owner == username {
+                group.owner = new_username.clone();
+            }
+        }
+        result
+    }
+
+    /**
+     * Remove the data of an user (see move_rows)
+     * @param username
+     * @return the number of removed rows
+     */
+    fn remove_rows(&mut self, username: &String) -> usize {
+        let before = self.unmatched.len() + self.languages.len() + self.preferences.len() + self.link_requests.len();
+        self.unmatched.retain(|(u, _)| u != username);
+        self.languages.remove(username);
+        self.preferences.retain(|(u, _), _| u != username);
+        self.link_requests.retain(|r| &r.username != username);
+        let mut result = before - self.unmatched.len() - self.languages.len() - self.preferences.len() - self.link_requests.len();
+        for group in self.groups.iter_mut() {
+            let before = group.members.len();
+            group.members.retain(|m| m != username);
+            result += before - group.members.len();
+            if &group.owner == username {
+                group.owner = String::new();
+            }
+        }
+        result
+    }
+
+    /**
+     * Apply a change to a device
+     * @return the number of modified devices
+     */
+    fn update<F: FnOnce(&mut DeviceRecord, &mut Vec<String>)>(&mut self, id: &i32, change: F) -> usize {
+        match self.devices.iter_mut().find(|(d, _)| d.id == *id) {
+            Some((device, datatypes)) => {
+                change(device, datatypes);
+                1
+            },
+            None => 0
+        }
+    }
+}
+
+/**
+ * Repository kept in memory, to test components without a database
+ */
+#[derive(Default)]
+pub struct InMemoryRepository {
+    storage: Mutex<Storage>,
+}
+
+impl InMemoryRepository {
+    /**
+     * Generate an empty repository
+     */
+    pub fn new() -> InMemoryRepository {
+        InMemoryRepository::default()
+    }
+
+    fn devices<F: Fn(&DeviceRecord) -> bool>(&self, filter: F) -> Vec<DeviceRecord> {
+        let storage = self.storage.lock().unwrap();
+        storage.devices.iter().map(|(d, _)| d).filter(|d| filter(d)).cloned().collect()
+    }
+}
+
+impl Repository for InMemoryRepository {
+    fn get_users(&self) -> RoriResult<Vec<UserRecord>> {
+        let mut users = self.storage.lock().unwrap().users.clone();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(users)
+    }
+
+    fn get_user(&self, username: &String) -> RoriResult<Option<UserRecord>> {
+        let storage = self.storage.lock().unwrap();
+        Ok(storage.users.iter().find(|u| &u.username == username).cloned())
+    }
+
+    fn set_display_name(&self, username: &String, display_name: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        match storage.users.iter_mut().find(|u| &u.username == username) {
+            Some(user) => {
+                user.display_name = display_name.clone();
+                Ok(1)
+            },
+            None => Ok(0)
+        }
+    }
+
+    fn set_role(&self, username: &String, role: Role) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        match storage.users.iter_mut().find(|u| &u.username == username) {
+            Some(user) => {
+                user.role = role;
+                Ok(1)
+            },
+            None => Ok(0)
+        }
+    }
+
+    fn bootstrap_admin(&self, hash: &String) -> RoriResult<usize> {
+        let username = {
+            let storage = self.storage.lock().unwrap();
+            let has_device = |u: &UserRecord| storage.devices.iter().any(|(d, _)| d.username == u.username);
+            if storage.users.iter().any(|u| u.role == Role::Admin && has_device(u)) {
+                return Ok(0);
+            }
+            match storage.devices.iter().find(|(d, _)| &d.hash == hash && !d.is_bridge) {
+                Some((device, _)) => device.username.clone(),
+                None => return Ok(0)
+            }
+        };
+        if username.is_empty() {
+            return Ok(0);
+        }
+        self.set_role(&username, Role::Admin)
+    }
+
+    fn rename_user(&self, username: &String, new_username: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        if !storage.users.iter().any(|u| &u.username == username) {
+            return Err(RoriError::NotFound(format!("user {}", username)));
+        }
+        // Rows left by an old user with the same name are removed, not merged with the data of the user
+        storage.remove_rows(new_username);
+        let mut result = storage.move_rows(username, new_username);
+        for user in storage.users.iter_mut().filter(|u| &u.username == username) {
+            user.username = new_username.clone();
+            result += 1;
+        }
+        for (device, _) in storage.devices.iter_mut().filter(|(d, _)| &d.username == username) {
+            device.username = new_username.clone();
+            result += 1;
+        }
+        Ok(result)
+    }
+
+    fn get_devices(&self) -> RoriResult<Vec<DeviceRecord>> {
+        Ok(self.devices(|_| true))
+    }
+
+    fn get_devices_for_hash(&self, hash: &str) -> RoriResult<Vec<DeviceRecord>> {
+        Ok(self.devices(|d| d.hash == hash))
+    }
+
+    fn get_devices_for_username(&self, username: &str) -> RoriResult<Vec<DeviceRecord>> {
+        Ok(self.devices(|d| d.username == username))
+    }
+
+    fn get_device(&self, hash: &String, username: &String) -> RoriResult<Option<DeviceRecord>> {
+        Ok(self.devices(|d| &d.hash == hash && &d.username == username).into_iter().next())
+    }
+
+    fn insert_new_device(&self, hash: &String, username: &String, devicename: &String, is_bridge: bool) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let exists = storage.devices.iter().any(|(d, _)| {
+            &d.hash == hash && (!is_bridge || (&d.username == username && &d.devicename == devicename))
+        });
+        if exists {
+            return Err(RoriError::AlreadyExists(String::from("Device already inserted")));
+        }
+        storage.add_user(username);
+        storage.last_device_id += 1;
+        let device = DeviceRecord {
+            id: storage.last_device_id,
+            hash: hash.clone(),
+            username: username.clone(),
+            devicename: devicename.clone(),
+            sub_author: String::new(),
+            is_bridge: is_bridge,
+        };
+        storage.devices.push((device, Vec::new()));
+        Ok(storage.last_device_id as usize)
+    }
+
+    fn remove_device(&self, id: &i32) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let before = storage.devices.len();
+        storage.devices.retain(|(d, _)| d.id != *id);
+        Ok(before - storage.devices.len())
+    }
+
+    fn update_username(&self, id: &i32, username: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        storage.add_user(username);
+        Ok(storage.update(id, |d, _| d.username = username.clone()))
+    }
+
+    fn update_devicename(&self, id: &i32, devicename: &String) -> RoriResult<usize> {
+        Ok(self.storage.lock().unwrap().update(id, |d, _| d.devicename = devicename.clone()))
+    }
+
+    fn update_sub_author(&self, id: &i32, sub_author: &String) -> RoriResult<usize> {
+        Ok(self.storage.lock().unwrap().update(id, |d, _| d.sub_author = sub_author.clone()))
+    }
+
+    fn sub_author(&self, hash: &String, sub_author: &String) -> RoriResult<String> {
+        let device = self.devices(|d| &d.hash == hash && &d.sub_author == sub_author).into_iter().next();
+        Ok(device.map(|d| d.username).unwrap_or(String::new()))
+    }
+
+    fn sub_author_id(&self, hash: &String, username: &String) -> RoriResult<String> {
+        let device = self.devices(|d| &d.hash == hash && &d.username == username).into_iter().next();
+        Ok(device.map(|d| d.sub_author).unwrap_or(String::new()))
+    }
+
+    fn is_bridge(&self, hash: &String) -> RoriResult<bool> {
+        Ok(!self.devices(|d| &d.hash == hash && d.is_bridge).is_empty())
+    }
+
+    fn bridgify(&self, id: &i32) -> RoriResult<usize> {
+        Ok(self.storage.lock().unwrap().update(id, |d, _| d.is_bridge = true))
+    }
+
+    fn get_datatypes(&self, id: &i32) -> RoriResult<Vec<String>> {
+        let storage = self.storage.lock().unwrap();
+        Ok(storage.devices.iter().find(|(d, _)| d.id == *id).map(|(_, dt)| dt.clone()).unwrap_or(Vec::new()))
+    }
+
+    fn set_datatypes(&self, id: &i32, datatypes: Vec<String>) -> RoriResult<usize> {
+        Ok(self.storage.lock().unwrap().update(id, |_, dt| *dt = datatypes))
+    }
+
+    fn set_capabilities(&self, id: &i32, capabilities: &Capabilities) -> RoriResult<usize> {
+        self.storage.lock().unwrap().capabilities.insert(*id, capabilities.clone());
+        Ok(capabilities.kinds.len() + capabilities.actions.len())
+    }
+
+    fn set_bridge_owner(&self, hash: &String, username: &String) -> RoriResult<usize> {
+        self.storage.lock().unwrap().bridge_owners.insert(hash.clone(), username.clone());
+        Ok(1)
+    }
+
+    fn get_bridge_owner(&self, hash: &String) -> RoriResult<Option<String>> {
+        Ok(self.storage.lock().unwrap().bridge_owners.get(hash).cloned())
+    }
+
+    fn add_unmatched(&self, username: &String, interaction: &Interaction) -> RoriResult<usize> {
+        self.storage.lock().unwrap().unmatched.push((username.clone(), interaction.body.clone()));
+        Ok(1)
+    }
+
+    fn set_lang(&self, username: &String, lang: &String) -> RoriResult<usize> {
+        self.storage.lock().unwrap().languages.insert(username.clone(), lang.clone());
+        Ok(1)
+    }
+
+    fn set_preference(&self, username: &String, datatype: &String, device: &i32) -> RoriResult<usize> {
+        self.storage.lock().unwrap().preferences.insert((username.clone(), datatype.clone()), *device);
+        Ok(1)
+    }
+
+    fn rm_preference(&self, username: &String, datatype: &String) -> RoriResult<usize> {
+        let removed = self.storage.lock().unwrap().preferences.remove(&(username.clone(), datatype.clone()));
+        Ok(removed.map(|_| 1).unwrap_or(0))
+    }
+
+    fn export_user(&self, username: &String) -> RoriResult<BTreeMap<String, Vec<Map<String, Value>>>> {
+        let storage = self.storage.lock().unwrap();
+        let mut result = BTreeMap::new();
+        let users = storage.users.iter().filter(|u| &u.username == username).map(|u| {
+            let mut row = Map::new();
+            row.insert(String::from("id"), Value::from(u.id));
+            row.insert(String::from("username"), Value::from(u.username.clone()));
+            row.insert(String::from("display_name"), Value::from(u.display_name.clone()));
+            row.insert(String::from("created"), Value::from(u.created));
+            row.insert(String::from("role"), Value::from(u.role.as_str()));
+            row
+        }).collect();
+        result.insert(String::from("users"), users);
+        let devices = storage.devices.iter().map(|(d, _)| d).filter(|d| &d.username == username).map(|d| {
+            let mut row = Map::new();
+            row.insert(String::from("id"), Value::from(d.id));
+            row.insert(String::from("hash"), Value::from(d.hash.clone()));
+            row.insert(String::from("devicename"), Value::from(d.devicename.clone()));
+            row.insert(String::from("sub_author"), Value::from(d.sub_author.clone()));
+            row.insert(String::from("is_bridge"), Value::from(d.is_bridge));
+            row.insert(String::from("username"), Value::from(d.username.clone()));
+            row
+        }).collect();
+        result.insert(String::from("devices"), devices);
+        let languages = storage.languages.get(username).map(|lang| {
+            let mut row = Map::new();
+            row.insert(String::from("username"), Value::from(username.clone()));
+            row.insert(String::from("lang"), Value::from(lang.clone()));
+            row
+        }).into_iter().collect();
+        result.insert(String::from("languages"), languages);
+        let preferences = storage.preferences.iter().filter(|((u, _), _)| u == username).map(|((_, datatype), device)| {
+            let mut row = Map::new();
+            row.insert(String::from("username"), Value::from(username.clone()));
+            row.insert(String::from("datatype"), Value::from(datatype.clone()));
+            row.insert(String::from("device"), Value::from(*device));
+            row
+        }).collect();
+        result.insert(String::from("preferences"), preferences);
+        let group_members = storage.groups.iter().filter(|g| g.members.contains(username)).map(|g| {
+            let mut row = Map::new();
+            row.insert(String::from("group"), Value::from(g.name.clone()));
+            row.insert(String::from("username"), Value::from(username.clone()));
+            row
+        }).collect();
+        result.insert(String::from("group_members"), group_members);
+        Ok(result)
+    }
+
+    fn purge_user(&self, username: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let before = storage.devices.len() + storage.users.len();
+        storage.devices.retain(|(d, _)| !d.is_bridge || &d.username != username);
+        storage.users.retain(|u| &u.username != username);
+        let mut result = before - storage.devices.len() - storage.users.len();
+        for (device, _) in storage.devices.iter_mut().filter(|(d, _)| &d.username == username) {
+            device.username = String::new();
+            device.devicename = String::new();
+            device.sub_author = String::new();
+            result += 1;
+        }
+        let Storage { ref devices, ref mut capabilities, .. } = *storage;
+        capabilities.retain(|id, _| devices.iter().any(|(d, _)| d.id == *id));
+        result += storage.remove_rows(username);
+        Ok(result)
+    }
+
+    fn add_link_request(&self, request: &LinkRequest) -> RoriResult<i32> {
+        let mut storage = self.storage.lock().unwrap();
+        storage.link_requests.retain(|r| r.hash != request.hash || r.username != request.username);
+        storage.last_link_request_id += 1;
+        let mut request = request.clone();
+        request.id = storage.last_link_request_id;
+        storage.link_requests.push(request);
+        Ok(storage.last_link_request_id)
+    }
+
+    fn get_link_request(&self, hash: &String, username: &String, now: i64) -> RoriResult<Option<LinkRequest>> {
+        let storage = self.storage.lock().unwrap();
+        Ok(storage.link_requests.iter().find(|r| &r.hash == hash && &r.username == username && r.expires > now).cloned())
+    }
+
+    fn rm_link_request(&self, id: &i32) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let before = storage.link_requests.len();
+        storage.link_requests.retain(|r| r.id != *id);
+        Ok(before - storage.link_requests.len())
+    }
+
+    fn rm_expired_link_requests(&self, now: i64) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let before = storage.link_requests.len();
+        storage.link_requests.retain(|r| r.expires > now);
+        Ok(before - storage.link_requests.len())
+    }
+
+    fn add_group(&self, name: &String, owner: &String) -> RoriResult<i32> {
+        let mut storage = self.storage.lock().unwrap();
+        if storage.users.iter().any(|u| &u.username == name) || storage.groups.iter().any(|g| &g.name == name) {
+            return Err(RoriError::AlreadyExists(format!("name {}", name)));
+        }
+        let owner = if storage.users.iter().any(|u| &u.username == owner) { owner.clone() } else { String::new() };
+        storage.groups.push(Group { name: name.clone(), members: Vec::new(), owner: owner });
+        Ok(storage.groups.len() as i32)
+    }
+
+    fn rm_group(&self, name: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let before = storage.groups.len();
+        storage.groups.retain(|g| &g.name != name);
+        Ok(before - storage.groups.len())
+    }
+
+    fn get_group(&self, name: &String) -> RoriResult<Option<Group>> {
+        Ok(self.storage.lock().unwrap().groups.iter().find(|g| &g.name == name).cloned())
+    }
+
+    fn get_groups(&self) -> RoriResult<Vec<Group>> {
+        let mut groups = self.storage.lock().unwrap().groups.clone();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(groups)
+    }
+
+    fn add_group_member(&self, name: &String, username: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        let group = match storage.groups.iter_mut().find(|g| &g.name == name) {
+            Some(group) => group,
+            None => return Err(RoriError::NotFound(format!("group {}", name)))
+        };
+        if group.members.contains(username) {
+            return Ok(0);
+        }
+        group.members.push(username.clone());
+        group.members.sort();
+        Ok(1)
+    }
+
+    fn rm_group_member(&self, name: &String, username: &String) -> RoriResult<usize> {
+        let mut storage = self.storage.lock().unwrap();
+        match storage.groups.iter_mut().find(|g| &g.name == name) {
+            Some(group) => {
+                let before = group.members.len();
+                group.members.retain(|m| m != username);
+                Ok(before - group.members.len())
+            },
+            None => Ok(0)
+        }
+    }
+
+    // Modules and tasks are loaded from rori_modules and the scheduler, never kept in memory
+
+    fn set_module_enabled(&self, _name: &String, _enabled: bool) -> RoriResult<usize> {
+        Ok(0)
+    }
+
+    fn rm_task(&self, _id: &i32) -> RoriResult<usize> {
+        Ok(0)
+    }
+}
@@ -0,0 +1,85 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use rori::capabilities;
+use rori::database::Database;
+use rori::error::RoriResult;
+use rori::presence::{self, Presence};
+use rori::repository::DeviceRecord;
+
+// Datatype handled by every device
+pub const TEXT: &'static str = "text/plain";
+
+/**
+ * A device able to receive a message, with what decided its rank
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub device: DeviceRecord,
+    pub presence: Presence,
+    // Chosen by the user for this datatype (or for every datatype)
+    pub preferred: bool,
+    // Timestamp of the last message sent by the device (0 if never)
+    pub last_active: i64,
+}
+
+/**
+ * Rank the devices of an user which can handle a datatype, best first:
+ * the preferred device if it's not offline (the presence stays unknown until the daemon reports it),
+ * then online devices, then unknown, then offline (the daemon delivers messages when they come back).
+ * For the same presence, the most recently active comes first.
+ * Bridges are not routed, their messages need a sub author.
+ * @param username
+ * @param datatype to send (see capabilities::handles)
+ * @return the routes, empty if no device can handle the datatype
+ */
+pub fn route(username: &String, datatype: &String) -> RoriResult<Vec<Route>> {
+    let preferences = Database::get_preferences(username)?;
+    let preferred = preferences.get(datatype).or(preferences.get("")).cloned();
+    let activity = Database::get_last_activity(username)?;
+    let mut routes = Vec::new();
+    for device in Database::get_devices_for_username(&*username)? {
+        if device.is_bridge {
+            continue;
+        }
+        if !capabilities::handles(&device.id, datatype)? {
+            continue;
+        }
+        routes.push(Route {
+            presence: presence::get(&device.hash).presence,
+            preferred: preferred == Some(device.id),
+            last_active: activity.get(&device.hash).cloned().unwrap_or(0),
+            device: device,
+        });
+    }
+    routes.sort_by(|a, b| (a.presence == Presence::Offline).cmp(&(b.presence == Presence::Offline))
+                          .then(b.preferred.cmp(&a.preferred))
+                          .then(a.presence.cmp(&b.presence))
+                          .then(b.last_active.cmp(&a.last_active))
+                          .then(a.device.id.cmp(&b.device.id)));
+    Ok(routes)
+}
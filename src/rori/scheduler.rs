@@ -28,7 +28,11 @@
 use clokwerk::{Interval, TimeUnits};
 use clokwerk::Interval::*;
 use rori::database::Database;
+use rori::error::{RoriError, RoriResult};
+use rori::group;
 use rori::interaction::Interaction;
+use rori::repository::DeviceRecord;
+use rori::routing;
 use rori::user::Device;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -95,6 +99,7 @@ impl Scheduler {
                         job.run_pending();
                     }
                 }
+                Scheduler::load_new_tasks(&cloned);
                 thread::sleep(Duration::from_secs(1));
             }
         }));
@@ -114,38 +119,12 @@ impl Scheduler {
      * @param content       Json representing the task
      * @return id of the task
      */
-    pub fn add_task(&mut self, content: &String) -> Option<i32> {
-        let content: HashMap<String, String> = serde_json::from_str(&*content).unwrap_or(HashMap::new());
-        if !content.contains_key("id")
-        || !content.contains_key("module")
-        || !content.contains_key("parameter")
-        || !content.contains_key("at")
-        || !content.contains_key("seconds")
-        || !content.contains_key("minutes")
-        || !content.contains_key("hours")
-        || !content.contains_key("days")
-        || !content.contains_key("repeat") {
-            return None;
-        }
-        let task = ScheduledTask {
-            id: content.get("id").unwrap().parse::<i32>().unwrap_or(-1),
-            module: content.get("module").unwrap().parse::<i32>().unwrap_or(0),
-            parameter: content.get("parameter").unwrap().to_string(),
-            at: content.get("at").unwrap().to_string(),
-            seconds: content.get("seconds").unwrap().parse::<u32>().unwrap_or(0),
-            minutes: content.get("minutes").unwrap().parse::<u32>().unwrap_or(0),
-            hours: content.get("hours").unwrap().parse::<u32>().unwrap_or(0),
-            days: content.get("days").unwrap().to_string(),
-            repeat: content.get("repeat").unwrap_or(&String::new()) == "True",
-        };
-        if task.id < 0 {
-            return None;
-        }
-        let result = Database::add_task(&task);
-        if !result.is_none() {
-            self.load_task(task);
-        }
-        result
+    pub fn add_task(&mut self, content: &String) -> RoriResult<i32> {
+        let mut task = Scheduler::parse_task(content)?;
+        task.id = Database::add_task(&task)?;
+        let id = task.id;
+        self.load_task(task);
+        Ok(id)
     }
 
     /**
@@ -154,40 +133,14 @@ impl Scheduler {
      * @param content       Json representing the task
      * @return id of the task
      */
-    pub fn update_task(&mut self, content: &String) -> Option<i32> {
-        let content: HashMap<String, String> = serde_json::from_str(&*content).unwrap_or(HashMap::new());
-        if !content.contains_key("id")
-        || !content.contains_key("module")
-        || !content.contains_key("parameter")
-        || !content.contains_key("at")
-        || !content.contains_key("seconds")
-        || !content.contains_key("minutes")
-        || !content.contains_key("hours")
-        || !content.contains_key("days")
-        || !content.contains_key("repeat") {
-            return None;
+    pub fn update_task(&mut self, content: &String) -> RoriResult<i32> {
+        let task = Scheduler::parse_task(content)?;
+        if Database::update_task(&task)? == 0 {
+            return Err(RoriError::NotFound(format!("task {}", task.id)));
         }
-        let task = ScheduledTask {
-            id: content.get("id").unwrap().parse::<i32>().unwrap_or(-1),
-            module: content.get("module").unwrap().parse::<i32>().unwrap_or(0),
-            parameter: content.get("parameter").unwrap().to_string(),
-            at: content.get("at").unwrap().to_string(),
-            seconds: content.get("seconds").unwrap().parse::<u32>().unwrap_or(0),
-            minutes: content.get("minutes").unwrap().parse::<u32>().unwrap_or(0),
-            hours: content.get("hours").unwrap().parse::<u32>().unwrap_or(0),
-            days: content.get("days").unwrap().to_string(),
-            repeat: content.get("repeat").unwrap_or(&String::new()) == "True",
-        };
-        if task.id < 0 {
-            return None;
-        }
-        let result = Database::update_task(&task);
-        if result.is_ok() && result.unwrap() != 0 {
-            let id = task.id;
-            self.load_task(task);
-            return Some(id)
-        }
-        None
+        let id = task.id;
+        self.load_task(task);
+        Ok(id)
     }
 
     /**
@@ -196,24 +149,83 @@ impl Scheduler {
      * @param id       Id of the task
      * @return id of the task
      */
-    pub fn rm_task(&mut self, id: &i32) -> Option<i32> {
-        let result = Database::rm_task(&id);
-        if result.is_ok() && result.unwrap() != 0 {
-            let jobs: &mut HashMap<i32, clokwerk::Scheduler> = &mut self.jobs.lock().unwrap();
-            jobs.retain(|jid, _| jid == id);
-            return Some(id.clone())
+    pub fn rm_task(&mut self, id: &i32) -> RoriResult<i32> {
+        if Database::rm_task(&id)? == 0 {
+            return Err(RoriError::NotFound(format!("task {}", id)));
         }
-        None
+        let jobs: &mut HashMap<i32, clokwerk::Scheduler> = &mut self.jobs.lock().unwrap();
+        jobs.retain(|jid, _| jid == id);
+        Ok(id.clone())
     }
 
 // private
+    /**
+     * Parse a task sent as Json
+     * @param content       Json representing the task
+     * @return the task or a parse error if a field is missing or invalid
+     */
+    fn parse_task(content: &String) -> RoriResult<ScheduledTask> {
+        let content: HashMap<String, String> = serde_json::from_str(&*content)?;
+        for key in &["id", "module", "parameter", "at", "seconds", "minutes", "hours", "days", "repeat"] {
+            if !content.contains_key(*key) {
+                return Err(RoriError::Parse(format!("missing {} in task", key)));
+            }
+        }
+        let task = ScheduledTask {
+            id: content["id"].parse::<i32>().unwrap_or(-1),
+            module: content["module"].parse::<i32>().unwrap_or(0),
+            parameter: content["parameter"].to_string(),
+            at: content["at"].to_string(),
+            seconds: content["seconds"].parse::<u32>().unwrap_or(0),
+            minutes: content["minutes"].parse::<u32>().unwrap_or(0),
+            hours: content["hours"].parse::<u32>().unwrap_or(0),
+            days: content["days"].to_string(),
+            repeat: content["repeat"] == "True",
+        };
+        if task.id < 0 {
+            return Err(RoriError::Parse(format!("invalid task id {}", content["id"])));
+        }
+        Ok(task)
+    }
+
     /**
      * Load tasks from the database
      * @param self
      */
     fn load_tasks(&mut self) {
-        for task in Database::get_tasks() {
-            self.load_task(task);
+        match Database::get_tasks() {
+            Ok(tasks) => {
+                for task in tasks {
+                    self.load_task(task);
+                }
+            },
+            Err(e) => error!("Can't load tasks: {}", e)
+        }
+    }
+
+    /**
+     * Load tasks added in the database by someone else (like modules via the rori python module)
+     * @param jobs  Jobs already loaded
+     */
+    fn load_new_tasks(jobs: &Arc<Mutex<HashMap<i32, clokwerk::Scheduler>>>) {
+        let tasks = match Database::get_tasks() {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                error!("Can't load new tasks: {}", e);
+                return;
+            }
+        };
+        let new_tasks: Vec<ScheduledTask> = {
+            let mut jobs = jobs.lock().unwrap();
+            // Tasks removed from the database (like tasks of forgotten users) are stopped
+            jobs.retain(|id, _| tasks.iter().any(|task| task.id == *id));
+            tasks.into_iter().filter(|task| !jobs.contains_key(&task.id)).collect()
+        };
+        for task in new_tasks {
+            let id = task.id;
+            if let Some(scheduler) = Scheduler::build_job(task) {
+                jobs.lock().unwrap().insert(id, scheduler);
+            }
         }
     }
 
@@ -223,6 +235,19 @@ impl Scheduler {
      * @param task  The task to load
      */
     fn load_task(&mut self, task: ScheduledTask) {
+        let id = task.id;
+        if let Some(scheduler) = Scheduler::build_job(task) {
+            let jobs: &mut HashMap<i32, clokwerk::Scheduler> = &mut self.jobs.lock().unwrap();
+            jobs.insert(id, scheduler);
+        }
+    }
+
+    /**
+     * Build the job to run for a task. Invalid tasks are removed from the database
+     * @param task  The task to build
+     * @return the job or None if the task is invalid
+     */
+    fn build_job(task: ScheduledTask) -> Option<clokwerk::Scheduler> {
         let mut scheduler = clokwerk::Scheduler::new();
         let mut interval: Option<Interval> = None;
         let mut first_interval = true;
@@ -299,41 +324,117 @@ impl Scheduler {
         }
 
         // Load the module to run
-        let module = Database::get_module(&task.module);
-
-        if module.is_none() {
-            warn!("Remove task with id {} because no module were found", task.id);
-            let _ = Database::rm_task(&task.id);
-            return;
-        }
-        let module = module.unwrap();
+        let module = match Database::get_module(&task.module) {
+            Ok(Some(module)) => module,
+            Ok(None) => {
+                warn!("Remove task with id {} because no module were found", task.id);
+                let _ = Database::rm_task(&task.id);
+                return None;
+            },
+            Err(e) => {
+                error!("Can't load module of task {}: {}", task.id, e);
+                return None;
+            }
+        };
         let metadatas: HashMap<String, String> = serde_json::from_str(&*task.parameter).unwrap_or(HashMap::new());
+        let is_group = match metadatas.get("username") {
+            Some(username) => match Database::get_group(username) {
+                Ok(group) => group.is_some(),
+                Err(e) => {
+                    error!("Can't load group of task {}: {}", task.id, e);
+                    return None;
+                }
+            },
+            None => false
+        };
+        if is_group {
+            // Members are resolved when the job runs, to follow changes of the group
+            info!("Scheduled new job for module {} with group {}", module.name, metadatas["username"]);
+            job.run(move || {
+                for interaction in Scheduler::group_interactions(&metadatas) {
+                    info!("Scheduler exec job for module {} with interaction {}", module.name, interaction);
+                    module.exec(&interaction, &interaction.metadatas["username"]);
+                }
+            });
+            return Some(scheduler);
+        }
         if metadatas.is_empty() || !metadatas.contains_key("ring_id") || !metadatas.contains_key("username") {
             warn!("Remove task {} with id {} because no parameters were specified", module.name, task.id);
             let _ = Database::rm_task(&task.id);
-            return;
+            return None;
         }
 
-        let device = Database::get_device(&metadatas["ring_id"], &metadatas["username"]);
-        if device.0 == -1 {
-            warn!("Remove task {} with id {} because no device were found", module.name, task.id);
-            let _ = Database::rm_task(&task.id);
-            return;
-        }
-        let interaction = Interaction {
-            device_author: Device::new(&device.0, &device.1),
-            body: String::new(),
-            metadatas: metadatas,
-            datatype: String::new(),
-            time: time::now()
+        let device = match Database::get_device(&metadatas["ring_id"], &metadatas["username"]) {
+            Ok(Some(device)) => device,
+            Ok(None) => {
+                warn!("Remove task {} with id {} because no device were found", module.name, task.id);
+                let _ = Database::rm_task(&task.id);
+                return None;
+            },
+            Err(e) => {
+                error!("Can't load device of task {}: {}", task.id, e);
+                return None;
+            }
         };
+        let interaction = Scheduler::interaction(&device, metadatas);
 
         info!("Scheduled new job for module {} with interaction {}", module.name, interaction);
         job.run(move || {
             info!("Scheduler exec job for module {} with interaction {}", module.name, interaction);
-            module.exec(&interaction);
+            module.exec(&interaction, &device.username);
         });
-        let jobs: &mut HashMap<i32, clokwerk::Scheduler> = &mut self.jobs.lock().unwrap();
-        jobs.insert(task.id, scheduler);
+        Some(scheduler)
+    }
+
+    /**
+     * Build the interactions of a task for a group: one per member, from its best device (see routing::route)
+     * @param metadatas parameters of the task, username is the name of the group
+     * @return the interactions, with the username and the ring_id of each member
+     */
+    pub fn group_interactions(metadatas: &HashMap<String, String>) -> Vec<Interaction> {
+        let mut interactions = Vec::new();
+        let members = match group::resolve(&metadatas["username"]) {
+            Ok(members) => members,
+            Err(e) => {
+                error!("Can't resolve group {}: {}", metadatas["username"], e);
+                return interactions;
+            }
+        };
+        for member in members {
+            let device = match routing::route(&member, &String::from(routing::TEXT)) {
+                Ok(routes) => match routes.into_iter().next() {
+                    Some(route) => route.device,
+                    None => {
+                        warn!("{} has no device for a scheduled task", member);
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    error!("Can't route a scheduled task to {}: {}", member, e);
+                    continue;
+                }
+            };
+            let mut member_metadatas = metadatas.clone();
+            member_metadatas.insert(String::from("username"), member);
+            member_metadatas.insert(String::from("ring_id"), device.hash.clone());
+            interactions.push(Scheduler::interaction(&device, member_metadatas));
+        }
+        interactions
+    }
+
+    /**
+     * Build the interaction sent to the module of a task
+     * @param device author of the interaction
+     * @param metadatas parameters of the task
+     * @return the interaction
+     */
+    fn interaction(device: &DeviceRecord, metadatas: HashMap<String, String>) -> Interaction {
+        Interaction {
+            device_author: Device::new(&device.id, &device.hash),
+            metadatas: metadatas,
+            datatype: String::new(),
+            lang: Database::get_lang(&device.username).ok().and_then(|lang| lang).unwrap_or(String::from("en")),
+            ..Interaction::default()
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,319 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use cpython::{exc, PyErr, PyDict, PyModule, PyResult, Python};
+use rori::database::Database;
+use rori::error::RoriError;
+use rori::group;
+use rori::presence;
+use rori::routing;
+use rori::scheduler::ScheduledTask;
+use rori::sender;
+use rori::vault::Vault;
+use serde_json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/**
+ * Version of the API exposed to python modules as rori.__version__
+ * Must be increased when a function is added or changed
+ */
+pub const SDK_VERSION: &'static str = "1.6";
+
+thread_local! {
+    // Name of the module currently executed by this thread. Used to isolate the store and secrets.
+    static CURRENT_MODULE: RefCell<String> = RefCell::new(String::new());
+    // Author of the interaction handled by this thread. Used to select secrets.
+    // NOTE: never store per-user data in the rori module, it is shared by every thread
+    static CURRENT_USER: RefCell<String> = RefCell::new(String::new());
+}
+
+/**
+ * Register the native rori module into sys.modules, so a python module can do:
+ * import rori
+ * rori.send("username", "hello", "text/plain")
+ * rori.send_best("username", "play", "music/play")
+ * rori.presence("ring_id")
+ * rori.devices_for("username", "music/play")
+ * rori.capabilities("ring_id")
+ * rori.schedule("module", '{"ring_id":"xxx","username":"foo"}', "10:00", 0, 0, 0, "Monday", True)
+ * rori.store.set("key", "value")
+ * rori.users()
+ * rori.group("family")
+ * rori.secrets()["token"]
+ * @param py
+ * @param module_name   name of the module which will be executed
+ * @param username      author of the interaction, used to select secrets (empty if anonymous)
+ * @return if success
+ */
+pub fn register(py: Python, module_name: &String, username: &String) -> PyResult<()> {
+    enter(module_name, username);
+
+    let store = PyModule::new(py, "rori.store")?;
+    store.add(py, "get", py_fn!(py, store_get(key: String)))?;
+    store.add(py, "set", py_fn!(py, store_set(key: String, value: String)))?;
+
+    let rori = PyModule::new(py, "rori")?;
+    rori.add(py, "__version__", SDK_VERSION)?;
+    rori.add(py, "send", py_fn!(py, send(target: String, body: String, datatype: String)))?;
+    rori.add(py, "send_best", py_fn!(py, send_best(username: String, body: String, datatype: String)))?;
+    rori.add(py, "presence", py_fn!(py, presence(hash: String)))?;
+    rori.add(py, "devices_for", py_fn!(py, devices_for(username: String, datatype: String)))?;
+    rori.add(py, "capabilities", py_fn!(py, capabilities(hash: String)))?;
+    rori.add(py, "schedule", py_fn!(py, schedule(module: String, parameter: String, at: String,
+                                                seconds: u32, minutes: u32, hours: u32,
+                                                days: String, repeat: bool)))?;
+    rori.add(py, "users", py_fn!(py, users()))?;
+    rori.add(py, "group", py_fn!(py, group(name: String)))?;
+    rori.add(py, "store", store)?;
+    rori.add(py, "secrets", py_fn!(py, secrets()))?;
+
+    let modules: PyDict = py.import("sys")?.get(py, "modules")?.cast_into(py)?;
+    modules.set_item(py, "rori", rori)
+}
+
+/**
+ * rori.send(target, body, datatype)
+ * Send an interaction to a device (via its hash), to all devices of a user or to the best
+ * device of each member of a group
+ * @return the number of interactions sent
+ */
+fn send(py: Python, target: String, body: String, datatype: String) -> PyResult<u64> {
+    let mut destinations = Vec::new();
+    if Database::search_hash(&target).map_err(|e| to_py_err(py, e))? {
+        destinations.push(target.clone());
+    } else if let Some(group) = Database::get_group(&target).map_err(|e| to_py_err(py, e))? {
+        for member in group.members {
+            if let Some(route) = routing::route(&member, &datatype).map_err(|e| to_py_err(py, e))?.into_iter().next() {
+                destinations.push(route.device.hash);
+            }
+        }
+    } else {
+        for device in Database::get_devices_for_username(&*target).map_err(|e| to_py_err(py, e))? {
+            destinations.push(device.hash);
+        }
+    }
+    if destinations.is_empty() {
+        return Err(PyErr::new::<exc::ValueError, _>(py, format!("unknown target {}", target)));
+    }
+    if sender::account().is_empty() {
+        return Err(PyErr::new::<exc::RuntimeError, _>(py, "no account configured"));
+    }
+    let mut sent = 0;
+    for destination in destinations {
+        match sender::send(&*destination, &*body, &*datatype) {
+            Ok(0) => {},
+            Ok(_) => sent += 1,
+            Err(e) => warn!("Can't send interaction to {}: {}", destination, e)
+        }
+    }
+    Ok(sent)
+}
+
+/**
+ * rori.send_best(username, body, datatype)
+ * Send an interaction to the best device of a user which can handle the datatype (see routing::route).
+ * For a group, the interaction is sent to the best device of each member
+ * @return the ring_id of the (first) device which received it, None if no device can handle it
+ */
+fn send_best(py: Python, username: String, body: String, datatype: String) -> PyResult<Option<String>> {
+    if sender::account().is_empty() {
+        return Err(PyErr::new::<exc::RuntimeError, _>(py, "no account configured"));
+    }
+    let mut received = None;
+    for member in group::resolve(&username).map_err(|e| to_py_err(py, e))? {
+        for route in routing::route(&member, &datatype).map_err(|e| to_py_err(py, e))? {
+            match sender::send(&*route.device.hash, &*body, &*datatype) {
+                Ok(0) => {},
+                Ok(_) => {
+                    received = received.or(Some(route.device.hash));
+                    break;
+                },
+                Err(e) => warn!("Can't send interaction to {}: {}", route.device.hash, e)
+            }
+        }
+    }
+    Ok(received)
+}
+
+/**
+ * rori.presence(hash)
+ * @return a dict with the presence of the device (online, offline or unknown) and
+ * last_seen, the timestamp of the last time it was online (0 if never)
+ */
+fn presence(py: Python, hash: String) -> PyResult<PyDict> {
+    let state = presence::get(&hash);
+    let result = PyDict::new(py);
+    result.set_item(py, "presence", state.presence.as_str())?;
+    result.set_item(py, "last_seen", state.last_seen)?;
+    Ok(result)
+}
+
+/**
+ * rori.devices_for(username, datatype)
+ * @return the ring_ids of the devices of the user which can handle the datatype, best first (see routing::route)
+ */
+fn devices_for(py: Python, username: String, datatype: String) -> PyResult<Vec<String>> {
+    let routes = routing::route(&username, &datatype).map_err(|e| to_py_err(py, e))?;
+    Ok(routes.into_iter().map(|route| route.device.hash).collect())
+}
+
+/**
+ * rori.capabilities(hash)
+ * @return the capabilities announced by the device as JSON ({"kinds": [...], "actions": [...]}),
+ * None if the device is unknown
+ */
+fn capabilities(py: Python, hash: String) -> PyResult<Option<String>> {
+    let device = match Database::get_devices_for_hash(&*hash).map_err(|e| to_py_err(py, e))?.into_iter().next() {
+        Some(device) => device,
+        None => return Ok(None)
+    };
+    let capabilities = Database::get_capabilities(&device.id).map_err(|e| to_py_err(py, e))?;
+    let json = serde_json::to_string(&capabilities).map_err(|e| to_py_err(py, RoriError::from(e)))?;
+    Ok(Some(json))
+}
+
+/**
+ * rori.schedule(module, parameter, at, seconds, minutes, hours, days, repeat)
+ * Add a task for the scheduler. The task will be loaded by the scheduler's thread
+ * @return the id of the task
+ */
+fn schedule(py: Python, module: String, parameter: String, at: String,
+            seconds: u32, minutes: u32, hours: u32, days: String, repeat: bool) -> PyResult<i32> {
+    let module_id = Database::get_module_id_by_name(&module).map_err(|e| to_py_err(py, e))?;
+    if module_id == 0 {
+        return Err(PyErr::new::<exc::ValueError, _>(py, format!("unknown module {}", module)));
+    }
+    let task = ScheduledTask {
+        id: 0,
+        module: module_id,
+        parameter,
+        at,
+        seconds,
+        minutes,
+        hours,
+        days,
+        repeat,
+    };
+    Database::add_task(&task).map_err(|e| to_py_err(py, e))
+}
+
+/**
+ * rori.users()
+ * @return the list of registered usernames
+ */
+fn users(py: Python) -> PyResult<Vec<String>> {
+    Database::get_usernames().map_err(|e| to_py_err(py, e))
+}
+
+/**
+ * rori.group(name)
+ * @return the members of the group, None if it's not a group
+ */
+fn group(py: Python, name: String) -> PyResult<Option<Vec<String>>> {
+    let group = Database::get_group(&name).map_err(|e| to_py_err(py, e))?;
+    Ok(group.map(|group| group.members))
+}
+
+/**
+ * rori.secrets()
+ * @return a dict name -> value with the secrets of the current module for the current user, empty if the vault is locked
+ */
+fn secrets(py: Python) -> PyResult<PyDict> {
+    let secrets = PyDict::new(py);
+    for (name, value) in current_secrets() {
+        secrets.set_item(py, name, value)?;
+    }
+    Ok(secrets)
+}
+
+/**
+ * Set the module executed by this thread and the author of its interaction (see register)
+ * @param module_name
+ * @param username (empty if anonymous)
+ */
+pub fn enter(module_name: &String, username: &String) {
+    CURRENT_MODULE.with(|current| *current.borrow_mut() = module_name.clone());
+    CURRENT_USER.with(|current| *current.borrow_mut() = username.clone());
+}
+
+/**
+ * Secrets visible by the module executed by this thread
+ * @return name -> value, secrets of the current user replace shared secrets. Empty if the vault is locked
+ */
+pub fn current_secrets() -> HashMap<String, String> {
+    if !Vault::is_unlocked() {
+        return HashMap::new();
+    }
+    let module_name = current_module();
+    let username = CURRENT_USER.with(|current| current.borrow().clone());
+    match Vault::for_module(&module_name, &username) {
+        Ok(values) => values,
+        Err(e) => {
+            error!("Can't decrypt secrets of {}: {}", module_name, e);
+            HashMap::new()
+        }
+    }
+}
+
+/**
+ * rori.store.get(key)
+ * @return the value stored by the current module or None
+ */
+fn store_get(py: Python, key: String) -> PyResult<Option<String>> {
+    Database::get_module_value(&current_module(), &key).map_err(|e| to_py_err(py, e))
+}
+
+/**
+ * rori.store.set(key, value)
+ * Store a value for the current module. A key equal to an username or starting with "username:"
+ * belongs to this user (exported, renamed and removed with the user, see Database::export_user)
+ */
+fn store_set(py: Python, key: String, value: String) -> PyResult<bool> {
+    Database::set_module_value(&current_module(), &key, &value).map_err(|e| to_py_err(py, e))?;
+    Ok(true)
+}
+
+// Private stuff
+
+/**
+ * Raise errors of RORI as RuntimeError in python modules
+ * @param py
+ * @param error to raise
+ * @return the python error
+ */
+fn to_py_err(py: Python, error: RoriError) -> PyErr {
+    PyErr::new::<exc::RuntimeError, _>(py, error.to_string())
+}
+
+/**
+ * @return the name of the module executed by this thread
+ */
+fn current_module() -> String {
+    CURRENT_MODULE.with(|current| current.borrow().clone())
+}
+
@@ -0,0 +1,80 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use dbus::{Connection, BusType, Message};
+use dbus::arg::Dict;
+use rori::error::{RoriError, RoriResult};
+use rori::history;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+lazy_static! {
+    // Id of the account used to send messages, set by the Server
+    static ref ACCOUNT: Mutex<String> = Mutex::new(String::new());
+}
+
+/**
+ * Set the account used to send messages
+ * @param id of the account
+ */
+pub fn set_account(id: &String) {
+    *ACCOUNT.lock().unwrap() = id.clone();
+}
+
+/**
+ * @return the id of the account used to send messages, empty if not configured
+ */
+pub fn account() -> String {
+    ACCOUNT.lock().unwrap().clone()
+}
+
+/**
+ * Send a new message from the account via the daemon and store it in the history
+ * @param destination ring_id of the destination
+ * @param body text to send
+ * @param datatype of the message
+ * @return the interaction id or the transport error. TODO, watch message status (if received)
+ */
+pub fn send(destination: &str, body: &str, datatype: &str) -> RoriResult<u64> {
+    let from = account();
+    if from.is_empty() {
+        return Err(RoriError::NotFound(String::from("account")));
+    }
+    let mut payloads: HashMap<&str, &str> = HashMap::new();
+    payloads.insert(datatype, body);
+    let payloads = Dict::new(payloads.iter());
+
+    let dbus_msg = Message::new_method_call("cx.ring.Ring", "/cx/ring/Ring/ConfigurationManager",
+                                            "cx.ring.Ring.ConfigurationManager", "sendTextMessage")
+                                            .map_err(RoriError::Transport)?;
+    let dbus = Connection::get_private(BusType::Session)?;
+    let response = dbus.send_with_reply_and_block(dbus_msg.append3(&*from, destination, payloads), 2000)?;
+    // sendTextMessage returns one argument, which is a u64.
+    let interaction_id = response.get1().unwrap_or(0);
+    history::record_outgoing(&String::from(destination), datatype, body);
+    Ok(interaction_id)
+}
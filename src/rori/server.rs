@@ -26,13 +26,27 @@
  **/
 
 use dbus::{Connection, BusType, Message};
-use dbus::arg::Dict;
 use rori::account::Account;
+use rori::capabilities;
+use rori::command::{self, Command, Order, Parsed, Scope};
 use rori::database::Database;
+use rori::emotions::Emotions;
+use rori::error::{RoriError, RoriResult};
+use rori::group;
+use rori::history;
 use rori::interaction::Interaction;
-use rori::modulemanager::ModuleManager;
+use rori::lang;
+use rori::link::{self, LinkPolicy, LinkRequest};
+use rori::modulemanager::{Fallback, ModuleManager};
+use rori::normalizer::Normalizer;
+use rori::presence;
+use rori::reply::{self, ErrorCode, Reply, SubAuthorMapping};
+use rori::repository::{DeviceRecord, Repository, Role};
+use rori::routing;
+use rori::sender;
 use rori::user::{Device, User};
-use std::collections::HashMap;
+use serde_json;
+use time;
 
 /**
  * Core class.
@@ -42,11 +56,18 @@ pub struct Server {
     pub registered_users: Vec<User>,
     pub anonymous_user: User,
     pub account: Account,
+    pub fallback: Fallback,
+    pub normalizer: Normalizer,
+    pub repository: Box<dyn Repository + Send>,
+    // Ring id of the device of the first admin (from config.json)
+    pub admin_device: String,
+    pub link_policy: LinkPolicy,
 
     ring_dbus: &'static str,
     configuration_path: &'static str,
     configuration_iface: &'static str,
-    id_to_account_linker: Vec<(String, String, bool, String)>
+    presence_path: &'static str,
+    presence_iface: &'static str,
 }
 
 impl Server {
@@ -54,25 +75,42 @@ impl Server {
      * Generate a new Server with no devices. Devices must be loaded with load_devices()
      */
     pub fn new(account: Account) -> Server {
+        sender::set_account(&account.id);
         Server {
             registered_users: Vec::new(),
             anonymous_user: User::new(),
             account: account,
+            fallback: Fallback::Nothing,
+            normalizer: Normalizer::new(),
+            repository: Box::new(Database),
+            admin_device: String::new(),
+            link_policy: LinkPolicy::new(),
 
             ring_dbus: "cx.ring.Ring",
             configuration_path: "/cx/ring/Ring/ConfigurationManager",
             configuration_iface: "cx.ring.Ring.ConfigurationManager",
-            id_to_account_linker: Vec::new()
+            presence_path: "/cx/ring/Ring/PresenceManager",
+            presence_iface: "cx.ring.Ring.PresenceManager",
         }
     }
 
+    /**
+     * Change the account of RORI, also used by modules to send messages
+     * @param self
+     * @param account
+     */
+    pub fn set_account(&mut self, account: Account) {
+        sender::set_account(&account.id);
+        self.account = account;
+    }
+
     /**
      * Add new device for the anonymous user
      * @param self
      * @param ring_id device to add
      */
     pub fn add_new_anonymous_device(&mut self, ring_id: &String) -> bool {
-        let insert_into_db = Database::insert_new_device(ring_id, &String::new(), &String::new(), false);
+        let insert_into_db = self.repository.insert_new_device(ring_id, &String::new(), &String::new(), false);
         match insert_into_db {
             Ok(i) => {
                 self.anonymous_user.devices.push(Device::new(&(i as i32), &ring_id));
@@ -87,7 +125,23 @@ impl Server {
     }
 
     /**
-     * Retrieve a ring_id for a given username or devicename
+     * Set the device of the first admin. The role is given to the user of this device when it is
+     * registered and if there is no admin yet, so the device gets the role back after an /unregister
+     * @param self
+     * @param hash ring_id of the device
+     * @return an error if the database fails
+     */
+    pub fn set_admin(&mut self, hash: &String) -> RoriResult<()> {
+        self.admin_device = hash.clone();
+        if !hash.is_empty() && self.repository.bootstrap_admin(hash)? > 0 {
+            info!("The user of {} is now admin", hash);
+        }
+        Ok(())
+    }
+
+    /**
+     * Retrieve a ring_id for a given username or devicename. Groups are not resolved, they
+     * reach every member through group::resolve
      * @param self
      * @param name username or devicename to find
      * @return the ring_id if found, else an empty String
@@ -117,10 +171,22 @@ impl Server {
      * @param interaction to process
      */
     pub fn handle_interaction(&mut self, interaction: Interaction) {
+        if let Err(e) = self.process_interaction(interaction) {
+            error!("interaction not handled: {}", e);
+        }
+    }
+
+    /**
+     * Process an interaction from a known or unknown device
+     * @param self
+     * @param interaction to process
+     * @return an error if the database or the daemon fails
+     */
+    fn process_interaction(&mut self, interaction: Interaction) -> RoriResult<()> {
         // Find linked device
         let mut username = String::new();
         let hash = interaction.device_author.ring_id.clone();
-        let is_bridge = Database::is_bridge(&hash);
+        let is_bridge = self.repository.is_bridge(&hash)?;
         let mut sub_author = String::new();
         if !is_bridge {
             let mut user_found = false;
@@ -144,7 +210,9 @@ impl Server {
             }
             if !user_found {
                 // User not found add it
-                self.add_contact(&*self.account.id, &*hash);
+                if let Err(e) = self.add_contact(&*self.account.id, &*hash) {
+                    warn!("cannot add contact {}: {}", hash, e);
+                }
                 self.add_new_anonymous_device(&hash);
             }
         } else {
@@ -153,97 +221,106 @@ impl Server {
                 None => String::new()
             };
             if sub_author.len() > 0 {
-                username = Database::sub_author(&hash, &sub_author);
+                username = self.repository.sub_author(&hash, &sub_author)?;
             }
         }
 
-        let tuple = Database::get_device(&hash, &username);
+        history::record(&username, &hash, false, &*interaction.datatype, &*interaction.body,
+                        interaction.time.to_timespec().sec);
+        presence::seen(&hash);
+
         let mut new_interaction = interaction.clone();
-        new_interaction.device_author = Device {
-            id: tuple.0,
-            name: tuple.3,
-            ring_id: tuple.1,
-            is_bridge: tuple.4 == 1
+        new_interaction.device_author = match self.repository.get_device(&hash, &username)? {
+            Some(device) => Device {
+                id: device.id,
+                name: device.devicename,
+                ring_id: device.hash,
+                is_bridge: device.is_bridge
+            },
+            None => Device::new(&-1, &String::new())
         };
+        if interaction.datatype == capabilities::DATATYPE {
+            // Announcements are for the core, not for modules
+            return self.try_announce(&hash, &new_interaction.device_author.id, &interaction.body);
+        }
+        new_interaction.normalized_body = self.normalizer.normalize(&interaction.body);
 
-        // TODO should be handle by a module
+        let mut unknown_command = None;
         if interaction.datatype == "rori/command" {
-            if username.len() == 0 {
-                // Anonymous to user
-                if interaction.body.starts_with("/register") {
-                    let split: Vec<&str> = interaction.body.split(' ').collect();
-                    if split.len() < 2 {
-                        warn!("register received, but no username detected");
-                        return;
+            match command::parse(&interaction.body) {
+                Parsed::Valid(command, order) => {
+                    if self.check_command(&hash, &username, command)? {
+                        let forget = order == Order::ForgetMe;
+                        self.execute(&hash, &username, &sub_author, order)?;
+                        if forget {
+                            // User is forgotten, nothing more to do with this interaction
+                            return Ok(());
+                        }
                     }
-                    self.try_register_username(&hash,
-                                               &String::from(*split.get(1).unwrap()), &sub_author);
-                }
+                },
+                Parsed::Malformed(command) => {
+                    warn!("{} received with bad arguments: {}", command.name, interaction.body);
+                    self.reply(&*hash, Reply::error(ErrorCode::Usage, &*format!("usage: {}", command.full_usage())))?;
+                },
+                // Maybe for a module, answered if no module handles it
+                Parsed::Unknown(name) => unknown_command = Some(name),
+            }
+        }
+
+        new_interaction.lang = lang::resolve(&username, &new_interaction);
+        if username.len() > 0 {
+            // Only messages from known users change RORI's emotions
+            new_interaction.emotions = if interaction.datatype == "text/plain" {
+                Emotions::update(&username, &new_interaction)?
             } else {
-                if interaction.body.starts_with("/add_device") {
-                    // User wants to register a device
-                    // /add_device name (ring_id)
-                   let split: Vec<&str> = interaction.body.split(' ').collect();
-                   if split.len() < 2 {
-                       warn!("add_device received, but no device detected");
-                       return;
-                   }
-                   let mut device_to_add = hash.clone();
-                   if split.len() == 3 && split.last().unwrap_or(&"").len() > 0 {
-                       device_to_add = split.last().unwrap_or(&"").to_string();
-                   }
-                   self.try_register_device(&hash, &device_to_add, &username,
-                                            &String::from(*split.get(1).unwrap()));
-                } else if interaction.body.starts_with("/rm_device") {
-                    // User wants to revoke a device
-                    // /rm_device name (ring_id)
-                    let mut device_to_remove = hash.clone();
-                    let split: Vec<&str> = interaction.body.split(' ').collect();
-                    if split.len() == 2 && split.last().unwrap_or(&"").len() > 0 {
-                        device_to_remove = split.last().unwrap_or(&"").to_string();
-                    }
-                    self.try_remove_device(&hash, &device_to_remove, &username);
-                } else if interaction.body.starts_with("/unregister") {
-                    // User wants to unregister
-                    self.try_unregister(&hash, &username);
-                }
+                Emotions::for_user(&username)?
+            };
+        }
+
+        let mm = ModuleManager::new(new_interaction, &username);
+        let matched = mm.process();
+        if let Some(name) = unknown_command {
+            if !matched {
+                self.reply(&*hash, Reply::error(ErrorCode::UnknownCommand, &*format!("unknown command {}, see /help", name)))?;
+            }
+        }
+        if !matched && mm.interaction.datatype == "text/plain" {
+            // Remember it to know which modules are missing
+            if let Err(e) = self.repository.add_unmatched(&username, &mm.interaction) {
+                error!("Can't remember unmatched interaction: {}", e);
             }
+            if let Some(answer) = mm.fallback(&self.fallback) {
+                self.send_interaction(&*hash, &*answer, "text/plain");
+            }
+        }
+        Ok(())
+    }
 
-            let device = Database::get_device(&hash, &username);
-
-            if interaction.body.starts_with("/add_types") {
-                // Handle add_type
-                let mut split: Vec<&str> = interaction.body.split(' ').collect();
-                split.remove(0);
-                self.add_datatypes(&device.0, split);
-            } else if interaction.body.starts_with("/bridgify") {
-                // Handle add_type
-                self.bridgify(&device.0);
-            } else if interaction.body.starts_with("/rm_types") {
-                // Handle rm_type
-                let mut split: Vec<&str> = interaction.body.split(' ').collect();
-                split.remove(0);
-                self.rm_datatypes(&device.0, split);
-            } else if interaction.body.starts_with("/set_types") {
-                // Handle set_type
-                let mut split: Vec<&str> = interaction.body.split(' ').collect();
-                split.remove(0);
-                self.set_datatypes(&device.0, split);
-            } else if interaction.body.starts_with("/link") {
-                // Handle multi-devices
-                let split: Vec<&str> = interaction.body.split(' ').collect();
-                if split.len() < 2 {
-                    warn!("link received, but no argument detected");
-                    return;
+    /**
+     * Send a message to the best device of an user (see routing::route), or of each member of a group.
+     * The next device is tried if the daemon can't send it
+     * @param self
+     * @param username destination (an user or a group)
+     * @param body to send
+     * @param datatype of the message
+     * @return the ring_ids of the devices which received the message (one per user)
+     */
+    pub fn send_to_user(&self, username: &String, body: &str, datatype: &String) -> RoriResult<Vec<String>> {
+        let mut received = Vec::new();
+        for member in group::resolve(username)? {
+            let mut sent = false;
+            for route in routing::route(&member, datatype)? {
+                if self.send_interaction(&*route.device.hash, body, datatype) != 0 {
+                    received.push(route.device.hash);
+                    sent = true;
+                    break;
                 }
-                self.try_link_new_device(&hash,
-                                         &String::from(*split.get(1).unwrap()), &username, &sub_author);
             }
-
+            if !sent {
+                warn!("no device of {} can receive {}", member, datatype);
+            }
         }
-
-        let mm = ModuleManager::new(new_interaction);
-        mm.process();
+        Ok(received)
     }
 
     /**
@@ -252,16 +329,17 @@ impl Server {
      * @param self
      * @param devices to process
      */
-    pub fn load_devices(&mut self, devices: Vec<(i32, String, String, String, bool)>) {
-        for (id, hash, username, devicename, _is_bridge) in devices {
+    pub fn load_devices(&mut self, devices: Vec<DeviceRecord>) {
+        for record in devices {
+            let username = record.username;
             if username == "" {
                 // it's an anon user.
-                self.anonymous_user.devices.push(Device::new(&id, &hash));
-                info!("new anonymous user: {}", hash);
+                self.anonymous_user.devices.push(Device::new(&record.id, &record.hash));
+                info!("new anonymous user: {}", record.hash);
             } else {
                 let mut already_present = false;
-                let mut device = Device::new(&id, &hash);
-                device.name = devicename;
+                let mut device = Device::new(&record.id, &record.hash);
+                device.name = record.devicename;
                 // Add a device to a known User
                 for registered in &mut self.registered_users {
                     info!("update account {} with device {} ({})", registered.name, device.name, device.ring_id);
@@ -285,6 +363,56 @@ impl Server {
         }
     }
 
+    /**
+     * Unlink a sub author from its user. The user is removed if it has no device anymore
+     * and the bridge is informed
+     * @param self
+     * @param bridge hash of the bridge
+     * @param sub_author to unlink
+     * @return the username which was linked, None if the sub author is unknown
+     */
+    pub fn remove_sub_author(&mut self, bridge: &String, sub_author: &String) -> RoriResult<Option<String>> {
+        let username = self.repository.sub_author(bridge, sub_author)?;
+        if username.is_empty() {
+            return Ok(None);
+        }
+        let device = match self.repository.get_device(bridge, &username)? {
+            Some(device) => device,
+            None => return Ok(None)
+        };
+        self.repository.remove_device(&device.id)?;
+        for registered in &mut self.registered_users {
+            registered.devices.retain(|d| d.id != device.id);
+        }
+        self.registered_users.retain(|u| !u.devices.is_empty());
+        info!("{} ({}) unlinked from {}", sub_author, username, bridge);
+        self.reply(bridge, Reply::Registered { registered: false, username: username.clone(), sa: sub_author.clone() })?;
+        Ok(Some(username))
+    }
+
+    /**
+     * Remove all data of an user. Devices become anonymous, except bridges
+     * @param self
+     * @param username to forget
+     * @return the number of removed or anonymized rows
+     */
+    pub fn purge_user(&mut self, username: &String) -> RoriResult<usize> {
+        let mut anonymized = Vec::new();
+        if let Some(registered) = self.registered_users.iter().find(|u| u.name == *username) {
+            for device in &registered.devices {
+                // NOTE: bridges are already in anonymous_user
+                if !self.repository.is_bridge(&device.ring_id)? {
+                    anonymized.push(Device::new(&device.id, &device.ring_id));
+                }
+            }
+        }
+        let result = self.repository.purge_user(username)?;
+        self.registered_users.retain(|u| u.name != *username);
+        self.anonymous_user.devices.append(&mut anonymized);
+        info!("{} forgotten ({} rows removed or anonymized)", username, result);
+        Ok(result)
+    }
+
 
 // Private stuff
 
@@ -293,47 +421,68 @@ impl Server {
      * @param self
      * @param account_id the account who accepts the request
      * @param from the contact to accept
+     * @return an error if the daemon can't be reached
      */
-    fn add_contact(&self, account_id: &str, from: &str) {
+    fn add_contact(&self, account_id: &str, from: &str) -> RoriResult<()> {
         let method = "addContact";
         let dbus_msg = Message::new_method_call(self.ring_dbus, self.configuration_path, self.configuration_iface,
-                                                method).ok().expect("method call fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("connection not ok.");
-        let _ = dbus.send_with_reply_and_block(
-                    dbus_msg.append2(account_id, from), 2000
-                ).unwrap();
+                                                method).map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        dbus.send_with_reply_and_block(dbus_msg.append2(account_id, from), 2000)?;
+        Ok(())
+    }
+
+    /**
+     * Ask the daemon to notify the presence of a device (newBuddyNotification)
+     * @param self
+     * @param hash of the device
+     * @return an error if the daemon fails
+     */
+    pub fn subscribe_presence(&self, hash: &str) -> RoriResult<()> {
+        let dbus_msg = Message::new_method_call(self.ring_dbus, self.presence_path, self.presence_iface,
+                                                "subscribeBuddy").map_err(RoriError::Transport)?;
+        let dbus = Connection::get_private(BusType::Session)?;
+        dbus.send_with_reply_and_block(dbus_msg.append3(&*self.account.id, hash, true), 2000)?;
+        Ok(())
     }
 
     /**
      * Add some datatypes of a device
      * @param device_id
      * @param add_types to add
+     * @return an error if the database fails
      */
-    fn add_datatypes(&self, device_id: &i32, add_types: Vec<&str>) {
-        let mut current_datatypes = Database::get_datatypes(&device_id);
+    fn add_datatypes(&self, device_id: &i32, add_types: Vec<&str>) -> RoriResult<()> {
+        let mut current_datatypes = self.repository.get_datatypes(&device_id)?;
         for dtype in add_types.into_iter() {
             match current_datatypes.iter().position(|dt| dt == dtype) {
                 Some(_) => {},
                 None => current_datatypes.push(String::from(dtype))
             }
         }
-        let _ = Database::set_datatypes(&device_id, current_datatypes);
+        self.repository.set_datatypes(&device_id, current_datatypes)?;
+        Ok(())
     }
 
     /**
      * Change a device to a bridge
      * @param device_id
+     * @return an error if the device is not anonymous or if the database fails
      */
-    fn bridgify(&mut self, device_id: &i32) {
-        // Update database
-        let _ = Database::bridgify(&device_id);
+    fn bridgify(&mut self, device_id: &i32) -> RoriResult<()> {
         // Update anonymous user
-        let index = self.anonymous_user.devices.iter().position(|d| d.id == *device_id).unwrap();
+        let index = match self.anonymous_user.devices.iter().position(|d| d.id == *device_id) {
+            Some(index) => index,
+            None => return Err(RoriError::NotFound(format!("anonymous device {}", device_id)))
+        };
+        // Update database
+        self.repository.bridgify(&device_id)?;
         let mut new_device = self.anonymous_user.devices.get(index).unwrap().clone();
         new_device.is_bridge = true;
         self.anonymous_user.devices.remove(index);
         self.anonymous_user.devices.push(new_device);
         info!("Device {} is now a bridge", &device_id);
+        Ok(())
     }
 
     /**
@@ -341,24 +490,19 @@ impl Server {
      * @param self
      * @param hash to move (must be in anonymous)
      * @param username new device username
+     * @return an error if the device can't be moved
      */
-    fn move_ring_to_user(&mut self, hash: &String, username: &String) {
-        let is_bridge = Database::is_bridge(hash);
+    fn move_ring_to_user(&mut self, hash: &String, username: &String) -> RoriResult<()> {
+        let is_bridge = self.repository.is_bridge(hash)?;
         let did : i32;
         if is_bridge {
-            let insert_into_db = Database::insert_new_device(hash, username, &String::new(), true);
-            match insert_into_db {
-                Ok(i) => {
-                    did = i as i32;
-                }
-                _ => {
-                    error!("move_ring_to_user failed");
-                    return;
-                }
-            }
+            did = self.repository.insert_new_device(hash, username, &String::new(), true)? as i32;
         } else {
             // Remove from anonymous_user
-            let index = self.anonymous_user.devices.iter().position(|d| d.ring_id == *hash).unwrap();
+            let index = match self.anonymous_user.devices.iter().position(|d| d.ring_id == *hash) {
+                Some(index) => index,
+                None => return Err(RoriError::NotFound(format!("anonymous device {}", hash)))
+            };
             did = self.anonymous_user.devices.get(index).unwrap().id;
             self.anonymous_user.devices.remove(index);
         }
@@ -369,16 +513,25 @@ impl Server {
             }
         }
         // Update database
-        let _ = Database::update_username(&did, username);
+        self.repository.update_username(&did, username)?;
+        if *hash == self.admin_device {
+            let admin_device = self.admin_device.clone();
+            self.set_admin(&admin_device)?;
+        }
+        if let Err(e) = self.subscribe_presence(hash) {
+            warn!("can't subscribe to the presence of {}: {}", hash, e);
+        }
+        Ok(())
     }
 
     /**
      * Remove some datatypes of a device
      * @param id
      * @param add_types to remove
+     * @return an error if the database fails
      */
-    fn rm_datatypes(&self, id: &i32, add_types: Vec<&str>) {
-        let mut current_datatypes = Database::get_datatypes(id);
+    fn rm_datatypes(&self, id: &i32, add_types: Vec<&str>) -> RoriResult<()> {
+        let mut current_datatypes = self.repository.get_datatypes(id)?;
         for dtype in add_types.into_iter() {
             match current_datatypes.iter().position(|dt| dt == dtype) {
                 Some(p) => {
@@ -387,90 +540,122 @@ impl Server {
                 None => {}
             }
         }
-        let _ = Database::set_datatypes(id, current_datatypes);
+        self.repository.set_datatypes(id, current_datatypes)?;
+        Ok(())
     }
 
     /**
-     * Handle link orders.
+     * Handle link orders. Both devices must ask before the request expires.
+     * If codes are enabled, RORI sends a code to a device of the user and the new device must echo it
      * @param self
      * @param from_id the sender of the order
      * @param argument the ring_id if registered, the username if not
+     * @param code echoed by the new device (empty if none)
      * @param username the username if bridge
      * @param sub_author the sub_author if bridge
+     * @return an error if the database fails
      */
-    fn try_link_new_device(&mut self, from_id: &String, argument: &String, username: &String, sub_author: &String) {
+    fn try_link_new_device(&mut self, from_id: &String, argument: &String, code: &str, username: &String, sub_author: &String) -> RoriResult<()> {
         // Retrieve users from database
-        let id = self.account.id.clone();
-        let is_bridge = Database::is_bridge(&from_id);
+        let is_bridge = self.repository.is_bridge(&from_id)?;
         let hash : String;
         let from_user : String;
-        if is_bridge {
-            let tuple = Database::get_device(&from_id, username);
-            hash = tuple.1;
-            from_user = tuple.2;
+        let device = if is_bridge {
+            self.repository.get_device(&from_id, username)?
         } else {
-            let tuple = Database::get_devices_for_hash(&from_id).get(0).unwrap().clone();
-            hash = tuple.1;
-            from_user = tuple.2;
-        }
-
-        // TODO inform user that a new device is linked
-        let mut do_push = true; // if we must store a temporary item to remember the order
-        let mut do_clean = false; // user linked, the temporary item is not necessary anymore
-        let linked_id : String;
-        let linked_user : String;
-        if from_user.len() == 0 {
-            linked_id = hash;
-            linked_user = argument.clone();
-
-            // unknown want to be connected as user.
-            for (hash, account, authentified, sub_author) in self.id_to_account_linker.clone() {
-                if hash == *from_id && account == *argument {
-                    do_push = false; // already here
-                    if authentified {
-                        // Do link and inform users
-                        do_clean = true;
-                        let msg = format!("{} linked to {}", from_id, argument);
-                        info!("{}", msg);
-                        self.move_ring_to_user(&hash, &account);
-                        let _ = Database::update_sub_author(&Database::get_device(&hash, argument).0, &sub_author);
-                        self.send_interaction(&*id, &*hash, &*format!("{{\"registered\":true, \"username\":\"{}\", \"sa\":\"{}\"}}", argument, sub_author), "rori/message");
-                        break;
-                    }
-                }
-            }
+            self.repository.get_devices_for_hash(&from_id)?.into_iter().next()
+        };
+        match device {
+            Some(device) => {
+                hash = device.hash;
+                from_user = device.username;
+            },
+            None => return Err(RoriError::NotFound(format!("device {}", from_id)))
+        }
+
+        let now = time::get_time().sec;
+        self.repository.rm_expired_link_requests(now)?;
+        // unknown wants to be connected as user, or known user wants a new device
+        let new_device = from_user.is_empty();
+        let (linked_id, linked_user) = if new_device {
+            (hash.clone(), argument.clone())
         } else {
-            linked_id = argument.clone();
-            linked_user = from_user.clone();
-            // known user want a new device
-            for (hash, account, authentified, sub_author) in self.id_to_account_linker.clone() {
-                if hash == *argument && account == from_user {
-                    do_push = false; // already here
-                    if !authentified {
-                        // Do link
-                        do_clean = true;
-                        let msg = format!("{} linked to {}", argument, from_user);
-                        info!("{}", msg);
-                        self.move_ring_to_user(&hash, &account);
-                        let _ = Database::update_sub_author(&Database::get_device(&hash, &from_user).0, &sub_author);
-                        self.send_interaction(&*id, &*hash, &*format!("{{\"registered\":true, \"username\":\"{}\", \"sa\":\"{}\"}}", from_user, sub_author), "rori/message");
-                        break;
-                    }
-                }
+            (argument.clone(), from_user.clone())
+        };
+        let pending = self.repository.get_link_request(&linked_id, &linked_user, now)?;
+
+        if new_device && !code.is_empty() {
+            // The new device echoes the code. A code can only be tried once
+            match pending {
+                Some(ref request) if !request.code.is_empty() && request.code == code => {
+                    self.repository.rm_link_request(&request.id)?;
+                    return self.link(&linked_id, &linked_user, sub_author);
+                },
+                Some(ref request) => {
+                    self.repository.rm_link_request(&request.id)?;
+                },
+                None => {}
             }
+            warn!("{} sent an invalid code to be linked to {}", linked_id, linked_user);
+            self.reply(&*hash, Reply::error(ErrorCode::InvalidCode, &*format!("invalid code for {}", linked_user)))?;
+            return Ok(());
         }
-        if do_push {
-            // Remember this order and wait for the order of the other device
-            info!("{} wants to be linked to {}", linked_id, linked_user);
-            self.id_to_account_linker.push((linked_id.clone(), linked_user.clone(), from_user.len() != 0, sub_author.clone()));
+
+        if !self.link_policy.code {
+            if let Some(ref request) = pending {
+                if request.from_user == new_device {
+                    // The other device already asked, do link
+                    self.repository.rm_link_request(&request.id)?;
+                    let sub_author = if new_device { sub_author.clone() } else { request.sub_author.clone() };
+                    return self.link(&linked_id, &linked_user, &sub_author);
+                }
+            }
         }
-        if do_clean {
-            // Already linked, clean id_to_account_linker
-            if let Some(index) = self.id_to_account_linker.iter()
-                                .position(|i| i.0 == *linked_id && i.1 == *linked_user) {
-                self.id_to_account_linker.remove(index);
+
+        // Remember this order and wait for the other device (or the code)
+        info!("{} wants to be linked to {}", linked_id, linked_user);
+        let code = if self.link_policy.code { link::generate_code()? } else { String::new() };
+        self.repository.add_link_request(&LinkRequest {
+            id: 0,
+            hash: linked_id.clone(),
+            username: linked_user.clone(),
+            from_user: !new_device,
+            sub_author: if new_device { sub_author.clone() } else { pending.map_or(String::new(), |r| r.sub_author) },
+            code: code.clone(),
+            expires: now + self.link_policy.ttl,
+        })?;
+        if !code.is_empty() {
+            // Send the code to the device of the user who asks, or to all devices of the user
+            let destinations: Vec<String> = if new_device {
+                self.repository.get_devices_for_username(&*linked_user)?.into_iter()
+                    .filter(|device| !device.is_bridge).map(|device| device.hash).collect()
+            } else {
+                vec![hash.clone()]
+            };
+            let answer = Reply::LinkCode { link_code: code, device: linked_id, ttl: self.link_policy.ttl };
+            for destination in destinations {
+                self.reply(&*destination, answer.clone())?;
             }
         }
+        Ok(())
+    }
+
+    /**
+     * Link a device to an user and inform the device
+     * @param self
+     * @param hash of the device
+     * @param username to link
+     * @param sub_author of the device if bridge
+     * @return an error if the database fails
+     */
+    fn link(&mut self, hash: &String, username: &String, sub_author: &String) -> RoriResult<()> {
+        info!("{} linked to {}", hash, username);
+        self.move_ring_to_user(hash, username)?;
+        if let Some(device) = self.repository.get_device(hash, username)? {
+            self.repository.update_sub_author(&device.id, sub_author)?;
+        }
+        self.reply(&*hash, Reply::Registered { registered: true, username: username.clone(), sa: sub_author.clone() })?;
+        Ok(())
     }
 
     /**
@@ -480,35 +665,38 @@ impl Server {
      * @param ring_id to register
      * @param devicename new devicename
      * @param sub_author if bridge
+     * @return an error if the database fails
      */
-    fn try_register_device(&mut self, from_id: &String, ring_id: &String, username: &String, devicename: &String) {
-        let id = self.account.id.clone();
-        let (_, from_id, from_user, _, _) = Database::get_device(from_id, username);
-        let (_, mod_id, mod_user, _, _) = Database::get_device(ring_id, username);
+    fn try_register_device(&mut self, from_id: &String, ring_id: &String, username: &String, devicename: &String) -> RoriResult<()> {
+        let from_id = match self.repository.get_device(from_id, username)? {
+            Some(device) => device.hash,
+            None => return Err(RoriError::NotFound(format!("device {}", from_id)))
+        };
         // Check if devices are for the same user
-        if from_user != &*mod_user {
-            let err = format!("!!!!!{} trying to register device with different user ({}) ", from_id, mod_id);
-            warn!("{}", err);
-            self.send_interaction(&*id, &*from_id, &*format!("{{\"dregistered\":false, \"username\":\"{}\", \"err\":\"bad_register error from device {}\"}}", username, from_id), "rori/message");
-            self.send_interaction(&*id, &*mod_id, &*format!("{{\"dregistered\":false, \"username\":\"{}\", \"err\":\"bad_register error from device {}\"}}", username, from_id), "rori/message");
-            return;
-        }
+        let device = match self.repository.get_device(ring_id, username)? {
+            Some(device) => device,
+            None => {
+                let err = format!("!!!!!{} trying to register device with different user ({}) ", from_id, ring_id);
+                warn!("{}", err);
+                let answer = Reply::error(ErrorCode::BadDevice, &*format!("{} is not a device of {}", ring_id, username));
+                self.reply(&*from_id, answer.clone())?;
+                self.reply(ring_id, answer)?;
+                return Ok(());
+            }
+        };
         // Search if it's already registered
         if self.get_hash(&format!("{}_{}", username, devicename)).len() > 0 {
             let err = format!("registering {} for {} failed because devicename was found", devicename, ring_id);
             warn!("{}", err);
-            self.send_interaction(&*id, ring_id, &*format!("{{\"dregistered\":false, \"devicename\":\"{}\", \"err\":\"{}_{} already registered\"}}", devicename, username, devicename), "rori/message");
+            self.reply(ring_id, Reply::error(ErrorCode::AlreadyRegistered, &*format!("{}_{} already registered", username, devicename)))?;
         } else {
-            // search device to modify
-            let device = Database::get_device(ring_id, username);
             // register device
-            Database::update_devicename(&device.0, devicename)
-            .ok().expect(&*format!("registering {}_{} for device {} failed when updating db", username, devicename, device.0));
+            self.repository.update_devicename(&device.id, devicename)?;
             // Update device for user
             for registered in &mut self.registered_users {
                 if registered.name == *username {
                     for d in &mut registered.devices {
-                        if device.0 == d.id {
+                        if device.id == d.id {
                             d.name = devicename.clone();
                             break;
                         }
@@ -516,10 +704,11 @@ impl Server {
                 }
             }
             // And inform user
-            let msg = format!("Device {} is now known as {}_{}", device.0.to_string(), username, devicename);
+            let msg = format!("Device {} is now known as {}_{}", device.id.to_string(), username, devicename);
             info!("{}", msg);
-            self.send_interaction(&*id, &*from_id, &*format!("{{\"dregistered\":true, \"devicename\":\"{}\"}}", devicename), "rori/message");
+            self.reply(&*from_id, Reply::DeviceRegistered { dregistered: true, devicename: devicename.clone() })?;
         }
+        Ok(())
     }
 
     /**
@@ -528,54 +717,55 @@ impl Server {
      * @param hash to register
      * @param username new username
      * @param sub_author if bridge
+     * @return an error if the database fails
      */
-    fn try_register_username(&mut self, hash: &String, username: &String, sub_author: &String) {
+    fn try_register_username(&mut self, hash: &String, username: &String, sub_author: &String) -> RoriResult<()> {
 
-        let id = self.account.id.clone();
-        let already_taken = self.get_hash(username).len() > 0;
+        // NOTE: users without devices keep their name and their data until they are purged
+        let already_taken = self.get_hash(username).len() > 0 || self.repository.get_user(username)?.is_some()
+                            || self.repository.get_group(username)?.is_some();
         if already_taken {
             let err = format!("registering {} for {} failed because username was found", username, hash);
             warn!("{}", err);
-            self.send_interaction(&*id, hash, &*format!("{{\"registered\":false, \"username\":\"{}\", \"err\":\"{} already registered\"}}", username, username), "rori/message");
+            self.reply(hash, Reply::error(ErrorCode::AlreadyRegistered, &*format!("{} already registered", username)))?;
         } else {
             // Register!
-            let is_bridge = Database::is_bridge(hash);
+            let is_bridge = self.repository.is_bridge(hash)?;
             if is_bridge {
                 // Add a new device for user
                 // NOTE: do not remove from anonymouses, it's a bridge!
-                let insert_into_db = Database::insert_new_device(hash, username, &String::new(), true);
-                match insert_into_db {
-                    Ok(i) => {
-                        let mut new_user = User::new();
-                        new_user.name = username.clone();
-                        new_user.devices.push(Device::new(&(i as i32), hash));
-                        self.registered_users.push(new_user);
-                        let _ = Database::update_sub_author(&(i as i32), sub_author);
-                    }
-                    _ => {
-                        error!("try_register_username failed when inserting new device");
-                        return;
-                    }
-                }
+                let i = self.repository.insert_new_device(hash, username, &String::new(), true)?;
+                let mut new_user = User::new();
+                new_user.name = username.clone();
+                new_user.devices.push(Device::new(&(i as i32), hash));
+                self.registered_users.push(new_user);
+                self.repository.update_sub_author(&(i as i32), sub_author)?;
             } else {
                 // Remove from anonymous_user
-                let index = self.anonymous_user.devices.iter().position(|d| d.ring_id == *hash).unwrap();
+                let index = match self.anonymous_user.devices.iter().position(|d| d.ring_id == *hash) {
+                    Some(index) => index,
+                    None => return Err(RoriError::NotFound(format!("anonymous device {}", hash)))
+                };
                 let id = self.anonymous_user.devices.get(index).unwrap().id;
-                self.anonymous_user.devices.remove(index);
                 // Update database
-                Database::update_username(&id, username)
-                .ok().expect(&*format!("registering {} for {} failed when updating db", username, hash));
+                self.repository.update_username(&id, username)?;
+                self.anonymous_user.devices.remove(index);
                 // Create new user
                 let mut new_user = User::new();
                 new_user.name = username.clone();
                 new_user.devices.push(Device::new(&id, hash));
                 self.registered_users.push(new_user);
             }
+            if *hash == self.admin_device {
+                let admin_device = self.admin_device.clone();
+                self.set_admin(&admin_device)?;
+            }
             // Inform user that they is registered.
             let msg = format!("{} is now known as {}", hash, username);
             info!("{}", msg);
-            self.send_interaction(&*id, hash, &*format!("{{\"registered\":true, \"username\":\"{}\", \"sa\":\"{}\"}}", username, sub_author), "rori/message");
+            self.reply(hash, Reply::Registered { registered: true, username: username.clone(), sa: sub_author.clone() })?;
         }
+        Ok(())
     }
 
     /**
@@ -584,18 +774,25 @@ impl Server {
      * @param from_id the device which asks (must be registered)
      * @param ring_id to revoke
      * @param username asking
+     * @return an error if the database fails
      */
-    fn try_remove_device(&mut self, from_id: &String, ring_id: &String, username: &String) {
-        let id = self.account.id.clone();
-        let (_, from_id, from_user, _, _) = Database::get_device(from_id, username);
-        let (mod_device_id, mod_id, mod_user, _, is_bridge) = Database::get_device(ring_id, username);
-        let sub_author = Database::sub_author_id(&mod_id, &username);
+    fn try_remove_device(&mut self, from_id: &String, ring_id: &String, username: &String) -> RoriResult<()> {
+        let from_id = match self.repository.get_device(from_id, username)? {
+            Some(device) => device.hash,
+            None => return Err(RoriError::NotFound(format!("device {}", from_id)))
+        };
         // Test if it's for a same user
-        if from_user != &*mod_user {
-            let err = format!("!!!!!{} trying to revoke device with different user ({}) ", from_id, mod_id);
-            warn!("{}", err);
-            return;
-        }
+        let device = match self.repository.get_device(ring_id, username)? {
+            Some(device) => device,
+            None => {
+                let err = format!("!!!!!{} trying to revoke device with different user ({}) ", from_id, ring_id);
+                warn!("{}", err);
+                return Ok(());
+            }
+        };
+        let mod_device_id = device.id;
+        let is_bridge = device.is_bridge;
+        let sub_author = device.sub_author;
         // Remove the device
         let mut success = false;
         let mut remove_user = false;
@@ -605,11 +802,11 @@ impl Server {
             for device in &mut registered.devices {
                 if device.id == mod_device_id {
                     device.name = String::new();
-                    if is_bridge == 1 {
-                        let _ = Database::remove_device(&mod_device_id);
+                    if is_bridge {
+                        self.repository.remove_device(&mod_device_id)?;
                     } else {
-                        let _ = Database::update_devicename(&mod_device_id, &String::new());
-                        let _ = Database::update_username(&mod_device_id, &String::new());
+                        self.repository.update_devicename(&mod_device_id, &String::new())?;
+                        self.repository.update_username(&mod_device_id, &String::new())?;
                     }
                     success = true;
                     break;
@@ -622,7 +819,7 @@ impl Server {
                 if registered.devices.len() == 0 {
                     remove_user = true;
                 }
-                if is_bridge != 1 {
+                if !is_bridge {
                     self.anonymous_user.devices.push(Device::new(&mod_device_id, ring_id));
                 }
                 break;
@@ -637,10 +834,79 @@ impl Server {
         if success {
             msg = format!("{} device name revoked", ring_id);
             info!("{}", msg);
-            self.send_interaction(&*id, &*ring_id, &*format!("{{\"registered\":false, \"username\":\"{}\", \"sa\":\"{}\"}}", username, sub_author), "rori/message");
+            self.reply(&*ring_id, Reply::Registered { registered: false, username: username.clone(), sa: sub_author })?;
         } else {
             warn!("{}", msg);
         }
+        Ok(())
+    }
+
+    /**
+     * Change the username of an user and inform all its devices
+     * @param self
+     * @param hash of the device which asks
+     * @param username current username
+     * @param new_username
+     * @return an error if the database fails
+     */
+    fn try_rename(&mut self, hash: &String, username: &String, new_username: &String) -> RoriResult<()> {
+        if self.get_hash(new_username).len() > 0 || self.repository.get_user(new_username)?.is_some()
+            || self.repository.get_group(new_username)?.is_some() {
+            warn!("renaming {} failed because {} was found", username, new_username);
+            return self.reply(hash, Reply::error(ErrorCode::AlreadyRegistered, &*format!("{} already registered", new_username)));
+        }
+        match self.repository.rename_user(username, new_username) {
+            Ok(_) => {},
+            Err(RoriError::Vault(e)) => {
+                // Secrets of the user must be encrypted again for the new username
+                warn!("renaming {} failed: {}", username, e);
+                return self.reply(hash, Reply::error(ErrorCode::VaultLocked, &*e));
+            },
+            Err(e) => return Err(e)
+        }
+        for registered in &mut self.registered_users {
+            if registered.name == *username {
+                registered.name = new_username.clone();
+            }
+        }
+        info!("{} is now known as {}", username, new_username);
+        self.reply_to_user(hash, new_username, Reply::Renamed { username: new_username.clone(), previous: username.clone() })
+    }
+
+    /**
+     * Change the name of a device of an user and inform all its devices
+     * @param self
+     * @param hash of the device which asks
+     * @param username owner of the device
+     * @param devicename current name of the device
+     * @param new_devicename
+     * @return an error if the database fails
+     */
+    fn try_rename_device(&mut self, hash: &String, username: &String, devicename: &String, new_devicename: &String) -> RoriResult<()> {
+        let device = match self.repository.get_devices_for_username(&*username)?.into_iter()
+                               .find(|device| !device.devicename.is_empty() && device.devicename == *devicename) {
+            Some(device) => device,
+            None => {
+                warn!("{} has no device named {}", username, devicename);
+                return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("{}_{} not found", username, devicename)));
+            }
+        };
+        if self.get_hash(&format!("{}_{}", username, new_devicename)).len() > 0 {
+            warn!("renaming {} failed because {} was found", devicename, new_devicename);
+            return self.reply(hash, Reply::error(ErrorCode::AlreadyRegistered, &*format!("{}_{} already registered", username, new_devicename)));
+        }
+        self.repository.update_devicename(&device.id, new_devicename)?;
+        for registered in &mut self.registered_users {
+            if registered.name == *username {
+                for d in &mut registered.devices {
+                    if d.id == device.id {
+                        d.name = new_devicename.clone();
+                    }
+                }
+            }
+        }
+        info!("{}_{} is now known as {}_{}", username, devicename, username, new_devicename);
+        self.reply_to_user(hash, username, Reply::DeviceRenamed { devicename: new_devicename.clone(), previous: devicename.clone() })
     }
 
     /**
@@ -648,11 +914,15 @@ impl Server {
      * @param self
      * @param hash of the device to revoke
      * @param username to revoke
+     * @return an error if the database fails
      */
-    fn try_unregister(&mut self, hash: &String, username: &String) {
+    fn try_unregister(&mut self, hash: &String, username: &String) -> RoriResult<()> {
         let mut name = String::new();
-        let is_bridge = Database::is_bridge(hash);
-        let sub_author = Database::sub_author_id(&hash, &username);
+        let is_bridge = self.repository.is_bridge(hash)?;
+        let sub_author = match self.repository.get_device(hash, username)? {
+            Some(device) => device.sub_author,
+            None => String::new()
+        };
         if is_bridge {
             name = username.clone();
         } else {
@@ -670,72 +940,628 @@ impl Server {
             }
             // anonymous
             if name.len() == 0 {
-                return;
+                return Ok(());
             }
         }
-        let id = self.account.id.clone();
 
         let mut idx = 0;
         // Update registered_users and anonymous
         for registered in &mut self.registered_users.clone() {
             if registered.name == &*name {
                 for device in &mut registered.devices {
-                    let is_bridge = Database::is_bridge(&device.ring_id);
+                    let is_bridge = self.repository.is_bridge(&device.ring_id)?;
                     if is_bridge {
-                        let _ = Database::remove_device(&device.id);
+                        self.repository.remove_device(&device.id)?;
                     } else {
-                        let _ = Database::update_username(&device.id, &String::new());
-                        let _ = Database::update_devicename(&device.id, &String::new());
+                        self.repository.update_username(&device.id, &String::new())?;
+                        self.repository.update_devicename(&device.id, &String::new())?;
                         self.anonymous_user.devices.push(Device::new(&device.id, &device.ring_id));
                     }
                     info!("update device {} for {}", device.ring_id, registered.name);
                 }
                 let msg = format!("{} unregistered", registered.name);
                 info!("{}", msg);
-                self.send_interaction(&*id, hash, &*format!("{{\"registered\":false, \"username\":\"{}\"\"sa\":\"{}\"}}", registered.name, sub_author), "rori/message");
+                self.reply(hash, Reply::Registered { registered: false, username: registered.name.clone(), sa: sub_author.clone() })?;
                 break;
             }
             idx += 1;
         }
         self.registered_users.remove(idx);
+        Ok(())
+    }
+
+    /**
+     * Change the language of an user
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the user
+     * @param new_lang language to use
+     * @return an error if the database fails
+     */
+    fn try_set_lang(&self, hash: &String, username: &String, new_lang: &String) -> RoriResult<()> {
+        if !lang::is_supported(&*new_lang) {
+            warn!("{} asks for an unsupported language: {}", username, new_lang);
+            let err = format!("unsupported language {}, supported languages: {}", new_lang, lang::SUPPORTED_LANGS.join(", "));
+            return self.reply(hash, Reply::error(ErrorCode::Usage, &*err));
+        }
+        self.repository.set_lang(username, new_lang)?;
+        info!("{} now speaks {}", username, new_lang);
+        self.reply(hash, Reply::Lang { lang: new_lang.clone() })?;
+        Ok(())
     }
 
     /**
-     * Send a new text message
+     * Check that the author of a command can send it, and inform the device if not
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command (empty if anonymous)
+     * @param command to check
+     * @return if the command can be executed
+     */
+    fn check_command(&self, hash: &String, username: &String, command: &Command) -> RoriResult<bool> {
+        let role = self.role(username)?;
+        let err = match command.scope {
+            Scope::Anonymous if role.is_some() => "only for anonymous devices",
+            Scope::Registered if role.is_none() => "only for registered users",
+            _ => "",
+        };
+        if !err.is_empty() {
+            warn!("{} ({}) can't use {}: {}", username, hash, command.name, err);
+            self.reply(hash, Reply::error(ErrorCode::Scope, &*format!("{} is {}", command.name, err)))?;
+            return Ok(false);
+        }
+        if !role.unwrap_or_default().allows(command.role) {
+            self.deny(hash, username, command.name)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /**
+     * Execute a valid command
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command (empty if anonymous)
+     * @param sub_author the sub_author if bridge
+     * @param order to execute
+     * @return an error if the database or the daemon fails
+     */
+    fn execute(&mut self, hash: &String, username: &String, sub_author: &String, order: Order) -> RoriResult<()> {
+        let device_id = match self.repository.get_device(hash, username)? {
+            Some(device) => device.id,
+            None => -1
+        };
+        match order {
+            Order::Help => {
+                let answer = command::help(self.role(username)?);
+                self.send_interaction(hash, &*answer, "text/plain");
+            },
+            Order::Register { username: new_username } => self.try_register_username(hash, &new_username, sub_author)?,
+            Order::Link { argument, code } => self.try_link_new_device(hash, &argument, &*code, username, sub_author)?,
+            Order::AddDevice { name, ring_id } => {
+                let device_to_add = ring_id.unwrap_or(hash.clone());
+                self.try_register_device(hash, &device_to_add, username, &name)?;
+            },
+            Order::RmDevice { ring_id } => {
+                let device_to_remove = ring_id.unwrap_or(hash.clone());
+                self.try_remove_device(hash, &device_to_remove, username)?;
+            },
+            Order::Unregister => self.try_unregister(hash, username)?,
+            Order::Rename { username: new_username } => self.try_rename(hash, username, &new_username)?,
+            Order::RenameDevice { name, new_name } => self.try_rename_device(hash, username, &name, &new_name)?,
+            Order::Lang { lang } => self.try_set_lang(hash, username, &lang)?,
+            Order::Prefer { device, datatype } => self.try_prefer(hash, username, &device, &datatype)?,
+            Order::RmPrefer { datatype } => {
+                self.repository.rm_preference(username, &datatype)?;
+                self.reply(hash, Reply::Preferred { devicename: String::new(), datatype: datatype })?;
+            },
+            Order::Groups => {
+                let groups = self.repository.get_groups()?.into_iter()
+                    .filter(|g| g.members.contains(username) || &g.owner == username).collect();
+                self.reply(hash, Reply::Groups { groups: groups })?;
+            },
+            Order::AddGroup { name } => self.try_add_group(hash, username, &name)?,
+            Order::RmGroup { name } => {
+                if self.check_group(hash, username, &name, "/rm_group")?.is_some() {
+                    self.repository.rm_group(&name)?;
+                    info!("{} removes group {}", username, name);
+                    self.reply(hash, Reply::GroupRemoved { name: name })?;
+                }
+            },
+            Order::AddMember { group, username: member } => self.try_add_member(hash, username, &group, &member)?,
+            Order::RmMember { group, username: member } => self.try_rm_member(hash, username, &group, &member)?,
+            Order::Export => self.try_export(hash, username)?,
+            Order::ForgetMe => self.try_forget(hash, username)?,
+            Order::History { page, words } => self.try_search_history(hash, username, page, &words)?,
+            Order::SetRole { username: target, role } => self.try_set_role(hash, username, &target, role)?,
+            Order::EnableModule { name, enabled } => self.try_enable_module(hash, username, &name, enabled)?,
+            Order::RmTask { id } => self.try_rm_task(hash, username, &id)?,
+            Order::AddTypes { device, types } => {
+                if let Some(id) = self.types_target(hash, username, &device_id, &device)? {
+                    self.add_datatypes(&id, types.iter().map(|t| &**t).collect())?;
+                }
+            },
+            Order::RmTypes { device, types } => {
+                if let Some(id) = self.types_target(hash, username, &device_id, &device)? {
+                    self.rm_datatypes(&id, types.iter().map(|t| &**t).collect())?;
+                }
+            },
+            Order::SetTypes { device, types } => {
+                if let Some(id) = self.types_target(hash, username, &device_id, &device)? {
+                    self.set_datatypes(&id, types.iter().map(|t| &**t).collect())?;
+                }
+            },
+            Order::Bridgify { ring_id } => {
+                let target = ring_id.unwrap_or(hash.clone());
+                let target_id = match self.anonymous_user.devices.iter().find(|d| d.ring_id == target) {
+                    Some(device) => device.id,
+                    None => return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("anonymous device {} not found", target)))
+                };
+                self.bridgify(&target_id)?;
+                self.repository.set_bridge_owner(&target, username)?;
+            },
+            Order::SubAuthors { bridge } => self.try_list_sub_authors(hash, username, &bridge)?,
+            Order::SubAuthor { bridge, name } => self.try_sub_author(hash, username, &bridge, &name)?,
+            Order::RmSubAuthor { bridge, sub_author } => self.try_rm_sub_author(hash, username, &bridge, &sub_author)?,
+        }
+        Ok(())
+    }
+
+    /**
+     * Store the capabilities announced by a device (see capabilities::parse)
+     * @param self
+     * @param hash of the device
+     * @param device_id id of the device (-1 if unknown)
+     * @param body of the rori/capabilities message
+     * @return an error if the database or the daemon fails
+     */
+    fn try_announce(&self, hash: &String, device_id: &i32, body: &str) -> RoriResult<()> {
+        if *device_id < 0 {
+            return self.reply(hash, Reply::error(ErrorCode::NotFound, "unknown device"));
+        }
+        let capabilities = match capabilities::parse(body) {
+            Ok(capabilities) => capabilities,
+            Err(e) => {
+                warn!("invalid capabilities from {}: {}", hash, e);
+                return self.reply(hash, Reply::error(ErrorCode::Usage, &*e.to_string()));
+            }
+        };
+        self.repository.set_capabilities(device_id, &capabilities)?;
+        info!("{} announces {} actions", hash, capabilities.actions.len());
+        self.reply(hash, Reply::Capabilities {
+            kinds: capabilities.kinds,
+            actions: capabilities.actions.into_iter().map(|action| action.datatype).collect(),
+        })
+    }
+
+    /**
+     * Only admins and the bridge operator who created a bridge can manage its sub authors
+     * @param self
+     * @param username of the operator
+     * @param bridge hash of the bridge
+     * @return if the user can manage the bridge
+     */
+    pub fn can_manage_bridge(&self, username: &String, bridge: &String) -> RoriResult<bool> {
+        if self.role(username)? == Some(Role::Admin) {
+            return Ok(true);
+        }
+        Ok(!username.is_empty() && self.repository.get_bridge_owner(bridge)?.as_ref() == Some(username))
+    }
+
+    /**
+     * Check that a command targets a bridge managed by the author, else answer with an error
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param bridge hash given to the command
+     * @param command name of the command
+     * @return if the author can manage the bridge
+     */
+    fn check_bridge(&self, hash: &String, username: &String, bridge: &String, command: &str) -> RoriResult<bool> {
+        if !self.repository.is_bridge(bridge)? {
+            self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("bridge {} not found", bridge)))?;
+            return Ok(false);
+        }
+        if !self.can_manage_bridge(username, bridge)? {
+            self.deny(hash, username, command)?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /**
+     * Send the sub authors of a bridge
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param bridge hash of the bridge
+     * @return an error if the database fails
+     */
+    fn try_list_sub_authors(&self, hash: &String, username: &String, bridge: &String) -> RoriResult<()> {
+        if !self.check_bridge(hash, username, bridge, "/sub_authors")? {
+            return Ok(());
+        }
+        let sub_authors = self.repository.get_devices_for_hash(&*bridge)?.into_iter()
+            .filter(|device| device.is_bridge && !device.username.is_empty())
+            .map(|device| SubAuthorMapping { sa: device.sub_author, username: device.username })
+            .collect();
+        self.reply(hash, Reply::SubAuthors { bridge: bridge.clone(), sub_authors: sub_authors })
+    }
+
+    /**
+     * Send the user linked to a sub author, or the sub author of an user
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param bridge hash of the bridge
+     * @param name sub author or username
+     * @return an error if the database fails
+     */
+    fn try_sub_author(&self, hash: &String, username: &String, bridge: &String, name: &String) -> RoriResult<()> {
+        if !self.check_bridge(hash, username, bridge, "/sub_author")? {
+            return Ok(());
+        }
+        let username = self.repository.sub_author(bridge, name)?;
+        if !username.is_empty() {
+            return self.reply(hash, Reply::SubAuthor { bridge: bridge.clone(), sa: name.clone(), username: username });
+        }
+        let sub_author = self.repository.sub_author_id(bridge, name)?;
+        if !sub_author.is_empty() {
+            return self.reply(hash, Reply::SubAuthor { bridge: bridge.clone(), sa: sub_author, username: name.clone() });
+        }
+        self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("{} not found on {}", name, bridge)))
+    }
+
+    /**
+     * Unlink a sub author from its user (see remove_sub_author)
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param bridge hash of the bridge
+     * @param sub_author to unlink
+     * @return an error if the database fails
+     */
+    fn try_rm_sub_author(&mut self, hash: &String, username: &String, bridge: &String, sub_author: &String) -> RoriResult<()> {
+        if !self.check_bridge(hash, username, bridge, "/rm_sub_author")? {
+            return Ok(());
+        }
+        match self.remove_sub_author(bridge, sub_author)? {
+            Some(username) => self.reply(hash, Reply::SubAuthorRemoved { bridge: bridge.clone(), sa: sub_author.clone(), username: username }),
+            None => self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("{} not found on {}", sub_author, bridge)))
+        }
+    }
+
+    /**
+     * Choose the device receiving messages of an user
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the user
+     * @param device name or ring_id of a device of the user
+     * @param datatype routed to this device (empty for every datatype)
+     * @return an error if the database fails
+     */
+    fn try_prefer(&self, hash: &String, username: &String, device: &String, datatype: &String) -> RoriResult<()> {
+        let record = match self.repository.get_devices_for_username(&*username)?.into_iter()
+                               .find(|d| !d.is_bridge && (d.hash == *device || (!d.devicename.is_empty() && d.devicename == *device))) {
+            Some(record) => record,
+            None => return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("{} is not a device of {}", device, username)))
+        };
+        self.repository.set_preference(username, datatype, &record.id)?;
+        info!("{} prefers {} for \"{}\"", username, record.hash, datatype);
+        self.reply(hash, Reply::Preferred { devicename: record.devicename, datatype: datatype.clone() })
+    }
+
+    /**
+     * Change the role of an user. Admins can't change their own role
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the admin
+     * @param target user to change
+     * @param role new role
+     * @return an error if the database fails
+     */
+    fn try_set_role(&self, hash: &String, username: &String, target: &String, role: Role) -> RoriResult<()> {
+        if target == username {
+            return self.deny(hash, username, "/role");
+        }
+        if self.repository.set_role(target, role)? == 0 {
+            return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("user {} not found", target)));
+        }
+        info!("{} is now {}", target, role);
+        self.reply(hash, Reply::Role { username: target.clone(), role: role.to_string() })?;
+        Ok(())
+    }
+
+    /**
+     * Enable or disable a module
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the admin
+     * @param module name of the module
+     * @param enabled new state of the module
+     * @return an error if the database fails
+     */
+    fn try_enable_module(&self, hash: &String, username: &String, module: &String, enabled: bool) -> RoriResult<()> {
+        if self.repository.set_module_enabled(module, enabled)? == 0 {
+            return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("module {} not found", module)));
+        }
+        info!("{} {} module {}", username, if enabled { "enables" } else { "disables" }, module);
+        self.reply(hash, Reply::Module { module: module.clone(), enabled: enabled })?;
+        Ok(())
+    }
+
+    /**
+     * Remove a scheduled task
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the admin
+     * @param id of the task
+     * @return an error if the database fails
+     */
+    fn try_rm_task(&self, hash: &String, username: &String, id: &i32) -> RoriResult<()> {
+        if self.repository.rm_task(id)? == 0 {
+            return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("task {} not found", id)));
+        }
+        info!("{} removes task {}", username, id);
+        self.reply(hash, Reply::TaskRemoved { task: *id })?;
+        Ok(())
+    }
+
+    /**
+     * @param self
+     * @param username
+     * @return the role of an user, None if anonymous or unknown
+     */
+    fn role(&self, username: &String) -> RoriResult<Option<Role>> {
+        if username.is_empty() {
+            return Ok(None);
+        }
+        Ok(self.repository.get_user(username)?.map(|user| user.role))
+    }
+
+    /**
+     * Create a group with the author as owner and first member
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param name of the group (must not be used by an user or a group)
+     * @return an error if the database fails
+     */
+    fn try_add_group(&mut self, hash: &String, username: &String, name: &String) -> RoriResult<()> {
+        if self.get_hash(name).len() > 0 {
+            return self.reply(hash, Reply::error(ErrorCode::AlreadyRegistered, &*format!("{} already registered", name)));
+        }
+        match self.repository.add_group(name, username) {
+            Ok(_) => {},
+            Err(RoriError::AlreadyExists(_)) => {
+                return self.reply(hash, Reply::error(ErrorCode::AlreadyRegistered, &*format!("{} already registered", name)));
+            },
+            Err(e) => return Err(e)
+        }
+        self.repository.add_group_member(name, username)?;
+        info!("{} creates group {}", username, name);
+        self.reply_group(hash, name)
+    }
+
+    /**
+     * Add an user to a group
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param group name of the group
+     * @param member username to add
+     * @return an error if the database fails
+     */
+    fn try_add_member(&self, hash: &String, username: &String, group: &String, member: &String) -> RoriResult<()> {
+        if self.check_group(hash, username, group, "/add_member")?.is_none() {
+            return Ok(());
+        }
+        if self.repository.get_user(member)?.is_none() {
+            return self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("user {} not found", member)));
+        }
+        self.repository.add_group_member(group, member)?;
+        info!("{} adds {} to {}", username, member, group);
+        self.reply_group(hash, group)
+    }
+
+    /**
+     * Remove an user from a group. Members can leave a group, only its owner and admins can remove other members
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param group name of the group
+     * @param member username to remove
+     * @return an error if the database fails
+     */
+    fn try_rm_member(&self, hash: &String, username: &String, group: &String, member: &String) -> RoriResult<()> {
+        let leaves = member == username && self.repository.get_group(group)?.map_or(false, |g| g.members.contains(username));
+        if !leaves && self.check_group(hash, username, group, "/rm_member")?.is_none() {
+            return Ok(());
+        }
+        self.repository.rm_group_member(group, member)?;
+        info!("{} removes {} from {}", username, member, group);
+        self.reply_group(hash, group)
+    }
+
+    /**
+     * Get a group managed by the author. Only its owner and admins can change a group
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param name of the group
+     * @param command name of the command, for the error
+     * @return the group, None if not found or refused
+     */
+    fn check_group(&self, hash: &String, username: &String, name: &String, command: &str) -> RoriResult<Option<group::Group>> {
+        let group = match self.repository.get_group(name)? {
+            Some(group) => group,
+            None => {
+                self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("group {} not found", name)))?;
+                return Ok(None);
+            }
+        };
+        if &group.owner != username && self.role(username)? != Some(Role::Admin) {
+            self.deny(hash, username, command)?;
+            return Ok(None);
+        }
+        Ok(Some(group))
+    }
+
+    /**
+     * Send the members of a group
+     * @param self
+     * @param hash of the device which asks
+     * @param name of the group
+     * @return an error if the database fails
+     */
+    fn reply_group(&self, hash: &String, name: &String) -> RoriResult<()> {
+        let members = self.repository.get_group(name)?.map(|group| group.members).unwrap_or(Vec::new());
+        self.reply(hash, Reply::Group { name: name.clone(), members: members })
+    }
+
+    /**
+     * Inform a device that its command is not allowed
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param command refused
+     * @return Ok, a refused command is not an error
+     */
+    fn deny(&self, hash: &String, username: &String, command: &str) -> RoriResult<()> {
+        warn!("{} ({}) is not allowed to use {}", username, hash, command);
+        self.reply(hash, Reply::error(ErrorCode::Denied, command))
+    }
+
+    /**
+     * Get the device targeted by /add_types, /rm_types or /set_types. Only admins can change the types of other devices
+     * @param self
+     * @param hash of the device which asks
+     * @param username author of the command
+     * @param device_id id of the device which asks
+     * @param device ring_id given with --device, if any
+     * @return the id of the device to change, None if refused or unknown
+     */
+    fn types_target(&self, hash: &String, username: &String, device_id: &i32, device: &Option<String>) -> RoriResult<Option<i32>> {
+        let target = match *device {
+            Some(ref target) => target,
+            None => return Ok(Some(*device_id))
+        };
+        if target != hash && self.role(username)? != Some(Role::Admin) {
+            self.deny(hash, username, "--device")?;
+            return Ok(None);
+        }
+        match self.repository.get_devices_for_hash(&*target)?.into_iter().next() {
+            Some(device) => Ok(Some(device.id)),
+            None => {
+                self.reply(hash, Reply::error(ErrorCode::NotFound, &*format!("device {} not found", target)))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /**
+     * Send to an user all its data
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the user
+     * @return an error if the database fails
+     */
+    fn try_export(&self, hash: &String, username: &String) -> RoriResult<()> {
+        let data = self.repository.export_user(username)?;
+        info!("{} exports its data", username);
+        self.reply(hash, Reply::Export { export: serde_json::to_value(&data)? })
+    }
+
+    /**
+     * Remove all data of an user and inform the device
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the user
+     * @return an error if the database fails
+     */
+    fn try_forget(&mut self, hash: &String, username: &String) -> RoriResult<()> {
+        self.purge_user(username)?;
+        self.reply(hash, Reply::Forgotten { username: username.clone() })?;
+        Ok(())
+    }
+
+    /**
+     * Send to an user its messages matching a search
+     * @param self
+     * @param hash of the device which asks
+     * @param username of the user
+     * @param page of results, starting at 1
+     * @param words to search
+     * @return an error if the database fails
+     */
+    fn try_search_history(&self, hash: &String, username: &String, page: u32, words: &String) -> RoriResult<()> {
+        let entries = history::search(username, &*words, page)?;
+        info!("{} searches \"{}\" in history ({} found)", username, words, entries.len());
+        self.send_interaction(hash, &*history::format(&entries), "text/plain");
+        Ok(())
+    }
+
+    /**
+     * Send a reply of RORI as rori/message
+     * @param self
+     * @param destination ring_id of the device
+     * @param reply to send
+     * @return an error if the reply can't be serialized
+     */
+    fn reply(&self, destination: &str, reply: Reply) -> RoriResult<()> {
+        let body = reply::to_json(&reply)?;
+        self.send_interaction(destination, &*body, "rori/message");
+        Ok(())
+    }
+
+    /**
+     * Send a reply to the device which asks and to every other device of an user (except bridges)
+     * @param self
+     * @param hash of the device which asks
+     * @param username
+     * @param reply to send
+     * @return an error if the database fails
+     */
+    fn reply_to_user(&self, hash: &String, username: &String, reply: Reply) -> RoriResult<()> {
+        let mut destinations: Vec<String> = self.repository.get_devices_for_username(&*username)?.into_iter()
+            .filter(|device| !device.is_bridge && device.hash != *hash).map(|device| device.hash).collect();
+        destinations.insert(0, hash.clone());
+        for destination in destinations {
+            self.reply(&*destination, reply.clone())?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Send a new message from the account (see sender::send)
      * @param self
-     * @param from the account who send this
      * @param destination ring_id of the destination
      * @param body text to send
      * @param datatype of the message
-     * @return the interaction id if success. TODO, watch message status (if received)
+     * @return the interaction id if success, else 0
      */
-    fn send_interaction(&self, from: &str, destination: &str, body: &str, datatype: &str) -> u64 {
-        let mut payloads: HashMap<&str, &str> = HashMap::new();
-        payloads.insert(datatype, body);
-        let payloads = Dict::new(payloads.iter());
-
-        let dbus_msg = Message::new_method_call(self.ring_dbus, self.configuration_path, self.configuration_iface,
-                                                "sendTextMessage").ok().expect("sendTextMessage fails. Please verify daemon's API.");
-        let dbus = Connection::get_private(BusType::Session).ok().expect("connection failed");
-        let response = dbus.send_with_reply_and_block(dbus_msg.append3(from, destination, payloads), 2000).unwrap();
-        // sendTextMessage returns one argument, which is a u64.
-        let interaction_id: u64  = match response.get1() {
-            Some(interaction_id) => interaction_id,
-            None => 0
-        };
-        interaction_id
+    fn send_interaction(&self, destination: &str, body: &str, datatype: &str) -> u64 {
+        match sender::send(destination, body, datatype) {
+            Ok(interaction_id) => interaction_id,
+            Err(e) => {
+                error!("can't send interaction to {}: {}", destination, e);
+                0
+            }
+        }
     }
 
     /**
      * Change the datatypes of a device
      * @param id
      * @param datatypes
+     * @return an error if the database fails
      */
-    fn set_datatypes(&self, id: &i32, datatypes: Vec<&str>) {
+    fn set_datatypes(&self, id: &i32, datatypes: Vec<&str>) -> RoriResult<()> {
         let mut dt: Vec<String> = Vec::new();
         for datatype in datatypes.into_iter() {
             dt.push(String::from(datatype));
         }
-        let _ = Database::set_datatypes(&id, dt);
+        self.repository.set_datatypes(&id, dt)?;
+        Ok(())
     }
 }
@@ -0,0 +1,82 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+/**
+ * Helpers used to compare what users say with modules conditions
+ */
+
+/**
+ * Split a text into lowercase words
+ * @param text to split
+ * @return words found (alphanumeric sequences)
+ */
+pub fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| String::from(w))
+        .collect()
+}
+
+/**
+ * Levenshtein distance between two strings
+ * @param a
+ * @param b
+ * @return the number of insertions, deletions or substitutions to go from a to b
+ */
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+    for i in 0..a.len() {
+        current[0] = i + 1;
+        for j in 0..b.len() {
+            let substitution = if a[i] == b[j] { previous[j] } else { previous[j] + 1 };
+            current[j + 1] = *[previous[j + 1] + 1, current[j] + 1, substitution].iter().min().unwrap();
+        }
+        previous.clone_from(&current);
+    }
+    previous[b.len()]
+}
+
+/**
+ * Tells if two words are close enough to be considered the same
+ * @param a
+ * @param b
+ * @param max_distance accepted for words longer than 3 characters
+ * @return if words are similar
+ */
+pub fn similar_words(a: &str, b: &str, max_distance: usize) -> bool {
+    if a == b {
+        return true;
+    }
+    if a.chars().count() <= 3 || b.chars().count() <= 3 {
+        return false;
+    }
+    edit_distance(a, b) <= max_distance
+}
@@ -25,6 +25,7 @@
  * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  **/
 
+use rori::presence::{self, DevicePresence};
 use serde::ser::{Serialize, SerializeStruct};
 use serde::Serializer;
 use std::fmt;
@@ -55,6 +56,13 @@ impl Device {
             is_bridge: false
         }
     }
+
+    /**
+     * @return the last presence reported by the daemon for this device
+     */
+    pub fn presence(&self) -> DevicePresence {
+        presence::get(&self.ring_id)
+    }
 }
 
 /**
@@ -64,12 +72,15 @@ impl Serialize for Device {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where S: Serializer
     {
-        // 4 is the number of fields in the struct.
-        let mut state = serializer.serialize_struct("Device", 4)?;
+        // 6 is the number of fields in the struct.
+        let mut state = serializer.serialize_struct("Device", 6)?;
         state.serialize_field("id", &self.id).unwrap();
         state.serialize_field("name", &self.name).unwrap();
         state.serialize_field("ring_id", &self.ring_id).unwrap();
         state.serialize_field("is_bridge", &self.is_bridge).unwrap();
+        let presence = self.presence();
+        state.serialize_field("presence", &presence.presence).unwrap();
+        state.serialize_field("last_seen", &presence.last_seen).unwrap();
         state.end()
     }
 }
@@ -0,0 +1,299 @@
+/**
+ * Copyright (c) 2020, Sébastien Blin <sebastien.blin@enconn.fr>
+ * All rights reserved.
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * * Redistributions of source code must retain the above copyright
+ *  notice, this list of conditions and the following disclaimer.
+ * * Redistributions in binary form must reproduce the above copyright
+ *  notice, this list of conditions and the following disclaimer in the
+ *  documentation and/or other materials provided with the distribution.
+ * * Neither the name of the University of California, Berkeley nor the
+ *  names of its contributors may be used to endorse or promote products
+ *  derived from this software without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE REGENTS AND CONTRIBUTORS ``AS IS'' AND ANY
+ * EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE IMPLIED
+ * WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE REGENTS AND CONTRIBUTORS BE LIABLE FOR ANY
+ * DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES
+ * (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES;
+ * LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND
+ * ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+ * (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ **/
+
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use rori::backup::{from_hex, to_hex};
+use rori::database::Database;
+use rori::error::{RoriError, RoriResult};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use time;
+
+// Iterations of PBKDF2 used to derive the key from the passphrase
+const ITERATIONS: usize = 100000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// Encrypted when the vault is created, to detect a wrong passphrase
+const VERIFIER: &'static str = "rori vault";
+
+lazy_static! {
+    // Key of the unlocked vault
+    static ref KEY: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+}
+
+/**
+ * A secret of a module, as stored in the database
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Secret {
+    pub module: String,
+    // Empty if the secret is shared by every user
+    pub username: String,
+    pub name: String,
+    // nonce + ciphertext + tag in hexadecimal
+    pub value: String,
+    pub updated: i64,
+}
+
+/**
+ * Secrets of modules (API tokens, passwords...) encrypted with AES-256-GCM.
+ * The key is derived from a passphrase given at startup and only kept in memory.
+ */
+pub struct Vault;
+
+impl Vault {
+    /**
+     * Derive the key from the passphrase. The first unlock creates the vault.
+     * @param passphrase
+     * @return if success (Vault error if the passphrase is wrong)
+     */
+    pub fn unlock(passphrase: &str) -> RoriResult<()> {
+        if passphrase.is_empty() {
+            return Err(RoriError::Vault(String::from("empty passphrase")));
+        }
+        let key = match Database::get_vault()? {
+            Some((salt, verifier)) => {
+                let key = Vault::derive(passphrase, &from_hex(&*salt)?)?;
+                if Vault::decrypt(&key, b"vault", &*verifier).ok() != Some(String::from(VERIFIER)) {
+                    return Err(RoriError::Vault(String::from("wrong passphrase")));
+                }
+                key
+            },
+            None => {
+                let (salt, verifier, key) = Vault::generate(passphrase)?;
+                Database::set_vault(&salt, &verifier)?;
+                key
+            }
+        };
+        *KEY.lock().unwrap() = Some(key);
+        Ok(())
+    }
+
+    /**
+     * Forget the key
+     */
+    pub fn lock() {
+        *KEY.lock().unwrap() = None;
+    }
+
+    /**
+     * @return if the vault is unlocked
+     */
+    pub fn is_unlocked() -> bool {
+        KEY.lock().unwrap().is_some()
+    }
+
+    /**
+     * Encrypt and store a secret (replace the previous one)
+     * @param module name of the module using the secret
+     * @param username owner of the secret (empty for every user)
+     * @param name of the secret
+     * @param value to encrypt
+     * @return if success
+     */
+    pub fn set(module: &String, username: &String, name: &String, value: &String) -> RoriResult<usize> {
+        let key = Vault::key()?;
+        Database::set_secret(&Secret {
+            module: module.clone(),
+            username: username.clone(),
+            name: name.clone(),
+            value: Vault::encrypt(&key, &*Vault::aad(module, username, name), value)?,
+            updated: time::now().to_timespec().sec,
+        })
+    }
+
+    /**
+     * Remove a secret
+     * @param module name of the module using the secret
+     * @param username owner of the secret (empty for every user)
+     * @param name of the secret
+     * @return the number of removed secrets
+     */
+    pub fn delete(module: &String, username: &String, name: &String) -> RoriResult<usize> {
+        Database::rm_secret(module, username, name)
+    }
+
+    /**
+     * List secrets without their values. Works even if the vault is locked
+     * @param module name of the module (every module if empty)
+     * @return the secrets, with an empty value
+     */
+    pub fn list(module: &String) -> RoriResult<Vec<Secret>> {
+        Ok(Database::get_secrets(module)?.into_iter()
+            .map(|secret| Secret { value: String::new(), ..secret })
+            .collect())
+    }
+
+    /**
+     * Decrypt the secrets available for a module executed for an user.
+     * Secrets of the user replace secrets shared by every user
+     * @param module name of the executed module
+     * @param username author of the interaction (empty if anonymous)
+     * @return name -> value
+     */
+    pub fn for_module(module: &String, username: &String) -> RoriResult<HashMap<String, String>> {
+        let key = Vault::key()?;
+        let mut secrets = Database::get_secrets(module)?;
+        // Shared secrets first, so they are overriden by secrets of the user
+        secrets.retain(|secret| secret.username.is_empty() || secret.username == *username);
+        secrets.sort_by_key(|secret| !secret.username.is_empty());
+        let mut result = HashMap::new();
+        for secret in secrets {
+            let aad = Vault::aad(&secret.module, &secret.username, &secret.name);
+            result.insert(secret.name.clone(), Vault::decrypt(&key, &*aad, &*secret.value)?);
+        }
+        Ok(result)
+    }
+
+    /**
+     * Re-encrypt the secrets of an user for a new username, without storing them.
+     * The vault must be unlocked if the user has secrets
+     * @param username current owner of the secrets
+     * @param new_username
+     * @return the secrets to store for new_username
+     */
+    pub fn rename_user(username: &String, new_username: &String) -> RoriResult<Vec<Secret>> {
+        let mut secrets = Database::get_secrets(&String::new())?;
+        secrets.retain(|secret| !username.is_empty() && secret.username == *username);
+        if secrets.is_empty() {
+            return Ok(secrets);
+        }
+        let key = Vault::key()?;
+        for secret in &mut secrets {
+            let value = Vault::decrypt(&key, &*Vault::aad(&secret.module, username, &secret.name), &*secret.value)?;
+            secret.value = Vault::encrypt(&key, &*Vault::aad(&secret.module, new_username, &secret.name), &value)?;
+            secret.username = new_username.clone();
+        }
+        Ok(secrets)
+    }
+
+    /**
+     * Re-encrypt every secret with a new passphrase. The vault must be unlocked
+     * @param passphrase the new passphrase
+     * @return the number of re-encrypted secrets
+     */
+    pub fn rotate(passphrase: &str) -> RoriResult<usize> {
+        if passphrase.is_empty() {
+            return Err(RoriError::Vault(String::from("empty passphrase")));
+        }
+        let old_key = Vault::key()?;
+        let (salt, verifier, key) = Vault::generate(passphrase)?;
+        let mut secrets = Database::get_secrets(&String::new())?;
+        for secret in &mut secrets {
+            let aad = Vault::aad(&secret.module, &secret.username, &secret.name);
+            let value = Vault::decrypt(&old_key, &*aad, &*secret.value)?;
+            secret.value = Vault::encrypt(&key, &*aad, &value)?;
+        }
+        let result = Database::rekey_secrets(&salt, &verifier, &secrets)?;
+        *KEY.lock().unwrap() = Some(key);
+        Ok(result)
+    }
+
+    // Private stuff
+
+    /**
+     * @return the key of the unlocked vault
+     */
+    fn key() -> RoriResult<Vec<u8>> {
+        match *KEY.lock().unwrap() {
+            Some(ref key) => Ok(key.clone()),
+            None => Err(RoriError::Vault(String::from("vault is locked"))),
+        }
+    }
+
+    /**
+     * Generate a new salt and the key for a passphrase
+     * @param passphrase
+     * @return (salt, verifier, key), salt and verifier in hexadecimal
+     */
+    fn generate(passphrase: &str) -> RoriResult<(String, String, Vec<u8>)> {
+        let mut salt = vec![0; SALT_LEN];
+        rand_bytes(&mut salt)?;
+        let key = Vault::derive(passphrase, &salt)?;
+        let verifier = Vault::encrypt(&key, b"vault", &String::from(VERIFIER))?;
+        Ok((to_hex(&salt), verifier, key))
+    }
+
+    /**
+     * PBKDF2-HMAC-SHA256
+     * @param passphrase
+     * @param salt
+     * @return the key
+     */
+    fn derive(passphrase: &str, salt: &[u8]) -> RoriResult<Vec<u8>> {
+        let mut key = vec![0; KEY_LEN];
+        pbkdf2_hmac(passphrase.as_bytes(), salt, ITERATIONS, MessageDigest::sha256(), &mut key)?;
+        Ok(key)
+    }
+
+    /**
+     * The location of a secret is authenticated, so a value can't be moved to another module or user
+     * @return additional data for AES-GCM
+     */
+    fn aad(module: &String, username: &String, name: &String) -> Vec<u8> {
+        format!("{}\0{}\0{}", module, username, name).into_bytes()
+    }
+
+    /**
+     * @param key
+     * @param aad additional authenticated data
+     * @param value to encrypt
+     * @return nonce + ciphertext + tag in hexadecimal
+     */
+    fn encrypt(key: &[u8], aad: &[u8], value: &String) -> RoriResult<String> {
+        let mut nonce = vec![0; NONCE_LEN];
+        rand_bytes(&mut nonce)?;
+        let mut tag = vec![0; TAG_LEN];
+        let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), aad, value.as_bytes(), &mut tag)?;
+        let mut result = nonce;
+        result.extend(ciphertext);
+        result.extend(tag);
+        Ok(to_hex(&result))
+    }
+
+    /**
+     * @param key
+     * @param aad additional authenticated data
+     * @param value built by encrypt
+     * @return the decrypted value, or a Vault error if the key or the data is wrong
+     */
+    fn decrypt(key: &[u8], aad: &[u8], value: &str) -> RoriResult<String> {
+        let data = from_hex(value)?;
+        if data.len() < NONCE_LEN + TAG_LEN {
+            return Err(RoriError::Vault(String::from("invalid secret")));
+        }
+        let (nonce, rest) = data.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let plaintext = decrypt_aead(Cipher::aes_256_gcm(), key, Some(nonce), aad, ciphertext, tag)?;
+        String::from_utf8(plaintext).map_err(|_| RoriError::Vault(String::from("invalid secret")))
+    }
+}
@@ -15,6 +15,7 @@ pub struct Storage {
     pub account: Arc<Mutex<Account>>,
     pub accounts_added: Vec<HashMap<String, String>>,
     pub request_accepted: Vec<String>,
+    pub buddies_subscribed: Vec<(String, bool)>,
 }
 
 // Every storage device has its own object path.
@@ -40,6 +41,7 @@ pub struct Daemon {
     pub storage: Arc<Mutex<Storage>>,
     emit_incoming_trust_request: Arc<AtomicBool>,
     emit_incoming_account_message: Vec<(String, String)>,
+    emit_new_buddy_notification: Vec<(String, bool)>,
 }
 
 impl Daemon {
@@ -63,9 +65,11 @@ impl Daemon {
                 account: Arc::new(Mutex::new(glados_account)),
                 accounts_added: Vec::new(),
                 request_accepted: Vec::new(),
+                buddies_subscribed: Vec::new(),
             })),
             emit_incoming_trust_request: Arc::new(AtomicBool::new(false)),
             emit_incoming_account_message: Vec::new(),
+            emit_new_buddy_notification: Vec::new(),
         }
     }
 
@@ -78,6 +82,8 @@ impl Daemon {
         let ring_dbus = "cx.ring.Ring";
         let configuration_path = "/cx/ring/Ring/ConfigurationManager";
         let configuration_iface = "cx.ring.Ring.ConfigurationManager";
+        let presence_path = "/cx/ring/Ring/PresenceManager";
+        let presence_iface = "cx.ring.Ring.PresenceManager";
         connection.register_name(ring_dbus, NameFlag::ReplaceExisting as u32).unwrap();
         let f = Factory::new_fn::<TData>();
 
@@ -98,6 +104,15 @@ impl Daemon {
              .arg(("payload", "ay"))
         ));
         let signal_incoming_account_message = incoming_account_message.clone().unwrap();
+
+        let new_buddy_notification = Some(Arc::new(
+            f.signal("newBuddyNotification", ())
+             .arg(("accountID", "s"))
+             .arg(("buddyUri", "s"))
+             .arg(("status", "b"))
+             .arg(("lineStatus", "s"))
+        ));
+        let signal_new_buddy_notification = new_buddy_notification.clone().unwrap();
         let storage = daemon.lock().unwrap().storage.clone();
 
         let add_contact = f.method("addContact", (), move |m| {
@@ -225,6 +240,17 @@ impl Daemon {
                                .in_arg(("from", "s"))
                                .out_arg(("success", "v"));
 
+        let subscribe_buddy = f.method("subscribeBuddy", (), move |m| {
+                                  let storage: &Arc<Mutex<Storage>> = m.path.get_data();
+                                  let (_, uri, flag) = m.msg.get3::<&str, &str, bool>();
+                                  storage.lock().unwrap().buddies_subscribed.push((String::from(uri.unwrap_or("")), flag.unwrap_or(false)));
+                                  let rm = m.msg.method_return();
+                                  Ok(vec!(rm))
+                              })
+                              .in_arg(("accountID", "s"))
+                              .in_arg(("uri", "s"))
+                              .in_arg(("flag", "b"));
+
         // We create a tree with one object path inside and make that path introspectable.
         let tree = f.tree(())
                     .add(f.object_path(configuration_path, storage.clone()).introspectable().add(
@@ -240,6 +266,11 @@ impl Daemon {
                          .add_m(accept_trust_request)
                          .add_s(signal_incoming_trust_request)
                          .add_s(signal_incoming_account_message)
+                    ))
+                    .add(f.object_path(presence_path, storage.clone()).introspectable().add(
+                        f.interface(presence_iface, ())
+                         .add_m(subscribe_buddy)
+                         .add_s(signal_new_buddy_notification)
                     ));
 
         // We register all object paths in the tree.
@@ -255,6 +286,7 @@ impl Daemon {
             connection.incoming(100).next();
             let emit_incoming_trust_request = daemon.lock().unwrap().emit_incoming_trust_request.load(Ordering::SeqCst);
             let emit_incoming_account_message = daemon.lock().unwrap().emit_incoming_account_message.clone();
+            let emit_new_buddy_notification = daemon.lock().unwrap().emit_new_buddy_notification.clone();
             if emit_incoming_trust_request {
                 let storage = daemon.lock().unwrap().storage.clone();
                 storage.lock().unwrap().request_accepted = Vec::new();
@@ -277,6 +309,16 @@ impl Daemon {
                 let _ = connection.send(msg).map_err(|_| "Sending DBus signal failed");
                 daemon.lock().unwrap().emit_incoming_account_message = Vec::new();
             }
+            if emit_new_buddy_notification.len() > 0 {
+                let signal = new_buddy_notification.clone().unwrap();
+                let path = presence_path.to_string().into();
+                let iface = presence_iface.to_string().into();
+                for (hash, online) in emit_new_buddy_notification {
+                    let msg = signal.msg(&path, &iface).append2("GLaDOs_id", &*hash).append2(online, "");
+                    let _ = connection.send(msg).map_err(|_| "Sending DBus signal failed");
+                }
+                daemon.lock().unwrap().emit_new_buddy_notification = Vec::new();
+            }
 
             let stop = daemon.lock().unwrap().stop.load(Ordering::SeqCst);
             if stop {
@@ -305,6 +347,17 @@ impl Daemon {
         self.emit_incoming_account_message.push((datatype.clone(), body.clone()));
     }
 
+    /**
+     * emit newBuddyNotification()
+     * @param self
+     * @param hash of the device
+     * @param online new state of the device
+     */
+    #[allow(dead_code)]
+    pub fn emit_new_buddy_notification(&mut self, hash: &String, online: bool) {
+        self.emit_new_buddy_notification.push((hash.clone(), online));
+    }
+
     /**
      * Stop the execution of the mock
      * @param self
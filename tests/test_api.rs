@@ -9,8 +9,10 @@ mod mocks;
 mod tests_api {
     use core::rori::api::API;
     use core::rori::database::Database;
+    use core::rori::emotions::Emotions;
     use core::rori::interaction::Interaction;
     use core::rori::manager::Manager;
+    use core::rori::repository::Role;
     use core::rori::user::Device;
     use mocks::Daemon;
     use reqwest;
@@ -25,7 +27,7 @@ mod tests_api {
 
     fn setup() {
         let _ = fs::remove_file("rori.db");
-        Database::init_db(); // assert this function is correct.
+        Database::init_db().unwrap(); // assert this function is correct.
     }
 
     fn teardown() {
@@ -56,8 +58,7 @@ mod tests_api {
                 },
                 body: String::from("/register weasley"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             m.lock().unwrap().server.handle_interaction(Interaction {
                 device_author: Device {
@@ -68,8 +69,7 @@ mod tests_api {
                 },
                 body: String::from("/add_device core"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             let mut api = API::new(m, String::from("0.0.0.0:1412"));
             api.start();
@@ -156,8 +156,7 @@ mod tests_api {
                 },
                 body: String::from("/register weasley"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new(),
+                ..Interaction::default()
             });
             let mut api = API::new(m, String::from("0.0.0.0:1413"));
             api.start();
@@ -209,18 +208,21 @@ mod tests_api {
             Daemon::run(cloned_daemon);
         });
         let _ = thread::spawn(move|| {
+            // Weasley is turned into a bridge by a bridge operator
+            Database::insert_new_device(&String::from("Weasley"), &String::new(), &String::new(), false).unwrap();
+            Database::insert_new_device(&String::from("Wheatley_id"), &String::from("Wheatley"), &String::new(), false).unwrap();
+            Database::set_role(&String::from("Wheatley"), Role::BridgeOperator).unwrap();
             let m = Arc::new(Mutex::new(Manager::init("GLaDOs_id").unwrap()));
             m.lock().unwrap().server.handle_interaction(Interaction {
                 device_author: Device {
-                    id: 1,
+                    id: 2,
                     name: String::new(),
-                    ring_id: String::from("Weasley"),
+                    ring_id: String::from("Wheatley_id"),
                     is_bridge: false
                 },
-                body: String::from("/bridgify"),
+                body: String::from("/bridgify Weasley"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             m.lock().unwrap().server.handle_interaction(Interaction {
                 device_author: Device {
@@ -231,8 +233,7 @@ mod tests_api {
                 },
                 body: String::from("/register weasley"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             m.lock().unwrap().server.handle_interaction(Interaction {
                 device_author: Device {
@@ -243,8 +244,7 @@ mod tests_api {
                 },
                 body: String::from("/add_device core"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             let mut api = API::new(m, String::from("0.0.0.0:1414"));
             api.start();
@@ -312,8 +312,7 @@ mod tests_api {
                 },
                 body: String::from("/register weasley"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             m.lock().unwrap().server.handle_interaction(Interaction {
                 device_author: Device {
@@ -324,8 +323,7 @@ mod tests_api {
                 },
                 body: String::from("/add_device core"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             let mut api = API::new(m, String::from("0.0.0.0:1416"));
             api.start();
@@ -372,7 +370,7 @@ mod tests_api {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("Weasley"), &String::from("Weasley"), &String::from("Weasley"), false);
+        Database::insert_new_device(&String::from("Weasley"), &String::from("Weasley"), &String::from("Weasley"), false).unwrap();
 
         let daemon = Arc::new(Mutex::new(Daemon::new()));
         let cloned_daemon = daemon.clone();
@@ -390,8 +388,7 @@ mod tests_api {
                 },
                 body: String::from("/register weasley"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             m.lock().unwrap().server.handle_interaction(Interaction {
                 device_author: Device {
@@ -402,8 +399,7 @@ mod tests_api {
                 },
                 body: String::from("/add_device core"),
                 datatype: String::from("rori/command"),
-                time: time::now(),
-                metadatas: HashMap::new()
+                ..Interaction::default()
             });
             let mut api = API::new(m, String::from("0.0.0.0:1417"));
             api.start();
@@ -455,6 +451,7 @@ mod tests_api {
                 panic!("Can't get good result from API");
             }
         };
+        assert!(res.status().as_u16() == 400);
         let mut body: String = String::new();
         let _ = res.read_to_string(&mut body);
         let v: Value = from_str(&body).unwrap();
@@ -481,6 +478,7 @@ mod tests_api {
                 panic!("Can't get good result from API");
             }
         };
+        assert!(res.status().as_u16() == 400);
         let mut body: String = String::new();
         let _ = res.read_to_string(&mut body);
         let v: Value = from_str(&body).unwrap();
@@ -546,6 +544,7 @@ mod tests_api {
                 panic!("Can't get good result from API");
             }
         };
+        assert!(res.status().as_u16() == 404);
         let mut body: String = String::new();
         let _ = res.read_to_string(&mut body);
         let v: Value = from_str(&body).unwrap();
@@ -568,4 +567,101 @@ mod tests_api {
         teardown();
     }
 
+    #[test]
+    // Scenario
+    // 1. get /history/weasley without token is refused
+    // 2. with a wrong token too
+    // 3. with the admin token, the history is returned
+    // 4. export and purge need the token too
+    fn api_admin_token() {
+        setup();
+        let daemon = Arc::new(Mutex::new(Daemon::new()));
+        let cloned_daemon = daemon.clone();
+        let daemon_thread = thread::spawn(move|| {
+            Daemon::run(cloned_daemon);
+        });
+        let _ = thread::spawn(move|| {
+            let m = Arc::new(Mutex::new(Manager::init("GLaDOs_id").unwrap()));
+            m.lock().unwrap().server.handle_interaction(Interaction {
+                device_author: Device {
+                    id: 0,
+                    name: String::new(),
+                    ring_id: String::from("Weasley"),
+                    is_bridge: false
+                },
+                body: String::from("/register weasley"),
+                datatype: String::from("rori/command"),
+                ..Interaction::default()
+            });
+            let mut api = API::new(m, String::from("0.0.0.0:1418"));
+            api.set_admin_token(&String::from("still alive"));
+            api.start();
+        });
+
+        let three_secs = Duration::from_millis(3000);
+        thread::sleep(three_secs);
+
+        let client = reqwest::ClientBuilder::new()
+                    .danger_accept_invalid_certs(true)
+                    .build().unwrap();
+
+        let res = client.get("http://127.0.0.1:1418/history/weasley").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.get("http://127.0.0.1:1418/history/weasley")
+                        .header("Authorization", "Bearer the cake is a lie").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let mut res = client.get("http://127.0.0.1:1418/history/weasley")
+                            .header("Authorization", "Bearer still alive").send().unwrap();
+        assert!(res.status().as_u16() == 200);
+        let mut body: String = String::new();
+        let _ = res.read_to_string(&mut body);
+        let v: Value = from_str(&body).unwrap();
+        assert!(v["page"] == 1);
+
+        // Sub authors are only managed by admins, whatever the query says
+        let res = client.get("http://127.0.0.1:1418/bridge/Weasley/sub_authors").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.get("http://127.0.0.1:1418/bridge/Weasley/sub_authors/Ron").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.delete("http://127.0.0.1:1418/bridge/Weasley/sub_authors/Ron?operator=weasley").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.get("http://127.0.0.1:1418/bridge/Weasley/sub_authors")
+                        .header("Authorization", "Bearer still alive").send().unwrap();
+        assert!(res.status().as_u16() == 404);
+
+        // Groups too
+        let res = client.post("http://127.0.0.1:1418/group/burrow").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.put("http://127.0.0.1:1418/group/burrow/members/weasley").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.get("http://127.0.0.1:1418/groups").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        assert!(Database::get_group(&String::from("burrow")).unwrap().is_none());
+        let res = client.post("http://127.0.0.1:1418/group/burrow")
+                        .header("Authorization", "Bearer still alive").send().unwrap();
+        assert!(res.status().as_u16() == 200);
+        let res = client.put("http://127.0.0.1:1418/group/burrow/members/weasley")
+                        .header("Authorization", "Bearer still alive").send().unwrap();
+        assert!(res.status().as_u16() == 200);
+        assert!(Database::get_group(&String::from("burrow")).unwrap().unwrap().members == vec![String::from("weasley")]);
+        let res = client.delete("http://127.0.0.1:1418/group/burrow").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+
+        let res = client.get("http://127.0.0.1:1418/user/weasley/export").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        let res = client.delete("http://127.0.0.1:1418/user/weasley").send().unwrap();
+        assert!(res.status().as_u16() == 403);
+        assert!(Database::get_user(&String::from("weasley")).unwrap().is_some());
+        let res = client.get("http://127.0.0.1:1418/user/weasley/export")
+                        .header("Authorization", "Bearer still alive").send().unwrap();
+        assert!(res.status().as_u16() == 200);
+        let res = client.delete("http://127.0.0.1:1418/user/weasley")
+                        .header("Authorization", "Bearer still alive").send().unwrap();
+        assert!(res.status().as_u16() == 200);
+        assert!(Database::get_user(&String::from("weasley")).unwrap().is_none());
+
+        daemon.lock().unwrap().stop();
+        let _ = daemon_thread.join();
+        teardown();
+    }
 }
@@ -0,0 +1,151 @@
+extern crate core;
+extern crate rusqlite;
+extern crate serde_json;
+#[cfg(test)]
+mod tests_backup {
+    use core::rori::backup::{self, Backup, BACKUP_VERSION};
+    use core::rori::database::{Database, MIGRATIONS};
+    use core::rori::emotions::Emotions;
+    use core::rori::error::RoriError;
+    use serde_json::Value;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        let _ = fs::remove_file("rori_backup.json");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+        let _ = fs::remove_file("rori_backup.json");
+    }
+
+    // Remove the database, the next connection creates an empty one
+    fn reset_database() {
+        let _ = fs::remove_file("rori.db");
+        Database::set_path("rori.db");
+    }
+
+    fn config() -> Value {
+        serde_json::from_str("{\"ring_id\": \"GLaDOs_id\", \"api_listener\": \"0.0.0.0:1412\"}").unwrap()
+    }
+
+    #[test]
+    fn test_hex() {
+        assert!(backup::to_hex(&[0, 15, 255]) == "000fff");
+        assert!(backup::from_hex("000fff").unwrap() == vec![0, 15, 255]);
+        assert!(backup::from_hex("0f0").is_err());
+        assert!(backup::from_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_backup_restore() {
+        setup();
+        let pbody = String::from("PBody");
+        let id = Database::insert_new_device(&String::from("PBody_hash"), &pbody, &String::from("Atlas"), true).unwrap() as i32;
+        Database::update_sub_author(&id, &String::from("Wheatley")).unwrap();
+        Database::set_datatypes(&id, vec![String::from("music")]).unwrap();
+        Database::set_display_name(&pbody, &String::from("P-Body")).unwrap();
+        let mut emotions = Emotions::default();
+        emotions.joy = 42;
+        Database::set_emotions(&pbody, &emotions, 12).unwrap();
+        Database::set_lang(&pbody, &String::from("fr")).unwrap();
+        Database::set_module_value(&String::from("alarm"), &String::from("last"), &String::from("7")).unwrap();
+        // Save
+        let saved = Backup::create(&config(), None).unwrap();
+        assert!(saved.version == BACKUP_VERSION);
+        assert!(saved.schema == MIGRATIONS.len() as i32);
+        assert!(saved.account.is_none());
+        saved.save("rori_backup.json").unwrap();
+        // Only readable by its owner
+        let mode = fs::metadata("rori_backup.json").unwrap().permissions().mode();
+        assert!(mode & 0o777 == 0o600);
+
+        // Restore on a new host
+        reset_database();
+        let loaded = Backup::load("rori_backup.json").unwrap();
+        assert!(loaded.config["ring_id"] == "GLaDOs_id");
+        assert!(loaded.restore_database().unwrap() == MIGRATIONS.len() as i32);
+        let device = Database::get_device(&String::from("PBody_hash"), &pbody).unwrap().unwrap();
+        assert!(device.id == id);
+        assert!(device.is_bridge);
+        assert!(device.sub_author == "Wheatley");
+        assert!(Database::get_datatypes(&id).unwrap() == vec![String::from("music")]);
+        assert!(Database::get_user(&pbody).unwrap().unwrap().display_name == "P-Body");
+        assert!(Database::get_emotions(&pbody).unwrap().unwrap() == (emotions, 12));
+        assert!(Database::get_lang(&pbody).unwrap().unwrap() == "fr");
+        assert!(Database::get_module_value(&String::from("alarm"), &String::from("last")).unwrap().unwrap() == "7");
+        // No account in this backup
+        assert!(loaded.restore_account("").unwrap().is_none());
+        // The database is not empty anymore
+        match loaded.restore_database() {
+            Err(RoriError::AlreadyExists(_)) => {},
+            _ => panic!("restore should not overwrite a database")
+        }
+        teardown();
+    }
+
+    #[test]
+    fn test_backup_old_schema() {
+        setup();
+        reset_database();
+        let old_schema = MIGRATIONS.len() as i32 - 1;
+        Database::migrate_to(old_schema).unwrap();
+        // The backup doesn't upgrade the database
+        match Backup::create(&config(), None) {
+            Err(RoriError::Parse(_)) => {},
+            _ => panic!("an outdated database must not be saved")
+        }
+        assert!(Database::version().unwrap() == old_schema);
+        teardown();
+    }
+
+    #[test]
+    fn test_restore_old_schema() {
+        setup();
+        // Backup made before the users table
+        let mut saved = Backup::create(&config(), None).unwrap();
+        saved.schema = 5;
+        saved.tables.clear();
+        let device: serde_json::Map<String, Value> = serde_json::from_str("{\"id\": 1, \"hash\": \"PBody_hash\", \
+            \"username\": \"PBody\", \"sub_author\": \"\", \"devicename\": \"Atlas\", \"additional_types\": \"\", \
+            \"is_bridge\": 0}").unwrap();
+        saved.tables.insert(String::from("devices"), vec![device]);
+        reset_database();
+        assert!(saved.restore_database().unwrap() == MIGRATIONS.len() as i32);
+        // Data are migrated
+        assert!(Database::get_user(&String::from("PBody")).unwrap().is_some());
+        let device = Database::get_device(&String::from("PBody_hash"), &String::from("PBody")).unwrap().unwrap();
+        assert!(device.devicename == "Atlas");
+        teardown();
+    }
+
+    #[test]
+    fn test_restore_invalid() {
+        setup();
+        let mut saved = Backup::create(&config(), None).unwrap();
+        saved.version = BACKUP_VERSION + 1;
+        saved.save("rori_backup.json").unwrap();
+        assert!(Backup::load("rori_backup.json").is_err());
+        saved.version = BACKUP_VERSION;
+        saved.schema = MIGRATIONS.len() as i32 + 1;
+        saved.save("rori_backup.json").unwrap();
+        assert!(Backup::load("rori_backup.json").is_err());
+        assert!(Backup::load("rori_missing_backup.json").is_err());
+        // Unknown columns are refused and nothing is inserted
+        saved.schema = MIGRATIONS.len() as i32;
+        let user: serde_json::Map<String, Value> = serde_json::from_str("{\"id\": 1, \"username\": \"PBody\"}").unwrap();
+        let device: serde_json::Map<String, Value> = serde_json::from_str("{\"id\": 1, \"password\": \"cake\"}").unwrap();
+        saved.tables.insert(String::from("users"), vec![user]);
+        saved.tables.insert(String::from("devices"), vec![device]);
+        reset_database();
+        match saved.restore_database() {
+            Err(RoriError::Parse(_)) => {},
+            _ => panic!("invalid column should be refused")
+        }
+        assert!(Database::get_users().unwrap().is_empty());
+        teardown();
+    }
+}
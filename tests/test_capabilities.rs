@@ -0,0 +1,71 @@
+extern crate core;
+#[cfg(test)]
+mod tests_capabilities {
+    use core::rori::capabilities::{self, Kind};
+    use core::rori::database::Database;
+    use core::rori::routing;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    #[test]
+    fn test_parse() {
+        let capabilities = capabilities::parse("{\"version\":1,\"kinds\":[\"speaker\",\"screen\"],\
+            \"actions\":[{\"datatype\":\"music/play\",\"parameters\":{\"type\":\"object\"}},{\"datatype\":\"music/stop\"}]}").unwrap();
+        assert!(capabilities.kinds == vec![Kind::Speaker, Kind::Screen]);
+        assert!(capabilities.action("music/play").unwrap().parameters["type"] == "object");
+        assert!(capabilities.action("music/stop").unwrap().parameters.is_null());
+        assert!(capabilities.action("video/play").is_none());
+        // Everything is optional but the version
+        assert!(capabilities::parse("{\"version\":1}").unwrap().actions.is_empty());
+        assert!(capabilities::parse("{\"kinds\":[\"speaker\"]}").is_err());
+        // Invalid announcements
+        assert!(capabilities::parse("{\"version\":2}").is_err());
+        assert!(capabilities::parse("{\"version\":1,\"kinds\":[\"toaster\"]}").is_err());
+        assert!(capabilities::parse("{\"version\":1,\"actions\":[{\"datatype\":\"music play\"}]}").is_err());
+        assert!(capabilities::parse("{\"version\":1,\"actions\":[{\"datatype\":\"music/play\",\"parameters\":3}]}").is_err());
+        assert!(Kind::parse("shell") == Some(Kind::Shell));
+        assert!(Kind::parse(Kind::Screen.as_str()) == Some(Kind::Screen));
+    }
+
+    #[test]
+    fn test_storage() {
+        setup();
+        let pbody = String::from("PBody");
+        let home = Database::insert_new_device(&String::from("Home_hash"), &pbody, &String::from("Home"), false).unwrap() as i32;
+        let phone = Database::insert_new_device(&String::from("Phone_hash"), &pbody, &String::from("Phone"), false).unwrap() as i32;
+        assert!(Database::get_capabilities(&home).unwrap() == Default::default());
+        let announced = capabilities::parse("{\"version\":1,\"kinds\":[\"speaker\"],\
+            \"actions\":[{\"datatype\":\"music/play\",\"parameters\":{\"type\":\"object\"}}]}").unwrap();
+        assert!(Database::set_capabilities(&home, &announced).unwrap() == 2);
+        assert!(Database::get_capabilities(&home).unwrap() == announced);
+
+        // Announced actions and /add_types are both routed
+        Database::set_datatypes(&phone, vec![String::from("video/play")]).unwrap();
+        assert!(capabilities::handles(&home, &String::from("music/play")).unwrap());
+        assert!(capabilities::handles(&home, &String::from("text/plain")).unwrap());
+        assert!(!capabilities::handles(&home, &String::from("video/play")).unwrap());
+        assert!(capabilities::handles(&phone, &String::from("video/play")).unwrap());
+        let routes = routing::route(&pbody, &String::from("music/play")).unwrap();
+        assert!(routes.len() == 1 && routes[0].device.id == home);
+
+        // A new announcement replaces the previous one
+        let announced = capabilities::parse("{\"version\":1,\"actions\":[{\"datatype\":\"music/stop\"}]}").unwrap();
+        Database::set_capabilities(&home, &announced).unwrap();
+        assert!(Database::get_capabilities(&home).unwrap() == announced);
+        assert!(routing::route(&pbody, &String::from("music/play")).unwrap().is_empty());
+        assert!(Database::export_user(&pbody).unwrap()["capabilities"].len() == 1);
+
+        // Removed with the device
+        Database::remove_device(&home).unwrap();
+        assert!(Database::get_capabilities(&home).unwrap().actions.is_empty());
+        teardown();
+    }
+}
@@ -0,0 +1,87 @@
+extern crate core;
+#[cfg(test)]
+mod tests_command {
+    use core::rori::command::{self, Order, Parsed, Scope};
+    use core::rori::repository::Role;
+
+    fn order(body: &str) -> Order {
+        match command::parse(body) {
+            Parsed::Valid(_, order) => order,
+            _ => panic!("{} should be valid", body)
+        }
+    }
+
+    fn malformed(body: &str) -> bool {
+        match command::parse(body) {
+            Parsed::Malformed(_) => true,
+            _ => false
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(order("/register Atlas") == Order::Register { username: String::from("Atlas") });
+        assert!(order("/add_device Home") == Order::AddDevice { name: String::from("Home"), ring_id: None });
+        assert!(order("/add_device Home Atlas_hash") == Order::AddDevice { name: String::from("Home"), ring_id: Some(String::from("Atlas_hash")) });
+        assert!(order("/rm_device") == Order::RmDevice { ring_id: None });
+        assert!(order("/lang  fr ") == Order::Lang { lang: String::from("fr") });
+        assert!(order("/history 2 cake lie") == Order::History { page: 2, words: String::from("cake lie") });
+        assert!(order("/history cake") == Order::History { page: 1, words: String::from("cake") });
+        assert!(order("/role Atlas bridge-operator") == Order::SetRole { username: String::from("Atlas"), role: Role::BridgeOperator });
+        assert!(order("/module disable weather") == Order::EnableModule { name: String::from("weather"), enabled: false });
+        assert!(order("/task rm 3") == Order::RmTask { id: 3 });
+        assert!(order("/link Atlas 123456") == Order::Link { argument: String::from("Atlas"), code: String::from("123456") });
+        assert!(order("/set_types ") == Order::SetTypes { device: None, types: Vec::new() });
+        assert!(order("/sub_authors Tars") == Order::SubAuthors { bridge: String::from("Tars") });
+        assert!(order("/sub_author Tars Eve") == Order::SubAuthor { bridge: String::from("Tars"), name: String::from("Eve") });
+        assert!(order("/rm_sub_author Tars Eve") == Order::RmSubAuthor { bridge: String::from("Tars"), sub_author: String::from("Eve") });
+        assert!(order("/groups") == Order::Groups);
+        assert!(order("/add_group Aperture") == Order::AddGroup { name: String::from("Aperture") });
+        assert!(order("/add_member Aperture Atlas") == Order::AddMember { group: String::from("Aperture"), username: String::from("Atlas") });
+        assert!(order("/rm_member Aperture Atlas") == Order::RmMember { group: String::from("Aperture"), username: String::from("Atlas") });
+        assert!(order("/add_types --device Tars_hash music") == Order::AddTypes {
+            device: Some(String::from("Tars_hash")),
+            types: vec![String::from("music")]
+        });
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(malformed("/register"));
+        assert!(malformed("/register Atlas PBody"));
+        assert!(malformed("/lang"));
+        assert!(malformed("/history 0 cake"));
+        assert!(malformed("/role Atlas god"));
+        assert!(malformed("/module restart weather"));
+        assert!(malformed("/task rm cake"));
+        assert!(malformed("/add_types"));
+        assert!(malformed("/rm_types --device"));
+        assert!(malformed("/help me"));
+        assert!(malformed("/sub_authors"));
+        assert!(malformed("/rm_sub_author Tars"));
+        assert!(malformed("/add_group"));
+        assert!(malformed("/rm_group Aperture Science"));
+        assert!(malformed("/add_member Aperture"));
+        // Only the exact name matches
+        assert!(command::parse("/language fr") == Parsed::Unknown(String::from("/language")));
+        assert!(command::parse("/weather Paris") == Parsed::Unknown(String::from("/weather")));
+    }
+
+    #[test]
+    fn test_help() {
+        let anonymous = command::help(None);
+        assert!(anonymous.contains("/register <username>"));
+        assert!(!anonymous.contains("/lang"));
+        assert!(!anonymous.contains("/role"));
+        let user = command::help(Some(Role::User));
+        assert!(!user.contains("/register"));
+        assert!(user.contains("/lang <lang>"));
+        assert!(!user.contains("/bridgify"));
+        let operator = command::help(Some(Role::BridgeOperator));
+        assert!(operator.contains("/bridgify"));
+        assert!(!operator.contains("/module"));
+        let admin = command::help(Some(Role::Admin));
+        assert!(admin.contains("/module <enable|disable> <name>"));
+        assert!(admin.lines().count() == command::COMMANDS.iter().filter(|c| c.scope != Scope::Anonymous).count());
+    }
+}
@@ -1,20 +1,33 @@
 extern crate core;
 #[cfg(test)]
 mod tests_database {
-    use core::rori::database::Database;
+    use core::rori::database::{Database, MIGRATIONS};
+    use core::rori::emotions::Emotions;
+    use core::rori::error::RoriError;
+    use core::rori::interaction::Interaction;
+    use core::rori::link::LinkRequest;
+    use core::rori::repository::Role;
     use core::rori::scheduler::ScheduledTask;
+    use core::rori::user::Device;
     use std::collections::HashMap;
     use std::fs;
 
     fn setup() {
         let _ = fs::remove_file("rori.db");
-        Database::init_db(); // assert this function is correct.
+        Database::init_db().unwrap(); // assert this function is correct.
     }
 
     fn teardown() {
         let _ = fs::remove_file("rori.db");
     }
 
+    // Tasks must reference an existing module
+    fn add_module() {
+        let conn = rusqlite::Connection::open("rori.db").unwrap();
+        conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
+                      VALUES (\"alarm\", 1, 1, \"text\", \"wake\", \"alarm\")", rusqlite::NO_PARAMS).unwrap();
+    }
+
     #[test]
     fn test_insert_new_device() {
         setup();
@@ -25,16 +38,16 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
         assert!(!row.is_ok());
         // Should be retrieven
-        let devices = Database::get_devices();
+        let devices = Database::get_devices().unwrap();
         assert!(devices.len() == 1);
         let device = devices.first().unwrap();
-        assert!(device.1 == "GLaDOs");
-        assert!(device.2 == "PBody");
-        assert!(device.3 == "Atlas");
+        assert!(device.hash == "GLaDOs");
+        assert!(device.username == "PBody");
+        assert!(device.devicename == "Atlas");
         // Can handle more devices
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("Atlas"), false);
         assert!(row.is_ok());
-        let devices = Database::get_devices();
+        let devices = Database::get_devices().unwrap();
         assert!(devices.len() == 2);
         teardown();
     }
@@ -48,21 +61,19 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false);
         assert!(row.is_ok());
         // Should be retrieven
-        let device = Database::get_device(&String::from("GLaDOs"), &String::from("PBody"));
-        assert!(device.1 == "GLaDOs");
-        assert!(device.2 == "PBody");
-        assert!(device.3 == "Atlas");
+        let device = Database::get_device(&String::from("GLaDOs"), &String::from("PBody")).unwrap().unwrap();
+        assert!(device.hash == "GLaDOs");
+        assert!(device.username == "PBody");
+        assert!(device.devicename == "Atlas");
         // Can handle more devices
-        let device = Database::get_device(&String::from("Tars"), &String::new());
-        assert!(device.1 == "Tars");
-        assert!(device.2 == "");
-        assert!(device.3 == "alexa");
+        let device = Database::get_device(&String::from("Tars"), &String::new()).unwrap().unwrap();
+        assert!(device.hash == "Tars");
+        assert!(device.username == "");
+        assert!(device.devicename == "alexa");
 
         // If device doesn't exist.
-        let device = Database::get_device(&String::from("Eve"), &String::new());
-        assert!(device.1 == "");
-        assert!(device.2 == "");
-        assert!(device.3 == "");
+        let device = Database::get_device(&String::from("Eve"), &String::new()).unwrap();
+        assert!(device.is_none());
         teardown();
     }
 
@@ -70,20 +81,20 @@ mod tests_database {
     fn test_get_devices() {
         setup();
         // No devices, should be empty
-        assert!(Database::get_devices().len() == 0);
+        assert!(Database::get_devices().unwrap().len() == 0);
         // Insert new devices
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
         assert!(row.is_ok());
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false);
         assert!(row.is_ok());
         // Retrieve all devices and no more
-        for (_, id, username, devicename, _) in Database::get_devices() {
-            if id == "GLaDOs" {
-                assert!(username == "PBody");
-                assert!(devicename == "Atlas");
-            } else if id == "Tars" {
-                assert!(username == "");
-                assert!(devicename == "alexa");
+        for device in Database::get_devices().unwrap() {
+            if device.hash == "GLaDOs" {
+                assert!(device.username == "PBody");
+                assert!(device.devicename == "Atlas");
+            } else if device.hash == "Tars" {
+                assert!(device.username == "");
+                assert!(device.devicename == "alexa");
             } else {
                 panic!("Unknown user");
             }
@@ -98,23 +109,21 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false);
         assert!(row.is_ok());
         // No devices, should be empty
-        assert!(Database::get_devices().len() == 1);
+        assert!(Database::get_devices().unwrap().len() == 1);
         let row = Database::remove_device(&2);
         assert!(row.is_ok());
-        assert!(Database::get_devices().len() == 1);
+        assert!(Database::get_devices().unwrap().len() == 1);
         // Insert new device
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
         assert!(row.is_ok());
         // Remove should succeed
-        assert!(Database::get_devices().len() == 2);
+        assert!(Database::get_devices().unwrap().len() == 2);
         let row = Database::remove_device(&2);
         assert!(row.is_ok());
         // And get_device fails
-        let device = Database::get_device(&String::from("GLaDOs"), &String::from("PBody"));
-        assert!(device.1 == "");
-        assert!(device.2 == "");
-        assert!(device.3 == "");
-        assert!(Database::get_devices().len() == 1);
+        let device = Database::get_device(&String::from("GLaDOs"), &String::from("PBody")).unwrap();
+        assert!(device.is_none());
+        assert!(Database::get_devices().unwrap().len() == 1);
         teardown();
     }
 
@@ -122,7 +131,7 @@ mod tests_database {
     fn test_search_device() {
         setup();
         // Search should return false if no device found
-        let found = Database::search_devicename(&String::from("PBody"), &String::from("Atlas"));
+        let found = Database::search_devicename(&String::from("PBody"), &String::from("Atlas")).unwrap();
         assert!(!found);
         // Insert new devices
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
@@ -130,7 +139,7 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false);
         assert!(row.is_ok());
         // Search should succeed
-        let found = Database::search_devicename(&String::from("PBody"), &String::from("Atlas"));
+        let found = Database::search_devicename(&String::from("PBody"), &String::from("Atlas")).unwrap();
         assert!(found);
         teardown();
     }
@@ -139,7 +148,7 @@ mod tests_database {
     fn test_search_ring_id() {
         setup();
         // Search should return false if no device found
-        let found = Database::search_hash(&String::from("GLaDOs"));
+        let found = Database::search_hash(&String::from("GLaDOs")).unwrap();
         assert!(!found);
         // Insert new devices
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
@@ -147,7 +156,7 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false);
         assert!(row.is_ok());
         // Search should succeed
-        let found = Database::search_hash(&String::from("GLaDOs"));
+        let found = Database::search_hash(&String::from("GLaDOs")).unwrap();
         assert!(found);
         teardown();
     }
@@ -156,7 +165,7 @@ mod tests_database {
     fn test_search_username() {
         setup();
         // Search should return false if no device found
-        let found = Database::search_username(&String::from("PBody"));
+        let found = Database::search_username(&String::from("PBody")).unwrap();
         assert!(!found);
         // Insert new devices
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
@@ -164,17 +173,17 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false);
         assert!(row.is_ok());
         // Search should succeed
-        let found = Database::search_username(&String::from("PBody"));
+        let found = Database::search_username(&String::from("PBody")).unwrap();
         assert!(found);
 
         // RORI should be found
-        let found = Database::search_username(&String::from("RORI"));
+        let found = Database::search_username(&String::from("RORI")).unwrap();
         assert!(found);
-        let found = Database::search_username(&String::from("RoRI"));
+        let found = Database::search_username(&String::from("RoRI")).unwrap();
         assert!(found);
-        let found = Database::search_username(&String::from("Rori"));
+        let found = Database::search_username(&String::from("Rori")).unwrap();
         assert!(found);
-        let found = Database::search_username(&String::from("rori"));
+        let found = Database::search_username(&String::from("rori")).unwrap();
         assert!(found);
         teardown();
     }
@@ -191,10 +200,10 @@ mod tests_database {
         let row = Database::update_username(&1, &String::from("BPody"));
         assert!(row.is_ok());
         // Should be retrieven
-        let device = Database::get_device(&String::from("GLaDOs"), &String::from("BPody"));
-        assert!(device.1 == "GLaDOs");
-        assert!(device.2 == "BPody");
-        assert!(device.3 == "Atlas");
+        let device = Database::get_device(&String::from("GLaDOs"), &String::from("BPody")).unwrap().unwrap();
+        assert!(device.hash == "GLaDOs");
+        assert!(device.username == "BPody");
+        assert!(device.devicename == "Atlas");
         teardown();
     }
 
@@ -210,10 +219,10 @@ mod tests_database {
         let row = Database::update_devicename(&1, &String::from("Jupiter"));
         assert!(row.is_ok());
         // Should be retrieven
-        let device = Database::get_device(&String::from("GLaDOs"), &String::from("PBody"));
-        assert!(device.1 == "GLaDOs");
-        assert!(device.2 == "PBody");
-        assert!(device.3 == "Jupiter");
+        let device = Database::get_device(&String::from("GLaDOs"), &String::from("PBody")).unwrap().unwrap();
+        assert!(device.hash == "GLaDOs");
+        assert!(device.username == "PBody");
+        assert!(device.devicename == "Jupiter");
         teardown();
     }
 
@@ -224,21 +233,21 @@ mod tests_database {
         let mut dts = Vec::new();
         dts.push(String::from("dt1"));
         dts.push(String::from("dt2"));
-        let _ = Database::set_datatypes(&1, dts.clone());
-        let get = Database::get_datatypes(&1);
+        Database::set_datatypes(&1, dts.clone()).unwrap();
+        let get = Database::get_datatypes(&1).unwrap();
         assert!(get.len() == 0);
         // Insert new device
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false);
         assert!(row.is_ok());
-        let get = Database::get_datatypes(&1);
+        let get = Database::get_datatypes(&1).unwrap();
         assert!(get.len() == 0);
         // Valid set should get what we set
-        let _ = Database::set_datatypes(&1, dts.clone());
-        let get = Database::get_datatypes(&1);
+        Database::set_datatypes(&1, dts.clone()).unwrap();
+        let get = Database::get_datatypes(&1).unwrap();
         assert!(get == dts);
         // Should be rewritable
-        let _ = Database::set_datatypes(&1, Vec::new());
-        let get = Database::get_datatypes(&1);
+        Database::set_datatypes(&1, Vec::new()).unwrap();
+        let get = Database::get_datatypes(&1).unwrap();
         assert!(get.len() == 0);
         teardown();
     }
@@ -249,8 +258,29 @@ mod tests_database {
         // Insert new device
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), true);
         assert!(row.is_ok());
-        assert!(Database::is_bridge_with_username(&String::from("GLaDOs"), &String::from("PBody")));
-        assert!(!Database::is_bridge_with_username(&String::from("GLaDOs"), &String::from("NotPBody")));
+        assert!(Database::is_bridge_with_username(&String::from("GLaDOs"), &String::from("PBody")).unwrap());
+        assert!(!Database::is_bridge_with_username(&String::from("GLaDOs"), &String::from("NotPBody")).unwrap());
+        teardown();
+    }
+
+    #[test]
+    fn test_bridge_owner() {
+        setup();
+        let wheatley = String::from("Wheatley");
+        let tars = String::from("Tars");
+        let id = Database::insert_new_device(&String::from("Wheatley_id"), &wheatley, &String::new(), false).unwrap() as i32;
+        Database::insert_new_device(&tars, &String::new(), &String::new(), true).unwrap();
+        assert!(Database::get_bridge_owner(&tars).unwrap().is_none());
+        assert!(Database::set_bridge_owner(&tars, &wheatley).unwrap() == 1);
+        assert!(Database::get_bridge_owner(&tars).unwrap() == Some(wheatley.clone()));
+        // Owners are users, kept without devices
+        Database::remove_device(&id).unwrap();
+        assert!(Database::get_bridge_owner(&tars).unwrap() == Some(wheatley.clone()));
+        // A new user with the same name (after a purge) doesn't own the bridge
+        Database::purge_user(&wheatley).unwrap();
+        assert!(Database::get_bridge_owner(&tars).unwrap().is_none());
+        Database::insert_new_device(&String::from("Wheatley_id2"), &wheatley, &String::new(), false).unwrap();
+        assert!(Database::get_bridge_owner(&tars).unwrap().is_none());
         teardown();
     }
 
@@ -261,10 +291,10 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), true);
         assert!(row.is_ok());
         // Update sub author
-        assert!(Database::sub_author(&String::from("GLaDOs"), &String::from("Asimo")) == String::new());
+        assert!(Database::sub_author(&String::from("GLaDOs"), &String::from("Asimo")).unwrap() == String::new());
         let row = Database::update_sub_author(&1, &String::from("Asimo"));
         assert!(row.is_ok());
-        assert!(Database::sub_author(&String::from("GLaDOs"), &String::from("Asimo")) == String::from("PBody"));
+        assert!(Database::sub_author(&String::from("GLaDOs"), &String::from("Asimo")).unwrap() == String::from("PBody"));
         teardown();
     }
 
@@ -281,10 +311,10 @@ mod tests_database {
         let row = Database::insert_new_device(&String::from("Eve"), &String::from("Wall-E"), &String::from(""), false);
         assert!(row.is_ok());
         // Shoud get two devices
-        let user_devices = Database::get_devices_for_username(&String::from("PBody"));
+        let user_devices = Database::get_devices_for_username(&String::from("PBody")).unwrap();
         assert!(user_devices.len() == 2);
-        assert!(user_devices[0].0 == 1);
-        assert!(user_devices[1].0 == 2);
+        assert!(user_devices[0].id == 1);
+        assert!(user_devices[1].id == 2);
         teardown();
     }
 
@@ -308,11 +338,12 @@ mod tests_database {
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
         assert!(row.is_ok());
         // Get foo in datatypes
-        let datatypes = Database::get_modules_datatypes();
-        assert!(datatypes.len() == 3);
+        let datatypes = Database::get_modules_datatypes().unwrap();
+        assert!(datatypes.len() == 4);
         assert!(datatypes[0] == String::from("text/plain"));
         assert!(datatypes[1] == String::from("rori/command"));
-        assert!(datatypes[2] == String::from("foo"));
+        assert!(datatypes[2] == String::from("rori/capabilities"));
+        assert!(datatypes[3] == String::from("foo"));
         teardown();
     }
 
@@ -325,7 +356,7 @@ mod tests_database {
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
         assert!(row.is_ok());
         // Get foo in modules
-        let module = Database::get_module(&(row.unwrap() as i32));
+        let module = Database::get_module(&(row.unwrap() as i32)).unwrap();
         assert!(!module.is_none());
         assert!(module.unwrap().name == String::from("foo"));
         teardown();
@@ -335,7 +366,7 @@ mod tests_database {
     fn test_get_module_fail() {
         setup();
         // Get foo in modules
-        let module = Database::get_module(&1);
+        let module = Database::get_module(&1).unwrap();
         assert!(module.is_none());
         teardown();
     }
@@ -349,7 +380,7 @@ mod tests_database {
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
         assert!(row.is_ok());
         // Get foo in modules
-        let module = Database::get_module_id_by_name(&String::from("foo"));
+        let module = Database::get_module_id_by_name(&String::from("foo")).unwrap();
         assert!(module != 0);
         assert!(module == row.unwrap() as i32);
         teardown();
@@ -359,7 +390,7 @@ mod tests_database {
     fn test_get_module_id_by_name_fail() {
         setup();
         // Get foo in modules
-        let module = Database::get_module_id_by_name(&String::from("foo"));
+        let module = Database::get_module_id_by_name(&String::from("foo")).unwrap();
         assert!(module == 0);
         teardown();
     }
@@ -368,7 +399,7 @@ mod tests_database {
     fn test_get_tasks_no_tasks() {
         setup();
         // test get tasks no task
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 0);
         teardown();
     }
@@ -403,10 +434,10 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         // test get tasks
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 2);
         assert!(tasks[0].seconds == task1.seconds);
         assert!(tasks[1].seconds == task2.seconds);
@@ -443,10 +474,10 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         // test get task
-        let task = Database::get_task(&1);
+        let task = Database::get_task(&1).unwrap();
         assert!(!task.is_none());
         assert!(task.as_ref().unwrap().id == 1);
         assert!(task.as_ref().unwrap().seconds == task1.seconds);
@@ -457,7 +488,7 @@ mod tests_database {
     fn test_get_task_failed() {
         setup();
         // test get task fails
-        let task = Database::get_task(&1);
+        let task = Database::get_task(&1).unwrap();
         assert!(task.is_none());
         teardown();
     }
@@ -481,9 +512,9 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         // test get search task no parameter
-        let task = Database::search_task(&String::from("1"), HashMap::new());
+        let task = Database::search_task(&String::from("1"), HashMap::new()).unwrap();
         assert!(!task.is_none());
         assert!(task.as_ref().unwrap().id == 1);
         assert!(task.as_ref().unwrap().seconds == task1.seconds);
@@ -509,11 +540,11 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         // test get search task sub parameter
         let mut parameter = HashMap::new();
         parameter.insert(String::from("author"), String::from("foo"));
-        let task = Database::search_task(&String::from("1"), parameter);
+        let task = Database::search_task(&String::from("1"), parameter).unwrap();
         assert!(!task.is_none());
         assert!(task.as_ref().unwrap().id == 1);
         assert!(task.as_ref().unwrap().seconds == task1.seconds);
@@ -539,12 +570,12 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         // test get search task exact parameter
         let mut parameter = HashMap::new();
         parameter.insert(String::from("author"), String::from("foo"));
         parameter.insert(String::from("param"), String::from("bar"));
-        let task = Database::search_task(&String::from("1"), parameter);
+        let task = Database::search_task(&String::from("1"), parameter).unwrap();
         assert!(!task.is_none());
         assert!(task.as_ref().unwrap().id == 1);
         assert!(task.as_ref().unwrap().seconds == task1.seconds);
@@ -570,13 +601,13 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         // test get search task too maty parameter
         let mut parameter = HashMap::new();
         parameter.insert(String::from("author"), String::from("foo"));
         parameter.insert(String::from("param"), String::from("bar"));
         parameter.insert(String::from("param2"), String::from("bar2"));
-        let task = Database::search_task(&String::from("1"), parameter);
+        let task = Database::search_task(&String::from("1"), parameter).unwrap();
         assert!(task.is_none());
         teardown();
     }
@@ -600,12 +631,12 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         // test get search task not found
         let mut parameter = HashMap::new();
         parameter.insert(String::from("author"), String::from("foo"));
         parameter.insert(String::from("param"), String::from("bar"));
-        let task = Database::search_task(&String::from("2"), parameter);
+        let task = Database::search_task(&String::from("2"), parameter).unwrap();
         assert!(task.is_none());
         teardown();
     }
@@ -629,12 +660,12 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         // test get search task not found parameter
         let mut parameter = HashMap::new();
         parameter.insert(String::from("author"), String::from("bar"));
         parameter.insert(String::from("param"), String::from("foo"));
-        let task = Database::search_task(&String::from("1"), parameter);
+        let task = Database::search_task(&String::from("1"), parameter).unwrap();
         assert!(task.is_none());
         teardown();
     }
@@ -669,8 +700,8 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         let task3 = ScheduledTask {
             id : 1,
             module : module_id,
@@ -683,12 +714,12 @@ mod tests_database {
             repeat : false
         };
         // test update task
-        let _ = Database::update_task(&task3);
-        let task_retrieven = Database::get_task(&1);
+        Database::update_task(&task3).unwrap();
+        let task_retrieven = Database::get_task(&1).unwrap();
         assert!(!task_retrieven.is_none());
         assert!(task_retrieven.as_ref().unwrap().id == 1);
         assert!(task_retrieven.unwrap().seconds == task3.seconds);
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 2);
         teardown();
     }
@@ -724,8 +755,8 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         let task3 = ScheduledTask {
             id : 196,
             module : module_id,
@@ -738,11 +769,11 @@ mod tests_database {
             repeat : false
         };
         // test update task failed
-        let _ = Database::update_task(&task3);
-        let task_retrieven = Database::get_task(&1);
+        Database::update_task(&task3).unwrap();
+        let task_retrieven = Database::get_task(&1).unwrap();
         assert!(task_retrieven.as_ref().unwrap().id == 1);
         assert!(task_retrieven.unwrap().seconds == 0);
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 2);
         teardown();
     }
@@ -777,13 +808,13 @@ mod tests_database {
             days : String::new(),
             repeat : false
         };
-        let id1 = Database::add_task(&task1);
-        Database::add_task(&task2);
+        let id1 = Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         // test rm task
-        let task_rm = Database::rm_task(&id1.unwrap());
+        let task_rm = Database::rm_task(&id1);
         assert!(task_rm.is_ok());
-        assert!(task_rm.unwrap() as i32 == id1.unwrap());
-        let tasks = Database::get_tasks();
+        assert!(task_rm.unwrap() as i32 == id1);
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 1);
         assert!(tasks[0].seconds == task2.seconds);
         teardown();
@@ -798,4 +829,351 @@ mod tests_database {
         teardown();
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_unmatched() {
+        setup();
+        assert!(Database::get_unmatched().unwrap().len() == 0);
+        let interaction = Interaction {
+            device_author: Device::new(&1, &String::from("GLaDOs")),
+            body: String::from("make me a sandwich"),
+            ..Interaction::default()
+        };
+        assert!(Database::add_unmatched(&String::from("PBody"), &interaction).is_ok());
+        assert!(Database::add_unmatched(&String::from("Atlas"), &interaction).is_ok());
+        let mut other = interaction.clone();
+        other.body = String::from("open the pod bay doors");
+        assert!(Database::add_unmatched(&String::new(), &other).is_ok());
+        // Most frequent first
+        let unmatched = Database::get_unmatched().unwrap();
+        assert!(unmatched.len() == 2);
+        assert!(unmatched[0] == (String::from("make me a sandwich"), 2));
+        assert!(unmatched[1] == (String::from("open the pod bay doors"), 1));
+        teardown();
+    }
+
+    #[test]
+    fn test_module_store() {
+        setup();
+        let module = String::from("alarm");
+        let key = String::from("last");
+        assert!(Database::get_module_value(&module, &key).unwrap().is_none());
+        assert!(Database::set_module_value(&module, &key, &String::from("07:00")).is_ok());
+        assert!(Database::get_module_value(&module, &key).unwrap().unwrap() == "07:00");
+        // Replace
+        assert!(Database::set_module_value(&module, &key, &String::from("08:00")).is_ok());
+        assert!(Database::get_module_value(&module, &key).unwrap().unwrap() == "08:00");
+        // Isolated per module
+        assert!(Database::get_module_value(&String::from("weather"), &key).unwrap().is_none());
+        teardown();
+    }
+
+    #[test]
+    fn test_get_usernames() {
+        setup();
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::from("PBody"), false).unwrap();
+        Database::insert_new_device(&String::from("PBody_hash2"), &String::from("PBody"), &String::from("Phone"), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::from("Atlas"), false).unwrap();
+        Database::insert_new_device(&String::from("Anonymous_hash"), &String::new(), &String::new(), false).unwrap();
+        assert!(Database::get_usernames().unwrap() == vec![String::from("Atlas"), String::from("PBody")]);
+        teardown();
+    }
+
+    #[test]
+    fn test_add_task_returns_id() {
+        setup();
+        add_module();
+        let task = ScheduledTask {
+            id : 0,
+            module : 1,
+            parameter : String::new(),
+            at : String::new(),
+            seconds : 0,
+            minutes : 0,
+            hours : 0,
+            days : String::new(),
+            repeat : false
+        };
+        assert!(Database::add_task(&task).unwrap() == 1);
+        assert!(Database::add_task(&task).unwrap() == 2);
+        Database::rm_task(&1).unwrap();
+        assert!(Database::add_task(&task).unwrap() == 3);
+        teardown();
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        setup();
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::new(), false).unwrap();
+        let conn = Database::connection().unwrap();
+        // Devices reference existing users
+        assert!(conn.execute("INSERT INTO devices (hash, user_id) VALUES (\"Atlas_hash\", 42)", rusqlite::NO_PARAMS).is_err());
+        assert!(conn.execute("DELETE FROM users", rusqlite::NO_PARAMS).is_err());
+        teardown();
+    }
+
+    #[test]
+    fn test_memory_database() {
+        Database::set_path(":memory:");
+        Database::init_db().unwrap();
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::from("PBody"), false).unwrap();
+        assert!(Database::search_hash(&String::from("PBody_hash")).unwrap());
+        assert!(Database::get_usernames().unwrap() == vec![String::from("PBody")]);
+        // A new database is created by init_db
+        Database::init_db().unwrap();
+        assert!(!Database::search_hash(&String::from("PBody_hash")).unwrap());
+        Database::set_path("rori.db");
+        assert!(Database::path() == "rori.db");
+    }
+
+    #[test]
+    fn test_init_invalid_database() {
+        teardown();
+        let _ = fs::remove_file("rori.db-wal");
+        let _ = fs::remove_file("rori.db-shm");
+        fs::write("rori.db", "This is not a database. ".repeat(100)).unwrap();
+        // An error, not a panic
+        assert!(Database::init_db().is_err());
+        teardown();
+    }
+
+    #[test]
+    fn test_migrate_from_version_1() {
+        teardown();
+        // Database created by a RORI using the version 1
+        {
+            let conn = rusqlite::Connection::open("rori.db").unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.pragma_update(None, "user_version", &1).unwrap();
+            conn.execute("INSERT INTO devices (hash, username, sub_author, devicename, additional_types, is_bridge)
+                          VALUES (\"PBody_hash\", \"PBody\", \"\", \"PBody\", \"\", 0)", rusqlite::NO_PARAMS).unwrap();
+            conn.execute("INSERT INTO emotions (username, love, joy, surprise, anger, sadness, fear)
+                          VALUES (\"PBody\", 10, 0, 0, 0, 0, 0)", rusqlite::NO_PARAMS).unwrap();
+        }
+        Database::init_db().unwrap();
+        assert!(Database::version().unwrap() == MIGRATIONS.len() as i32);
+        // Data are kept
+        assert!(Database::search_hash(&String::from("PBody_hash")).unwrap());
+        let (emotions, updated) = Database::get_emotions(&String::from("PBody")).unwrap().unwrap();
+        assert!(emotions.love == 10);
+        assert!(updated == 0);
+        // Users are extracted from devices
+        let user = Database::get_user(&String::from("PBody")).unwrap().unwrap();
+        assert!(user.username == "PBody");
+        let device = Database::get_device(&String::from("PBody_hash"), &String::from("PBody")).unwrap().unwrap();
+        assert!(device.devicename == "PBody");
+        // New tables are usable
+        assert!(Database::set_module_value(&String::from("alarm"), &String::from("last"), &String::from("7")).is_ok());
+        assert!(Database::set_lang(&String::from("PBody"), &String::from("fr")).is_ok());
+        // Nothing to do the next time
+        assert!(Database::migrate().unwrap() == MIGRATIONS.len() as i32);
+        teardown();
+    }
+
+    #[test]
+    fn test_users() {
+        setup();
+        assert!(Database::get_users().unwrap().len() == 0);
+        // An user is created with its first device
+        Database::insert_new_device(&String::from("GLaDOs"), &String::from("PBody"), &String::from("Atlas"), false).unwrap();
+        Database::insert_new_device(&String::from("Tars"), &String::from(""), &String::from("alexa"), false).unwrap();
+        Database::insert_new_device(&String::from("Weasley"), &String::from("PBody"), &String::from("Asimo"), false).unwrap();
+        let users = Database::get_users().unwrap();
+        assert!(users.len() == 1);
+        assert!(users[0].username == "PBody");
+        assert!(users[0].display_name == "");
+        assert!(Database::get_user(&String::from("Tars")).unwrap().is_none());
+        // Display name
+        assert!(Database::set_display_name(&String::from("PBody"), &String::from("P-Body")).unwrap() == 1);
+        assert!(Database::get_user(&String::from("PBody")).unwrap().unwrap().display_name == "P-Body");
+        assert!(Database::set_display_name(&String::from("Atlas"), &String::from("Atlas")).unwrap() == 0);
+        // Registering an anonymous device creates the user
+        Database::update_username(&2, &String::from("Wall-E")).unwrap();
+        assert!(Database::get_usernames().unwrap() == vec![String::from("PBody"), String::from("Wall-E")]);
+        // An user without devices is kept until purged
+        Database::update_username(&2, &String::new()).unwrap();
+        assert!(Database::get_user(&String::from("Wall-E")).unwrap().is_some());
+        Database::remove_device(&1).unwrap();
+        Database::remove_device(&3).unwrap();
+        assert!(Database::get_users().unwrap().len() == 2);
+        Database::purge_user(&String::from("Wall-E")).unwrap();
+        assert!(Database::get_user(&String::from("Wall-E")).unwrap().is_none());
+        teardown();
+    }
+
+    #[test]
+    fn test_roles() {
+        setup();
+        let pbody = String::from("PBody");
+        let atlas = String::from("Atlas");
+        Database::insert_new_device(&String::from("GLaDOs"), &pbody, &String::from("Atlas"), false).unwrap();
+        Database::insert_new_device(&String::from("Tars"), &atlas, &String::new(), false).unwrap();
+        assert!(Database::get_user(&pbody).unwrap().unwrap().role == Role::User);
+        // Only the first admin is bootstrapped, from its device
+        assert!(Database::bootstrap_admin(&String::from("Wall-E")).unwrap() == 0);
+        assert!(Database::bootstrap_admin(&String::from("GLaDOs")).unwrap() == 1);
+        assert!(Database::get_user(&pbody).unwrap().unwrap().role == Role::Admin);
+        assert!(Database::bootstrap_admin(&String::from("Tars")).unwrap() == 0);
+        assert!(Database::get_user(&atlas).unwrap().unwrap().role == Role::User);
+        assert!(Database::set_role(&atlas, Role::BridgeOperator).unwrap() == 1);
+        assert!(Database::get_users().unwrap()[0].role == Role::BridgeOperator);
+        assert!(Database::set_role(&String::from("Wall-E"), Role::Admin).unwrap() == 0);
+        // The admin unregisters, the user keeps its role
+        Database::update_username(&1, &String::new()).unwrap();
+        assert!(Database::get_user(&pbody).unwrap().unwrap().role == Role::Admin);
+        assert!(Database::bootstrap_admin(&String::from("GLaDOs")).unwrap() == 0);
+        // But the device of the admin gets the role back, as no admin has a device
+        Database::update_username(&1, &String::from("Orange")).unwrap();
+        assert!(Database::bootstrap_admin(&String::from("GLaDOs")).unwrap() == 1);
+        assert!(Database::get_user(&String::from("Orange")).unwrap().unwrap().role == Role::Admin);
+        // A new user with the name of a purged admin is not admin
+        Database::purge_user(&pbody).unwrap();
+        Database::insert_new_device(&String::from("Wheatley"), &pbody, &String::new(), false).unwrap();
+        assert!(Database::get_user(&pbody).unwrap().unwrap().role == Role::User);
+        teardown();
+    }
+
+    #[test]
+    fn test_set_module_enabled() {
+        setup();
+        let conn = rusqlite::Connection::open("rori.db").unwrap();
+        let row = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
+                                VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
+        let id = row.unwrap() as i32;
+        assert!(Database::set_module_enabled(&String::from("foo"), false).unwrap() == 1);
+        assert!(!Database::get_module(&id).unwrap().unwrap().enabled);
+        assert!(Database::get_enabled_modules(1).unwrap().is_empty());
+        assert!(Database::set_module_enabled(&String::from("foo"), true).unwrap() == 1);
+        assert!(Database::get_enabled_modules(1).unwrap().len() == 1);
+        assert!(Database::set_module_enabled(&String::from("bar"), true).unwrap() == 0);
+        teardown();
+    }
+
+    #[test]
+    fn test_link_requests() {
+        setup();
+        let request = LinkRequest {
+            id: 0,
+            hash: String::from("Tars"),
+            username: String::from("PBody"),
+            from_user: true,
+            sub_author: String::new(),
+            code: String::from("123456"),
+            expires: 100,
+        };
+        let id = Database::add_link_request(&request).unwrap();
+        let found = Database::get_link_request(&request.hash, &request.username, 50).unwrap().unwrap();
+        assert!(found == LinkRequest { id: id, ..request.clone() });
+        assert!(Database::get_link_request(&request.hash, &String::from("Atlas"), 50).unwrap().is_none());
+        // Expired requests are ignored
+        assert!(Database::get_link_request(&request.hash, &request.username, 100).unwrap().is_none());
+        // A new request replaces the previous one
+        Database::add_link_request(&LinkRequest { code: String::from("654321"), expires: 200, ..request.clone() }).unwrap();
+        let found = Database::get_link_request(&request.hash, &request.username, 150).unwrap().unwrap();
+        assert!(found.code == "654321");
+        Database::add_link_request(&LinkRequest { username: String::from("Atlas"), ..request.clone() }).unwrap();
+        assert!(Database::rm_expired_link_requests(150).unwrap() == 1);
+        assert!(Database::rm_link_request(&found.id).unwrap() == 1);
+        assert!(Database::get_link_request(&request.hash, &request.username, 150).unwrap().is_none());
+        teardown();
+    }
+
+    // Store some data for PBody and Atlas
+    fn fill_users() {
+        let pbody = String::from("PBody");
+        let atlas = String::from("Atlas");
+        Database::insert_new_device(&String::from("PBody_hash"), &pbody, &String::from("Home"), false).unwrap();
+        Database::insert_new_device(&String::from("Bridge_hash"), &pbody, &String::new(), true).unwrap();
+        Database::insert_new_device(&String::from("Atlas_hash"), &atlas, &String::new(), false).unwrap();
+        Database::set_emotions(&pbody, &Emotions::default(), 0).unwrap();
+        Database::set_lang(&pbody, &String::from("fr")).unwrap();
+        Database::set_lang(&atlas, &String::from("en")).unwrap();
+        Database::set_module_value(&String::from("alarm"), &String::from("PBody:wake"), &String::from("7")).unwrap();
+        Database::set_module_value(&String::from("alarm"), &String::from("PBodyguard"), &String::from("8")).unwrap();
+        add_module();
+        let mut task = ScheduledTask {
+            id : 0,
+            module : 1,
+            parameter : String::from("{\"ring_id\":\"PBody_hash\",\"username\":\"PBody\"}"),
+            at : String::new(),
+            seconds : 0,
+            minutes : 0,
+            hours : 0,
+            days : String::new(),
+            repeat : false
+        };
+        Database::add_task(&task).unwrap();
+        task.parameter = String::from("{\"ring_id\":\"Atlas_hash\",\"username\":\"Atlas\"}");
+        Database::add_task(&task).unwrap();
+    }
+
+    #[test]
+    fn test_export_user() {
+        setup();
+        fill_users();
+        let data = Database::export_user(&String::from("PBody")).unwrap();
+        assert!(data["users"].len() == 1);
+        assert!(data["devices"].len() == 2);
+        assert!(data["devices"][0]["devicename"] == "Home");
+        assert!(data["emotions"].len() == 1);
+        assert!(data["languages"][0]["lang"] == "fr");
+        assert!(data["module_store"].len() == 1);
+        assert!(data["module_store"][0]["key"] == "PBody:wake");
+        assert!(data["scheduler"].len() == 1);
+        assert!(data["scheduler"][0]["id"] == 1);
+        // Unknown user
+        let data = Database::export_user(&String::from("Wheatley")).unwrap();
+        assert!(data.values().all(|rows| rows.is_empty()));
+        teardown();
+    }
+
+    #[test]
+    fn test_purge_user() {
+        setup();
+        fill_users();
+        let pbody = String::from("PBody");
+        assert!(Database::purge_user(&pbody).unwrap() > 0);
+        let data = Database::export_user(&pbody).unwrap();
+        assert!(data.values().all(|rows| rows.is_empty()));
+        // The device is anonymous, the bridge is removed
+        let device = Database::get_device(&String::from("PBody_hash"), &String::new()).unwrap().unwrap();
+        assert!(device.devicename == "");
+        assert!(Database::get_devices_for_hash(&String::from("Bridge_hash")).unwrap().is_empty());
+        assert!(Database::get_module_value(&String::from("alarm"), &String::from("PBodyguard")).unwrap().is_some());
+        // Other users are kept
+        assert!(Database::get_lang(&String::from("Atlas")).unwrap().is_some());
+        assert!(Database::get_tasks().unwrap().len() == 1);
+        assert!(Database::get_users().unwrap().len() == 1);
+        teardown();
+    }
+
+    #[test]
+    fn test_rename_user() {
+        setup();
+        fill_users();
+        let pbody = String::from("PBody");
+        let orange = String::from("Orange");
+        // Rows left by an old Orange are not merged
+        Database::set_lang(&orange, &String::from("de")).unwrap();
+        Database::set_module_value(&String::from("alarm"), &String::from("Orange:sleep"), &String::from("23")).unwrap();
+        assert!(Database::rename_user(&pbody, &orange, &Vec::new()).unwrap() > 0);
+        assert!(Database::get_user(&pbody).unwrap().is_none());
+        let data = Database::export_user(&orange).unwrap();
+        assert!(data["users"].len() == 1);
+        assert!(data["devices"].len() == 2);
+        assert!(data["emotions"].len() == 1);
+        assert!(data["languages"].len() == 1);
+        assert!(data["languages"][0]["lang"] == "fr");
+        assert!(data["module_store"].len() == 1);
+        assert!(data["module_store"][0]["key"] == "Orange:wake");
+        assert!(data["scheduler"].len() == 1);
+        assert!(Database::get_module_value(&String::from("alarm"), &String::from("PBodyguard")).unwrap().is_some());
+        // Other users are kept
+        assert!(Database::get_lang(&String::from("Atlas")).unwrap() == Some(String::from("en")));
+        // Unknown user
+        match Database::rename_user(&pbody, &String::from("Blue"), &Vec::new()) {
+            Err(RoriError::NotFound(_)) => {},
+            _ => panic!("PBody doesn't exist anymore")
+        }
+        teardown();
+    }
+}
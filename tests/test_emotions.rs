@@ -0,0 +1,70 @@
+extern crate core;
+extern crate time;
+#[cfg(test)]
+mod tests_emotions {
+    use core::rori::database::Database;
+    use core::rori::emotions::Emotions;
+    use core::rori::interaction::Interaction;
+    use core::rori::user::Device;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    fn interaction(body: &str) -> Interaction {
+        Interaction {
+            device_author: Device::new(&1, &String::from("GLaDOs")),
+            body: String::from(body),
+            ..Interaction::default()
+        }
+    }
+
+    #[test]
+    fn test_emotions_update() {
+        setup();
+        let username = String::from("PBody");
+        assert!(Emotions::for_user(&username).unwrap() == Emotions::default());
+        let emotions = Emotions::update(&username, &interaction("Thanks RORI!")).unwrap();
+        assert!(emotions.love == 5 && emotions.joy == 5 && emotions.anger == 0);
+        // Stored
+        assert!(Emotions::for_user(&username).unwrap() == emotions);
+        assert!(Database::get_emotions(&username).unwrap().unwrap().0 == emotions);
+        // Insults just after, RORI is angry and surprised
+        let emotions = Emotions::update(&username, &interaction("you are stupid")).unwrap();
+        assert!(emotions.love == 0);
+        assert!(emotions.anger == 17);
+        assert!(emotions.surprise == 5);
+        assert!(emotions.sadness == 10);
+        // Other users are not impacted
+        assert!(Emotions::for_user(&String::from("Atlas")).unwrap() == Emotions::default());
+        teardown();
+    }
+
+    #[test]
+    fn test_emotions_decay() {
+        setup();
+        let emotions = Emotions {
+            love: 100,
+            joy: 50,
+            surprise: 0,
+            anger: 10,
+            sadness: 0,
+            fear: 1,
+        };
+        assert!(emotions.decay(0) == emotions);
+        let decayed = emotions.decay(86400);
+        assert!(decayed.love == 50 && decayed.joy == 25 && decayed.anger == 5);
+        assert!(emotions.decay(30 * 86400) == Emotions::default());
+        // Decay since the last update when read
+        let username = String::from("PBody");
+        Database::set_emotions(&username, &emotions, time::get_time().sec - 2 * 86400).unwrap();
+        assert!(Emotions::for_user(&username).unwrap().love == 25);
+        teardown();
+    }
+}
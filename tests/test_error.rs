@@ -0,0 +1,63 @@
+extern crate core;
+extern crate rusqlite;
+extern crate serde_json;
+#[cfg(test)]
+mod tests_error {
+    use core::rori::database::Database;
+    use core::rori::error::RoriError;
+    use serde_json::Value;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    #[test]
+    fn test_http_status() {
+        assert!(RoriError::Parse(String::from("task")).http_status() == 400);
+        assert!(RoriError::Denied(String::from("bridge Tars")).http_status() == 403);
+        assert!(RoriError::NotFound(String::from("task 1")).http_status() == 404);
+        assert!(RoriError::AlreadyExists(String::from("device")).http_status() == 409);
+        assert!(RoriError::Database(rusqlite::Error::QueryReturnedNoRows).http_status() == 500);
+        assert!(RoriError::Vault(String::from("vault is locked")).http_status() == 500);
+        assert!(RoriError::Transport(String::from("no daemon")).http_status() == 502);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let error = RoriError::from(serde_json::from_str::<Value>("{").unwrap_err());
+        match error {
+            RoriError::Parse(_) => {},
+            _ => panic!("invalid json should be a parse error")
+        }
+    }
+
+    #[test]
+    fn test_already_exists() {
+        setup();
+        let hash = String::from("GLaDOs");
+        assert!(Database::insert_new_device(&hash, &String::new(), &String::new(), false).is_ok());
+        match Database::insert_new_device(&hash, &String::new(), &String::new(), false) {
+            Err(RoriError::AlreadyExists(_)) => {},
+            _ => panic!("second insertion should fail")
+        }
+        teardown();
+    }
+
+    #[test]
+    fn test_database_unavailable() {
+        // The database can't be opened, but nothing should panic
+        Database::set_path("rori_missing_dir/rori.db");
+        match Database::get_devices() {
+            Err(RoriError::Database(_)) => {},
+            _ => panic!("the database should not be usable")
+        }
+        assert!(Database::migrate().is_err());
+        Database::set_path("rori.db");
+    }
+}
@@ -0,0 +1,88 @@
+extern crate core;
+#[cfg(test)]
+mod tests_group {
+    use core::rori::database::Database;
+    use core::rori::error::RoriError;
+    use core::rori::group;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    #[test]
+    fn test_storage() {
+        setup();
+        let aperture = String::from("Aperture");
+        let atlas = String::from("Atlas");
+        let pbody = String::from("PBody");
+        Database::insert_new_device(&String::from("Atlas_hash"), &atlas, &String::new(), false).unwrap();
+        assert!(Database::get_group(&aperture).unwrap().is_none());
+        Database::add_group(&aperture, &atlas).unwrap();
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members.is_empty());
+        assert!(Database::get_group(&aperture).unwrap().unwrap().owner == atlas);
+        // Names are shared with users
+        match Database::add_group(&aperture, &String::new()) {
+            Err(RoriError::AlreadyExists(_)) => {},
+            _ => panic!("Aperture already exists")
+        }
+        assert!(Database::add_group(&atlas, &String::new()).is_err());
+        // Members are sorted and unique
+        assert!(Database::add_group_member(&aperture, &pbody).unwrap() == 1);
+        assert!(Database::add_group_member(&aperture, &atlas).unwrap() == 1);
+        assert!(Database::add_group_member(&aperture, &atlas).unwrap() == 0);
+        assert!(Database::add_group_member(&String::from("Enrichment"), &atlas).is_err());
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![atlas.clone(), pbody.clone()]);
+        assert!(Database::get_groups().unwrap().len() == 1);
+        assert!(Database::rm_group_member(&aperture, &pbody).unwrap() == 1);
+        assert!(Database::rm_group_member(&aperture, &pbody).unwrap() == 0);
+        assert!(Database::rm_group(&aperture).unwrap() > 0);
+        assert!(Database::get_group(&aperture).unwrap().is_none());
+        assert!(Database::get_groups().unwrap().is_empty());
+        teardown();
+    }
+
+    #[test]
+    fn test_resolve() {
+        setup();
+        let aperture = String::from("Aperture");
+        let atlas = String::from("Atlas");
+        // Not a group
+        assert!(group::resolve(&atlas).unwrap() == vec![atlas.clone()]);
+        Database::add_group(&aperture, &String::new()).unwrap();
+        assert!(group::resolve(&aperture).unwrap().is_empty());
+        Database::add_group_member(&aperture, &atlas).unwrap();
+        assert!(group::resolve(&aperture).unwrap() == vec![atlas.clone()]);
+        teardown();
+    }
+
+    #[test]
+    fn test_user_changes() {
+        setup();
+        let aperture = String::from("Aperture");
+        let atlas = String::from("Atlas");
+        let pbody = String::from("PBody");
+        Database::insert_new_device(&String::from("Atlas_hash"), &atlas, &String::new(), false).unwrap();
+        Database::insert_new_device(&String::from("PBody_hash"), &pbody, &String::new(), false).unwrap();
+        Database::add_group(&aperture, &atlas).unwrap();
+        Database::add_group_member(&aperture, &atlas).unwrap();
+        Database::add_group_member(&aperture, &pbody).unwrap();
+        // A renamed user stays in its groups and keeps owning them
+        let tars = String::from("Tars");
+        Database::rename_user(&atlas, &tars, &Vec::new()).unwrap();
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![pbody.clone(), tars.clone()]);
+        assert!(Database::get_group(&aperture).unwrap().unwrap().owner == tars);
+        // A purged user leaves its groups, but the group stays
+        Database::purge_user(&pbody).unwrap();
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![tars.clone()]);
+        Database::purge_user(&tars).unwrap();
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members.is_empty());
+        assert!(Database::get_group(&aperture).unwrap().unwrap().owner.is_empty());
+        teardown();
+    }
+}
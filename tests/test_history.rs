@@ -0,0 +1,122 @@
+extern crate core;
+#[cfg(test)]
+mod tests_history {
+    use core::rori::database::Database;
+    use core::rori::history;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    #[test]
+    fn test_fts_query() {
+        assert!(history::fts_query("") == "");
+        assert!(history::fts_query("  pizza   party ") == "\"pizza\" \"party\"");
+        // FTS5 syntax is escaped
+        assert!(history::fts_query("say \"hi\" OR*") == "\"say\" \"\"\"hi\"\"\" \"OR*\"");
+    }
+
+    #[test]
+    fn test_search() {
+        setup();
+        let pbody = String::from("PBody");
+        let atlas = String::from("Atlas");
+        let device = String::from("PBody_hash");
+        history::record(&pbody, &device, false, "text/plain", "I like pizza with pineapple", 10);
+        history::record(&pbody, &device, true, "text/plain", "Pizza noted", 11);
+        history::record(&pbody, &device, false, "rori/command", "/lang fr", 12);
+        history::record(&atlas, &String::from("Atlas_hash"), false, "text/plain", "pizza is bad", 13);
+        // Only text messages of the user, most recent first
+        let entries = history::search(&pbody, "", 1).unwrap();
+        assert!(entries.len() == 2);
+        assert!(entries[0].body == "Pizza noted");
+        assert!(entries[0].outgoing);
+        assert!(entries[1].time == 10);
+        // Full-text search
+        let entries = history::search(&pbody, "pineapple", 1).unwrap();
+        assert!(entries.len() == 1);
+        assert!(entries[0].device == "PBody_hash");
+        assert!(history::search(&pbody, "PIZZA", 1).unwrap().len() == 2);
+        assert!(history::search(&pbody, "pizza bad", 1).unwrap().len() == 0);
+        assert!(history::search(&atlas, "pizza bad", 1).unwrap().len() == 1);
+        // Invalid FTS5 syntax doesn't fail
+        assert!(history::search(&pbody, "\"pizza AND * NEAR(", 1).is_ok());
+        teardown();
+    }
+
+    #[test]
+    fn test_search_punctuation() {
+        setup();
+        let pbody = String::from("PBody");
+        let device = String::from("PBody_hash");
+        history::record(&pbody, &device, false, "text/plain", "what about \"X\"?", 10);
+        history::record(&pbody, &device, false, "text/plain", "foo-bar AND baz", 11);
+        history::record(&pbody, &device, false, "text/plain", "salt and pepper", 12);
+        // Users' text is quoted before the MATCH, whatever the caller
+        assert!(Database::search_history(&pbody, "what about \"X", 0, 10).unwrap().len() == 1);
+        assert!(Database::search_history(&pbody, "foo-bar", 0, 10).unwrap().len() == 1);
+        assert!(Database::search_history(&pbody, "AND", 0, 10).unwrap().len() == 2);
+        assert!(Database::search_history(&pbody, "NEAR(pepper", 0, 10).unwrap().is_empty());
+        assert!(history::search(&pbody, "pep*", 1).unwrap().is_empty());
+        assert!(history::search(&pbody, "\"salt", 1).unwrap().len() == 1);
+        teardown();
+    }
+
+    #[test]
+    fn test_pages() {
+        setup();
+        let pbody = String::from("PBody");
+        for i in 0..(history::PAGE_SIZE + 3) {
+            history::record(&pbody, &String::from("PBody_hash"), false, "text/plain", &*format!("message {}", i), i as i64);
+        }
+        let first = history::search(&pbody, "message", 1).unwrap();
+        assert!(first.len() == history::PAGE_SIZE as usize);
+        assert!(first[0].body == format!("message {}", history::PAGE_SIZE + 2));
+        let second = history::search(&pbody, "message", 2).unwrap();
+        assert!(second.len() == 3);
+        assert!(second[2].body == "message 0");
+        assert!(history::search(&pbody, "message", 3).unwrap().is_empty());
+        teardown();
+    }
+
+    #[test]
+    fn test_removed_user() {
+        setup();
+        let pbody = String::from("PBody");
+        let device = String::from("PBody_hash");
+        Database::insert_new_device(&device, &pbody, &String::new(), false).unwrap();
+        history::record(&pbody, &device, false, "text/plain", "I like pizza", 10);
+        assert!(history::search(&pbody, "pizza", 1).unwrap().len() == 1);
+        // Unregistered, the history of the user is kept
+        let id = Database::get_devices_for_hash(&device).unwrap()[0].id;
+        Database::update_username(&id, &String::new()).unwrap();
+        assert!(history::search(&pbody, "pizza", 1).unwrap().len() == 1);
+        // Until the user is purged, then a new user with the same name doesn't see it
+        Database::purge_user(&pbody).unwrap();
+        assert!(history::search(&pbody, "", 1).unwrap().is_empty());
+        Database::update_username(&id, &pbody).unwrap();
+        assert!(history::search(&pbody, "pizza", 1).unwrap().is_empty());
+        teardown();
+    }
+
+    #[test]
+    fn test_format() {
+        setup();
+        assert!(history::format(&Vec::new()) == "Nothing found");
+        let pbody = String::from("PBody");
+        history::record(&pbody, &String::from("PBody_hash"), false, "text/plain", "hello", 0);
+        history::record(&pbody, &String::from("PBody_hash"), true, "text/plain", "hi", 60);
+        let answer = history::format(&history::search(&pbody, "", 1).unwrap());
+        let lines: Vec<&str> = answer.lines().collect();
+        assert!(lines.len() == 2);
+        assert!(lines[0].ends_with("] rori: hi"));
+        assert!(lines[1].ends_with("] you: hello"));
+        teardown();
+    }
+}
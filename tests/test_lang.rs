@@ -0,0 +1,56 @@
+extern crate core;
+#[cfg(test)]
+mod tests_lang {
+    use core::rori::database::Database;
+    use core::rori::interaction::Interaction;
+    use core::rori::lang;
+    use core::rori::user::Device;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(lang::detect(&String::from("What is the weather in Paris?")).unwrap() == "en");
+        assert!(lang::detect(&String::from("Quel est le temps à Paris ?")).unwrap() == "fr");
+        assert!(lang::detect(&String::from("Bonjour, comment ça va ?")).unwrap() == "fr");
+        assert!(lang::detect(&String::from("Paris")).is_none());
+        assert!(lang::is_supported("fr"));
+        assert!(!lang::is_supported("tlh"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        setup();
+        let mut interaction = Interaction {
+            device_author: Device::new(&1, &String::from("GLaDOs")),
+            body: String::from("Paris"),
+            lang: String::new(),
+            ..Interaction::default()
+        };
+        let username = String::from("PBody");
+        // Default
+        assert!(lang::resolve(&username, &interaction) == "en");
+        // Detected
+        interaction.body = String::from("quel temps fait-il à Paris");
+        assert!(lang::resolve(&username, &interaction) == "fr");
+        // Sent by the client
+        interaction.metadatas.insert(String::from("lang"), String::from("en"));
+        assert!(lang::resolve(&username, &interaction) == "en");
+        // Chosen by the user
+        assert!(Database::get_lang(&username).unwrap().is_none());
+        assert!(Database::set_lang(&username, &String::from("fr")).is_ok());
+        assert!(Database::get_lang(&username).unwrap().unwrap() == "fr");
+        assert!(lang::resolve(&username, &interaction) == "fr");
+        // But not for anonymous
+        assert!(lang::resolve(&String::new(), &interaction) == "en");
+        teardown();
+    }
+}
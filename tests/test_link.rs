@@ -0,0 +1,29 @@
+extern crate core;
+extern crate serde_json;
+#[cfg(test)]
+mod tests_link {
+    use core::rori::link::{self, LinkPolicy, DEFAULT_TTL};
+    use serde_json::Value;
+
+    #[test]
+    fn test_policy_from_config() {
+        assert!(LinkPolicy::from_config(&Value::Null) == LinkPolicy::new());
+        assert!(LinkPolicy::new().ttl == DEFAULT_TTL);
+        assert!(!LinkPolicy::new().code);
+        let config: Value = serde_json::from_str("{\"ttl\": 300, \"code\": true}").unwrap();
+        assert!(LinkPolicy::from_config(&config) == LinkPolicy { ttl: 300, code: true });
+        // Invalid ttl
+        let config: Value = serde_json::from_str("{\"ttl\": -1}").unwrap();
+        assert!(LinkPolicy::from_config(&config).ttl == DEFAULT_TTL);
+    }
+
+    #[test]
+    fn test_generate_code() {
+        let codes: Vec<String> = (0..10).map(|_| link::generate_code().unwrap()).collect();
+        for code in &codes {
+            assert!(code.len() == 6);
+            assert!(code.chars().all(|c| c.is_ascii_digit()));
+        }
+        assert!(codes.iter().any(|code| *code != codes[0]));
+    }
+}
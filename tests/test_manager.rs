@@ -7,6 +7,7 @@ mod mocks;
 mod tests_manager {
     use core::rori::database::Database;
     use core::rori::manager::Manager;
+    use core::rori::presence::{self, Presence};
     use mocks::Daemon;
     use std::fs;
     use std::sync::atomic::{AtomicBool, Ordering};
@@ -16,9 +17,9 @@ mod tests_manager {
 
     fn setup() {
         let _ = fs::remove_file("rori.db");
-        Database::init_db(); // assert this function is correct.
-        let _ = Database::insert_new_device(&String::from("Atlas"), &String::new(), &String::new(), false);
-        let _ = Database::insert_new_device(&String::from("Heisenberg"), &String::new(), &String::new(), false);
+        Database::init_db().unwrap(); // assert this function is correct.
+        Database::insert_new_device(&String::from("Atlas"), &String::new(), &String::new(), false).unwrap();
+        Database::insert_new_device(&String::from("Heisenberg"), &String::new(), &String::new(), false).unwrap();
     }
 
     fn teardown() {
@@ -52,7 +53,7 @@ mod tests_manager {
         assert!(account.id == "GLaDOs_id");
 
         // Ensures that contacts are in server and in db.
-        assert!(Database::get_devices().len() == 4);
+        assert!(Database::get_devices().unwrap().len() == 4);
         let devices = m.unwrap().server.anonymous_user.devices;
         assert!(devices.len() == 4);
         match devices.iter().find(|d| d.ring_id == "Atlas") {
@@ -211,6 +212,59 @@ mod tests_manager {
         teardown();
     }
 
+    #[test]
+    // Scenario
+    // 1. Ensures that registered devices are subscribed when the manager starts
+    // 2. Ensures that a manager correctly handle signal newBuddyNotification
+    fn manager_handle_signal_new_buddy_notification() {
+        setup();
+        Database::insert_new_device(&String::from("PBody"), &String::from("PBody"), &String::new(), false).unwrap();
+        let daemon = Arc::new(Mutex::new(Daemon::new()));
+        let cloned_daemon = daemon.clone();
+        let daemon_thread = thread::spawn(move|| {
+            Daemon::run(cloned_daemon);
+        });
+        let one_sec = Duration::from_millis(1000);
+        thread::sleep(one_sec);
+
+        let shared_manager : Arc<Mutex<Manager>> = Arc::new(Mutex::new(
+            Manager::init("GLaDOs_id")
+            .ok().expect("Can't initialize ConfigurationManager"))
+        );
+        // Only registered devices are subscribed
+        let storage = daemon.lock().unwrap().storage.clone();
+        let subscribed = storage.lock().unwrap().buddies_subscribed.clone();
+        assert!(subscribed == vec![(String::from("PBody"), true)]);
+
+        let shared_manager_cloned = shared_manager.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_cloned = stop.clone();
+        let test = thread::spawn(move || {
+            Manager::handle_signals(shared_manager_cloned, stop_cloned);
+        });
+        thread::sleep(one_sec);
+
+        let pbody = String::from("PBody");
+        let hundred_millis = Duration::from_millis(100);
+        for online in vec![true, false] {
+            daemon.lock().unwrap().emit_new_buddy_notification(&pbody, online);
+            let expected = if online { Presence::Online } else { Presence::Offline };
+            let mut idx_signal = 0;
+            while idx_signal < 100 && presence::get(&pbody).presence != expected {
+                thread::sleep(hundred_millis);
+                idx_signal += 1;
+            }
+            assert!(presence::get(&pbody).presence == expected);
+        }
+        assert!(presence::get(&pbody).last_seen > 0);
+
+        stop.store(true, Ordering::SeqCst);
+        let _ = test.join();
+        daemon.lock().unwrap().stop();
+        let _ = daemon_thread.join();
+        teardown();
+    }
+
     #[test]
     // Scenario
     // 1. Retrieve account list
@@ -224,7 +278,7 @@ mod tests_manager {
         let one_sec = Duration::from_millis(1000);
         thread::sleep(one_sec);
 
-        let account_list = Manager::get_account_list();
+        let account_list = Manager::get_account_list().unwrap();
         assert!(account_list.len() == 1);
         let account = account_list.first().unwrap();
         assert!(account.id == "GLaDOs_id");
@@ -250,8 +304,8 @@ mod tests_manager {
         });
         let one_sec = Duration::from_millis(1000);
         thread::sleep(one_sec);
-        Manager::add_account("caroline.gz", "GLaDOs is a bad robot", true);
-        Manager::add_account("Cave Johnson", "Chell is my daughter", false);
+        Manager::add_account("caroline.gz", "GLaDOs is a bad robot", true).unwrap();
+        Manager::add_account("Cave Johnson", "Chell is my daughter", false).unwrap();
         let storage = daemon.lock().unwrap().storage.clone();
         let accounts_added = storage.lock().unwrap().accounts_added.clone();
         assert!(accounts_added.len() == 2);
@@ -0,0 +1,30 @@
+extern crate core;
+extern crate serde_json;
+#[cfg(test)]
+mod tests_normalizer {
+    use core::rori::normalizer::Normalizer;
+    use serde_json::from_str;
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = Normalizer::new();
+        assert!(normalizer.normalize(&String::from("Ça va ?")) == "ca va");
+        assert!(normalizer.normalize(&String::from("RORI,   réveille-moi à 7h!")) == "reveille moi a 7h");
+        assert!(normalizer.normalize(&String::from("  Rori what's   up")) == "what s up");
+        // Only as a leading word
+        assert!(normalizer.normalize(&String::from("roribot is here, rori")) == "roribot is here rori");
+        // NFKC
+        assert!(normalizer.normalize(&String::from("ｆｕｌｌ ﬁle")) == "full file");
+    }
+
+    #[test]
+    fn test_normalize_from_config() {
+        let config = from_str(r#"{"fold_accents": false, "strip_punctuation": false, "strip_address": false}"#).unwrap();
+        let normalizer = Normalizer::from_config(&config);
+        assert!(!normalizer.fold_accents);
+        assert!(normalizer.collapse_whitespaces);
+        assert!(normalizer.normalize(&String::from("Rori,   ça va ?")) == "rori, ça va ?");
+        // Missing config means all steps
+        assert!(Normalizer::from_config(&serde_json::Value::Null) == Normalizer::new());
+    }
+}
@@ -0,0 +1,45 @@
+extern crate core;
+#[cfg(test)]
+mod tests_presence {
+    use core::rori::presence::{self, Presence};
+    use core::rori::user::Device;
+
+    #[test]
+    fn test_update() {
+        let atlas = String::from("Atlas_presence");
+        // Never reported
+        assert!(presence::get(&atlas).presence == Presence::Unknown);
+        assert!(presence::get(&atlas).last_seen == 0);
+        // An offline device was never seen
+        presence::update(&atlas, false);
+        assert!(presence::get(&atlas).presence == Presence::Offline);
+        assert!(presence::get(&atlas).last_seen == 0);
+        presence::update(&atlas, true);
+        assert!(presence::get(&atlas).presence == Presence::Online);
+        let last_seen = presence::get(&atlas).last_seen;
+        assert!(last_seen > 0);
+        presence::update(&atlas, false);
+        assert!(presence::get(&atlas).presence == Presence::Offline);
+        assert!(presence::get(&atlas).last_seen >= last_seen);
+    }
+
+    #[test]
+    fn test_seen() {
+        let pbody = String::from("PBody_presence");
+        presence::update(&pbody, false);
+        // A device sending a message is online
+        presence::seen(&pbody);
+        assert!(presence::get(&pbody).presence == Presence::Online);
+        assert!(presence::get(&pbody).last_seen > 0);
+        assert!(Presence::Online < Presence::Unknown && Presence::Unknown < Presence::Offline);
+        assert!(Presence::Offline.as_str() == "offline");
+    }
+
+    #[test]
+    fn test_device() {
+        let device = Device::new(&1, &String::from("Wheatley_presence"));
+        presence::set(&device.ring_id, Presence::Offline);
+        assert!(device.presence().presence == Presence::Offline);
+        assert!(device.presence().last_seen == 0);
+    }
+}
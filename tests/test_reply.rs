@@ -0,0 +1,49 @@
+extern crate core;
+extern crate serde_json;
+#[cfg(test)]
+mod tests_reply {
+    use core::rori::reply::{self, ErrorCode, Reply, PROTOCOL_VERSION};
+    use serde_json::{self, Value};
+
+    #[test]
+    fn test_to_json() {
+        let answer = Reply::Registered {
+            registered: false,
+            username: String::from("Atlas \"the\" robot"),
+            sa: String::from("PBody"),
+        };
+        let body = reply::to_json(&answer).unwrap();
+        let value: Value = serde_json::from_str(&*body).unwrap();
+        assert!(value["version"] == PROTOCOL_VERSION);
+        assert!(value["type"] == "registered");
+        assert!(value["registered"] == false);
+        assert!(value["username"] == "Atlas \"the\" robot");
+        assert!(value["sa"] == "PBody");
+
+        let body = reply::to_json(&Reply::error(ErrorCode::AlreadyRegistered, "Atlas already registered")).unwrap();
+        let value: Value = serde_json::from_str(&*body).unwrap();
+        assert!(value["type"] == "error");
+        assert!(value["code"] == "already_registered");
+        assert!(value["err"] == "Atlas already registered");
+    }
+
+    #[test]
+    fn test_parse() {
+        let replies = vec![
+            Reply::DeviceRegistered { dregistered: true, devicename: String::from("Home") },
+            Reply::LinkCode { link_code: String::from("123456"), device: String::from("Atlas_hash"), ttl: 600 },
+            Reply::TaskRemoved { task: 3 },
+            Reply::Export { export: serde_json::from_str("{\"users\":[]}").unwrap() },
+            Reply::error(ErrorCode::Denied, "/role"),
+        ];
+        for answer in replies {
+            assert!(reply::parse(&*reply::to_json(&answer).unwrap()).unwrap() == answer);
+        }
+        assert!(reply::parse("{\"version\":1, \"type\":\"lang\", \"lang\":\"fr\"}").unwrap() == Reply::Lang { lang: String::from("fr") });
+        // Invalid or newer messages are refused
+        assert!(reply::parse("{\"registered\":false, \"username\":\"Atlas\"\"sa\":\"\"}").is_err());
+        assert!(reply::parse("{\"type\":\"lang\", \"lang\":\"fr\"}").is_err());
+        assert!(reply::parse("{\"version\":2, \"type\":\"lang\", \"lang\":\"fr\"}").is_err());
+        assert!(reply::parse("{\"version\":1, \"type\":\"cake\"}").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+extern crate core;
+#[cfg(test)]
+mod tests_repository {
+    use core::rori::database::Database;
+    use core::rori::error::RoriError;
+    use core::rori::link::LinkRequest;
+    use core::rori::repository::{InMemoryRepository, Repository, Role};
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    // Both repositories must behave the same way
+    fn scenario(repository: &dyn Repository) {
+        let glados = String::from("GLaDOs");
+        let tars = String::from("Tars");
+        let pbody = String::from("PBody");
+        assert!(repository.get_devices().unwrap().is_empty());
+        // Insert devices
+        let id = repository.insert_new_device(&glados, &pbody, &String::from("Atlas"), false).unwrap() as i32;
+        match repository.insert_new_device(&glados, &pbody, &String::from("Atlas"), false) {
+            Err(RoriError::AlreadyExists(_)) => {},
+            _ => panic!("second insertion should fail")
+        }
+        let anonymous = repository.insert_new_device(&tars, &String::new(), &String::new(), false).unwrap() as i32;
+        assert!(repository.get_devices().unwrap().len() == 2);
+        let device = repository.get_device(&glados, &pbody).unwrap().unwrap();
+        assert!(device.id == id);
+        assert!(device.devicename == "Atlas");
+        assert!(!device.is_bridge);
+        assert!(repository.get_device(&glados, &String::new()).unwrap().is_none());
+        // Users
+        let users = repository.get_users().unwrap();
+        assert!(users.len() == 1);
+        assert!(users[0].username == "PBody");
+        assert!(repository.set_display_name(&pbody, &String::from("P-Body")).unwrap() == 1);
+        assert!(repository.get_user(&pbody).unwrap().unwrap().display_name == "P-Body");
+        // Roles
+        assert!(repository.get_user(&pbody).unwrap().unwrap().role == Role::User);
+        assert!(repository.bootstrap_admin(&tars).unwrap() == 0);
+        assert!(repository.bootstrap_admin(&glados).unwrap() == 1);
+        assert!(repository.bootstrap_admin(&glados).unwrap() == 0);
+        assert!(repository.get_user(&pbody).unwrap().unwrap().role == Role::Admin);
+        assert!(repository.set_role(&pbody, Role::BridgeOperator).unwrap() == 1);
+        assert!(repository.get_user(&pbody).unwrap().unwrap().role == Role::BridgeOperator);
+        assert!(repository.set_role(&tars, Role::Admin).unwrap() == 0);
+        // Update devices
+        assert!(repository.update_devicename(&id, &String::from("Jupiter")).unwrap() == 1);
+        assert!(repository.get_devices_for_username(&*pbody).unwrap()[0].devicename == "Jupiter");
+        assert!(repository.update_username(&anonymous, &String::from("Wall-E")).unwrap() == 1);
+        assert!(repository.get_devices_for_hash(&*tars).unwrap()[0].username == "Wall-E");
+        assert!(repository.get_users().unwrap().len() == 2);
+        // Rename users
+        let walle = String::from("Wall-E");
+        assert!(repository.rename_user(&walle, &String::from("Eve")).unwrap() > 0);
+        assert!(repository.get_user(&walle).unwrap().is_none());
+        assert!(repository.get_devices_for_hash(&*tars).unwrap()[0].username == "Eve");
+        assert!(repository.rename_user(&String::from("Eve"), &walle).unwrap() > 0);
+        // Bridges
+        assert!(!repository.is_bridge(&tars).unwrap());
+        assert!(repository.bridgify(&anonymous).unwrap() == 1);
+        assert!(repository.update_sub_author(&anonymous, &String::from("Eve")).unwrap() == 1);
+        assert!(repository.is_bridge(&tars).unwrap());
+        assert!(repository.sub_author(&tars, &String::from("Eve")).unwrap() == "Wall-E");
+        assert!(repository.sub_author(&tars, &String::from("Asimo")).unwrap() == "");
+        assert!(repository.sub_author_id(&tars, &String::from("Wall-E")).unwrap() == "Eve");
+        assert!(repository.sub_author_id(&tars, &String::from("Asimo")).unwrap() == "");
+        // Datatypes
+        assert!(repository.get_datatypes(&id).unwrap().is_empty());
+        repository.set_datatypes(&id, vec![String::from("music"), String::from("command")]).unwrap();
+        assert!(repository.get_datatypes(&id).unwrap() == vec![String::from("music"), String::from("command")]);
+        // Users without devices are kept until purged
+        assert!(repository.remove_device(&anonymous).unwrap() == 1);
+        assert!(repository.get_user(&String::from("Wall-E")).unwrap().is_some());
+        assert!(repository.update_username(&id, &String::new()).unwrap() == 1);
+        assert!(repository.get_users().unwrap().len() == 2);
+        assert!(repository.remove_device(&anonymous).unwrap() == 0);
+        assert!(repository.purge_user(&String::from("Wall-E")).unwrap() == 1);
+        assert!(repository.get_users().unwrap().len() == 1);
+    }
+
+    // Data owned by users must behave the same way too
+    fn data_scenario(repository: &dyn Repository) {
+        let glados = String::from("GLaDOs");
+        let pbody = String::from("PBody");
+        let atlas = String::from("Atlas");
+        let id = repository.insert_new_device(&glados, &pbody, &String::new(), false).unwrap() as i32;
+        repository.insert_new_device(&String::from("Tars"), &atlas, &String::new(), false).unwrap();
+        // Groups
+        let testers = String::from("testers");
+        assert!(repository.add_group(&testers, &pbody).is_ok());
+        assert!(repository.get_group(&testers).unwrap().unwrap().owner == pbody);
+        match repository.add_group(&pbody, &String::new()) {
+            Err(RoriError::AlreadyExists(_)) => {},
+            _ => panic!("a group can't use the name of an user")
+        }
+        assert!(repository.add_group_member(&testers, &pbody).unwrap() == 1);
+        assert!(repository.add_group_member(&testers, &atlas).unwrap() == 1);
+        assert!(repository.add_group_member(&testers, &atlas).unwrap() == 0);
+        match repository.add_group_member(&String::from("cores"), &pbody) {
+            Err(RoriError::NotFound(_)) => {},
+            _ => panic!("unknown group")
+        }
+        assert!(repository.get_group(&testers).unwrap().unwrap().members == vec![atlas.clone(), pbody.clone()]);
+        assert!(repository.rm_group_member(&testers, &atlas).unwrap() == 1);
+        assert!(repository.get_groups().unwrap().len() == 1);
+        // Link requests
+        let request = LinkRequest {
+            id: 0,
+            hash: glados.clone(),
+            username: pbody.clone(),
+            from_user: true,
+            sub_author: String::new(),
+            code: String::from("1234"),
+            expires: 100,
+        };
+        let request_id = repository.add_link_request(&request).unwrap();
+        assert!(repository.get_link_request(&glados, &pbody, 50).unwrap().unwrap().id == request_id);
+        assert!(repository.get_link_request(&glados, &pbody, 100).unwrap().is_none());
+        assert!(repository.rm_expired_link_requests(50).unwrap() == 0);
+        assert!(repository.rm_link_request(&request_id).unwrap() == 1);
+        repository.add_link_request(&request).unwrap();
+        assert!(repository.rm_expired_link_requests(100).unwrap() == 1);
+        // Preferences and languages
+        assert!(repository.set_preference(&pbody, &String::from("music"), &id).unwrap() == 1);
+        assert!(repository.rm_preference(&pbody, &String::from("music")).unwrap() == 1);
+        assert!(repository.rm_preference(&pbody, &String::from("music")).unwrap() == 0);
+        repository.set_preference(&pbody, &String::new(), &id).unwrap();
+        repository.set_lang(&pbody, &String::from("fr")).unwrap();
+        let data = repository.export_user(&pbody).unwrap();
+        assert!(data["languages"].len() == 1);
+        assert!(data["preferences"].len() == 1);
+        assert!(data["group_members"].len() == 1);
+        // Purge
+        assert!(repository.purge_user(&pbody).unwrap() > 0);
+        assert!(repository.get_user(&pbody).unwrap().is_none());
+        assert!(repository.get_group(&testers).unwrap().unwrap().members.is_empty());
+        assert!(repository.get_group(&testers).unwrap().unwrap().owner.is_empty());
+        let data = repository.export_user(&pbody).unwrap();
+        assert!(data.values().all(|rows| rows.is_empty()));
+        assert!(repository.rm_group(&testers).unwrap() == 1);
+        assert!(repository.get_group(&testers).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_in_memory_repository() {
+        scenario(&InMemoryRepository::new());
+        data_scenario(&InMemoryRepository::new());
+    }
+
+    #[test]
+    fn test_database_repository() {
+        setup();
+        scenario(&Database);
+        teardown();
+        setup();
+        data_scenario(&Database);
+        teardown();
+    }
+}
@@ -0,0 +1,75 @@
+extern crate core;
+#[cfg(test)]
+mod tests_routing {
+    use core::rori::database::Database;
+    use core::rori::history;
+    use core::rori::presence::{self, Presence};
+    use core::rori::routing;
+    use std::fs;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+    }
+
+    fn teardown() {
+        let _ = fs::remove_file("rori.db");
+    }
+
+    fn hashes(username: &String, datatype: &str) -> Vec<String> {
+        routing::route(username, &String::from(datatype)).unwrap().into_iter().map(|r| r.device.hash).collect()
+    }
+
+    #[test]
+    fn test_route() {
+        setup();
+        let pbody = String::from("PBody");
+        let home = Database::insert_new_device(&String::from("Home_hash"), &pbody, &String::from("Home"), false).unwrap() as i32;
+        let phone = Database::insert_new_device(&String::from("Phone_hash"), &pbody, &String::from("Phone"), false).unwrap() as i32;
+        Database::insert_new_device(&String::from("Bridge_hash"), &pbody, &String::new(), true).unwrap();
+        Database::set_datatypes(&home, vec![String::from("music/play")]).unwrap();
+        history::record(&pbody, &String::from("Home_hash"), false, "text/plain", "hello", 10);
+        history::record(&pbody, &String::from("Phone_hash"), false, "text/plain", "hello", 20);
+        history::record(&pbody, &String::from("Home_hash"), true, "text/plain", "hi", 30);
+
+        // Text goes to every device but bridges, the most recently active first
+        assert!(hashes(&pbody, "text/plain") == vec![String::from("Phone_hash"), String::from("Home_hash")]);
+        let routes = routing::route(&pbody, &String::from("text/plain")).unwrap();
+        assert!(routes[0].last_active == 20);
+        assert!(routes[0].presence == Presence::Unknown);
+        // Other datatypes only to devices handling them
+        assert!(hashes(&pbody, "music/play") == vec![String::from("Home_hash")]);
+        assert!(hashes(&pbody, "video/play").is_empty());
+        assert!(hashes(&String::from("Atlas"), "text/plain").is_empty());
+
+        // Preferences, for every datatype or for one
+        Database::set_preference(&pbody, &String::new(), &home).unwrap();
+        assert!(hashes(&pbody, "text/plain")[0] == "Home_hash");
+        Database::set_preference(&pbody, &String::from("text/plain"), &phone).unwrap();
+        assert!(hashes(&pbody, "text/plain")[0] == "Phone_hash");
+        assert!(Database::get_preferences(&pbody).unwrap().len() == 2);
+        assert!(Database::rm_preference(&pbody, &String::from("text/plain")).unwrap() == 1);
+        assert!(hashes(&pbody, "text/plain")[0] == "Home_hash");
+
+        // Offline devices come last, even if preferred
+        presence::set(&String::from("Home_hash"), Presence::Offline);
+        assert!(hashes(&pbody, "text/plain") == vec![String::from("Phone_hash"), String::from("Home_hash")]);
+        assert!(hashes(&pbody, "music/play") == vec![String::from("Home_hash")]);
+        // The preferred device with an unknown presence comes before an online device
+        presence::set(&String::from("Home_hash"), Presence::Unknown);
+        presence::set(&String::from("Phone_hash"), Presence::Online);
+        assert!(hashes(&pbody, "text/plain") == vec![String::from("Home_hash"), String::from("Phone_hash")]);
+        // Without preference, online devices come first
+        Database::rm_preference(&pbody, &String::new()).unwrap();
+        presence::set(&String::from("Home_hash"), Presence::Online);
+        presence::set(&String::from("Phone_hash"), Presence::Unknown);
+        assert!(hashes(&pbody, "text/plain") == vec![String::from("Home_hash"), String::from("Phone_hash")]);
+        presence::set(&String::from("Home_hash"), Presence::Unknown);
+
+        // Preferences follow the user
+        Database::set_preference(&pbody, &String::new(), &home).unwrap();
+        Database::purge_user(&pbody).unwrap();
+        assert!(Database::get_preferences(&pbody).unwrap().is_empty());
+        teardown();
+    }
+}
@@ -8,7 +8,7 @@ mod tests_scheduler {
 
     fn setup() {
         let _ = fs::remove_file("rori.db");
-        Database::init_db(); // assert this function is correct.
+        Database::init_db().unwrap(); // assert this function is correct.
     }
 
     fn teardown() {
@@ -22,7 +22,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -45,10 +45,10 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         Scheduler::new();
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 2);
         teardown();
     }
@@ -56,8 +56,11 @@ mod tests_scheduler {
     #[test]
     fn test_init_scheduler_ok_invalid_module() {
         setup();
-        // Insert module foo
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        // Insert module foo, removed later (generate_modules.py doesn't check foreign keys)
+        let conn = rusqlite::Connection::open("rori.db").unwrap();
+        let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
+                                VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -80,10 +83,11 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
+        let _ = conn.execute("DELETE FROM modules", rusqlite::NO_PARAMS);
         Scheduler::new();
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 0);
         teardown();
     }
@@ -95,7 +99,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -118,10 +122,10 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         Scheduler::new();
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 0);
         teardown();
     }
@@ -155,10 +159,10 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         Scheduler::new();
-        let tasks = Database::get_tasks();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 0);
         teardown();
     }
@@ -170,7 +174,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let mut task1 = HashMap::new();
         task1.insert(String::from("id"), String::from("0"));
         task1.insert(String::from("module"), String::from("1"));
@@ -183,8 +187,8 @@ mod tests_scheduler {
         task1.insert(String::from("repeat"), String::from("True"));
         let mut scheduler = Scheduler::new();
         let task1 = serde_json::to_string(&task1).unwrap_or(String::new());
-        scheduler.add_task(&task1);
-        let tasks = Database::get_tasks();
+        scheduler.add_task(&task1).unwrap();
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 1);
         teardown();
     }
@@ -196,7 +200,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let mut task1 = HashMap::new();
         task1.insert(String::from("id"), String::from("0"));
         task1.insert(String::from("module"), String::from("1"));
@@ -208,8 +212,8 @@ mod tests_scheduler {
         task1.insert(String::from("repeat"), String::from("True"));
         let mut scheduler = Scheduler::new();
         let task1 = serde_json::to_string(&task1).unwrap_or(String::new());
-        scheduler.add_task(&task1);
-        let tasks = Database::get_tasks();
+        assert!(scheduler.add_task(&task1).is_err());
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 0);
         teardown();
     }
@@ -221,7 +225,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -233,9 +237,9 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         let mut scheduler = Scheduler::new();
-        let pre_update_task = Database::get_tasks()[0].clone();
+        let pre_update_task = Database::get_tasks().unwrap()[0].clone();
 
         let mut task1 = HashMap::new();
         task1.insert(String::from("id"), String::from("1"));
@@ -248,9 +252,9 @@ mod tests_scheduler {
         task1.insert(String::from("days"), String::new());
         task1.insert(String::from("repeat"), String::from("True"));
         let task1 = serde_json::to_string(&task1).unwrap_or(String::new());
-        scheduler.update_task(&task1);
+        scheduler.update_task(&task1).unwrap();
 
-        let new_tasks = Database::get_tasks();
+        let new_tasks = Database::get_tasks().unwrap();
         assert!(new_tasks.len() == 1);
         assert!(new_tasks[0] != pre_update_task);
 
@@ -264,7 +268,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -276,9 +280,9 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         let mut scheduler = Scheduler::new();
-        let pre_update_task = Database::get_tasks()[0].clone();
+        let pre_update_task = Database::get_tasks().unwrap()[0].clone();
 
         let mut task1 = HashMap::new();
         task1.insert(String::from("module"), String::from("1"));
@@ -290,9 +294,9 @@ mod tests_scheduler {
         task1.insert(String::from("days"), String::new());
         task1.insert(String::from("repeat"), String::from("True"));
         let task1 = serde_json::to_string(&task1).unwrap_or(String::new());
-        scheduler.update_task(&task1);
+        assert!(scheduler.update_task(&task1).is_err());
 
-        let new_tasks = Database::get_tasks();
+        let new_tasks = Database::get_tasks().unwrap();
         assert!(new_tasks.len() == 1);
         assert!(new_tasks[0] == pre_update_task);
 
@@ -306,7 +310,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -318,9 +322,9 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
+        Database::add_task(&task1).unwrap();
         let mut scheduler = Scheduler::new();
-        let pre_update_task = Database::get_tasks()[0].clone();
+        let pre_update_task = Database::get_tasks().unwrap()[0].clone();
 
         let mut task1 = HashMap::new();
         task1.insert(String::from("id"), String::from("1412"));
@@ -333,9 +337,9 @@ mod tests_scheduler {
         task1.insert(String::from("days"), String::new());
         task1.insert(String::from("repeat"), String::from("True"));
         let task1 = serde_json::to_string(&task1).unwrap_or(String::new());
-        scheduler.update_task(&task1);
+        assert!(scheduler.update_task(&task1).is_err());
 
-        let new_tasks = Database::get_tasks();
+        let new_tasks = Database::get_tasks().unwrap();
         assert!(new_tasks.len() == 1);
         assert!(new_tasks[0] == pre_update_task);
 
@@ -349,7 +353,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -372,12 +376,12 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         let mut scheduler = Scheduler::new();
         let result = scheduler.rm_task(&1);
-        assert!(!result.is_none());
-        let tasks = Database::get_tasks();
+        assert!(result.is_ok());
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 1);
         assert!(tasks[0].id == 2);
         teardown();
@@ -390,7 +394,7 @@ mod tests_scheduler {
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         let _ = conn.execute("INSERT INTO modules (name, priority, enabled, type, condition, path)
                                 VALUES (\"foo\", 1, 1, \"foo\", \"foo\", \"foo\")", rusqlite::NO_PARAMS);
-        let _ = Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false);
+        Database::insert_new_device(&String::from("foo"), &String::from("bar"), &String::from("bar"), false).unwrap();
         let task1 = ScheduledTask {
             id : 0,
             module : 1,
@@ -413,11 +417,11 @@ mod tests_scheduler {
             days : String::new(),
             repeat : false
         };
-        Database::add_task(&task1);
-        Database::add_task(&task2);
+        Database::add_task(&task1).unwrap();
+        Database::add_task(&task2).unwrap();
         let mut scheduler = Scheduler::new();
-        scheduler.rm_task(&1412);
-        let tasks = Database::get_tasks();
+        assert!(scheduler.rm_task(&1412).is_err());
+        let tasks = Database::get_tasks().unwrap();
         assert!(tasks.len() == 2);
         teardown();
     }
@@ -9,6 +9,8 @@ mod tests_server {
     use core::rori::account::Account;
     use core::rori::database::Database;
     use core::rori::interaction::Interaction;
+    use core::rori::link::LinkPolicy;
+    use core::rori::repository::{DeviceRecord, Role};
     use core::rori::server::Server;
     use core::rori::user::{Device, User};
     use mocks::Daemon;
@@ -22,7 +24,7 @@ mod tests_server {
 
     fn setup(anonymous: User, registered_users: Vec<User>) -> Server {
         let _ = fs::remove_file("rori.db");
-        Database::init_db();
+        Database::init_db().unwrap();
         let account = Account {
             id: String::from("GLaDOs_id"),
             ring_id: String::from("GLaDOs_hash"),
@@ -39,6 +41,41 @@ mod tests_server {
         let _ = fs::remove_file("rori.db");
     }
 
+    fn record(id: i32, hash: &str, username: &str, devicename: &str) -> DeviceRecord {
+        DeviceRecord {
+            id: id,
+            hash: String::from(hash),
+            username: String::from(username),
+            devicename: String::from(devicename),
+            sub_author: String::new(),
+            is_bridge: false,
+        }
+    }
+
+    fn command(ring_id: &str, body: &str) -> Interaction {
+        Interaction {
+            device_author: Device::new(&0, &String::from(ring_id)),
+            body: String::from(body),
+            datatype: String::from("rori/command"),
+            ..Interaction::default()
+        }
+    }
+
+    // A bridge operator turns an anonymous device into a bridge, then is removed
+    fn bridgify(server: &mut Server, ring_id: &str) {
+        let operator = String::from("Wheatley");
+        let id = Database::insert_new_device(&String::from("Wheatley_id"), &operator, &String::new(), false).unwrap() as i32;
+        Database::set_role(&operator, Role::BridgeOperator).unwrap();
+        let mut user = User::new();
+        user.name = operator.clone();
+        user.devices.push(Device::new(&id, &String::from("Wheatley_id")));
+        server.registered_users.push(user);
+        server.handle_interaction(command("Wheatley_id", &*format!("/bridgify {}", ring_id)));
+        server.registered_users.retain(|user| user.name != operator);
+        Database::remove_device(&id).unwrap();
+        assert!(Database::is_bridge(&String::from(ring_id)).unwrap());
+    }
+
     #[test]
     fn test_formatted_account() {
         let server = setup(User::new(), Vec::new());
@@ -60,9 +97,8 @@ mod tests_server {
                 is_bridge: false
             },
             body: String::from("My joke percentage is at 70%!"),
-            datatype: String::from("text/plain"),
-            time: time::now(),
             metadatas: metadatas,
+            ..Interaction::default()
         };
         let formatted_account = format!("{}", interaction);
         assert!(formatted_account == format!("{} ({};{:?}): {}", interaction.device_author, interaction.datatype, interaction.metadatas, interaction.body));
@@ -73,7 +109,7 @@ mod tests_server {
     // 1. Ask the server to add some anonymouses
     fn server_add_new_anonymous_device() {
         let mut server = setup(User::new(), Vec::new());
-        assert!(Database::get_devices().len() == 0);
+        assert!(Database::get_devices().unwrap().len() == 0);
         assert!(server.anonymous_user.devices.len() == 0);
         // Insert new devices
         let ok = server.add_new_anonymous_device(&String::from("PBody"));
@@ -81,10 +117,10 @@ mod tests_server {
         let ok = server.add_new_anonymous_device(&String::from("Atlas"));
         assert!(ok);
         // Device can be retrieven
-        let device = Database::get_device(&String::from("PBody"), &String::new());
-        assert!(device.1 == "PBody");
-        assert!(device.2 == "");
-        assert!(device.3 == "");
+        let device = Database::get_device(&String::from("PBody"), &String::new()).unwrap().unwrap();
+        assert!(device.hash == "PBody");
+        assert!(device.username == "");
+        assert!(device.devicename == "");
         // Anonymous user should contains 2 devices
         assert!(server.anonymous_user.devices.len() == 2);
         // Retrieve all devices and no more
@@ -126,10 +162,9 @@ mod tests_server {
             },
             body: String::from("/add_types"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&did.clone());
+        let dt = Database::get_datatypes(&did.clone()).unwrap();
         assert!(dt.len() == 0);
 
         // Tars_id do a /add_types with 2 datatypes
@@ -142,10 +177,9 @@ mod tests_server {
             },
             body: String::from("/add_types music command"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&did.clone());
+        let dt = Database::get_datatypes(&did.clone()).unwrap();
         assert!(dt.len() == 2);
         assert!(dt.first().unwrap() == "music");
         assert!(dt.last().unwrap() == "command");
@@ -160,10 +194,9 @@ mod tests_server {
             },
             body: String::from("/add_types music other"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&did);
+        let dt = Database::get_datatypes(&did).unwrap();
         assert!(dt.len() == 3);
         assert!(dt.last().unwrap() == "other");
 
@@ -186,7 +219,7 @@ mod tests_server {
         anonymous.devices.push(Device::new(&0, &String::from("Tars_id")));
         let mut server = setup(anonymous, Vec::new());
         assert!(server.anonymous_user.devices.len() == 1);
-        let _ = Database::insert_new_device(&String::from("Tars_id"), &String::new(), &String::new(), false);
+        Database::insert_new_device(&String::from("Tars_id"), &String::new(), &String::new(), false).unwrap();
 
         // Tars_id do a /add_types with 3 datatypes
         server.handle_interaction(Interaction {
@@ -198,10 +231,9 @@ mod tests_server {
             },
             body: String::from("/add_types music command other"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 3);
 
         // Tars_id do a /rm_types with nothing
@@ -214,10 +246,9 @@ mod tests_server {
             },
             body: String::from("/rm_types"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 3);
 
         // Tars_id do a /rm_types with something not in types
@@ -230,10 +261,9 @@ mod tests_server {
             },
             body: String::from("/rm_types nothing"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 3);
 
         // Tars_id do a /rm_types with something not in types and 2 in types
@@ -246,10 +276,9 @@ mod tests_server {
             },
             body: String::from("/rm_types nothing music command"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 1);
         assert!(dt.first().unwrap() == "other");
 
@@ -263,10 +292,9 @@ mod tests_server {
             },
             body: String::from("/rm_types other"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 0);
 
         teardown();
@@ -287,7 +315,7 @@ mod tests_server {
         anonymous.devices.push(Device::new(&0, &String::from("Tars_id")));
         let mut server = setup(anonymous, Vec::new());
         assert!(server.anonymous_user.devices.len() == 1);
-        let _ = Database::insert_new_device(&String::from("Tars_id"), &String::new(), &String::new(), false);
+        Database::insert_new_device(&String::from("Tars_id"), &String::new(), &String::new(), false).unwrap();
 
         // Tars_id do a /add_types with 3 datatypes
         server.handle_interaction(Interaction {
@@ -299,10 +327,9 @@ mod tests_server {
             },
             body: String::from("/add_types music command other"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 3);
 
         // Tars_id do a /set_types with two types
@@ -315,10 +342,9 @@ mod tests_server {
             },
             body: String::from("/set_types type1 type2"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 2);
         assert!(dt.first().unwrap() == "type1");
         assert!(dt.last().unwrap() == "type2");
@@ -333,10 +359,9 @@ mod tests_server {
             },
             body: String::from("/set_types "),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
-        let dt = Database::get_datatypes(&1);
+        let dt = Database::get_datatypes(&1).unwrap();
         assert!(dt.len() == 0);
 
         teardown();
@@ -386,13 +411,13 @@ mod tests_server {
         // load_devices
         let mut devices = Vec::new();
         // anonymous user
-        devices.push((0, String::from("GLaDOs"), String::from(""), String::from(""), false));
-        devices.push((1, String::from("PBOdy"), String::from(""), String::from(""), false));
+        devices.push(record(0, "GLaDOs", "", ""));
+        devices.push(record(1, "PBOdy", "", ""));
         // Create new user
-        devices.push((2, String::from("Alexa"), String::from("Alexa"), String::from("Alexa"), false));
-        devices.push((3, String::from("Home"), String::from("Home"), String::from("Home"), false));
+        devices.push(record(2, "Alexa", "Alexa", "Alexa"));
+        devices.push(record(3, "Home", "Home", "Home"));
         // Add to known
-        devices.push((4, String::from("Tars"), String::from("Alexa"), String::from("Tars"), false));
+        devices.push(record(4, "Tars", "Alexa", "Tars"));
         server.load_devices(devices);
         // anonymous should containe 2 devices
         assert!(server.anonymous_user.devices.len() == 2);
@@ -436,9 +461,7 @@ mod tests_server {
                 is_bridge: false
             },
             body: String::from("My joke percentage is at 70%!"),
-            datatype: String::from("text/plain"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // Should be in anonymouses
         assert!(server.anonymous_user.devices.len() == 1);
@@ -497,8 +520,7 @@ mod tests_server {
             },
             body: String::from("/register tars"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // And bad tars try to to the same thing
         server.handle_interaction(Interaction {
@@ -510,8 +532,7 @@ mod tests_server {
             },
             body: String::from("/register tars"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // Bad_Tars_id should still be an anonymous
         assert!(server.anonymous_user.devices.len() == 1);
@@ -565,8 +586,7 @@ mod tests_server {
             },
             body: String::from("/register"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Bad_Tars_id should still be an anonymous
         assert!(server.anonymous_user.devices.len() == 1);
@@ -597,8 +617,8 @@ mod tests_server {
         users.push(tars);
         users.push(badtars);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_id2"), &String::from("Tars"), &String::from(""), false);
+        Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_id2"), &String::from("Tars"), &String::from(""), false).unwrap();
 
         // Tars_id do a /add_device android (should succeed)
         server.handle_interaction(Interaction {
@@ -610,8 +630,7 @@ mod tests_server {
             },
             body: String::from("/add_device android"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Tars_id2 do a /add_device pc (should fails because of Tars_pc)
         server.handle_interaction(Interaction {
@@ -623,8 +642,7 @@ mod tests_server {
             },
             body: String::from("/add_device pc"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Tars_id should now be recognized for Tars_android
         let mut confirmed = false;
@@ -685,9 +703,9 @@ mod tests_server {
         users.push(tars);
         users.push(badtars);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_id1"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_pc"), &String::from("Tars_pc"), &String::from(""), false);
+        Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_id1"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_pc"), &String::from("Tars_pc"), &String::from(""), false).unwrap();
 
         // Tars_pc do a /add_device pc of Tars_id (should fails because of it's someone else device)
         server.handle_interaction(Interaction {
@@ -699,8 +717,7 @@ mod tests_server {
             },
             body: String::from("/add_device pc Tars_id"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Tars_id should now be recognized for Tars_android
         let mut confirmed = false;
@@ -765,8 +782,7 @@ mod tests_server {
             },
             body: String::from("/add_device"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Tars_id should now be recognized for Tars_android
         let mut confirmed = false;
@@ -809,9 +825,9 @@ mod tests_server {
         users.push(tars);
         users.push(badtars);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_id2"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id"), &String::from("Atlas"), &String::from(""), false);
+        Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_id2"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id"), &String::from("Atlas"), &String::from(""), false).unwrap();
 
         // Tars_id do a /add_device pc Tars_id2 (should succeed)
         server.handle_interaction(Interaction {
@@ -823,8 +839,7 @@ mod tests_server {
             },
             body: String::from("/add_device pc Tars_id2"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // Tars_id2 do a /add_device pc2 Atlas_id (should fails)
         server.handle_interaction(Interaction {
@@ -836,8 +851,7 @@ mod tests_server {
             },
             body: String::from("/add_device pc2 Atlas_id"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // Tars_id2 should now be recognized for Tars_pc and Atlas_id as nothing
         let mut confirmed_tars = false;
@@ -888,8 +902,8 @@ mod tests_server {
         let mut users = Vec::new();
         users.push(atlas);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from("Device"), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from("Device2"), false);
+        Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from("Device"), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from("Device2"), false).unwrap();
         assert!(server.anonymous_user.devices.len() == 0);
 
         // Atlas_id1 do a /rm_devce (should succeed)
@@ -902,8 +916,7 @@ mod tests_server {
             },
             body: String::from("/rm_device"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
 
         assert!(server.anonymous_user.devices.len() == 1);
@@ -921,8 +934,7 @@ mod tests_server {
             },
             body: String::from("/rm_device"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
 
         assert!(server.anonymous_user.devices.len() == 2);
@@ -980,10 +992,10 @@ mod tests_server {
         users.push(atlas);
         users.push(tars);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_id1"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_id2"), &String::from("Tars"), &String::from(""), false);
+        Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_id1"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_id2"), &String::from("Tars"), &String::from(""), false).unwrap();
 
         assert!(server.anonymous_user.devices.len() == 0);
 
@@ -997,8 +1009,7 @@ mod tests_server {
             },
             body: String::from("/rm_device Tars_id1"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
 
         assert!(server.anonymous_user.devices.len() == 1);
@@ -1052,8 +1063,7 @@ mod tests_server {
             },
             body: String::from("/rm_device randomId"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
 
         assert!(server.registered_users.len() == 2);
@@ -1087,9 +1097,9 @@ mod tests_server {
         users.push(tars);
         users.push(badtars);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Tars_id1"), &String::from("Tars"), &String::from("Device"), false);
-        let _ = Database::insert_new_device(&String::from("Tars_pc"), &String::from("Tars_pc"), &String::from(""), false);
+        Database::insert_new_device(&String::from("Tars_id"), &String::from("Tars"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_id1"), &String::from("Tars"), &String::from("Device"), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_pc"), &String::from("Tars_pc"), &String::from(""), false).unwrap();
 
         // Tars_pc do a /add_device pc of Tars_id (should fails because of it's someone else device)
         server.handle_interaction(Interaction {
@@ -1101,8 +1111,7 @@ mod tests_server {
             },
             body: String::from("/rm_device Tars_id1"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Tars_id should now be recognized for Tars_android
         let mut confirmed = false;
@@ -1149,8 +1158,7 @@ mod tests_server {
             },
             body: String::from("/register Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.anonymous_user.devices.len() == 2);
         assert!(server.registered_users.len() == 1);
@@ -1165,8 +1173,7 @@ mod tests_server {
             },
             body: String::from("/link Atlas_id2"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.anonymous_user.devices.len() == 2);
         assert!(server.registered_users.first().unwrap().devices.len() == 1);
@@ -1180,8 +1187,7 @@ mod tests_server {
             },
             body: String::from("/link Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
 
         assert!(server.anonymous_user.devices.len() == 1);
@@ -1198,8 +1204,7 @@ mod tests_server {
             },
             body: String::from("/link Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.anonymous_user.devices.len() == 1);
         assert!(server.registered_users.first().unwrap().devices.len() == 2);
@@ -1213,8 +1218,7 @@ mod tests_server {
             },
             body: String::from("/link Atlas_id3"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.anonymous_user.devices.len() == 0);
 
@@ -1261,8 +1265,7 @@ mod tests_server {
             },
             body: String::from("/register Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.registered_users.len() == 1);
 
@@ -1276,8 +1279,7 @@ mod tests_server {
             },
             body: String::from("/link"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.registered_users.first().unwrap().devices.len() == 1);
 
@@ -1310,10 +1312,10 @@ mod tests_server {
         users.push(atlas);
         users.push(weasley);
         let mut server = setup(User::new(), users);
-        let _ = Database::insert_new_device(&String::from("Eve_id1"), &String::from("Eve"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from(""), false);
-        let _ = Database::insert_new_device(&String::from("Weasley_id1"), &String::from("Weasley"), &String::from(""), false);
+        Database::insert_new_device(&String::from("Eve_id1"), &String::from("Eve"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from(""), false).unwrap();
+        Database::insert_new_device(&String::from("Weasley_id1"), &String::from("Weasley"), &String::from(""), false).unwrap();
         assert!(server.registered_users.len() == 3);
 
         // Atlas_id1 unregister user.
@@ -1326,8 +1328,7 @@ mod tests_server {
             },
             body: String::from("/unregister"),
             datatype: String::from("rori/command"),
-            metadatas: HashMap::new(),
-            time: time::now(),
+            ..Interaction::default()
         });
         assert!(server.anonymous_user.devices.len() == 2);
         assert!(server.registered_users.len() == 2);
@@ -1369,18 +1370,7 @@ mod tests_server {
         server.add_new_anonymous_device(&String::from("Atlas_id1"));
 
         // Add a bridge with one user
-        server.handle_interaction(Interaction {
-            device_author: Device {
-                id: 1,
-                name: String::new(),
-                ring_id: String::from("Atlas_id1"),
-                is_bridge: true
-            },
-            body: String::from("/bridgify"),
-            datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
-        });
+        bridgify(&mut server, "Atlas_id1");
         server.handle_interaction(Interaction {
             device_author: Device {
                 id: 1,
@@ -1390,8 +1380,7 @@ mod tests_server {
             },
             body: String::from("/register Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.registered_users.len() == 1);
 
@@ -1407,8 +1396,8 @@ mod tests_server {
             },
             body: String::from("/unregister"),
             datatype: String::from("rori/command"),
-            time: time::now(),
             metadatas: metadatas,
+            ..Interaction::default()
         });
         // Still one registered user
         assert!(server.registered_users.len() == 1);
@@ -1446,8 +1435,7 @@ mod tests_server {
             },
             body: String::from("/unregister"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         // Should change nothing
         assert!(server.anonymous_user.devices.len() == 2);
@@ -1499,25 +1487,13 @@ mod tests_server {
             },
             body: String::from("/register Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
         assert!(server.anonymous_user.devices.len() == 2);
         assert!(server.registered_users.len() == 1);
 
         // Add a bridge with one user and ask linking
-        server.handle_interaction(Interaction {
-            device_author: Device {
-                id: 2,
-                name: String::new(),
-                ring_id: String::from("Atlas_id2"),
-                is_bridge: true
-            },
-            body: String::from("/bridgify"),
-            datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
-        });
+        bridgify(&mut server, "Atlas_id2");
         server.handle_interaction(Interaction {
             device_author: Device {
                 id: 2,
@@ -1527,8 +1503,7 @@ mod tests_server {
             },
             body: String::from("/link Atlas"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
 
         // Atlas_id authorizes Atlas_id2 to link
@@ -1541,8 +1516,7 @@ mod tests_server {
             },
             body: String::from("/link Atlas_id2"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
+            ..Interaction::default()
         });
 
         // The bridge should be anonymous + Atlas now!
@@ -1579,22 +1553,11 @@ mod tests_server {
         server.add_new_anonymous_device(&String::from("Atlas_id2"));
 
         // Add a bridge with one user
-        server.handle_interaction(Interaction {
-            device_author: Device {
-                id: 1,
-                name: String::new(),
-                ring_id: String::from("Atlas_id2"),
-                is_bridge: true
-            },
-            body: String::from("/bridgify"),
-            datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
-        });
+        bridgify(&mut server, "Atlas_id2");
 
-        let _ = Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from("Device"), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from("Device2"), true);
-        let _ = Database::update_sub_author(&3, &String::from("Atlas"));
+        Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from("Device"), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from("Device2"), true).unwrap();
+        Database::update_sub_author(&3, &String::from("Atlas")).unwrap();
         assert!(server.anonymous_user.devices.len() == 1);
 
         // Atlas_id2 do a /unregister (should succeed)
@@ -1611,8 +1574,8 @@ mod tests_server {
             },
             body: String::from("/rm_device"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: metadatas
+            metadatas: metadatas,
+            ..Interaction::default()
         });
 
         assert!(server.anonymous_user.devices.len() == 1);
@@ -1648,22 +1611,11 @@ mod tests_server {
         server.add_new_anonymous_device(&String::from("Atlas_id2"));
 
         // Add a bridge with one user
-        server.handle_interaction(Interaction {
-            device_author: Device {
-                id: 1,
-                name: String::new(),
-                ring_id: String::from("Atlas_id2"),
-                is_bridge: true
-            },
-            body: String::from("/bridgify"),
-            datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: HashMap::new(),
-        });
+        bridgify(&mut server, "Atlas_id2");
 
-        let _ = Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from("Device"), false);
-        let _ = Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from("Device2"), true);
-        let _ = Database::update_sub_author(&3, &String::from("Atlas"));
+        Database::insert_new_device(&String::from("Atlas_id1"), &String::from("Atlas"), &String::from("Device"), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_id2"), &String::from("Atlas"), &String::from("Device2"), true).unwrap();
+        Database::update_sub_author(&3, &String::from("Atlas")).unwrap();
         assert!(server.anonymous_user.devices.len() == 1);
 
         // Atlas_id2 do a /unregister (should succeed)
@@ -1680,8 +1632,8 @@ mod tests_server {
             },
             body: String::from("/unregister"),
             datatype: String::from("rori/command"),
-            time: time::now(),
-            metadatas: metadatas
+            metadatas: metadatas,
+            ..Interaction::default()
         });
 
         assert!(server.anonymous_user.devices.len() == 2);
@@ -1692,4 +1644,373 @@ mod tests_server {
         daemon.lock().unwrap().stop();
         let _ = daemon_thread.join();
     }
+
+    #[test]
+    // Scenario
+    // 1. An user with a device and a bridge is forgotten
+    // 2. The device becomes anonymous, the bridge is removed
+    fn server_purge_user() {
+        let mut server = setup(User::new(), Vec::new());
+        let pbody = String::from("PBody");
+        Database::insert_new_device(&String::from("PBody_hash"), &pbody, &String::from("Home"), false).unwrap();
+        Database::insert_new_device(&String::from("Bridge_hash"), &String::new(), &String::new(), true).unwrap();
+        Database::insert_new_device(&String::from("Bridge_hash"), &pbody, &String::new(), true).unwrap();
+        Database::set_lang(&pbody, &String::from("fr")).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+        assert!(server.registered_users.len() == 1);
+        assert!(server.anonymous_user.devices.len() == 1);
+
+        assert!(server.purge_user(&pbody).unwrap() > 0);
+        assert!(server.registered_users.len() == 0);
+        assert!(server.anonymous_user.devices.len() == 2);
+        assert!(server.anonymous_user.devices.iter().any(|d| d.ring_id == "PBody_hash"));
+        assert!(Database::get_devices().unwrap().len() == 2);
+        assert!(Database::get_lang(&pbody).unwrap().is_none());
+        teardown();
+    }
+
+    #[test]
+    // Scenario
+    // 1. Anonymous devices and simple users can't create bridges or change other devices
+    // 2. The admin from config gives roles
+    // 3. A bridge operator creates a bridge
+    fn server_roles() {
+        let mut server = setup(User::new(), Vec::new());
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::from("Home"), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::from("Home"), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_hash"), &String::new(), &String::new(), false).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+        server.set_admin(&String::from("PBody_hash")).unwrap();
+        assert!(Database::get_user(&String::from("PBody")).unwrap().unwrap().role == Role::Admin);
+
+        // Anonymous and users can't bridgify
+        server.handle_interaction(command("Tars_hash", "/bridgify"));
+        server.handle_interaction(command("Atlas_hash", "/bridgify Tars_hash"));
+        assert!(!Database::is_bridge(&String::from("Tars_hash")).unwrap());
+        // Nor change the types of other devices, nor roles
+        server.handle_interaction(command("Atlas_hash", "/set_types --device Tars_hash music"));
+        assert!(Database::get_datatypes(&3).unwrap().is_empty());
+        server.handle_interaction(command("Atlas_hash", "/set_types --device Atlas_hash music"));
+        assert!(Database::get_datatypes(&2).unwrap() == vec![String::from("music")]);
+        server.handle_interaction(command("Atlas_hash", "/role Atlas admin"));
+        assert!(Database::get_user(&String::from("Atlas")).unwrap().unwrap().role == Role::User);
+
+        // Admin changes types of other devices and roles
+        server.handle_interaction(command("PBody_hash", "/add_types --device Tars_hash music video"));
+        assert!(Database::get_datatypes(&3).unwrap() == vec![String::from("music"), String::from("video")]);
+        server.handle_interaction(command("PBody_hash", "/role Atlas bridge-operator"));
+        assert!(Database::get_user(&String::from("Atlas")).unwrap().unwrap().role == Role::BridgeOperator);
+        server.handle_interaction(command("PBody_hash", "/role PBody user"));
+        assert!(Database::get_user(&String::from("PBody")).unwrap().unwrap().role == Role::Admin);
+
+        // Bridge operator creates a bridge
+        server.handle_interaction(command("Atlas_hash", "/bridgify Tars_hash"));
+        assert!(Database::is_bridge(&String::from("Tars_hash")).unwrap());
+        server.handle_interaction(command("Atlas_hash", "/role Atlas admin"));
+        assert!(Database::get_user(&String::from("Atlas")).unwrap().unwrap().role == Role::BridgeOperator);
+        teardown();
+    }
+
+    #[test]
+    // Scenario
+    // 1. An admin targets an unknown user, module, task, device and anonymous device
+    // 2. Each command is answered by an error instead of being dropped
+    fn server_admin_not_found() {
+        let daemon = Arc::new(Mutex::new(Daemon::new()));
+        let cloned_daemon = daemon.clone();
+        let daemon_thread = thread::spawn(move|| {
+            Daemon::run(cloned_daemon);
+        });
+        let mut server = setup(User::new(), Vec::new());
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::from("Home"), false).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+        server.set_admin(&String::from("PBody_hash")).unwrap();
+
+        server.handle_interaction(command("PBody_hash", "/role Nobody admin"));
+        server.handle_interaction(command("PBody_hash", "/module enable cake"));
+        server.handle_interaction(command("PBody_hash", "/task rm 42"));
+        server.handle_interaction(command("PBody_hash", "/set_types --device Unknown_hash music"));
+        server.handle_interaction(command("PBody_hash", "/bridgify Unknown_hash"));
+        assert!(Database::get_user(&String::from("Nobody")).unwrap().is_none());
+        assert!(!Database::is_bridge(&String::from("Unknown_hash")).unwrap());
+        assert!(Database::get_bridge_owner(&String::from("Unknown_hash")).unwrap().is_none());
+
+        // Every command should be answered
+        let mut idx_signal = 0;
+        let hundred_millis = Duration::from_millis(100);
+        while idx_signal < 10 {
+            let storage = daemon.lock().unwrap().storage.clone();
+            let interactions = storage.lock().unwrap().interactions_sent.clone();
+            if interactions.len() == 5 {
+                assert!(interactions.iter().all(|&(_, ref to)| to == "PBody_hash"));
+                break;
+            }
+            thread::sleep(hundred_millis);
+            idx_signal += 1;
+            if idx_signal == 10 {
+                panic!("interactions not set!");
+            }
+        }
+        teardown();
+        daemon.lock().unwrap().stop();
+        let _ = daemon_thread.join();
+    }
+
+    #[test]
+    fn server_command_registry() {
+        let mut server = setup(User::new(), Vec::new());
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::from("Home"), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_hash"), &String::new(), &String::new(), false).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+        let atlas = String::from("Atlas");
+
+        // Names must match exactly and arguments must be valid
+        server.handle_interaction(command("Atlas_hash", "/language fr"));
+        server.handle_interaction(command("Atlas_hash", "/lang fr en"));
+        assert!(Database::get_lang(&atlas).unwrap().is_none());
+        server.handle_interaction(command("Atlas_hash", "/lang fr"));
+        assert!(Database::get_lang(&atlas).unwrap() == Some(String::from("fr")));
+        // Unsupported languages are refused
+        server.handle_interaction(command("Atlas_hash", "/lang tlh"));
+        assert!(Database::get_lang(&atlas).unwrap() == Some(String::from("fr")));
+
+        // Commands are restricted to their scope
+        server.handle_interaction(command("Atlas_hash", "/register PBody"));
+        assert!(Database::get_user(&String::from("PBody")).unwrap().is_none());
+        server.handle_interaction(command("Tars_hash", "/lang en"));
+        server.handle_interaction(command("Tars_hash", "/module disable weather"));
+        server.handle_interaction(command("Tars_hash", "/help"));
+        assert!(server.anonymous_user.devices.len() == 1);
+        server.handle_interaction(command("Tars_hash", "/register PBody"));
+        assert!(server.anonymous_user.devices.is_empty());
+        teardown();
+    }
+
+    #[test]
+    fn server_rename() {
+        let mut server = setup(User::new(), Vec::new());
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::from("Home"), false).unwrap();
+        Database::insert_new_device(&String::from("Atlas_hash2"), &String::from("Atlas"), &String::from("Work"), false).unwrap();
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::new(), false).unwrap();
+        Database::set_lang(&String::from("Atlas"), &String::from("fr")).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+
+        // Usernames are unique
+        server.handle_interaction(command("Atlas_hash", "/rename PBody"));
+        assert!(Database::get_user(&String::from("Atlas")).unwrap().is_some());
+        server.handle_interaction(command("Atlas_hash", "/rename Blue"));
+        assert!(Database::get_user(&String::from("Atlas")).unwrap().is_none());
+        assert!(Database::get_lang(&String::from("Blue")).unwrap() == Some(String::from("fr")));
+        assert!(server.get_hash(&String::from("Blue")) == "Atlas_hash");
+        assert!(server.get_hash(&String::from("Atlas")).is_empty());
+
+        // Devicenames are unique for an user
+        server.handle_interaction(command("Atlas_hash2", "/rename_device Home Work"));
+        server.handle_interaction(command("Atlas_hash2", "/rename_device Kitchen Lab"));
+        assert!(server.get_hash(&String::from("Blue_Home")) == "Atlas_hash");
+        server.handle_interaction(command("Atlas_hash2", "/rename_device Home Lab"));
+        assert!(server.get_hash(&String::from("Blue_Lab")) == "Atlas_hash");
+        assert!(Database::get_device(&String::from("Atlas_hash"), &String::from("Blue")).unwrap().unwrap().devicename == "Lab");
+        // Only devices of the user
+        server.handle_interaction(command("PBody_hash", "/rename_device Lab Kitchen"));
+        assert!(server.get_hash(&String::from("Blue_Lab")) == "Atlas_hash");
+        teardown();
+    }
+
+    #[test]
+    fn server_prefer() {
+        let mut server = setup(User::new(), Vec::new());
+        let atlas = String::from("Atlas");
+        let home = Database::insert_new_device(&String::from("Atlas_hash"), &atlas, &String::from("Home"), false).unwrap() as i32;
+        let work = Database::insert_new_device(&String::from("Atlas_hash2"), &atlas, &String::new(), false).unwrap() as i32;
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::from("Lab"), false).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+
+        // By name or by ring_id, only devices of the user
+        server.handle_interaction(command("Atlas_hash2", "/prefer Home"));
+        server.handle_interaction(command("Atlas_hash2", "/prefer Atlas_hash2 music/play"));
+        server.handle_interaction(command("Atlas_hash2", "/prefer Lab video/play"));
+        let preferences = Database::get_preferences(&atlas).unwrap();
+        assert!(preferences.len() == 2);
+        assert!(preferences[""] == home);
+        assert!(preferences["music/play"] == work);
+        server.handle_interaction(command("Atlas_hash", "/rm_prefer music/play"));
+        assert!(Database::get_preferences(&atlas).unwrap().len() == 1);
+        teardown();
+    }
+
+    #[test]
+    // Scenario
+    // 1. Only the operator who owns the bridge and admins can manage sub authors
+    // 2. Removed sub authors are unlinked, users without devices are kept and their name stays reserved
+    fn server_sub_authors() {
+        let mut server = setup(User::new(), Vec::new());
+        let tars = String::from("Tars");
+        let wheatley = String::from("Wheatley");
+        Database::insert_new_device(&String::from("Wheatley_id"), &wheatley, &String::new(), false).unwrap();
+        Database::set_role(&wheatley, Role::BridgeOperator).unwrap();
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::from("Home"), false).unwrap();
+        let bridge = Database::insert_new_device(&tars, &String::new(), &String::new(), false).unwrap() as i32;
+        Database::bridgify(&bridge).unwrap();
+        Database::set_bridge_owner(&tars, &wheatley).unwrap();
+        let glados = String::from("GLaDOs");
+        Database::insert_new_device(&String::from("GLaDOs_id"), &glados, &String::new(), false).unwrap();
+        Database::set_role(&glados, Role::BridgeOperator).unwrap();
+        let atlas = Database::insert_new_device(&tars, &String::from("Atlas"), &String::new(), true).unwrap() as i32;
+        Database::update_sub_author(&atlas, &String::from("atlas_irc")).unwrap();
+        let pbody = Database::insert_new_device(&tars, &String::from("PBody"), &String::new(), true).unwrap() as i32;
+        Database::update_sub_author(&pbody, &String::from("pbody_irc")).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+        assert!(server.registered_users.len() == 4);
+
+        // Users can't remove sub authors
+        server.handle_interaction(command("Atlas_hash", "/rm_sub_author Tars pbody_irc"));
+        assert!(Database::sub_author(&tars, &String::from("pbody_irc")).unwrap() == "PBody");
+        // Nor operators of other bridges
+        server.handle_interaction(command("GLaDOs_id", "/rm_sub_author Tars pbody_irc"));
+        assert!(Database::sub_author(&tars, &String::from("pbody_irc")).unwrap() == "PBody");
+        assert!(!server.can_manage_bridge(&glados, &tars).unwrap());
+        Database::set_role(&glados, Role::Admin).unwrap();
+        assert!(server.can_manage_bridge(&glados, &tars).unwrap());
+        assert!(server.can_manage_bridge(&wheatley, &tars).unwrap());
+        // Not a bridge
+        server.handle_interaction(command("Wheatley_id", "/rm_sub_author Atlas_hash pbody_irc"));
+        assert!(Database::sub_author(&tars, &String::from("pbody_irc")).unwrap() == "PBody");
+        server.handle_interaction(command("Wheatley_id", "/sub_authors Tars"));
+        server.handle_interaction(command("Wheatley_id", "/sub_author Tars PBody"));
+
+        // PBody only had the bridge
+        server.handle_interaction(command("Wheatley_id", "/rm_sub_author Tars pbody_irc"));
+        assert!(Database::sub_author(&tars, &String::from("pbody_irc")).unwrap() == "");
+        assert!(Database::get_user(&String::from("PBody")).unwrap().is_some());
+        assert!(server.registered_users.iter().all(|u| u.name != "PBody"));
+        let walle = Database::insert_new_device(&String::from("Wall-E_hash"), &String::new(), &String::new(), false).unwrap() as i32;
+        server.anonymous_user.devices.push(Device::new(&walle, &String::from("Wall-E_hash")));
+        server.handle_interaction(command("Wall-E_hash", "/register PBody"));
+        assert!(Database::get_devices_for_username("PBody").unwrap().is_empty());
+        // Atlas keeps its other device
+        assert!(server.remove_sub_author(&tars, &String::from("atlas_irc")).unwrap() == Some(String::from("Atlas")));
+        assert!(server.remove_sub_author(&tars, &String::from("atlas_irc")).unwrap().is_none());
+        let registered = server.registered_users.iter().find(|u| u.name == "Atlas").unwrap();
+        assert!(registered.devices.len() == 1 && registered.devices[0].ring_id == "Atlas_hash");
+        // The bridge stays
+        assert!(Database::is_bridge(&tars).unwrap());
+        assert!(server.anonymous_user.devices.iter().any(|d| d.ring_id == "Tars"));
+        teardown();
+    }
+
+    #[test]
+    fn server_groups() {
+        let mut server = setup(User::new(), Vec::new());
+        let aperture = String::from("Aperture");
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::new(), false).unwrap();
+        Database::insert_new_device(&String::from("PBody_hash"), &String::from("PBody"), &String::new(), false).unwrap();
+        Database::insert_new_device(&String::from("Wheatley_hash"), &String::from("Wheatley"), &String::new(), false).unwrap();
+        Database::insert_new_device(&String::from("Tars_hash"), &String::new(), &String::new(), false).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+
+        // The author is the owner and first member
+        server.handle_interaction(command("PBody_hash", "/add_group Aperture"));
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![String::from("PBody")]);
+        assert!(Database::get_group(&aperture).unwrap().unwrap().owner == "PBody");
+        server.handle_interaction(command("PBody_hash", "/add_member Aperture Atlas"));
+        server.handle_interaction(command("PBody_hash", "/add_member Aperture Unknown"));
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![String::from("Atlas"), String::from("PBody")]);
+        // Names are shared between users and groups
+        server.handle_interaction(command("Wheatley_hash", "/add_group Atlas"));
+        server.handle_interaction(command("Wheatley_hash", "/add_group Aperture"));
+        assert!(Database::get_groups().unwrap().len() == 1);
+        server.handle_interaction(command("Tars_hash", "/register Aperture"));
+        assert!(server.anonymous_user.devices.len() == 1);
+        // A group is not resolved to one of its members
+        assert!(server.get_hash(&aperture).is_empty());
+
+        // Only the owner can change the group, other members can't add anyone
+        server.handle_interaction(command("Wheatley_hash", "/add_member Aperture Wheatley"));
+        server.handle_interaction(command("Wheatley_hash", "/rm_member Aperture Atlas"));
+        server.handle_interaction(command("Wheatley_hash", "/rm_group Aperture"));
+        server.handle_interaction(command("Atlas_hash", "/add_member Aperture Wheatley"));
+        server.handle_interaction(command("Atlas_hash", "/rm_member Aperture PBody"));
+        server.handle_interaction(command("Atlas_hash", "/rm_group Aperture"));
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![String::from("Atlas"), String::from("PBody")]);
+        // But members can leave
+        server.handle_interaction(command("Atlas_hash", "/rm_member Aperture Atlas"));
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![String::from("PBody")]);
+        // And admins can change any group
+        Database::set_role(&String::from("Wheatley"), Role::Admin).unwrap();
+        server.handle_interaction(command("Wheatley_hash", "/add_member Aperture Atlas"));
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members.len() == 2);
+        server.handle_interaction(command("Wheatley_hash", "/rm_member Aperture Atlas"));
+        assert!(Database::get_group(&aperture).unwrap().unwrap().members == vec![String::from("PBody")]);
+        server.handle_interaction(command("PBody_hash", "/rm_group Aperture"));
+        assert!(Database::get_group(&aperture).unwrap().is_none());
+        assert!(server.get_hash(&aperture).is_empty());
+        teardown();
+    }
+
+    #[test]
+    fn server_capabilities() {
+        let mut server = setup(User::new(), Vec::new());
+        let atlas = String::from("Atlas");
+        let home = Database::insert_new_device(&String::from("Atlas_hash"), &atlas, &String::from("Home"), false).unwrap() as i32;
+        server.load_devices(Database::get_devices().unwrap());
+
+        let mut announce = command("Atlas_hash", "{\"version\":1,\"kinds\":[\"speaker\"],\
+            \"actions\":[{\"datatype\":\"music/play\",\"parameters\":{\"type\":\"object\"}}]}");
+        announce.datatype = String::from("rori/capabilities");
+        server.handle_interaction(announce.clone());
+        let capabilities = Database::get_capabilities(&home).unwrap();
+        assert!(capabilities.kinds.len() == 1);
+        assert!(capabilities.action("music/play").is_some());
+        // Announcements are not for modules
+        assert!(Database::get_unmatched().unwrap().is_empty());
+        // An invalid announcement doesn't change anything
+        announce.body = String::from("{\"version\":1,\"actions\":[{\"datatype\":\"\"}]}");
+        server.handle_interaction(announce);
+        assert!(Database::get_capabilities(&home).unwrap().actions.len() == 1);
+        teardown();
+    }
+
+    #[test]
+    // Scenario
+    // 1. Atlas asks to link Atlas_id2, RORI generates a code
+    // 2. A wrong code is refused and the request is removed
+    // 3. Atlas_id2 echoes the good code and is linked
+    fn server_link_device_with_code() {
+        let mut server = setup(User::new(), Vec::new());
+        server.link_policy = LinkPolicy { ttl: 60, code: true };
+        let atlas = String::from("Atlas");
+        let atlas_id2 = String::from("Atlas_id2");
+        Database::insert_new_device(&String::from("Atlas_id1"), &atlas, &String::new(), false).unwrap();
+        Database::insert_new_device(&atlas_id2, &String::new(), &String::new(), false).unwrap();
+        server.load_devices(Database::get_devices().unwrap());
+        let now = time::get_time().sec;
+
+        // Without code, two /link are not enough
+        server.handle_interaction(command("Atlas_id1", "/link Atlas_id2"));
+        server.handle_interaction(command("Atlas_id2", "/link Atlas"));
+        assert!(server.anonymous_user.devices.len() == 1);
+        let request = Database::get_link_request(&atlas_id2, &atlas, now).unwrap().unwrap();
+        assert!(request.code.len() == 6);
+        assert!(request.expires <= now + 61);
+
+        // Wrong code
+        let wrong = if request.code == "000000" { "111111" } else { "000000" };
+        server.handle_interaction(command("Atlas_id2", &*format!("/link Atlas {}", wrong)));
+        assert!(server.anonymous_user.devices.len() == 1);
+        assert!(Database::get_link_request(&atlas_id2, &atlas, now).unwrap().is_none());
+
+        // Good code
+        server.handle_interaction(command("Atlas_id1", "/link Atlas_id2"));
+        let request = Database::get_link_request(&atlas_id2, &atlas, now).unwrap().unwrap();
+        server.handle_interaction(command("Atlas_id2", &*format!("/link Atlas {}", request.code)));
+        assert!(server.anonymous_user.devices.is_empty());
+        assert!(server.registered_users.first().unwrap().devices.len() == 2);
+        assert!(Database::get_device(&atlas_id2, &atlas).unwrap().is_some());
+        assert!(Database::get_link_request(&atlas_id2, &atlas, now).unwrap().is_none());
+        // The code can't be used twice
+        server.handle_interaction(command("Atlas_id2", &*format!("/link Atlas {}", request.code)));
+        assert!(server.registered_users.first().unwrap().devices.len() == 2);
+        teardown();
+    }
 }
@@ -0,0 +1,203 @@
+extern crate core;
+#[cfg(test)]
+mod tests_vault {
+    use core::rori::database::Database;
+    use core::rori::error::RoriError;
+    use core::rori::sdk;
+    use core::rori::vault::Vault;
+    use std::fs;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    fn setup() {
+        let _ = fs::remove_file("rori.db");
+        Database::init_db().unwrap(); // assert this function is correct.
+        Vault::lock();
+    }
+
+    fn teardown() {
+        Vault::lock();
+        let _ = fs::remove_file("rori.db");
+    }
+
+    #[test]
+    fn test_unlock() {
+        setup();
+        let weather = String::from("weather");
+        let token = String::from("token");
+        match Vault::set(&weather, &String::new(), &token, &String::from("cake")) {
+            Err(RoriError::Vault(_)) => {},
+            _ => panic!("a locked vault can't store secrets")
+        }
+        assert!(Vault::unlock("").is_err());
+        // The first unlock creates the vault
+        Vault::unlock("still alive").unwrap();
+        assert!(Vault::is_unlocked());
+        Vault::set(&weather, &String::new(), &token, &String::from("cake")).unwrap();
+        Vault::lock();
+        assert!(!Vault::is_unlocked());
+        assert!(Vault::for_module(&weather, &String::new()).is_err());
+        match Vault::unlock("the cake is a lie") {
+            Err(RoriError::Vault(_)) => {},
+            _ => panic!("wrong passphrase should fail")
+        }
+        assert!(!Vault::is_unlocked());
+        Vault::unlock("still alive").unwrap();
+        assert!(Vault::for_module(&weather, &String::new()).unwrap()["token"] == "cake");
+        teardown();
+    }
+
+    #[test]
+    fn test_encrypted() {
+        setup();
+        Vault::unlock("still alive").unwrap();
+        let module = String::from("weather");
+        Vault::set(&module, &String::new(), &String::from("token"), &String::from("cake")).unwrap();
+        let stored = Database::get_secrets(&module).unwrap();
+        assert!(stored.len() == 1);
+        assert!(!stored[0].value.contains("cake"));
+        assert!(!stored[0].value.contains("63616b65")); // cake in hexadecimal
+        // Secrets are listed without their values
+        let listed = Vault::list(&String::new()).unwrap();
+        assert!(listed.len() == 1);
+        assert!(listed[0].name == "token");
+        assert!(listed[0].value.is_empty());
+        // A value moved to another module can't be decrypted
+        let mut moved = stored[0].clone();
+        moved.module = String::from("alarm");
+        Database::set_secret(&moved).unwrap();
+        assert!(Vault::for_module(&String::from("alarm"), &String::new()).is_err());
+        teardown();
+    }
+
+    #[test]
+    fn test_scopes() {
+        setup();
+        Vault::unlock("still alive").unwrap();
+        let weather = String::from("weather");
+        let pbody = String::from("PBody");
+        let atlas = String::from("Atlas");
+        Vault::set(&weather, &String::new(), &String::from("token"), &String::from("shared")).unwrap();
+        Vault::set(&weather, &String::new(), &String::from("city"), &String::from("Aperture")).unwrap();
+        Vault::set(&weather, &pbody, &String::from("token"), &String::from("pbody")).unwrap();
+        Vault::set(&String::from("alarm"), &String::new(), &String::from("password"), &String::from("cake")).unwrap();
+        // Secrets of the user replace shared secrets
+        let secrets = Vault::for_module(&weather, &pbody).unwrap();
+        assert!(secrets.len() == 2);
+        assert!(secrets["token"] == "pbody");
+        assert!(secrets["city"] == "Aperture");
+        let secrets = Vault::for_module(&weather, &atlas).unwrap();
+        assert!(secrets["token"] == "shared");
+        // Other modules are isolated
+        assert!(Vault::for_module(&String::from("wikipedia"), &pbody).unwrap().is_empty());
+        assert!(!Vault::for_module(&String::from("alarm"), &pbody).unwrap().contains_key("token"));
+        // Delete
+        assert!(Vault::delete(&weather, &pbody, &String::from("token")).unwrap() == 1);
+        assert!(Vault::delete(&weather, &pbody, &String::from("token")).unwrap() == 0);
+        assert!(Vault::for_module(&weather, &pbody).unwrap()["token"] == "shared");
+        // Secrets of forgotten users are removed
+        Vault::set(&weather, &pbody, &String::from("token"), &String::from("pbody")).unwrap();
+        Database::purge_user(&pbody).unwrap();
+        assert!(Vault::list(&weather).unwrap().len() == 2);
+        teardown();
+    }
+
+    #[test]
+    fn test_parallel_modules() {
+        setup();
+        Vault::unlock("still alive").unwrap();
+        let weather = String::from("weather");
+        let alarm = String::from("alarm");
+        let token = String::from("token");
+        Vault::set(&weather, &String::from("PBody"), &token, &String::from("pbody")).unwrap();
+        Vault::set(&alarm, &String::from("Atlas"), &token, &String::from("atlas")).unwrap();
+        // Two modules executed at the same time for different users only see their secrets
+        let barrier = Arc::new(Barrier::new(2));
+        let mut children = Vec::new();
+        for (module, username) in vec![(weather, "PBody"), (alarm, "Atlas")] {
+            let barrier = barrier.clone();
+            children.push(thread::spawn(move || {
+                sdk::enter(&module, &String::from(username));
+                barrier.wait();
+                sdk::current_secrets()
+            }));
+        }
+        let results: Vec<_> = children.into_iter().map(|child| child.join().unwrap()).collect();
+        assert!(results[0].len() == 1 && results[0]["token"] == "pbody");
+        assert!(results[1].len() == 1 && results[1]["token"] == "atlas");
+        // Nothing leaks to a thread executing another user
+        sdk::enter(&String::from("weather"), &String::from("Atlas"));
+        assert!(sdk::current_secrets().is_empty());
+        teardown();
+    }
+
+    #[test]
+    fn test_unregister() {
+        setup();
+        Vault::unlock("still alive").unwrap();
+        let weather = String::from("weather");
+        let pbody = String::from("PBody");
+        Database::insert_new_device(&String::from("PBody_hash"), &pbody, &String::new(), false).unwrap();
+        Vault::set(&weather, &String::new(), &String::from("token"), &String::from("shared")).unwrap();
+        Vault::set(&weather, &pbody, &String::from("token"), &String::from("pbody")).unwrap();
+        // Secrets of an unregistered user are kept until the user is purged
+        let id = Database::get_devices_for_hash(&String::from("PBody_hash")).unwrap()[0].id;
+        Database::update_username(&id, &String::new()).unwrap();
+        assert!(Vault::list(&weather).unwrap().len() == 2);
+        Database::purge_user(&pbody).unwrap();
+        assert!(Vault::list(&weather).unwrap().len() == 1);
+        // A new user registered with the same name only gets shared secrets
+        Database::update_username(&id, &pbody).unwrap();
+        assert!(Vault::for_module(&weather, &pbody).unwrap()["token"] == "shared");
+        teardown();
+    }
+
+    #[test]
+    fn test_rotate() {
+        setup();
+        let weather = String::from("weather");
+        assert!(Vault::rotate("new passphrase").is_err());
+        Vault::unlock("still alive").unwrap();
+        Vault::set(&weather, &String::new(), &String::from("token"), &String::from("cake")).unwrap();
+        Vault::set(&weather, &String::from("PBody"), &String::from("token"), &String::from("pbody")).unwrap();
+        let before = Database::get_secrets(&weather).unwrap();
+        assert!(Vault::rotate("").is_err());
+        assert!(Vault::rotate("want you gone").unwrap() == 2);
+        let after = Database::get_secrets(&weather).unwrap();
+        assert!(before[0].value != after[0].value);
+        assert!(Vault::for_module(&weather, &String::new()).unwrap()["token"] == "cake");
+        // Only the new passphrase works
+        Vault::lock();
+        assert!(Vault::unlock("still alive").is_err());
+        Vault::unlock("want you gone").unwrap();
+        assert!(Vault::for_module(&weather, &String::from("PBody")).unwrap()["token"] == "pbody");
+        teardown();
+    }
+
+    #[test]
+    fn test_rename_user() {
+        setup();
+        let weather = String::from("weather");
+        let pbody = String::from("PBody");
+        let orange = String::from("Orange");
+        Vault::unlock("still alive").unwrap();
+        Vault::set(&weather, &String::new(), &String::from("token"), &String::from("shared")).unwrap();
+        Vault::set(&weather, &pbody, &String::from("token"), &String::from("pbody")).unwrap();
+        // The locked vault can only rename users without secrets
+        Vault::lock();
+        assert!(Vault::rename_user(&String::from("Atlas"), &orange).unwrap().is_empty());
+        match Vault::rename_user(&pbody, &orange) {
+            Err(RoriError::Vault(_)) => {},
+            _ => panic!("secrets can't be moved while the vault is locked")
+        }
+        Vault::unlock("still alive").unwrap();
+        let secrets = Vault::rename_user(&pbody, &orange).unwrap();
+        assert!(secrets.len() == 1);
+        assert!(secrets[0].username == "Orange");
+        Database::insert_new_device(&String::from("PBody_hash"), &pbody, &String::new(), false).unwrap();
+        Database::rename_user(&pbody, &orange, &secrets).unwrap();
+        assert!(Vault::for_module(&weather, &orange).unwrap()["token"] == "pbody");
+        assert!(Vault::for_module(&weather, &pbody).unwrap()["token"] == "shared");
+        teardown();
+    }
+}
@@ -1,18 +1,22 @@
 extern crate core;
+extern crate cpython;
 extern crate dbus;
 extern crate rusqlite;
 extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
-extern crate time;
 
 mod mocks;
 #[cfg(test)]
 mod tests_server {
     use core::rori::account::Account;
+    use cpython::{PyDict, Python};
     use core::rori::database::Database;
     use core::rori::interaction::Interaction;
+    use core::rori::module::{module_condition, parse_condition, Condition, IntentCondition, TextCondition};
+    use core::rori::modulemanager::{Fallback, ModuleManager};
+    use core::rori::sdk;
     use core::rori::server::Server;
     use core::rori::user::{Device,User};
     use mocks::Daemon;
@@ -26,7 +30,6 @@ mod tests_server {
     use std::sync::atomic::Ordering;
     use std::thread;
     use std::time::Duration;
-    use time;
 
     /**
      * ConfigFile structure
@@ -42,7 +45,7 @@ mod tests_server {
 
     fn setup(anonymous: User, registered_users: Vec<User>) -> Server {
         let _ = fs::remove_file("rori.db");
-        Database::init_db();
+        Database::init_db().unwrap();
         // Insert modules
         let conn = rusqlite::Connection::open("rori.db").unwrap();
         // NOTE: if much modules, launch generate_modules.py
@@ -94,13 +97,13 @@ mod tests_server {
     // Retrieve some modules from the database
     fn database_get_enabled_modules() {
         setup(User::new(), Vec::new());
-        let modules = Database::get_enabled_modules(0);
+        let modules = Database::get_enabled_modules(0).unwrap();
         assert!(modules.len() == 1);
         assert!(modules.first().unwrap().name == "history");
-        let modules = Database::get_enabled_modules(1);
+        let modules = Database::get_enabled_modules(1).unwrap();
         assert!(modules.len() == 1);
         assert!(modules.first().unwrap().name == "hello_world");
-        let modules = Database::get_enabled_modules(10);
+        let modules = Database::get_enabled_modules(10).unwrap();
         assert!(modules.len() == 0);
         teardown();
     }
@@ -110,7 +113,7 @@ mod tests_server {
     // Retrieve priorities
     fn database_get_descending_priorities() {
         setup(User::new(), Vec::new());
-        let priorities = Database::get_descending_priorities();
+        let priorities = Database::get_descending_priorities().unwrap();
         assert!(priorities.len() == 4);
         assert!(*priorities.get(0).unwrap() == 0);
         assert!(*priorities.get(1).unwrap() == 1);
@@ -119,7 +122,7 @@ mod tests_server {
 
     #[test]
     // Scenario
-    // 1. If a new message is received, should be stored in the history table via the history module
+    // 1. If a new message is received, should be stored in the history (interactions table)
     fn modules_test_history() {
         let daemon = Arc::new(Mutex::new(Daemon::new()));
         let cloned_daemon = daemon.clone();
@@ -136,9 +139,7 @@ mod tests_server {
                 is_bridge: false
             },
             body: String::from("My joke percentage is at 70%!"),
-            datatype: String::from("text/plain"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // Let the time to the module
         let fhundred_millis = Duration::from_millis(500);
@@ -146,7 +147,7 @@ mod tests_server {
 
         // Should be present
         let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM History").unwrap();
+        let mut stmt = conn.prepare("SELECT * FROM interactions").unwrap();
         let mut rows = stmt.query(rusqlite::NO_PARAMS).unwrap();
         let mut nbrows = 0;
         while let Ok(Some(_)) = rows.next() {
@@ -163,9 +164,7 @@ mod tests_server {
                 is_bridge: false
             },
             body: String::from("My joke percentage is at 60%!"),
-            datatype: String::from("text/plain"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
         // Let the time to the module
         let fhundred_millis = Duration::from_millis(500);
@@ -173,7 +172,7 @@ mod tests_server {
 
         // Should be present
         let conn = rusqlite::Connection::open("rori.db").unwrap();
-        let mut stmt = conn.prepare("SELECT * FROM History").unwrap();
+        let mut stmt = conn.prepare("SELECT * FROM interactions").unwrap();
         let mut rows = stmt.query(rusqlite::NO_PARAMS).unwrap();
         let mut nbrows = 0;
         while let Ok(Some(_)) = rows.next() {
@@ -205,9 +204,7 @@ mod tests_server {
                 is_bridge: false
             },
             body: String::from("yo"),
-            datatype: String::from("text/plain"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
 
         // This should has sent 1 message
@@ -251,9 +248,7 @@ mod tests_server {
                 is_bridge: false
             },
             body: String::from("name"),
-            datatype: String::from("text/plain"),
-            time: time::now(),
-            metadatas: HashMap::new()
+            ..Interaction::default()
         });
 
         // This should has sent 1 message (not 2!)
@@ -278,6 +273,245 @@ mod tests_server {
         let _ = daemon_thread.join();
     }
 
+    #[test]
+    // Scenario
+    // 1. Someone says something not understood. The fallback message should be sent
+    // and the interaction stored
+    fn modules_test_fallback_message() {
+        let daemon = Arc::new(Mutex::new(Daemon::new()));
+        let cloned_daemon = daemon.clone();
+        let daemon_thread = thread::spawn(move|| {
+            Daemon::run(cloned_daemon);
+        });
+        let mut server = setup(User::new(), Vec::new());
+        let mut messages = HashMap::new();
+        messages.insert(String::from("en"), String::from("I didn't understand"));
+        server.fallback = Fallback::Message(messages);
+        server.handle_interaction(Interaction {
+            device_author: Device {
+                id: 0,
+                name: String::new(),
+                ring_id: String::from("Tars_id"),
+                is_bridge: false
+            },
+            body: String::from("make me a sandwich"),
+            ..Interaction::default()
+        });
+
+        // This should has sent 1 message
+        let mut idx_signal = 0;
+        let hundred_millis = Duration::from_millis(100);
+        while idx_signal < 10 {
+            let storage = daemon.lock().unwrap().storage.clone();
+            let has_new_info = storage.lock().unwrap().new_info.load(Ordering::SeqCst);
+            if has_new_info {
+                let interactions = storage.lock().unwrap().interactions_sent.clone();
+                assert!(interactions.len() == 1);
+                break;
+            }
+            thread::sleep(hundred_millis);
+            idx_signal += 1;
+            if idx_signal == 10 {
+                panic!("interactions not set!");
+            }
+        }
+        let unmatched = Database::get_unmatched().unwrap();
+        assert!(unmatched.len() == 1);
+        assert!(unmatched[0].0 == "make me a sandwich");
+        teardown();
+        daemon.lock().unwrap().stop();
+        let _ = daemon_thread.join();
+    }
+
+    #[test]
+    // Scenario
+    // Search modules near of a typo
+    fn modules_test_nearest_modules() {
+        setup(User::new(), Vec::new());
+        let nearest = ModuleManager::nearest_modules(&String::from("helo"), 3);
+        assert!(nearest == vec![String::from("hello_world")]);
+        let nearest = ModuleManager::nearest_modules(&String::from("tell me your namme"), 1);
+        assert!(nearest.len() == 1);
+        assert!(nearest[0].starts_with("name"));
+        // history (priority 0) is never suggested
+        let nearest = ModuleManager::nearest_modules(&String::from("sandwich"), 3);
+        assert!(nearest.is_empty());
+        teardown();
+    }
+
+    #[test]
+    // Scenario
+    // Named groups of a text condition are extracted as slots
+    fn modules_test_condition_slots() {
+        let condition = TextCondition::new(String::from("(wake|veille).{0,100}(in|at|dans|à|a).(?P<hours>[0-9]+)(:|h|.*)(?P<minutes>[0-9]*)")).unwrap();
+        let mut interaction = Interaction {
+            device_author: Device {
+                id: 0,
+                name: String::new(),
+                ring_id: String::from("Tars_id"),
+                is_bridge: false
+            },
+            body: String::from("Wake me up at 7:30"),
+            ..Interaction::default()
+        };
+        assert!(condition.is_fulfilled_by(&interaction));
+        let slots = condition.slots(&interaction);
+        assert!(slots.len() == 2);
+        assert!(slots["hours"] == "7");
+        assert!(slots["minutes"] == "30");
+        // No named group, no slot
+        let condition = TextCondition::new(String::from("wake")).unwrap();
+        assert!(condition.slots(&interaction).is_empty());
+        // Not fulfilled, no slot
+        interaction.body = String::from("hello");
+        assert!(TextCondition::new(String::from("(?P<name>world)")).unwrap().slots(&interaction).is_empty());
+    }
+
+    #[test]
+    // Scenario
+    // Intent conditions match examples with typos and keywords, without regex
+    fn modules_test_intent_condition() {
+        let condition = IntentCondition::parse(serde_json::from_str("{\"type\":\"intent\",
+            \"examples\":[\"how are you\", \"how r u\", \"how do you feel\"], \"keywords\":[\"mood\"], \"threshold\":0.6}").unwrap()).unwrap();
+        let mut interaction = Interaction {
+            device_author: Device {
+                id: 0,
+                name: String::new(),
+                ring_id: String::from("Tars_id"),
+                is_bridge: false
+            },
+            body: String::from("How r u?"),
+            ..Interaction::default()
+        };
+        assert!(condition.score(&interaction) == Some(1.0));
+        assert!(condition.is_fulfilled_by(&interaction));
+        interaction.body = String::from("how are you rori");
+        assert!(condition.is_fulfilled_by(&interaction));
+        // With a typo
+        interaction.body = String::from("how do you feeel");
+        assert!(condition.is_fulfilled_by(&interaction));
+        interaction.body = String::from("what is your mood");
+        assert!(condition.score(&interaction).unwrap() < 0.6);
+        assert!(!condition.is_fulfilled_by(&interaction));
+        interaction.body = String::from("make me a sandwich");
+        assert!(condition.score(&interaction) == Some(0.0));
+        // Text conditions are not scored
+        assert!(TextCondition::new(String::from("sandwich")).unwrap().score(&interaction).is_none());
+        // Built from the database
+        let condition = module_condition(&String::from("test"), &String::from("{\"type\":\"intent\",\"examples\":[\"make me a sandwich\"]}"));
+        assert!(condition.is_fulfilled_by(&interaction));
+        assert!(condition.vocabulary() == vec!["make", "me", "a", "sandwich"]);
+        let condition = module_condition(&String::from("test"), &String::from("sandwich"));
+        assert!(condition.score(&interaction).is_none());
+    }
+
+    #[test]
+    // Scenario
+    // Invalid conditions are refused and never fulfilled, instead of panicking
+    fn modules_test_invalid_condition() {
+        let interaction = Interaction {
+            body: String::from("{cake}"),
+            ..Interaction::default()
+        };
+        let invalid = vec!["{\"type\":\"cake\"}", "{\"type\":\"intent\",\"examples\":\"cake\"}", "{cake}", "(cake",
+                           "{\"type\":\"lang\", \"variants\":{\"en\":\"(cake\"}}", "{\"type\":\"lang\"}"];
+        for condition in invalid {
+            assert!(parse_condition(&String::from(condition)).is_err());
+            let condition = module_condition(&String::from("test"), &String::from(condition));
+            assert!(!condition.is_fulfilled_by(&interaction));
+            assert!(condition.slots(&interaction).is_empty());
+            assert!(condition.vocabulary().is_empty());
+        }
+        assert!(TextCondition::new(String::from("(?P<cake>")).is_err());
+        assert!(parse_condition(&String::from("cake")).is_ok());
+    }
+
+    #[test]
+    // Scenario
+    // Only the variant of the author's language is tested
+    fn modules_test_lang_condition() {
+        let condition = module_condition(&String::from("test"), &String::from("{\"type\":\"lang\", \"variants\":{\"en\":\"^hello\",
+            \"fr\":{\"type\":\"intent\", \"examples\":[\"salut toi\"]}}}"));
+        let mut interaction = Interaction {
+            device_author: Device {
+                id: 0,
+                name: String::new(),
+                ring_id: String::from("Tars_id"),
+                is_bridge: false
+            },
+            body: String::from("hello"),
+            ..Interaction::default()
+        };
+        assert!(condition.is_fulfilled_by(&interaction));
+        assert!(condition.score(&interaction).is_none());
+        interaction.lang = String::from("fr");
+        assert!(!condition.is_fulfilled_by(&interaction));
+        interaction.body = String::from("salut toi");
+        assert!(condition.is_fulfilled_by(&interaction));
+        assert!(condition.score(&interaction) == Some(1.0));
+        interaction.lang = String::from("de");
+        assert!(!condition.is_fulfilled_by(&interaction));
+        assert!(condition.vocabulary().contains(&String::from("hello")));
+        assert!(condition.vocabulary().contains(&String::from("salut")));
+    }
+
+    #[test]
+    // Scenario
+    // A small python module uses the rori module to store values, schedule a task and send a message
+    fn modules_test_sdk() {
+        let daemon = Arc::new(Mutex::new(Daemon::new()));
+        let cloned_daemon = daemon.clone();
+        let daemon_thread = thread::spawn(move|| {
+            Daemon::run(cloned_daemon);
+        });
+        let _server = setup(User::new(), Vec::new());
+        Database::insert_new_device(&String::from("Atlas_hash"), &String::from("Atlas"), &String::new(), false).unwrap();
+        let module = "import rori\n\
+            version = rori.__version__\n\
+            stored = rori.store.set('Atlas:count', '1')\n\
+            count = rori.store.get('Atlas:count')\n\
+            missing = rori.store.get('PBody:count')\n\
+            task = rori.schedule('hello_world', '{\"username\":\"Atlas\"}', '', 0, 0, 1, '', False)\n\
+            unknown_module = False\n\
+            try:\n    rori.schedule('cake', '', '', 0, 0, 1, '', False)\n\
+            except ValueError:\n    unknown_module = True\n\
+            sent = rori.send('Atlas', 'hello', 'text/plain')\n\
+            secrets = len(rori.secrets())\n";
+        let mut file = File::create("rori_sdk_test.py").unwrap();
+        file.write_all(module.as_bytes()).unwrap();
+        {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            sdk::register(py, &String::from("hello_world"), &String::from("Atlas")).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item(py, "sys", py.import("sys").unwrap()).unwrap();
+            py.run("sys.path.append('.')", None, Some(&locals)).unwrap();
+            let module = py.import("rori_sdk_test").unwrap();
+            assert!(module.get(py, "version").unwrap().extract::<String>(py).unwrap() == sdk::SDK_VERSION);
+            assert!(module.get(py, "count").unwrap().extract::<String>(py).unwrap() == "1");
+            assert!(module.get(py, "missing").unwrap().is_none(py));
+            assert!(module.get(py, "unknown_module").unwrap().extract::<bool>(py).unwrap());
+            assert!(module.get(py, "sent").unwrap().extract::<u64>(py).unwrap() == 1);
+            // The vault is locked
+            assert!(module.get(py, "secrets").unwrap().extract::<u64>(py).unwrap() == 0);
+        }
+        // Values are private to the module
+        let hello_world = String::from("hello_world");
+        assert!(Database::get_module_value(&hello_world, &String::from("Atlas:count")).unwrap() == Some(String::from("1")));
+        assert!(Database::get_module_value(&String::from("name"), &String::from("Atlas:count")).unwrap().is_none());
+        let tasks = Database::get_tasks().unwrap();
+        assert!(tasks.len() == 1);
+        assert!(tasks[0].module == Database::get_module_id_by_name(&hello_world).unwrap());
+        let storage = daemon.lock().unwrap().storage.clone();
+        assert!(storage.lock().unwrap().interactions_sent.len() == 1);
+
+        let _ = fs::remove_file("rori_sdk_test.py");
+        let _ = fs::remove_dir_all("__pycache__");
+        teardown();
+        daemon.lock().unwrap().stop();
+        let _ = daemon_thread.join();
+    }
+
     // NOTE: modules will not be tested here. But related code in rust files should be tested.
     // Last two tests test the module activation's loop
 }
\ No newline at end of file